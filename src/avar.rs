@@ -0,0 +1,92 @@
+//! The `avar` table modifies the normalization of axis coordinates. It doesn't depend
+//! on the glyphs, so it can be kept as is. When partially instancing, the segment maps
+//! of pinned axes are removed, while the segment maps of restricted axes are
+//! renormalized to the new axis limits.
+
+use super::*;
+use crate::instancer::{piecewise_linear_map, round_f2dot14, NormalizedLimit};
+use skrifa::raw::types::F2Dot14;
+use skrifa::raw::{FontData, FontRead};
+use write_fonts::dump_table;
+use write_fonts::tables::avar::{Avar, AxisValueMap, SegmentMaps};
+
+pub fn subset(ctx: &mut Context) -> Result<()> {
+    let data = ctx.expect_table(Tag::AVAR).ok_or(MalformedFont)?;
+    let Interjector::Instancer(instancer) = &ctx.interjector else {
+        ctx.push(Tag::AVAR, data);
+        return Ok(());
+    };
+
+    let avar = skrifa::raw::tables::avar::Avar::read(FontData::new(data))
+        .map_err(|_| MalformedFont)?;
+    let mut segment_maps = vec![];
+
+    for (axis, maps) in instancer.axes().iter().zip(avar.axis_segment_maps().iter()) {
+        if axis.is_pinned() {
+            continue;
+        }
+
+        let maps = maps
+            .map_err(|_| MalformedFont)?
+            .axis_value_maps()
+            .iter()
+            .map(|map| {
+                (
+                    map.from_coordinate().to_f32() as f64,
+                    map.to_coordinate().to_f32() as f64,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let maps = if maps.is_empty() || axis.is_identity() {
+            maps
+        } else {
+            renormalize(&maps, axis.unmapped)
+        };
+
+        segment_maps.push(SegmentMaps::new(
+            maps.into_iter()
+                .map(|(from, to)| {
+                    AxisValueMap::new(
+                        F2Dot14::from_f32(from as f32),
+                        F2Dot14::from_f32(to as f32),
+                    )
+                })
+                .collect(),
+        ));
+    }
+
+    ctx.push(
+        Tag::AVAR,
+        dump_table(&Avar::new(segment_maps)).map_err(|_| Error::SubsetError)?,
+    );
+
+    Ok(())
+}
+
+/// Renormalize a segment map to new axis limits.
+fn renormalize(maps: &[(f64, f64)], limit: NormalizedLimit) -> Vec<(f64, f64)> {
+    let mapped = NormalizedLimit {
+        min: round_f2dot14(piecewise_linear_map(limit.min, maps)),
+        default: round_f2dot14(piecewise_linear_map(limit.default, maps)),
+        max: round_f2dot14(piecewise_linear_map(limit.max, maps)),
+        ..limit
+    };
+
+    let mut new = maps
+        .iter()
+        .filter(|(from, _)| limit.min <= *from && *from <= limit.max)
+        .map(|&(from, to)| {
+            (
+                round_f2dot14(limit.renormalize(from)),
+                round_f2dot14(mapped.renormalize(to)),
+            )
+        })
+        .filter(|(from, _)| ![-1.0, 0.0, 1.0].contains(from))
+        .collect::<Vec<_>>();
+
+    // The mandatory mappings.
+    new.extend([(-1.0, -1.0), (0.0, 0.0), (1.0, 1.0)]);
+    new.sort_by(|a, b| a.0.total_cmp(&b.0));
+    new
+}
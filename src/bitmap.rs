@@ -0,0 +1,544 @@
+//! Color bitmap fonts store their glyphs as images in the `CBDT` table, while the
+//! `CBLC` table locates the image of each glyph. The images are grouped into
+//! strikes for one size each. A strike has a list of index subtables, each of which
+//! covers a range of glyphs, in one of five formats. See the [`CBLC`] and [`CBDT`]
+//! specifications.
+//!
+//! The monochrome and grayscale bitmaps in the older [`EBLC`] and [`EBDT`] tables
+//! have the same structure. Their images can also be composed of the images of
+//! other glyphs, which are added to the subset by the closure. The [`EBSC`] table
+//! says which strikes to scale for sizes that don't have their own.
+//!
+//! To subset the tables, we look up the image of each glyph in the subset for each
+//! strike and write them in the order of the new glyph IDs. Consecutive glyphs with
+//! images of the same kind share an index subtable. If the images contain their own
+//! metrics, we write index format 1, which simply lists their offsets. Otherwise,
+//! all images of the original index subtable have the same size and metrics, and we
+//! write index format 2, which stores them only once. Strikes without any images in
+//! the subset are removed.
+//!
+//! [`CBLC`]: https://learn.microsoft.com/en-us/typography/opentype/spec/cblc
+//! [`CBDT`]: https://learn.microsoft.com/en-us/typography/opentype/spec/cbdt
+//! [`EBLC`]: https://learn.microsoft.com/en-us/typography/opentype/spec/eblc
+//! [`EBDT`]: https://learn.microsoft.com/en-us/typography/opentype/spec/ebdt
+//! [`EBSC`]: https://learn.microsoft.com/en-us/typography/opentype/spec/ebsc
+
+use super::*;
+use crate::Error::OverflowError;
+
+/// The size of a `BitmapSize` record, which describes a strike.
+const BITMAP_SIZE_LEN: usize = 48;
+
+/// The size of a `BitmapScale` record of the `EBSC` table.
+const BITMAP_SCALE_LEN: usize = 28;
+
+/// Add all glyphs to the subset whose images are components of the images of
+/// glyphs in the subset.
+pub fn closure(face: &Face, mapper: &mut GlyphRemapper) -> Result<()> {
+    let (Some(location), Some(data)) = (face.table(Tag::EBLC), face.table(Tag::EBDT))
+    else {
+        return Ok(());
+    };
+
+    tables_closure(location, data, mapper)
+}
+
+/// Add the components of the images until no new glyphs are found.
+fn tables_closure(
+    location: &[u8],
+    data: &[u8],
+    mapper: &mut GlyphRemapper,
+) -> Result<()> {
+    let mut r = Reader::new(location);
+    r.skip::<u32>();
+    let num_sizes = r.read::<u32>().ok_or(MalformedFont)?;
+
+    let mut strikes = vec![];
+    for _ in 0..num_sizes {
+        let record = r.read_bytes(BITMAP_SIZE_LEN).ok_or(MalformedFont)?;
+        strikes.push(Strike::parse(location, record).ok_or(MalformedFont)?);
+    }
+
+    let mut queue = mapper.remapped_gids().collect::<Vec<_>>();
+    while let Some(glyph) = queue.pop() {
+        for strike in &strikes {
+            let Some(image) = strike.image(data, glyph).ok_or(MalformedFont)? else {
+                continue;
+            };
+
+            for (_, component) in image.components().ok_or(MalformedFont)? {
+                if mapper.get(component).is_none() {
+                    mapper.remap(component);
+                    queue.push(component);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Subset the given location table and its data table.
+pub fn subset(ctx: &mut Context, tag: Tag) -> Result<()> {
+    let data_tag = if tag == Tag::CBLC { Tag::CBDT } else { Tag::EBDT };
+    let location = ctx.expect_table(tag).ok_or(MalformedFont)?;
+    let data = ctx.expect_table(data_tag).ok_or(MalformedFont)?;
+    if let Some((location, data)) = subset_tables(location, data, &ctx.mapper)? {
+        ctx.push(tag, location);
+        ctx.push(data_tag, data);
+    }
+    Ok(())
+}
+
+/// Subset the `EBSC` table to the scalings whose substitute strike is still part
+/// of the subsetted `EBLC` table.
+pub fn subset_ebsc(ctx: &mut Context) -> Result<()> {
+    let Some(eblc) = ctx.subsetted_table(Tag::EBLC) else {
+        return Ok(());
+    };
+
+    let mut r = Reader::new(eblc);
+    r.skip::<u32>();
+    let num_sizes = r.read::<u32>().ok_or(MalformedFont)?;
+    let mut ppems = vec![];
+    for _ in 0..num_sizes {
+        let record = r.read_bytes(BITMAP_SIZE_LEN).ok_or(MalformedFont)?;
+        ppems.push((record[44], record[45]));
+    }
+
+    let ebsc = ctx.expect_table(Tag::EBSC).ok_or(MalformedFont)?;
+    let mut r = Reader::new(ebsc);
+    let version = r.read::<u32>().ok_or(MalformedFont)?;
+    let num_sizes = r.read::<u32>().ok_or(MalformedFont)?;
+
+    let mut records = vec![];
+    for _ in 0..num_sizes {
+        let record = r.read_bytes(BITMAP_SCALE_LEN).ok_or(MalformedFont)?;
+        if ppems.contains(&(record[26], record[27])) {
+            records.push(record);
+        }
+    }
+
+    if records.is_empty() {
+        return Ok(());
+    }
+
+    let mut w = Writer::new();
+    w.write::<u32>(version);
+    w.write::<u32>(records.len() as u32);
+    for record in records {
+        w.extend(record);
+    }
+
+    ctx.push(Tag::EBSC, w.finish());
+    Ok(())
+}
+
+/// Subset the location and data tables. Returns `None` if none of the glyphs in
+/// the subset has an image.
+fn subset_tables(
+    location: &[u8],
+    data: &[u8],
+    mapper: &GlyphRemapper,
+) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+    let mut r = Reader::new(location);
+    let version = r.read::<u32>().ok_or(MalformedFont)?;
+    let num_sizes = r.read::<u32>().ok_or(MalformedFont)?;
+
+    // The data table only has a version as its header.
+    let mut sub_data = Writer::new();
+    sub_data.extend(data.get(..4).ok_or(MalformedFont)?);
+
+    let mut strikes = vec![];
+    for _ in 0..num_sizes {
+        let record = r.read_bytes(BITMAP_SIZE_LEN).ok_or(MalformedFont)?;
+        let strike = Strike::parse(location, record).ok_or(MalformedFont)?;
+
+        let mut images = vec![];
+        for (new_gid, old_gid) in (0..).zip(mapper.remapped_gids()) {
+            if let Some(image) = strike.image(data, old_gid).ok_or(MalformedFont)? {
+                images.push((new_gid, image));
+            }
+        }
+
+        if images.is_empty() {
+            continue;
+        }
+
+        let (list, num_subtables) = write_strike(&images, mapper, &mut sub_data)?;
+        let start = images.first().map(|&(gid, _)| gid).unwrap_or_default();
+        let end = images.last().map(|&(gid, _)| gid).unwrap_or_default();
+        strikes.push((record, list, num_subtables, start, end));
+    }
+
+    if strikes.is_empty() {
+        return Ok(None);
+    }
+
+    let mut sub_location = Writer::new();
+    sub_location.write::<u32>(version);
+    sub_location.write::<u32>(strikes.len() as u32);
+
+    let mut offset = 8 + strikes.len() * BITMAP_SIZE_LEN;
+    for (record, list, num_subtables, start, end) in &strikes {
+        sub_location.write::<u32>(u32::try_from(offset).map_err(|_| OverflowError)?);
+        sub_location.write::<u32>(list.len() as u32);
+        sub_location.write::<u32>(*num_subtables);
+        // The color reference (reserved in `EBLC`) and the line metrics stay the
+        // same.
+        sub_location.extend(&record[12..40]);
+        sub_location.write::<u16>(*start);
+        sub_location.write::<u16>(*end);
+        // The sizes, bit depth and flags stay the same.
+        sub_location.extend(&record[44..48]);
+        offset += list.len();
+    }
+
+    for (_, list, ..) in strikes {
+        sub_location.extend(&list);
+    }
+
+    Ok(Some((sub_location.finish(), sub_data.finish())))
+}
+
+/// Write the index subtable list of a strike with the given images and append the
+/// images to the data table. Returns the list and the number of index subtables.
+fn write_strike(
+    images: &[(u16, Image)],
+    mapper: &GlyphRemapper,
+    data: &mut Writer,
+) -> Result<(Vec<u8>, u32)> {
+    // Group consecutive glyphs whose images can share an index subtable.
+    let groups = images.chunk_by(|(prev_gid, prev), (gid, image)| {
+        prev_gid.checked_add(1) == Some(*gid)
+            && prev.format == image.format
+            && prev.constant == image.constant
+    });
+
+    let mut subtables = vec![];
+    for group in groups {
+        let (first, image) = &group[0];
+        let mut w = Writer::new();
+        let image_data_offset = u32::try_from(data.len()).map_err(|_| OverflowError)?;
+
+        match image.constant {
+            Some((image_size, metrics)) => {
+                w.write::<u16>(2);
+                w.write::<u16>(image.format);
+                w.write::<u32>(image_data_offset);
+                w.write::<u32>(image_size);
+                w.extend(metrics);
+            }
+            None => {
+                w.write::<u16>(1);
+                w.write::<u16>(image.format);
+                w.write::<u32>(image_data_offset);
+            }
+        }
+
+        let mut offset = 0u32;
+        for (_, image) in group.iter() {
+            if image.constant.is_none() {
+                w.write::<u32>(offset);
+            }
+            let mut image_data = image.data.to_vec();
+            for (pos, component) in image.components().ok_or(MalformedFont)? {
+                let new_gid = mapper.get(component).ok_or(MalformedFont)?;
+                image_data[pos..pos + 2].copy_from_slice(&new_gid.to_be_bytes());
+            }
+
+            data.extend(&image_data);
+            offset = u32::try_from(image.data.len())
+                .ok()
+                .and_then(|len| offset.checked_add(len))
+                .ok_or(OverflowError)?;
+        }
+
+        if image.constant.is_none() {
+            w.write::<u32>(offset);
+        }
+
+        let last = first + group.len() as u16 - 1;
+        subtables.push((*first, last, w.finish()));
+    }
+
+    // The records come first, followed by the subtables, which we align to four
+    // bytes.
+    let mut list = Writer::new();
+    let mut offset = 8 * subtables.len();
+    for (first, last, subtable) in &subtables {
+        list.write::<u16>(*first);
+        list.write::<u16>(*last);
+        list.write::<u32>(offset as u32);
+        offset += subtable.len().next_multiple_of(4);
+    }
+
+    for (_, _, subtable) in &subtables {
+        list.extend(subtable);
+        list.align(4);
+    }
+
+    Ok((list.finish(), subtables.len() as u32))
+}
+
+/// A strike of a location table.
+struct Strike<'a> {
+    /// The index subtable list.
+    list: &'a [u8],
+    /// The first glyph, last glyph and offset of each index subtable.
+    records: Vec<(u16, u16, u32)>,
+}
+
+impl<'a> Strike<'a> {
+    /// Parse the strike described by the given `BitmapSize` record.
+    fn parse(location: &'a [u8], record: &'a [u8]) -> Option<Self> {
+        let mut r = Reader::new(record);
+        let list_offset = r.read::<u32>()? as usize;
+        // indexSubtableListSize
+        r.skip::<u32>();
+        let num_subtables = r.read::<u32>()?;
+
+        let list = location.get(list_offset..)?;
+        let mut r = Reader::new(list);
+        let mut records = vec![];
+        for _ in 0..num_subtables {
+            records.push((r.read::<u16>()?, r.read::<u16>()?, r.read::<u32>()?));
+        }
+
+        Some(Self { list, records })
+    }
+
+    /// Find the image of a glyph in the data table. Returns `Some(None)` if the
+    /// glyph has no image in this strike.
+    fn image(&self, data: &'a [u8], glyph: u16) -> Option<Option<Image<'a>>> {
+        let Some(&(first, _, offset)) = self
+            .records
+            .iter()
+            .find(|&&(first, last, _)| (first..=last).contains(&glyph))
+        else {
+            return Some(None);
+        };
+
+        let subtable = self.list.get(offset as usize..)?;
+        let mut r = Reader::new(subtable);
+        let index_format = r.read::<u16>()?;
+        let format = r.read::<u16>()?;
+        let image_data_offset = r.read::<u32>()? as usize;
+        let i = usize::from(glyph - first);
+
+        let (start, end, constant) = match index_format {
+            // The images have their own metrics and are located by 32-bit or
+            // 16-bit offsets.
+            1 => {
+                r.skip_bytes(4 * i);
+                (r.read::<u32>()? as usize, r.read::<u32>()? as usize, None)
+            }
+            3 => {
+                r.skip_bytes(2 * i);
+                (usize::from(r.read::<u16>()?), usize::from(r.read::<u16>()?), None)
+            }
+            // Sparse glyphs with their own metrics.
+            4 => {
+                let count = r.read::<u32>()?;
+                let mut pairs = vec![];
+                for _ in 0..=count {
+                    pairs.push((r.read::<u16>()?, r.read::<u16>()?));
+                }
+                let Some(j) = pairs.iter().position(|&(gid, _)| gid == glyph) else {
+                    return Some(None);
+                };
+                let (_, end) = *pairs.get(j + 1)?;
+                (usize::from(pairs[j].1), usize::from(end), None)
+            }
+            // All images have the same size and metrics, either for consecutive
+            // or for sparse glyphs.
+            2 | 5 => {
+                let image_size = r.read::<u32>()?;
+                let metrics = r.read_bytes(8)?;
+                let i = if index_format == 5 {
+                    let count = r.read::<u32>()?;
+                    let glyphs = r.read_array16::<u16>(u16::try_from(count).ok()?)?;
+                    match glyphs.into_iter().position(|gid| gid == glyph) {
+                        Some(i) => i,
+                        None => return Some(None),
+                    }
+                } else {
+                    i
+                };
+                let start = i * image_size as usize;
+                (start, start + image_size as usize, Some((image_size, metrics)))
+            }
+            _ => return None,
+        };
+
+        // Glyphs without an image have an empty range.
+        if start == end {
+            return Some(None);
+        }
+
+        let start = image_data_offset.checked_add(start)?;
+        let end = image_data_offset.checked_add(end)?;
+        Some(Some(Image { format, data: data.get(start..end)?, constant }))
+    }
+}
+
+/// The image of a glyph.
+#[derive(Debug, Copy, Clone)]
+struct Image<'a> {
+    /// The format of the image data.
+    format: u16,
+    /// The image data.
+    data: &'a [u8],
+    /// The size and the big glyph metrics shared by all images of the index
+    /// subtable, if the images don't contain their own metrics.
+    constant: Option<(u32, &'a [u8])>,
+}
+
+impl Image<'_> {
+    /// The glyph IDs of the components of a composite image, with their position
+    /// in the image data. Returns an empty list for other images.
+    fn components(&self) -> Option<Vec<(usize, u16)>> {
+        // The components follow the small or big glyph metrics (with padding)
+        // and their count. Each has a glyph ID and an x and y offset.
+        let start = match self.format {
+            8 => 6,
+            9 => 8,
+            _ => return Some(vec![]),
+        };
+
+        let mut r = Reader::new(self.data.get(start..)?);
+        let count = r.read::<u16>()?;
+        (0..usize::from(count))
+            .map(|i| {
+                let glyph = r.read::<u16>()?;
+                r.skip::<u16>();
+                Some((start + 2 + 4 * i, glyph))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use skrifa::raw::tables::{cbdt::Cbdt, cblc::Cblc, eblc::Eblc};
+    use skrifa::raw::{FontData, FontRead};
+    use skrifa::GlyphId;
+
+    #[test]
+    fn subset_strike() {
+        // Glyphs 1 and 3 have PNG images with small metrics, glyph 2 has none.
+        // Glyphs 5 and 7 have PNG images that share their big metrics.
+        let mut cbdt = Vec::new();
+        cbdt.extend([0, 3, 0, 0]); // version
+        cbdt.extend([1; 5]); // smallGlyphMetrics
+        cbdt.extend(1u32.to_be_bytes()); // dataLen
+        cbdt.push(b'a'); // data
+        cbdt.extend([3; 5]); // smallGlyphMetrics
+        cbdt.extend(1u32.to_be_bytes()); // dataLen
+        cbdt.push(b'c'); // data
+        cbdt.extend(1u32.to_be_bytes()); // dataLen
+        cbdt.push(b'e'); // data
+        cbdt.extend(1u32.to_be_bytes()); // dataLen
+        cbdt.push(b'g'); // data
+
+        let mut cblc = Vec::new();
+        cblc.extend([0, 3, 0, 0]); // version
+        cblc.extend(1u32.to_be_bytes()); // numSizes
+        cblc.extend(56u32.to_be_bytes()); // indexSubtableListOffset
+        cblc.extend(68u32.to_be_bytes()); // indexSubtableListSize
+        cblc.extend(2u32.to_be_bytes()); // numberOfIndexSubtables
+        cblc.extend([0; 28]); // colorRef, hori, vert
+        cblc.extend(1u16.to_be_bytes()); // startGlyphIndex
+        cblc.extend(7u16.to_be_bytes()); // endGlyphIndex
+        cblc.extend([16, 16, 32, 1]); // ppemX, ppemY, bitDepth, flags
+        cblc.extend([0, 1, 0, 3, 0, 0, 0, 16]); // indexSubtableRecords[0]
+        cblc.extend([0, 5, 0, 7, 0, 0, 0, 40]); // indexSubtableRecords[1]
+        cblc.extend([0, 1, 0, 17, 0, 0, 0, 4]); // indexFormat, imageFormat, imageDataOffset
+        for offset in [0u32, 10, 10, 20] {
+            cblc.extend(offset.to_be_bytes()); // sbitOffsets
+        }
+        cblc.extend([0, 5, 0, 19, 0, 0, 0, 24]); // indexFormat, imageFormat, imageDataOffset
+        cblc.extend(5u32.to_be_bytes()); // imageSize
+        cblc.extend([7; 8]); // bigMetrics
+        cblc.extend(2u32.to_be_bytes()); // numGlyphs
+        cblc.extend([0, 5, 0, 7]); // glyphIdArray
+
+        let mapper = GlyphRemapper::new_from_glyphs(&[7, 2, 3, 1]);
+        let (cblc, cbdt) = subset_tables(&cblc, &cbdt, &mapper).unwrap().unwrap();
+
+        let cblc = Cblc::read(FontData::new(&cblc)).unwrap();
+        let cbdt = Cbdt::read(FontData::new(&cbdt)).unwrap();
+        let size = &cblc.bitmap_sizes()[0];
+        assert_eq!(
+            (size.start_glyph_index(), size.end_glyph_index()),
+            (1.into(), 4.into())
+        );
+
+        let location = size.location(cblc.offset_data(), GlyphId::new(1)).unwrap();
+        let data = cbdt.offset_data().as_bytes();
+        let image = &data[location.data_offset..][..location.data_size];
+        assert_eq!(image, [0, 0, 0, 1, b'g']);
+        assert_eq!(location.metrics.unwrap().height, 7);
+
+        // Glyph 2 doesn't have an image.
+        assert!(size.location(cblc.offset_data(), GlyphId::new(2)).is_err());
+
+        for (gid, expected) in [(3, b'c'), (4, b'a')] {
+            let location = size.location(cblc.offset_data(), GlyphId::new(gid)).unwrap();
+            let image = &data[location.data_offset..][..location.data_size];
+            assert_eq!(image[9], expected);
+        }
+    }
+
+    #[test]
+    fn subset_composite() {
+        // Glyph 1 is composed of the image of glyph 2.
+        let mut ebdt = Vec::new();
+        ebdt.extend([0, 2, 0, 0]); // version
+        ebdt.extend([1; 8]); // bigGlyphMetrics
+        ebdt.extend(1u16.to_be_bytes()); // numComponents
+        ebdt.extend([0, 2, 0, 0]); // glyphID, xOffset, yOffset
+        ebdt.extend([2; 8]); // bigGlyphMetrics
+        ebdt.push(0xAA); // imageData
+
+        let mut eblc = Vec::new();
+        eblc.extend([0, 2, 0, 0]); // version
+        eblc.extend(1u32.to_be_bytes()); // numSizes
+        eblc.extend(56u32.to_be_bytes()); // indexSubtableListOffset
+        eblc.extend(48u32.to_be_bytes()); // indexSubtableListSize
+        eblc.extend(2u32.to_be_bytes()); // numberOfIndexSubtables
+        eblc.extend([0; 28]); // colorRef, hori, vert
+        eblc.extend(1u16.to_be_bytes()); // startGlyphIndex
+        eblc.extend(2u16.to_be_bytes()); // endGlyphIndex
+        eblc.extend([16, 16, 1, 1]); // ppemX, ppemY, bitDepth, flags
+        eblc.extend([0, 1, 0, 1, 0, 0, 0, 16]); // indexSubtableRecords[0]
+        eblc.extend([0, 2, 0, 2, 0, 0, 0, 32]); // indexSubtableRecords[1]
+        eblc.extend([0, 1, 0, 9, 0, 0, 0, 4]); // indexFormat, imageFormat, imageDataOffset
+        eblc.extend([0, 0, 0, 0, 0, 0, 0, 14]); // sbitOffsets
+        eblc.extend([0, 1, 0, 7, 0, 0, 0, 18]); // indexFormat, imageFormat, imageDataOffset
+        eblc.extend([0, 0, 0, 0, 0, 0, 0, 9]); // sbitOffsets
+
+        let mut mapper = GlyphRemapper::new_from_glyphs(&[3, 1]);
+        tables_closure(&eblc, &ebdt, &mut mapper).unwrap();
+        assert_eq!(mapper.get(2), Some(3));
+
+        let (eblc, ebdt) = subset_tables(&eblc, &ebdt, &mapper).unwrap().unwrap();
+        let eblc = Eblc::read(FontData::new(&eblc)).unwrap();
+        let size = &eblc.bitmap_sizes()[0];
+        assert_eq!(
+            (size.start_glyph_index(), size.end_glyph_index()),
+            (2.into(), 3.into())
+        );
+
+        let location = size.location(eblc.offset_data(), GlyphId::new(2)).unwrap();
+        let image = &ebdt[location.data_offset..][..location.data_size];
+        assert_eq!(location.format, 9);
+        assert_eq!(image[8..14], [0, 1, 0, 3, 0, 0]);
+
+        let location = size.location(eblc.offset_data(), GlyphId::new(3)).unwrap();
+        let image = &ebdt[location.data_offset..][..location.data_size];
+        assert_eq!(location.format, 7);
+        assert_eq!(image[8], 0xAA);
+    }
+}
@@ -0,0 +1,240 @@
+//! Computing the exact bounding box of a charstring program. Since the programs
+//! are desubroutinized, we only need to interpret the path construction operators.
+//! Like in fontTools, the bounding box of a curve includes its extrema instead of
+//! its control points.
+
+use crate::cff::charstring::operators::*;
+use crate::cff::charstring::{Instruction, Program};
+
+/// A bounding box, as `[x_min, y_min, x_max, y_max]`.
+pub type Rect = [f64; 4];
+
+/// Compute the bounding box of the outline of a program. Returns `None` if the
+/// glyph is empty.
+pub fn program_bounds(program: &Program) -> Option<Rect> {
+    let mut pen = BoundsPen::default();
+    let mut args = vec![];
+
+    for instr in program.instructions() {
+        let operator = match instr {
+            Instruction::Operand(number) => {
+                args.push(number.as_f64());
+                continue;
+            }
+            Instruction::Operator(operator) => *operator,
+            Instruction::HintMask(_) => continue,
+        };
+
+        match operator {
+            // A leading width argument can only appear before the first move, so
+            // we only consider the last arguments.
+            MOVE_TO if args.len() >= 2 => {
+                let [dx, dy] = [args[args.len() - 2], args[args.len() - 1]];
+                pen.move_by(dx, dy);
+            }
+            HORIZONTAL_MOVE_TO if !args.is_empty() => {
+                pen.move_by(args[args.len() - 1], 0.0)
+            }
+            VERTICAL_MOVE_TO if !args.is_empty() => {
+                pen.move_by(0.0, args[args.len() - 1])
+            }
+            LINE_TO => {
+                for d in args.chunks_exact(2) {
+                    pen.line_by(d[0], d[1]);
+                }
+            }
+            HORIZONTAL_LINE_TO | VERTICAL_LINE_TO => {
+                let mut horizontal = operator == HORIZONTAL_LINE_TO;
+                for &d in &args {
+                    if horizontal {
+                        pen.line_by(d, 0.0);
+                    } else {
+                        pen.line_by(0.0, d);
+                    }
+                    horizontal = !horizontal;
+                }
+            }
+            CURVE_TO => {
+                for d in args.chunks_exact(6) {
+                    pen.curve_by(d[0], d[1], d[2], d[3], d[4], d[5]);
+                }
+            }
+            CURVE_LINE if args.len() >= 2 => {
+                let (curves, line) = args.split_at(args.len() - 2);
+                for d in curves.chunks_exact(6) {
+                    pen.curve_by(d[0], d[1], d[2], d[3], d[4], d[5]);
+                }
+                pen.line_by(line[0], line[1]);
+            }
+            LINE_CURVE if args.len() >= 6 => {
+                let (lines, d) = args.split_at(args.len() - 6);
+                for l in lines.chunks_exact(2) {
+                    pen.line_by(l[0], l[1]);
+                }
+                pen.curve_by(d[0], d[1], d[2], d[3], d[4], d[5]);
+            }
+            VV_CURVE_TO => {
+                let (mut dx1, rest) = match args.len() % 2 {
+                    1 => (args[0], &args[1..]),
+                    _ => (0.0, &args[..]),
+                };
+                for d in rest.chunks_exact(4) {
+                    pen.curve_by(dx1, d[0], d[1], d[2], 0.0, d[3]);
+                    dx1 = 0.0;
+                }
+            }
+            HH_CURVE_TO => {
+                let (mut dy1, rest) = match args.len() % 2 {
+                    1 => (args[0], &args[1..]),
+                    _ => (0.0, &args[..]),
+                };
+                for d in rest.chunks_exact(4) {
+                    pen.curve_by(d[0], dy1, d[1], d[2], d[3], 0.0);
+                    dy1 = 0.0;
+                }
+            }
+            HV_CURVE_TO | VH_CURVE_TO => {
+                let mut horizontal = operator == HV_CURVE_TO;
+                let mut chunks = args.chunks_exact(4).peekable();
+                let extra = args.chunks_exact(4).remainder().first().copied();
+                while let Some(d) = chunks.next() {
+                    // The last curve may end with an additional coordinate.
+                    let df = if chunks.peek().is_none() { extra } else { None };
+                    let df = df.unwrap_or(0.0);
+                    if horizontal {
+                        pen.curve_by(d[0], 0.0, d[1], d[2], df, d[3]);
+                    } else {
+                        pen.curve_by(0.0, d[0], d[1], d[2], d[3], df);
+                    }
+                    horizontal = !horizontal;
+                }
+            }
+            FLEX if args.len() >= 12 => {
+                let d = &args[..12];
+                pen.curve_by(d[0], d[1], d[2], d[3], d[4], d[5]);
+                pen.curve_by(d[6], d[7], d[8], d[9], d[10], d[11]);
+            }
+            HFLEX if args.len() >= 7 => {
+                let d = &args[..7];
+                pen.curve_by(d[0], 0.0, d[1], d[2], d[3], 0.0);
+                pen.curve_by(d[4], 0.0, d[5], -d[2], d[6], 0.0);
+            }
+            HFLEX1 if args.len() >= 9 => {
+                let d = &args[..9];
+                pen.curve_by(d[0], d[1], d[2], d[3], d[4], 0.0);
+                pen.curve_by(d[5], 0.0, d[6], d[7], d[8], -(d[1] + d[3] + d[7]));
+            }
+            FLEX1 if args.len() >= 11 => {
+                let d = &args[..11];
+                let dx = d[0] + d[2] + d[4] + d[6] + d[8];
+                let dy = d[1] + d[3] + d[5] + d[7] + d[9];
+                let (dx6, dy6) =
+                    if dx.abs() > dy.abs() { (d[10], -dy) } else { (-dx, d[10]) };
+                pen.curve_by(d[0], d[1], d[2], d[3], d[4], d[5]);
+                pen.curve_by(d[6], d[7], d[8], d[9], dx6, dy6);
+            }
+            _ => {}
+        }
+
+        args.clear();
+    }
+
+    pen.bounds
+}
+
+/// A pen that keeps track of the bounding box of the outline drawn with it.
+#[derive(Default)]
+struct BoundsPen {
+    /// The current point.
+    point: (f64, f64),
+    /// Whether the current point was moved to, but not drawn yet.
+    moved: bool,
+    bounds: Option<Rect>,
+}
+
+impl BoundsPen {
+    fn move_by(&mut self, dx: f64, dy: f64) {
+        self.point = (self.point.0 + dx, self.point.1 + dy);
+        self.moved = true;
+    }
+
+    fn line_by(&mut self, dx: f64, dy: f64) {
+        self.start();
+        self.point = (self.point.0 + dx, self.point.1 + dy);
+        self.extend(self.point);
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn curve_by(&mut self, dx1: f64, dy1: f64, dx2: f64, dy2: f64, dx3: f64, dy3: f64) {
+        self.start();
+        let p0 = self.point;
+        let p1 = (p0.0 + dx1, p0.1 + dy1);
+        let p2 = (p1.0 + dx2, p1.1 + dy2);
+        let p3 = (p2.0 + dx3, p2.1 + dy3);
+        self.point = p3;
+        self.extend(p3);
+
+        // The extrema of the curve are where the derivative of one of the
+        // coordinates is zero.
+        for t in extrema(p0.0, p1.0, p2.0, p3.0)
+            .into_iter()
+            .chain(extrema(p0.1, p1.1, p2.1, p3.1))
+            .flatten()
+        {
+            self.extend((
+                cubic(p0.0, p1.0, p2.0, p3.0, t),
+                cubic(p0.1, p1.1, p2.1, p3.1, t),
+            ));
+        }
+    }
+
+    /// Include a pending move in the bounding box, since it is the start of
+    /// a segment.
+    fn start(&mut self) {
+        if self.moved {
+            self.moved = false;
+            self.extend(self.point);
+        }
+    }
+
+    fn extend(&mut self, (x, y): (f64, f64)) {
+        self.bounds = Some(match self.bounds {
+            Some([x_min, y_min, x_max, y_max]) => {
+                [x_min.min(x), y_min.min(y), x_max.max(x), y_max.max(y)]
+            }
+            None => [x, y, x, y],
+        });
+    }
+}
+
+/// Evaluate one coordinate of a cubic Bézier curve at `t`.
+fn cubic(p0: f64, p1: f64, p2: f64, p3: f64, t: f64) -> f64 {
+    let mt = 1.0 - t;
+    mt * mt * mt * p0 + 3.0 * mt * mt * t * p1 + 3.0 * mt * t * t * p2 + t * t * t * p3
+}
+
+/// Find the parameters in `(0, 1)` at which one coordinate of a cubic Bézier
+/// curve has a local extremum.
+fn extrema(p0: f64, p1: f64, p2: f64, p3: f64) -> [Option<f64>; 2] {
+    // The derivative, divided by three, is `a * t^2 + b * t + c`.
+    let a = p3 - 3.0 * p2 + 3.0 * p1 - p0;
+    let b = 2.0 * (p2 - 2.0 * p1 + p0);
+    let c = p1 - p0;
+
+    let valid = |t: f64| Some(t).filter(|t| 0.0 < *t && *t < 1.0);
+
+    if a.abs() < 1e-12 {
+        if b.abs() < 1e-12 {
+            return [None, None];
+        }
+        return [valid(-c / b), None];
+    }
+
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return [None, None];
+    }
+
+    let root = discriminant.sqrt();
+    [valid((-b + root) / (2.0 * a)), valid((-b - root) / (2.0 * a))]
+}
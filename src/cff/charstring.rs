@@ -224,6 +224,61 @@ impl<'a> Program<'a> {
         self.0.push(instruction);
     }
 
+    /// Remove all stem hints and hint masks from the program. A width that is
+    /// given as an argument of the first hint is kept.
+    pub fn strip_hints(&mut self) {
+        let mut instructions = Vec::with_capacity(self.0.len());
+        let mut operands = vec![];
+        let mut width = None;
+        let mut first = true;
+
+        for instr in std::mem::take(&mut self.0) {
+            match instr {
+                Instruction::Operand(number) => operands.push(number),
+                Instruction::Operator(
+                    HORIZONTAL_STEM
+                    | VERTICAL_STEM
+                    | HORIZONTAL_STEM_HINT_MASK
+                    | VERTICAL_STEM_HINT_MASK
+                    | HINT_MASK
+                    | COUNTER_MASK,
+                ) => {
+                    // Stems are given in pairs, so an odd number of arguments to
+                    // the first stack-clearing operator means that the first one
+                    // is the width.
+                    if first && operands.len() % 2 == 1 {
+                        width = operands.first().copied();
+                    }
+
+                    first = false;
+                    operands.clear();
+                }
+                Instruction::HintMask(_) => {}
+                Instruction::Operator(op) => {
+                    if matches!(
+                        op,
+                        MOVE_TO | HORIZONTAL_MOVE_TO | VERTICAL_MOVE_TO | ENDCHAR
+                    ) {
+                        first = false;
+                    }
+
+                    instructions.extend(width.take().map(Instruction::Operand));
+                    instructions.extend(operands.drain(..).map(Instruction::Operand));
+                    instructions.push(Instruction::Operator(op));
+                }
+            }
+        }
+
+        instructions.extend(width.map(Instruction::Operand));
+        instructions.extend(operands.into_iter().map(Instruction::Operand));
+        self.0 = instructions;
+    }
+
+    /// The instructions of the program.
+    pub fn instructions(&self) -> &[Instruction<'a>] {
+        &self.0
+    }
+
     /// Compile the program.
     pub fn compile(&self) -> Vec<u8> {
         let mut w = Writer::new();
@@ -280,3 +335,55 @@ pub mod operators {
     pub const FLEX1: Operator = Operator::from_two_byte(37);
     pub const FIXED_16_16: Operator = Operator::from_one_byte(255);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decompile a charstring without subroutines, strip its hints and compile it
+    /// again.
+    fn strip(charstring: &[u8]) -> Vec<u8> {
+        let (gsubrs, lsubrs) = (SubroutineHandler::new(&[]), SubroutineHandler::new(&[]));
+        let mut program = Decompiler::new(gsubrs, lsubrs).decompile(charstring).unwrap();
+        program.strip_hints();
+        program.compile()
+    }
+
+    /// Encode a small integer as a charstring operand.
+    fn num(value: i8) -> u8 {
+        (i16::from(value) + 139) as u8
+    }
+
+    #[test]
+    fn strip_implicit_vertical_stems() {
+        // The operands of the first hintmask are vertical stems, which determine
+        // how many bytes the masks have.
+        let mut charstring = vec![num(10), num(20), num(30), num(40), 18]; // hstemhm
+        charstring.extend([num(50), num(60), 19, 0b1110_0000]); // hintmask
+        charstring.extend([num(0), num(0), 21]); // rmoveto
+        charstring.extend([19, 0b0001_0000]); // hintmask
+        charstring.extend([num(5), 6, 14]); // hlineto, endchar
+
+        assert_eq!(strip(&charstring), [num(0), num(0), 21, num(5), 6, 14]);
+    }
+
+    #[test]
+    fn strip_width_before_mask() {
+        // A width that is given as an argument of the first hintmask or cntrmask
+        // must be kept, even though the implicit stems are removed.
+        for mask in [19, 20] {
+            let mut charstring = vec![num(100), num(10), num(20), mask, 0b1000_0000];
+            charstring.extend([num(1), num(2), 21, 14]); // rmoveto, endchar
+
+            assert_eq!(strip(&charstring), [num(100), num(1), num(2), 21, 14]);
+        }
+
+        // If the width is given with the first stem hint, the operands of a later
+        // hintmask are only stems.
+        let mut charstring = vec![num(100), num(10), num(20), 1]; // hstem
+        charstring.extend([num(30), num(40), 19, 0b1100_0000]); // hintmask
+        charstring.extend([num(1), num(2), 21, 14]); // rmoveto, endchar
+
+        assert_eq!(strip(&charstring), [num(100), num(1), num(2), 21, 14]);
+    }
+}
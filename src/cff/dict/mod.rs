@@ -179,4 +179,25 @@ pub(crate) mod operators {
 
     // PRIVATE DICT OPERATORS
     pub const SUBRS: Operator = Operator(OperatorType::OneByteOperator([19]));
+
+    /// The operators of the private dict that only contain hinting parameters.
+    pub const HINTING_OPERATORS: [Operator; 14] = [
+        // BlueValues, OtherBlues, FamilyBlues, FamilyOtherBlues, StdHW, StdVW
+        Operator(OperatorType::OneByteOperator([6])),
+        Operator(OperatorType::OneByteOperator([7])),
+        Operator(OperatorType::OneByteOperator([8])),
+        Operator(OperatorType::OneByteOperator([9])),
+        Operator(OperatorType::OneByteOperator([10])),
+        Operator(OperatorType::OneByteOperator([11])),
+        // BlueScale, BlueShift, BlueFuzz, StemSnapH, StemSnapV, ForceBold,
+        // LanguageGroup, ExpansionFactor
+        Operator(OperatorType::TwoByteOperator([TWO_BYTE_OPERATOR_MARK, 9])),
+        Operator(OperatorType::TwoByteOperator([TWO_BYTE_OPERATOR_MARK, 10])),
+        Operator(OperatorType::TwoByteOperator([TWO_BYTE_OPERATOR_MARK, 11])),
+        Operator(OperatorType::TwoByteOperator([TWO_BYTE_OPERATOR_MARK, 12])),
+        Operator(OperatorType::TwoByteOperator([TWO_BYTE_OPERATOR_MARK, 13])),
+        Operator(OperatorType::TwoByteOperator([TWO_BYTE_OPERATOR_MARK, 14])),
+        Operator(OperatorType::TwoByteOperator([TWO_BYTE_OPERATOR_MARK, 17])),
+        Operator(OperatorType::TwoByteOperator([TWO_BYTE_OPERATOR_MARK, 18])),
+    ];
 }
@@ -31,10 +31,11 @@ pub fn rewrite_cid_private_dicts(
     offsets: &mut Offsets,
     metadata: &CIDMetadata,
     w: &mut Writer,
+    strip_hinting: bool,
 ) -> Result<()> {
     for (new_df, old_df) in fd_remapper.sorted_iter().enumerate() {
         let font_dict = metadata.font_dicts.get(old_df as usize).ok_or(SubsetError)?;
-        rewrite_private_dict(offsets, font_dict.private_dict, w, new_df)?;
+        rewrite_private_dict(offsets, font_dict.private_dict, w, new_df, strip_hinting)?;
     }
 
     Ok(())
@@ -45,6 +46,7 @@ pub(crate) fn rewrite_private_dict(
     private_dict_data: &[u8],
     w: &mut Writer,
     dict_index: usize,
+    strip_hinting: bool,
 ) -> Result<()> {
     let private_dict_offset = w.len();
 
@@ -56,12 +58,13 @@ pub(crate) fn rewrite_private_dict(
         let mut sub_w = Writer::new();
 
         // We just make sure that no subroutine offset gets written, all other operators stay the
-        // same.
+        // same. If the hinting is removed, the hinting parameters are dropped as well.
         while let Some(operator) = dict_parser.parse_next() {
             match operator {
                 SUBRS => {
                     // We don't have any subroutines, so don't rewrite this DICT entry.
                 }
+                _ if strip_hinting && HINTING_OPERATORS.contains(&operator) => {}
                 _ => {
                     dict_parser.parse_operands().ok_or(MalformedFont)?;
                     let operands = dict_parser.operands();
@@ -1,4 +1,5 @@
 mod argstack;
+mod bounds;
 mod charset;
 mod charstring;
 mod cid_font;
@@ -10,9 +11,11 @@ mod remapper;
 mod sid_font;
 mod subroutines;
 
+pub(crate) use number::U24;
+
 use super::*;
 use crate::cff::charset::rewrite_charset;
-use crate::cff::charstring::Decompiler;
+use crate::cff::charstring::{Decompiler, Program};
 use crate::cff::cid_font::{rewrite_fd_index, CIDMetadata};
 use crate::cff::dict::font_dict::{generate_font_dict_index, rewrite_font_dict_index};
 use crate::cff::dict::private_dict::{rewrite_cid_private_dicts, rewrite_private_dict};
@@ -24,7 +27,7 @@ use crate::cff::remapper::{FontDictRemapper, SidRemapper};
 use crate::cff::sid_font::SIDMetadata;
 use crate::cff::subroutines::{SubroutineCollection, SubroutineContainer};
 use crate::Error::{OverflowError, SubsetError};
-use number::{IntegerNumber, StringId};
+use number::{IntegerNumber, Number, StringId};
 use sid_font::generate_fd_index;
 use std::cmp::PartialEq;
 use std::collections::BTreeSet;
@@ -127,7 +130,14 @@ pub fn subset(ctx: &mut Context<'_>) -> Result<()> {
     let table = Table::parse(ctx)?;
 
     // Note: The charstrings are already in the new order that they need be written in.
-    let (char_strings, fd_remapper) = subset_charstrings(&table, &ctx.mapper)?;
+    let (char_strings, fd_remapper, bbox) =
+        subset_charstrings(&table, &ctx.mapper, &ctx.options)?;
+
+    let mut top_dict_data = table.top_dict_data.clone();
+    if let Some(bbox) = bbox {
+        top_dict_data.font_bbox = Some(bbox.map(|v| Number::from_i32(v as i32)));
+        ctx.bbox = Some(bbox);
+    }
 
     let sid_remapper = get_sid_remapper(&table, &fd_remapper).ok_or(SubsetError)?;
 
@@ -144,12 +154,7 @@ pub fn subset(ctx: &mut Context<'_>) -> Result<()> {
         // Name INDEX
         w.write(table.names);
         // Top DICT INDEX
-        rewrite_top_dict_index(
-            &table.top_dict_data,
-            &mut offsets,
-            &sid_remapper,
-            &mut w,
-        )?;
+        rewrite_top_dict_index(&top_dict_data, &mut offsets, &sid_remapper, &mut w)?;
         // String INDEX
         let index = create_index(
             sid_remapper
@@ -170,10 +175,22 @@ pub fn subset(ctx: &mut Context<'_>) -> Result<()> {
         match &table.font_kind {
             FontKind::Sid(sid) => {
                 // Since we convert SID-keyed to CID-keyed, we write one private dict with index 0.
-                rewrite_private_dict(&mut offsets, sid.private_dict_data, &mut w, 0)?;
+                rewrite_private_dict(
+                    &mut offsets,
+                    sid.private_dict_data,
+                    &mut w,
+                    0,
+                    ctx.options.strip_hinting,
+                )?;
             }
             FontKind::Cid(cid) => {
-                rewrite_cid_private_dicts(&fd_remapper, &mut offsets, cid, &mut w)?;
+                rewrite_cid_private_dicts(
+                    &fd_remapper,
+                    &mut offsets,
+                    cid,
+                    &mut w,
+                    ctx.options.strip_hinting,
+                )?;
             }
         }
 
@@ -238,10 +255,12 @@ fn update_offsets(offsets: &Offsets, buffer: &mut [u8]) -> Result<()> {
 }
 
 /// Create the list of bytes that constitute the programs of the charstrings, sorted in the new glyph order.
+/// If requested, the hints are removed and the bounding box of all programs is computed as well.
 fn subset_charstrings(
     table: &Table,
     remapper: &GlyphRemapper,
-) -> Result<(Vec<Vec<u8>>, FontDictRemapper)> {
+    options: &SubsetOptions,
+) -> Result<(Vec<Vec<u8>>, FontDictRemapper, Option<FontBBox>)> {
     let gsubrs = {
         let subroutines = table.global_subrs.into_iter().collect::<Vec<_>>();
         SubroutineContainer::new(subroutines)
@@ -285,7 +304,11 @@ fn subset_charstrings(
             lsubrs.get_handler(fd_index).ok_or(MalformedFont)?,
         );
         let charstring = table.char_strings.get(old_gid as u32).ok_or(MalformedFont)?;
-        char_strings.push(decompiler.decompile(charstring)?);
+        let mut program = decompiler.decompile(charstring)?;
+        if options.strip_hinting {
+            program.strip_hints();
+        }
+        char_strings.push(program);
     }
 
     let mut fd_remapper = FontDictRemapper::new();
@@ -294,7 +317,33 @@ fn subset_charstrings(
         fd_remapper.remap(fd);
     }
 
-    Ok((char_strings.iter().map(|p| p.compile()).collect(), fd_remapper))
+    let bbox = options.recompute_bbox.then(|| font_bbox(&char_strings));
+
+    Ok((char_strings.iter().map(|p| p.compile()).collect(), fd_remapper, bbox))
+}
+
+/// The bounding box of a font, as `[x_min, y_min, x_max, y_max]`.
+type FontBBox = [i16; 4];
+
+/// Compute the union of the bounding boxes of all programs, rounded outwards to
+/// integers.
+fn font_bbox(programs: &[Program]) -> FontBBox {
+    let mut bbox: Option<bounds::Rect> = None;
+    for rect in programs.iter().filter_map(bounds::program_bounds) {
+        bbox = Some(match bbox {
+            Some(b) => [
+                b[0].min(rect[0]),
+                b[1].min(rect[1]),
+                b[2].max(rect[2]),
+                b[3].max(rect[3]),
+            ],
+            None => rect,
+        });
+    }
+
+    let [x_min, y_min, x_max, y_max] = bbox.unwrap_or_default();
+    [x_min.floor(), y_min.floor(), x_max.ceil(), y_max.ceil()]
+        .map(|v| v.clamp(i16::MIN as f64, i16::MAX as f64) as i16)
 }
 
 fn get_sid_remapper<'a>(
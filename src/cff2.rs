@@ -12,6 +12,8 @@ pub fn subset(ctx: &mut Context) -> crate::Result<()> {
         let data = match &ctx.interjector {
             // We reject CFF2 fonts earlier if `variable-fonts` feature is not enabled.
             Interjector::Dummy(_) => unreachable!(),
+            // Partial instancing is only supported for TrueType fonts.
+            Interjector::Instancer(_) => unreachable!(),
             #[cfg(feature = "variable-fonts")]
             Interjector::Skrifa(s) => {
                 let (advance, lsb, data) =
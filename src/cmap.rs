@@ -0,0 +1,338 @@
+//! The `cmap` table maps Unicode codepoints to glyphs. PDF writers provide their
+//! own mapping, so we only keep it for general-purpose subsets. See the
+//! [specification].
+//!
+//! We read the mapping from the best Unicode subtable of the font and keep the
+//! codepoints of the glyphs in the subset. The new table contains a format 4
+//! subtable for the Basic Multilingual Plane and, if needed, a format 12 subtable
+//! for all codepoints, each referenced by the Unicode and Windows platforms. Fonts
+//! that only have a Windows symbol subtable keep using that encoding. Unicode
+//! variation sequences (format 14) are not kept.
+//!
+//! [specification]: https://learn.microsoft.com/en-us/typography/opentype/spec/cmap
+
+use super::*;
+use crate::Error::OverflowError;
+
+/// The platform and encoding of Windows symbol fonts.
+const WINDOWS_SYMBOL: (u16, u16) = (3, 0);
+
+pub fn subset(ctx: &mut Context) -> Result<()> {
+    let cmap = ctx.expect_table(Tag::CMAP).ok_or(MalformedFont)?;
+    let (data, mapping) = subset_table(cmap, &ctx.mapper)?;
+    ctx.codepoints = mapping
+        .first()
+        .zip(mapping.last())
+        .map(|(&(first, _), &(last, _))| (first, last));
+    ctx.push(Tag::CMAP, data);
+    Ok(())
+}
+
+/// Pairs of codepoints and glyph IDs.
+type Mapping = Vec<(u32, u16)>;
+
+/// Subset a `cmap` table. Also returns the new mapping, sorted by codepoint.
+fn subset_table(cmap: &[u8], mapper: &GlyphRemapper) -> Result<(Vec<u8>, Mapping)> {
+    let mut r = Reader::new(cmap);
+    // version
+    r.skip::<u16>();
+    let count = r.read::<u16>().ok_or(MalformedFont)?;
+
+    let mut best: Option<(u8, (u16, u16), usize)> = None;
+    for _ in 0..count {
+        let platform = r.read::<u16>().ok_or(MalformedFont)?;
+        let encoding = r.read::<u16>().ok_or(MalformedFont)?;
+        let offset = r.read::<u32>().ok_or(MalformedFont)? as usize;
+        let Some(rank) = rank(platform, encoding) else { continue };
+        if best.is_none_or(|(best_rank, ..)| rank > best_rank) {
+            best = Some((rank, (platform, encoding), offset));
+        }
+    }
+
+    let mut mapping = vec![];
+    let encoding = match best {
+        Some((_, encoding, offset)) => {
+            let subtable = cmap.get(offset..).ok_or(MalformedFont)?;
+            mappings(subtable, &mut |codepoint, glyph| {
+                if let Some(glyph) = mapper.get(glyph).filter(|&glyph| glyph != 0) {
+                    mapping.push((codepoint, glyph));
+                }
+            })
+            .ok_or(MalformedFont)?;
+            encoding
+        }
+        None => (3, 1),
+    };
+
+    mapping.sort();
+    mapping.dedup_by_key(|&mut (codepoint, _)| codepoint);
+
+    let bmp = mapping
+        .iter()
+        .filter_map(|&(codepoint, glyph)| Some((u16::try_from(codepoint).ok()?, glyph)))
+        .collect::<Vec<_>>();
+    let format_4 = format_4(&bmp).ok_or(OverflowError)?;
+    let format_12 = (bmp.len() < mapping.len()).then(|| format_12(&mapping));
+
+    let mut records = vec![];
+    if encoding == WINDOWS_SYMBOL {
+        records.push((WINDOWS_SYMBOL, 0));
+    } else {
+        records.push(((0, 3), 0));
+        records.push(((3, 1), 0));
+        if format_12.is_some() {
+            records.push(((0, 4), 1));
+            records.push(((3, 10), 1));
+        }
+    }
+    records.sort();
+
+    let mut w = Writer::new();
+    w.write::<u16>(0);
+    w.write::<u16>(records.len() as u16);
+
+    let start = 4 + 8 * records.len();
+    let offsets = [start, start + format_4.len()];
+    for ((platform, encoding), subtable) in records {
+        w.write::<u16>(platform);
+        w.write::<u16>(encoding);
+        w.write::<u32>(u32::try_from(offsets[subtable]).map_err(|_| OverflowError)?);
+    }
+
+    w.extend(&format_4);
+    if let Some(format_12) = format_12 {
+        w.extend(&format_12);
+    }
+
+    Ok((w.finish(), mapping))
+}
+
+/// How suitable a subtable is as the source of the mapping. Higher is better.
+/// Returns `None` for subtables that don't map Unicode codepoints.
+fn rank(platform: u16, encoding: u16) -> Option<u8> {
+    match (platform, encoding) {
+        (3, 10) | (0, 4) | (0, 6) => Some(3),
+        (3, 1) | (0, 0..=3) => Some(2),
+        WINDOWS_SYMBOL => Some(1),
+        _ => None,
+    }
+}
+
+/// Call `f` with each codepoint and glyph of a subtable.
+fn mappings(data: &[u8], f: &mut impl FnMut(u32, u16)) -> Option<()> {
+    let mut r = Reader::new(data);
+    let format = r.read::<u16>()?;
+    match format {
+        0 => {
+            // length, language
+            r.skip_bytes(4);
+            for codepoint in 0..256 {
+                f(codepoint, u16::from(r.read::<u8>()?));
+            }
+        }
+        4 => {
+            // length, language
+            r.skip_bytes(4);
+            let seg_count = r.read::<u16>()? / 2;
+            // searchRange, entrySelector, rangeShift
+            r.skip_bytes(6);
+            let end_codes = r.read_array16::<u16>(seg_count)?;
+            // reservedPad
+            r.skip::<u16>();
+            let start_codes = r.read_array16::<u16>(seg_count)?;
+            let deltas = r.read_array16::<u16>(seg_count)?;
+            let range_offsets_pos = r.offset();
+            let range_offsets = r.read_array16::<u16>(seg_count)?;
+
+            for i in 0..seg_count {
+                let start = start_codes.get(i)?;
+                let end = end_codes.get(i)?;
+                let delta = deltas.get(i)?;
+                let range_offset = range_offsets.get(i)?;
+                // The range offset is relative to its own position.
+                let pos =
+                    range_offsets_pos + 2 * usize::from(i) + usize::from(range_offset);
+
+                for codepoint in start..=end {
+                    if codepoint == 0xFFFF {
+                        break;
+                    }
+
+                    let glyph = if range_offset == 0 {
+                        codepoint.wrapping_add(delta)
+                    } else {
+                        let pos = pos + 2 * usize::from(codepoint - start);
+                        match Reader::new_at(data, pos).read::<u16>()? {
+                            0 => continue,
+                            glyph => glyph.wrapping_add(delta),
+                        }
+                    };
+
+                    f(u32::from(codepoint), glyph);
+                }
+            }
+        }
+        6 => {
+            // length, language
+            r.skip_bytes(4);
+            let first = r.read::<u16>()?;
+            let count = r.read::<u16>()?;
+            for (codepoint, glyph) in (first..).zip(r.read_array16::<u16>(count)?) {
+                f(u32::from(codepoint), glyph);
+            }
+        }
+        12 | 13 => {
+            // reserved, length, language
+            r.skip_bytes(10);
+            let count = r.read::<u32>()?;
+            for _ in 0..count {
+                let start = r.read::<u32>()?;
+                let end = r.read::<u32>()?.min(0x10FFFF);
+                let glyph = r.read::<u32>()?;
+                for codepoint in start..=end {
+                    // Format 13 maps all codepoints of a group to the same glyph.
+                    let glyph =
+                        if format == 12 { glyph + (codepoint - start) } else { glyph };
+                    f(codepoint, u16::try_from(glyph).ok()?);
+                }
+            }
+        }
+        _ => return None,
+    }
+
+    Some(())
+}
+
+/// Write a format 4 subtable for a mapping that is sorted by codepoint. Runs of
+/// consecutive codepoints form a segment. If their glyphs are consecutive, too,
+/// the segment only stores the difference. Otherwise, it refers to the glyphs in
+/// the glyph ID array.
+fn format_4(mapping: &[(u16, u16)]) -> Option<Vec<u8>> {
+    let mut segments = mapping
+        .chunk_by(|&(a, _), &(b, _)| a.checked_add(1) == Some(b))
+        .collect::<Vec<_>>();
+
+    // The last segment must map 0xFFFF.
+    let last = [(0xFFFF, 0)];
+    if segments
+        .last()
+        .and_then(|s| s.last())
+        .is_none_or(|&(c, _)| c != 0xFFFF)
+    {
+        segments.push(&last);
+    }
+
+    let seg_count = u16::try_from(segments.len()).ok()?;
+    let consecutive = |segment: &[(u16, u16)]| {
+        segment.windows(2).all(|w| w[0].1.checked_add(1) == Some(w[1].1))
+    };
+
+    let mut end_codes = Writer::new();
+    let mut start_codes = Writer::new();
+    let mut deltas = Writer::new();
+    let mut range_offsets = Writer::new();
+    let mut glyphs = Writer::new();
+    for (i, segment) in segments.iter().enumerate() {
+        let (start, first) = segment[0];
+        let (end, _) = segment[segment.len() - 1];
+        end_codes.write::<u16>(end);
+        start_codes.write::<u16>(start);
+
+        if consecutive(segment) {
+            deltas.write::<u16>(first.wrapping_sub(start));
+            range_offsets.write::<u16>(0);
+        } else {
+            // The offset from the range offset to the glyphs of the segment.
+            let offset = 2 * (segments.len() - i) + glyphs.len();
+            deltas.write::<u16>(0);
+            range_offsets.write::<u16>(u16::try_from(offset).ok()?);
+            for &(_, glyph) in *segment {
+                glyphs.write::<u16>(glyph);
+            }
+        }
+    }
+
+    let entry_selector = seg_count.ilog2() as u16;
+    let search_range = 2 * (1 << entry_selector);
+    let len = 16 + 8 * segments.len() + glyphs.len();
+
+    let mut w = Writer::new();
+    w.write::<u16>(4);
+    w.write::<u16>(u16::try_from(len).ok()?);
+    w.write::<u16>(0);
+    w.write::<u16>(2 * seg_count);
+    w.write::<u16>(search_range);
+    w.write::<u16>(entry_selector);
+    w.write::<u16>(2 * seg_count - search_range);
+    w.extend(&end_codes.finish());
+    w.write::<u16>(0);
+    w.extend(&start_codes.finish());
+    w.extend(&deltas.finish());
+    w.extend(&range_offsets.finish());
+    w.extend(&glyphs.finish());
+    Some(w.finish())
+}
+
+/// Write a format 12 subtable for a mapping that is sorted by codepoint.
+fn format_12(mapping: &[(u32, u16)]) -> Vec<u8> {
+    let groups = mapping
+        .chunk_by(|&(a, x), &(b, y)| a + 1 == b && x.checked_add(1) == Some(y))
+        .collect::<Vec<_>>();
+
+    let mut w = Writer::new();
+    w.write::<u16>(12);
+    w.write::<u16>(0);
+    w.write::<u32>(16 + 12 * groups.len() as u32);
+    w.write::<u32>(0);
+    w.write::<u32>(groups.len() as u32);
+    for group in groups {
+        let (start, glyph) = group[0];
+        let (end, _) = group[group.len() - 1];
+        w.write::<u32>(start);
+        w.write::<u32>(end);
+        w.write::<u32>(u32::from(glyph));
+    }
+    w.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use skrifa::raw::tables::cmap::Cmap;
+    use skrifa::raw::{FontData, FontRead};
+
+    #[test]
+    fn subset_mapping() {
+        // Glyphs 1 to 4 for 'a' to 'd' and glyph 5 for an emoji.
+        let mapping = [(0x61, 1), (0x62, 2), (0x63, 3), (0x64, 4), (0x1F600, 5)];
+        let mut cmap = Writer::new();
+        cmap.write::<[u16; 4]>([0, 1, 3, 10]);
+        cmap.write::<u32>(12);
+        cmap.extend(&format_12(&mapping));
+        let cmap = cmap.finish();
+
+        let mapper = GlyphRemapper::new_from_glyphs(&[5, 4, 2, 3]);
+        let (subset, mapping) = subset_table(&cmap, &mapper).unwrap();
+        assert_eq!(mapping, [(0x62, 3), (0x63, 4), (0x64, 2), (0x1F600, 1)]);
+
+        let cmap = Cmap::read(FontData::new(&subset)).unwrap();
+        let records = cmap
+            .encoding_records()
+            .iter()
+            .map(|record| (record.platform_id() as u16, record.encoding_id()))
+            .collect::<Vec<_>>();
+        assert_eq!(records, [(0, 3), (0, 4), (3, 1), (3, 10)]);
+
+        for record in cmap.encoding_records() {
+            let subtable = record.subtable(cmap.offset_data()).unwrap();
+            let bmp = record.encoding_id() < 4;
+            for (codepoint, glyph) in [(0x61, None), (0x62, Some(3)), (0x64, Some(2))]
+                .into_iter()
+                .chain([(0x1F600, (!bmp).then_some(1))])
+            {
+                let actual = subtable.map_codepoint(codepoint as u32);
+                assert_eq!(actual.map(|g| g.to_u32() as u16), glyph, "{codepoint:x}");
+            }
+        }
+    }
+}
@@ -0,0 +1,823 @@
+//! The `COLR` table defines color glyphs in terms of other glyphs of the font. In
+//! version 0, each base glyph is a stack of layer glyphs that are filled with a
+//! color from the `CPAL` table. Version 1 adds a graph of paint tables per base
+//! glyph, which can clip to glyphs, fill with gradients, apply transformations and
+//! reuse the paints of other color glyphs. See the [specification].
+//!
+//! All glyphs that the color glyphs of the subset refer to are added to the subset
+//! by the closure. To subset the table, we keep the base glyphs that are part of the
+//! subset and rebuild their layers and paint graphs with the new glyph IDs. Only the
+//! layers of the layer list and the palette entries that are still used are kept,
+//! so the `CPAL` table is subsetted afterwards. The variation data of version 1 is
+//! kept as is, since the paints keep their variation indices.
+//!
+//! [specification]: https://learn.microsoft.com/en-us/typography/opentype/spec/colr
+
+use super::*;
+use crate::cff::U24;
+use crate::layout::item_variation_store_node;
+use crate::pack::Node;
+use crate::Error::OverflowError;
+use rustc_hash::FxHashSet;
+use std::collections::BTreeSet;
+
+/// The palette index that stands for the text color.
+const FOREGROUND: u16 = 0xFFFF;
+
+/// How deeply paints can be nested.
+const MAX_PAINT_DEPTH: u8 = 64;
+
+/// Add all glyphs to the subset that the color glyphs in the subset refer to, be
+/// it as layers, as clip glyphs of paints or as other color glyphs.
+pub fn closure(face: &Face, mapper: &mut GlyphRemapper) -> Result<()> {
+    let Some(data) = face.table(Tag::COLR) else {
+        return Ok(());
+    };
+
+    let table = Table::parse(data).ok_or(MalformedFont)?;
+    table_closure(&table, mapper).ok_or(MalformedFont)
+}
+
+/// Form the closure over the color glyphs of a parsed `COLR` table.
+fn table_closure(table: &Table, mapper: &mut GlyphRemapper) -> Option<()> {
+    let mut usage = Usage::default();
+    let mut queue = mapper.remapped_gids().collect::<Vec<_>>();
+
+    while let Some(glyph) = queue.pop() {
+        table.visit_glyph(glyph, &mut usage)?;
+        for glyph in std::mem::take(&mut usage.glyphs) {
+            if mapper.get(glyph).is_none() {
+                mapper.remap(glyph);
+                queue.push(glyph);
+            }
+        }
+    }
+
+    Some(())
+}
+
+/// Add the layer glyphs of the version 0 color glyphs in the subset to it and
+/// return the layers of each color glyph, with the colors of the given palette.
+pub fn layers(
+    face: &Face,
+    mapper: &mut GlyphRemapper,
+    palette: u16,
+) -> Result<Vec<ColorGlyph>> {
+    let Some(data) = face.table(Tag::COLR) else {
+        return Ok(vec![]);
+    };
+
+    let table = Table::parse(data).ok_or(MalformedFont)?;
+    let colors = match face.table(Tag::CPAL) {
+        Some(cpal) => cpal::palette(cpal, palette)?,
+        None => vec![],
+    };
+
+    table_layers(&table, &colors, mapper)
+}
+
+/// Resolve the version 0 layers of the color glyphs in the subset.
+fn table_layers(
+    table: &Table,
+    colors: &[[u8; 4]],
+    mapper: &mut GlyphRemapper,
+) -> Result<Vec<ColorGlyph>> {
+    // Only the glyphs that were requested are drawn with layers, not the layer
+    // glyphs we add.
+    let base_glyphs = (0..)
+        .zip(mapper.remapped_gids())
+        .filter_map(|(glyph, old_gid)| Some((glyph, table.base_glyph(old_gid)?)))
+        .collect::<Vec<_>>();
+
+    let mut glyphs = vec![];
+    for (glyph, base_glyph) in base_glyphs {
+        let mut layers = vec![];
+        for i in base_glyph.layers() {
+            let layer = table.layers.get(i as usize).ok_or(MalformedFont)?;
+            let color = match layer.palette {
+                FOREGROUND => None,
+                i => Some(*colors.get(usize::from(i)).ok_or(MalformedFont)?),
+            };
+            layers.push(ColorLayer { glyph: mapper.remap(layer.glyph), color });
+        }
+
+        glyphs.push(ColorGlyph { glyph, layers });
+    }
+
+    Ok(glyphs)
+}
+
+pub fn subset(ctx: &mut Context) -> Result<()> {
+    let colr = ctx.expect_table(Tag::COLR).ok_or(MalformedFont)?;
+    if let Some((data, palette_entries)) = subset_table(colr, &ctx.mapper)? {
+        ctx.palette_entries = palette_entries;
+        ctx.push(Tag::COLR, data);
+    }
+    Ok(())
+}
+
+/// Subset a `COLR` table. Returns `None` if none of the color glyphs are left.
+/// Otherwise, the palette entries that are still used are returned as well.
+fn subset_table(
+    data: &[u8],
+    mapper: &GlyphRemapper,
+) -> Result<Option<(Vec<u8>, Vec<u16>)>> {
+    let table = Table::parse(data).ok_or(MalformedFont)?;
+
+    // The base glyphs of both versions that are part of the subset, in the order
+    // of their new glyph IDs.
+    let mut usage = Usage::default();
+    let mut base_glyphs = vec![];
+    let mut base_glyph_paints = vec![];
+    for (new_gid, old_gid) in (0..).zip(mapper.remapped_gids()) {
+        table.visit_glyph(old_gid, &mut usage).ok_or(MalformedFont)?;
+        if let Some(base_glyph) = table.base_glyph(old_gid) {
+            base_glyphs.push((new_gid, base_glyph));
+        }
+        if let Some(pos) = table.base_glyph_paint(old_gid) {
+            base_glyph_paints.push((new_gid, old_gid, pos));
+        }
+    }
+
+    if base_glyphs.is_empty() && base_glyph_paints.is_empty() {
+        return Ok(None);
+    }
+
+    let s = Subsetter {
+        table: &table,
+        mapper,
+        layers: usage.layers.into_iter().collect(),
+        palette: usage.palette.into_iter().filter(|&i| i != FOREGROUND).collect(),
+    };
+
+    let mut records = Node::new();
+    let mut layers = Node::new();
+    let mut num_layers = 0u16;
+    for &(new_gid, base_glyph) in &base_glyphs {
+        records.write::<u16>(new_gid);
+        records.write::<u16>(num_layers);
+        records.write::<u16>(base_glyph.num_layers);
+        for i in base_glyph.layers() {
+            let layer = table.layers.get(i as usize).ok_or(MalformedFont)?;
+            layers.write::<u16>(mapper.get(layer.glyph).ok_or(MalformedFont)?);
+            layers.write::<u16>(s.palette_index(layer.palette).ok_or(MalformedFont)?);
+        }
+        num_layers =
+            num_layers.checked_add(base_glyph.num_layers).ok_or(OverflowError)?;
+    }
+
+    let v1 = !base_glyph_paints.is_empty();
+    let mut root = Node::new();
+    root.write::<u16>(u16::from(v1));
+    root.write::<u16>(base_glyphs.len() as u16);
+    root.link32_opt((!base_glyphs.is_empty()).then_some(records));
+    root.link32_opt((num_layers > 0).then_some(layers));
+    root.write::<u16>(num_layers);
+
+    if v1 {
+        root.link32(s.base_glyph_list(&base_glyph_paints).ok_or(MalformedFont)?);
+        root.link32_opt(s.layer_list().ok_or(MalformedFont)?);
+        root.link32_opt(s.clip_list(&base_glyph_paints).ok_or(MalformedFont)?);
+
+        let var_index_map = table
+            .var_index_map
+            .map(|pos| data.get(pos..).and_then(delta_set_index_map_node))
+            .map(|node| node.ok_or(MalformedFont))
+            .transpose()?;
+        let variation_store = table
+            .variation_store
+            .map(|pos| data.get(pos..).and_then(item_variation_store_node))
+            .map(|node| node.ok_or(MalformedFont))
+            .transpose()?;
+        root.link32_opt(var_index_map);
+        root.link32_opt(variation_store);
+    }
+
+    let data = root.pack().ok_or(OverflowError)?;
+    Ok(Some((data, s.palette)))
+}
+
+/// A parsed `COLR` table. Positions of subtables are relative to the start of
+/// the table.
+struct Table<'a> {
+    data: &'a [u8],
+    base_glyphs: Vec<BaseGlyph>,
+    layers: Vec<Layer>,
+    base_glyph_paints: Vec<(u16, usize)>,
+    layer_paints: Vec<usize>,
+    clips: Vec<(u16, u16, usize)>,
+    var_index_map: Option<usize>,
+    variation_store: Option<usize>,
+}
+
+impl<'a> Table<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut r = Reader::new(data);
+        let version = r.read::<u16>()?;
+        let num_base_glyphs = r.read::<u16>()?;
+        let base_glyphs_offset = r.read::<u32>()? as usize;
+        let layers_offset = r.read::<u32>()? as usize;
+        let num_layers = r.read::<u16>()?;
+
+        let base_glyphs = Reader::new(data.get(base_glyphs_offset..)?)
+            .read_array16::<BaseGlyph>(num_base_glyphs)?
+            .into_iter()
+            .collect();
+        let layers = Reader::new(data.get(layers_offset..)?)
+            .read_array16::<Layer>(num_layers)?
+            .into_iter()
+            .collect();
+
+        let mut table = Self {
+            data,
+            base_glyphs,
+            layers,
+            base_glyph_paints: vec![],
+            layer_paints: vec![],
+            clips: vec![],
+            var_index_map: None,
+            variation_store: None,
+        };
+
+        if version == 0 {
+            return Some(table);
+        }
+
+        let mut offset = || match r.read::<u32>()? {
+            0 => Some(None),
+            offset => Some(Some(offset as usize)),
+        };
+        let base_glyph_list = offset()?;
+        let layer_list = offset()?;
+        let clip_list = offset()?;
+        table.var_index_map = offset()?;
+        table.variation_store = offset()?;
+
+        if let Some(pos) = base_glyph_list {
+            let mut r = Reader::new_at(data, pos);
+            let count = r.read::<u32>()?;
+            for _ in 0..count {
+                let glyph = r.read::<u16>()?;
+                let offset = r.read::<u32>()? as usize;
+                table.base_glyph_paints.push((glyph, pos.checked_add(offset)?));
+            }
+        }
+
+        if let Some(pos) = layer_list {
+            let mut r = Reader::new_at(data, pos);
+            let count = r.read::<u32>()?;
+            for _ in 0..count {
+                let offset = r.read::<u32>()? as usize;
+                table.layer_paints.push(pos.checked_add(offset)?);
+            }
+        }
+
+        if let Some(pos) = clip_list {
+            let mut r = Reader::new_at(data, pos);
+            // format
+            r.skip::<u8>();
+            let count = r.read::<u32>()?;
+            for _ in 0..count {
+                let start = r.read::<u16>()?;
+                let end = r.read::<u16>()?;
+                let offset = r.read::<U24>()?.0 as usize;
+                table.clips.push((start, end, pos.checked_add(offset)?));
+            }
+        }
+
+        Some(table)
+    }
+
+    /// The version 0 record of a base glyph.
+    fn base_glyph(&self, glyph: u16) -> Option<BaseGlyph> {
+        let i = self.base_glyphs.binary_search_by_key(&glyph, |record| record.glyph);
+        i.ok().map(|i| self.base_glyphs[i])
+    }
+
+    /// The position of the root paint of a version 1 base glyph.
+    fn base_glyph_paint(&self, glyph: u16) -> Option<usize> {
+        let i = self.base_glyph_paints.binary_search_by_key(&glyph, |&(g, _)| g);
+        i.ok().map(|i| self.base_glyph_paints[i].1)
+    }
+
+    /// The position of the clip box of a version 1 base glyph.
+    fn clip_box(&self, glyph: u16) -> Option<usize> {
+        let i = self.clips.partition_point(|&(start, _, _)| start <= glyph);
+        let &(_, end, pos) = self.clips.get(i.checked_sub(1)?)?;
+        (glyph <= end).then_some(pos)
+    }
+
+    /// Record what the color glyph with the given ID uses, if it is one.
+    fn visit_glyph(&self, glyph: u16, usage: &mut Usage) -> Option<()> {
+        if let Some(base_glyph) = self.base_glyph(glyph) {
+            for i in base_glyph.layers() {
+                let layer = self.layers.get(i as usize)?;
+                usage.glyphs.push(layer.glyph);
+                usage.palette.insert(layer.palette);
+            }
+        }
+
+        if let Some(pos) = self.base_glyph_paint(glyph) {
+            self.visit_paint(pos, usage, 0)?;
+        }
+
+        Some(())
+    }
+
+    /// Record what the paint at the given position and its children use.
+    fn visit_paint(&self, pos: usize, usage: &mut Usage, depth: u8) -> Option<()> {
+        if depth > MAX_PAINT_DEPTH {
+            return None;
+        }
+
+        if !usage.visited.insert(pos) {
+            return Some(());
+        }
+
+        let mut r = Reader::new_at(self.data, pos);
+        let format = r.read::<u8>()?;
+        if format == COLR_LAYERS {
+            let num_layers = r.read::<u8>()?;
+            let first = r.read::<u32>()?;
+            for i in first..first.checked_add(u32::from(num_layers))? {
+                usage.layers.insert(i);
+                self.visit_paint(*self.layer_paints.get(i as usize)?, usage, depth + 1)?;
+            }
+            return Some(());
+        }
+
+        for &field in fields(format)? {
+            match field {
+                Field::Paint => {
+                    self.visit_paint(offset(&mut r, pos)?, usage, depth + 1)?
+                }
+                Field::ColorLine(var) => {
+                    let mut r = Reader::new_at(self.data, offset(&mut r, pos)?);
+                    // extend
+                    r.skip::<u8>();
+                    let count = r.read::<u16>()?;
+                    for _ in 0..count {
+                        // stopOffset
+                        r.skip::<u16>();
+                        usage.palette.insert(r.read::<u16>()?);
+                        // alpha and varIndexBase
+                        r.skip_bytes(if var { 6 } else { 2 });
+                    }
+                }
+                Field::Affine(_) => r.skip::<U24>(),
+                Field::Glyph => usage.glyphs.push(r.read::<u16>()?),
+                Field::Palette => {
+                    usage.palette.insert(r.read::<u16>()?);
+                }
+                Field::Bytes(n) => r.skip_bytes(n),
+            }
+        }
+
+        Some(())
+    }
+}
+
+/// The things that color glyphs refer to.
+#[derive(Default)]
+struct Usage {
+    /// The glyphs that are used as layers, clip glyphs and color glyphs.
+    glyphs: Vec<u16>,
+    /// The indices into the layer list.
+    layers: BTreeSet<u32>,
+    /// The palette indices.
+    palette: BTreeSet<u16>,
+    /// The positions of paints that were already visited.
+    visited: FxHashSet<usize>,
+}
+
+/// Rebuilds the version 1 subtables for the subset.
+struct Subsetter<'a> {
+    table: &'a Table<'a>,
+    mapper: &'a GlyphRemapper,
+    /// The indices into the layer list that are kept, in ascending order.
+    layers: Vec<u32>,
+    /// The palette entries that are kept, in ascending order.
+    palette: Vec<u16>,
+}
+
+impl Subsetter<'_> {
+    /// The new index of a palette entry.
+    fn palette_index(&self, index: u16) -> Option<u16> {
+        if index == FOREGROUND {
+            return Some(FOREGROUND);
+        }
+        self.palette.binary_search(&index).ok().map(|i| i as u16)
+    }
+
+    /// Write the list of base glyphs with their root paints.
+    fn base_glyph_list(&self, base_glyph_paints: &[(u16, u16, usize)]) -> Option<Node> {
+        let mut node = Node::new();
+        node.write::<u32>(base_glyph_paints.len() as u32);
+        for &(new_gid, _, pos) in base_glyph_paints {
+            node.write::<u16>(new_gid);
+            node.link32(self.paint(pos, 0)?);
+        }
+        Some(node)
+    }
+
+    /// Write the layers that are still in use. Since all layers of a
+    /// `PaintColrLayers` table are kept, they stay consecutive.
+    fn layer_list(&self) -> Option<Option<Node>> {
+        if self.layers.is_empty() {
+            return Some(None);
+        }
+
+        let mut node = Node::new();
+        node.write::<u32>(self.layers.len() as u32);
+        for &i in &self.layers {
+            node.link32(self.paint(*self.table.layer_paints.get(i as usize)?, 0)?);
+        }
+        Some(Some(node))
+    }
+
+    /// Write the clip boxes of the base glyphs. Consecutive glyphs with the same
+    /// clip box share a clip record.
+    fn clip_list(&self, base_glyph_paints: &[(u16, u16, usize)]) -> Option<Option<Node>> {
+        let mut clips: Vec<(u16, u16, usize)> = vec![];
+        for &(new_gid, old_gid, _) in base_glyph_paints {
+            let Some(pos) = self.table.clip_box(old_gid) else { continue };
+            match clips.last_mut() {
+                Some((_, end, prev))
+                    if end.checked_add(1) == Some(new_gid) && *prev == pos =>
+                {
+                    *end = new_gid
+                }
+                _ => clips.push((new_gid, new_gid, pos)),
+            }
+        }
+
+        if clips.is_empty() {
+            return Some(None);
+        }
+
+        let mut node = Node::new();
+        node.write::<u8>(1);
+        node.write::<u32>(clips.len() as u32);
+        for (start, end, pos) in clips {
+            node.write::<u16>(start);
+            node.write::<u16>(end);
+
+            // The variable format has an additional variation index.
+            let data = self.table.data.get(pos..)?;
+            let len = match *data.first()? {
+                1 => 9,
+                2 => 13,
+                _ => return None,
+            };
+            node.link24(Node::from_bytes(data.get(..len)?));
+        }
+        Some(Some(node))
+    }
+
+    /// Write the paint at the given position and its children.
+    fn paint(&self, pos: usize, depth: u8) -> Option<Node> {
+        if depth > MAX_PAINT_DEPTH {
+            return None;
+        }
+
+        let data = self.table.data;
+        let mut r = Reader::new_at(data, pos);
+        let format = r.read::<u8>()?;
+
+        let mut node = Node::new();
+        node.write::<u8>(format);
+
+        if format == COLR_LAYERS {
+            let num_layers = r.read::<u8>()?;
+            let first = r.read::<u32>()?;
+            let first = self.layers.binary_search(&first).ok()?;
+            node.write::<u8>(num_layers);
+            node.write::<u32>(first as u32);
+            return Some(node);
+        }
+
+        for &field in fields(format)? {
+            match field {
+                Field::Paint => node.link24(self.paint(offset(&mut r, pos)?, depth + 1)?),
+                Field::ColorLine(var) => {
+                    let mut r = Reader::new_at(data, offset(&mut r, pos)?);
+                    let mut color_line = Node::new();
+                    color_line.write::<u8>(r.read::<u8>()?);
+                    let count = r.read::<u16>()?;
+                    color_line.write::<u16>(count);
+                    for _ in 0..count {
+                        color_line.extend(r.read_bytes(2)?);
+                        color_line.write::<u16>(self.palette_index(r.read::<u16>()?)?);
+                        color_line.extend(r.read_bytes(if var { 6 } else { 2 })?);
+                    }
+                    node.link24(color_line);
+                }
+                Field::Affine(var) => {
+                    // Six 16.16 values, and a variation index for the variable version.
+                    let pos = offset(&mut r, pos)?;
+                    let len = if var { 28 } else { 24 };
+                    node.link24(Node::from_bytes(data.get(pos..pos + len)?));
+                }
+                Field::Glyph => node.write::<u16>(self.mapper.get(r.read::<u16>()?)?),
+                Field::Palette => {
+                    node.write::<u16>(self.palette_index(r.read::<u16>()?)?)
+                }
+                Field::Bytes(n) => node.extend(r.read_bytes(n)?),
+            }
+        }
+
+        Some(node)
+    }
+}
+
+/// A version 0 base glyph record.
+#[derive(Debug, Copy, Clone)]
+struct BaseGlyph {
+    glyph: u16,
+    first_layer: u16,
+    num_layers: u16,
+}
+
+impl BaseGlyph {
+    /// The indices of the layer records.
+    fn layers(&self) -> std::ops::Range<u32> {
+        let first = u32::from(self.first_layer);
+        first..first + u32::from(self.num_layers)
+    }
+}
+
+impl Readable<'_> for BaseGlyph {
+    const SIZE: usize = 6;
+
+    fn read(r: &mut Reader) -> Option<Self> {
+        Some(Self {
+            glyph: r.read::<u16>()?,
+            first_layer: r.read::<u16>()?,
+            num_layers: r.read::<u16>()?,
+        })
+    }
+}
+
+/// A version 0 layer record.
+#[derive(Debug, Copy, Clone)]
+struct Layer {
+    glyph: u16,
+    palette: u16,
+}
+
+impl Readable<'_> for Layer {
+    const SIZE: usize = 4;
+
+    fn read(r: &mut Reader) -> Option<Self> {
+        Some(Self { glyph: r.read::<u16>()?, palette: r.read::<u16>()? })
+    }
+}
+
+/// The format of `PaintColrLayers`, which refers to a slice of the layer list.
+const COLR_LAYERS: u8 = 1;
+
+/// A field of a paint table.
+#[derive(Debug, Copy, Clone)]
+enum Field {
+    /// An offset to another paint.
+    Paint,
+    /// An offset to a color line, which has variations if the flag is set.
+    ColorLine(bool),
+    /// An offset to a transformation matrix, which has variations if the flag
+    /// is set.
+    Affine(bool),
+    /// A glyph ID.
+    Glyph,
+    /// A palette index.
+    Palette,
+    /// Other data, which is copied as is.
+    Bytes(usize),
+}
+
+/// The fields of the paint tables of the given format, except for `PaintColrLayers`.
+fn fields(format: u8) -> Option<&'static [Field]> {
+    use Field::*;
+    Some(match format {
+        // PaintSolid and PaintVarSolid
+        2 => &[Palette, Bytes(2)],
+        3 => &[Palette, Bytes(6)],
+        // PaintLinearGradient, PaintRadialGradient and PaintSweepGradient,
+        // followed by their variable versions.
+        4 | 6 => &[ColorLine(false), Bytes(12)],
+        5 | 7 => &[ColorLine(true), Bytes(16)],
+        8 => &[ColorLine(false), Bytes(8)],
+        9 => &[ColorLine(true), Bytes(12)],
+        // PaintGlyph and PaintColrGlyph
+        10 => &[Paint, Glyph],
+        11 => &[Glyph],
+        // PaintTransform and PaintVarTransform
+        12 => &[Paint, Affine(false)],
+        13 => &[Paint, Affine(true)],
+        // The other transformations consist of a couple of values, and a variation
+        // index for the variable versions.
+        20 | 24 => &[Paint, Bytes(2)],
+        14 | 16 | 28 => &[Paint, Bytes(4)],
+        21 | 22 | 25 | 26 => &[Paint, Bytes(6)],
+        15 | 17 | 18 | 29 | 30 => &[Paint, Bytes(8)],
+        23 | 27 => &[Paint, Bytes(10)],
+        19 | 31 => &[Paint, Bytes(12)],
+        // PaintComposite
+        32 => &[Paint, Bytes(1), Paint],
+        _ => return None,
+    })
+}
+
+/// Read a 24-bit offset relative to the given position. The subtables of paints
+/// are required, so a zero offset, which would point to the paint itself, is
+/// rejected.
+fn offset(r: &mut Reader, pos: usize) -> Option<usize> {
+    match r.read::<U24>()?.0 {
+        0 => None,
+        offset => pos.checked_add(offset as usize),
+    }
+}
+
+/// Copy a delta set index map. The paints keep their variation indices, so it
+/// stays valid.
+fn delta_set_index_map_node(data: &[u8]) -> Option<Node> {
+    let mut r = Reader::new(data);
+    let format = r.read::<u8>()?;
+    let entry_format = r.read::<u8>()?;
+    let (header_len, count) = match format {
+        0 => (4, r.read::<u16>()? as usize),
+        1 => (6, r.read::<u32>()? as usize),
+        _ => return None,
+    };
+
+    let entry_size = usize::from((entry_format >> 4) & 0x3) + 1;
+    Some(Node::from_bytes(data.get(..header_len + count * entry_size)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paint(format: u8, children: Vec<Node>, data: &[u16]) -> Node {
+        let mut node = Node::new();
+        node.write::<u8>(format);
+        children.into_iter().for_each(|child| node.link24(child));
+        data.iter().for_each(|&v| node.write::<u16>(v));
+        node
+    }
+
+    #[test]
+    fn subset_versions_0_and_1() {
+        // Glyph 3 has two version 0 layers, glyph 5 two version 1 layers and
+        // glyph 10 reuses glyph 5.
+        let mut records = Node::new();
+        records.write::<[u16; 3]>([3, 0, 2]);
+        let mut layers = Node::new();
+        layers.write::<[u16; 4]>([4, 2, 6, FOREGROUND]);
+
+        let mut base_glyph_list = Node::new();
+        base_glyph_list.write::<u32>(2);
+        base_glyph_list.write::<u16>(5);
+        let mut colr_layers = Node::new();
+        colr_layers.write::<u8>(COLR_LAYERS);
+        colr_layers.write::<u8>(2);
+        colr_layers.write::<u32>(1);
+        base_glyph_list.link32(colr_layers);
+        base_glyph_list.write::<u16>(10);
+        base_glyph_list.link32(paint(11, vec![], &[5]));
+
+        let mut color_line = Node::new();
+        color_line.write::<u8>(0);
+        color_line.write::<[u16; 7]>([2, 0, 1, 0x4000, 0x4000, 3, 0x4000]);
+        let mut layer_list = Node::new();
+        layer_list.write::<u32>(3);
+        layer_list.link32(paint(10, vec![paint(2, vec![], &[0, 0x4000])], &[9]));
+        layer_list.link32(paint(10, vec![paint(2, vec![], &[3, 0x4000])], &[7]));
+        let gradient = paint(4, vec![color_line], &[0, 0, 100, 0, 0, 100]);
+        layer_list.link32(paint(10, vec![gradient], &[8]));
+
+        let mut root = Node::new();
+        root.write::<u16>(1);
+        root.write::<u16>(1);
+        root.link32(records);
+        root.link32(layers);
+        root.write::<u16>(2);
+        root.link32(base_glyph_list);
+        root.link32(layer_list);
+        root.write::<[u32; 3]>([0; 3]);
+        let data = root.pack().unwrap();
+
+        let table = Table::parse(&data).unwrap();
+        let mut mapper = GlyphRemapper::new_from_glyphs(&[10, 3]);
+        table_closure(&table, &mut mapper).unwrap();
+        let gids = mapper.remapped_gids().collect::<Vec<_>>();
+        assert_eq!(gids, [0, 10, 3, 4, 6, 5, 7, 8]);
+
+        let (subset, palette) = subset_table(&data, &mapper).unwrap().unwrap();
+        assert_eq!(palette, [1, 2, 3]);
+
+        // The paints can be read by other implementations.
+        use skrifa::raw::tables::colr::{Colr, Paint};
+        use skrifa::raw::{FontData, FontRead};
+        let colr = Colr::read(FontData::new(&subset)).unwrap();
+        let glyph = skrifa::GlyphId::new(5);
+        let Some((Paint::ColrLayers(layers), _)) = colr.v1_base_glyph(glyph).unwrap()
+        else {
+            panic!("expected layers");
+        };
+        assert_eq!(layers.first_layer_index(), 0);
+        let Ok((Paint::Glyph(paint), _)) = colr.v1_layer(1) else {
+            panic!("expected glyph paint");
+        };
+        assert_eq!(paint.glyph_id().to_u32(), 7);
+        assert!(matches!(paint.paint(), Ok(Paint::LinearGradient(_))));
+
+        let table = Table::parse(&subset).unwrap();
+        let base_glyph = table.base_glyph(2).unwrap();
+        assert_eq!((base_glyph.first_layer, base_glyph.num_layers), (0, 2));
+        let layers =
+            table.layers.iter().map(|l| (l.glyph, l.palette)).collect::<Vec<_>>();
+        assert_eq!(layers, [(3, 1), (4, FOREGROUND)]);
+        assert_eq!(table.base_glyph_paints.len(), 2);
+        assert_eq!(table.layer_paints.len(), 2);
+
+        let mut usage = Usage::default();
+        table.visit_glyph(1, &mut usage).unwrap();
+        assert_eq!(usage.glyphs, [5]);
+
+        let mut usage = Usage::default();
+        table.visit_glyph(5, &mut usage).unwrap();
+        assert_eq!(usage.glyphs, [6, 7]);
+        assert_eq!(usage.layers.into_iter().collect::<Vec<_>>(), [0, 1]);
+        assert_eq!(usage.palette.into_iter().collect::<Vec<_>>(), [0, 2]);
+    }
+
+    #[test]
+    fn reject_cyclic_and_deep_paints() {
+        let colr = |paint: Node| {
+            let mut base_glyph_list = Node::new();
+            base_glyph_list.write::<u32>(1);
+            base_glyph_list.write::<u16>(5);
+            base_glyph_list.link32(paint);
+
+            let mut root = Node::new();
+            root.write::<u16>(1);
+            root.write::<u16>(0);
+            root.write::<[u32; 2]>([0; 2]);
+            root.write::<u16>(0);
+            root.link32(base_glyph_list);
+            root.write::<[u32; 4]>([0; 4]);
+            root.pack().unwrap()
+        };
+
+        // A `PaintGlyph` whose child offset points to itself.
+        let mut cyclic = Node::new();
+        cyclic.write::<u8>(10);
+        cyclic.write::<[u8; 3]>([0; 3]);
+        cyclic.write::<u16>(7);
+
+        // A chain of `PaintTranslate` tables that is nested too deeply.
+        let mut deep = paint(2, vec![], &[0, 0x4000]);
+        for _ in 0..=MAX_PAINT_DEPTH {
+            deep = paint(14, vec![deep], &[10, 10]);
+        }
+
+        for data in [colr(cyclic), colr(deep)] {
+            let table = Table::parse(&data).unwrap();
+            let mut mapper = GlyphRemapper::new_from_glyphs(&[5]);
+            assert_eq!(table_closure(&table, &mut mapper), None);
+            assert_eq!(subset_table(&data, &mapper), Err(MalformedFont));
+        }
+    }
+
+    #[test]
+    fn resolve_layers() {
+        // Glyph 3 has a red layer and a layer in the text color.
+        let mut records = Node::new();
+        records.write::<[u16; 3]>([3, 0, 2]);
+        let mut layers = Node::new();
+        layers.write::<[u16; 4]>([4, 0, 6, FOREGROUND]);
+
+        let mut root = Node::new();
+        root.write::<u16>(0);
+        root.write::<u16>(1);
+        root.link32(records);
+        root.link32(layers);
+        root.write::<u16>(2);
+        let data = root.pack().unwrap();
+
+        let table = Table::parse(&data).unwrap();
+        let mut mapper = GlyphRemapper::new_from_glyphs(&[5, 3]);
+        let glyphs = table_layers(&table, &[[255, 0, 0, 255]], &mut mapper).unwrap();
+
+        assert_eq!(
+            glyphs,
+            [ColorGlyph {
+                glyph: 2,
+                layers: vec![
+                    ColorLayer { glyph: 3, color: Some([255, 0, 0, 255]) },
+                    ColorLayer { glyph: 4, color: None },
+                ],
+            }]
+        );
+    }
+}
@@ -0,0 +1,235 @@
+//! The `CPAL` table contains the palettes that the `COLR` table refers to. Each
+//! palette has the same number of entries. When subsetting, we only keep the
+//! entries that the subsetted `COLR` table still uses, in the order that it
+//! determined, but we keep all palettes. See the [specification].
+//!
+//! [specification]: https://learn.microsoft.com/en-us/typography/opentype/spec/cpal
+
+use super::*;
+use crate::Error::OverflowError;
+
+/// The name ID that stands for a missing label.
+const NO_LABEL: u16 = 0xFFFF;
+
+pub fn subset(ctx: &mut Context) -> Result<()> {
+    // The palettes are only needed by the `COLR` table.
+    if ctx.subsetted_table(Tag::COLR).is_none() {
+        return Ok(());
+    }
+
+    let cpal = ctx.expect_table(Tag::CPAL).ok_or(MalformedFont)?;
+    let data = subset_table(cpal, &ctx.palette_entries, &mut ctx.name_ids)?;
+    ctx.push(Tag::CPAL, data);
+    Ok(())
+}
+
+/// The colors of the palette with the given index, in RGBA.
+pub fn palette(cpal: &[u8], index: u16) -> Result<Vec<[u8; 4]>> {
+    let table = Table::parse(cpal).ok_or(MalformedFont)?;
+    let first = table.first_records.get(index).ok_or(Error::UnknownPalette)?;
+    (0..table.num_entries)
+        .map(|i| {
+            let start = 4 * (usize::from(first) + usize::from(i));
+            // The color records are stored in BGRA order.
+            match *table.records.get(start..start + 4)? {
+                [b, g, r, a] => Some([r, g, b, a]),
+                _ => None,
+            }
+        })
+        .collect::<Option<_>>()
+        .ok_or(MalformedFont)
+}
+
+/// Subset a `CPAL` table to the given entries. The name IDs of the labels are
+/// added to `name_ids`.
+fn subset_table(
+    cpal: &[u8],
+    entries: &[u16],
+    name_ids: &mut Vec<u16>,
+) -> Result<Vec<u8>> {
+    let table = Table::parse(cpal).ok_or(MalformedFont)?;
+
+    let num_entries = entries.len() as u16;
+    let num_palettes = table.first_records.len();
+    let num_records = u16::try_from(usize::from(num_palettes) * entries.len())
+        .map_err(|_| OverflowError)?;
+
+    let mut colors = Writer::new();
+    for first in table.first_records {
+        for &i in entries {
+            let start = 4 * (usize::from(first) + usize::from(i));
+            colors.extend(table.records.get(start..start + 4).ok_or(MalformedFont)?);
+        }
+    }
+
+    // Version 1 adds the palette types, the palette labels and the labels of
+    // the entries. They are written after the color records.
+    let mut arrays = vec![];
+    if table.version >= 1 {
+        let types = table.types.map(|types| {
+            let mut w = Writer::new();
+            types.into_iter().for_each(|ty| w.write::<u32>(ty));
+            w.finish()
+        });
+
+        let mut labels = |labels: Option<Vec<u16>>| {
+            labels.filter(|labels| !labels.is_empty()).map(|labels| {
+                let mut w = Writer::new();
+                for label in labels {
+                    if label != NO_LABEL {
+                        name_ids.push(label);
+                    }
+                    w.write::<u16>(label);
+                }
+                w.finish()
+            })
+        };
+
+        let palette_labels =
+            labels(table.labels.map(|labels| labels.into_iter().collect()));
+        let entry_labels = table
+            .entry_labels
+            .map(|labels| entries.iter().map(|&i| labels.get(i)).collect::<Option<_>>())
+            .map(|labels| labels.ok_or(MalformedFont))
+            .transpose()?;
+        let entry_labels = labels(entry_labels);
+        arrays = vec![types, palette_labels, entry_labels];
+    }
+
+    let header_len = 12 + 2 * usize::from(num_palettes) + 4 * arrays.len();
+    let mut w = Writer::new();
+    w.write::<u16>(table.version.min(1));
+    w.write::<u16>(num_entries);
+    w.write::<u16>(num_palettes);
+    w.write::<u16>(num_records);
+    w.write::<u32>(header_len as u32);
+
+    for i in 0..num_palettes {
+        w.write::<u16>(i * num_entries);
+    }
+
+    let mut offset = header_len + colors.len();
+    for array in &arrays {
+        match array {
+            Some(array) => {
+                w.write::<u32>(u32::try_from(offset).map_err(|_| OverflowError)?);
+                offset += array.len();
+            }
+            None => w.write::<u32>(0),
+        }
+    }
+
+    w.extend(&colors.finish());
+    for array in arrays.into_iter().flatten() {
+        w.extend(&array);
+    }
+
+    Ok(w.finish())
+}
+
+/// A parsed `CPAL` table.
+struct Table<'a> {
+    version: u16,
+    num_entries: u16,
+    /// The index of the first color record of each palette.
+    first_records: LazyArray16<'a, u16>,
+    /// The color records, starting from the first one.
+    records: &'a [u8],
+    types: Option<LazyArray16<'a, u32>>,
+    labels: Option<LazyArray16<'a, u16>>,
+    entry_labels: Option<LazyArray16<'a, u16>>,
+}
+
+impl<'a> Table<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut r = Reader::new(data);
+        let version = r.read::<u16>()?;
+        let num_entries = r.read::<u16>()?;
+        let num_palettes = r.read::<u16>()?;
+        // numColorRecords
+        r.skip::<u16>();
+        let records = data.get(r.read::<u32>()? as usize..)?;
+        let first_records = r.read_array16::<u16>(num_palettes)?;
+
+        let mut table = Self {
+            version,
+            num_entries,
+            first_records,
+            records,
+            types: None,
+            labels: None,
+            entry_labels: None,
+        };
+
+        if version >= 1 {
+            table.types = array(data, r.read::<u32>()?, num_palettes)?;
+            table.labels = array(data, r.read::<u32>()?, num_palettes)?;
+            table.entry_labels = array(data, r.read::<u32>()?, num_entries)?;
+        }
+
+        Some(table)
+    }
+}
+
+/// Read an optional array at the given offset.
+fn array<'a, T: Readable<'a>>(
+    data: &'a [u8],
+    offset: u32,
+    count: u16,
+) -> Option<Option<LazyArray16<'a, T>>> {
+    if offset == 0 {
+        return Some(None);
+    }
+
+    let data = data.get(offset as usize..)?;
+    Reader::new(data).read_array16::<T>(count).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subset_version_1() {
+        let mut data = Vec::new();
+        data.extend(1u16.to_be_bytes()); // version
+        data.extend(3u16.to_be_bytes()); // numPaletteEntries
+        data.extend(2u16.to_be_bytes()); // numPalettes
+        data.extend(6u16.to_be_bytes()); // numColorRecords
+        data.extend(28u32.to_be_bytes()); // colorRecordsArrayOffset
+        data.extend(0u16.to_be_bytes()); // colorRecordIndices[0]
+        data.extend(3u16.to_be_bytes()); // colorRecordIndices[1]
+        data.extend(0u32.to_be_bytes()); // paletteTypesArrayOffset
+        data.extend(0u32.to_be_bytes()); // paletteLabelsArrayOffset
+        data.extend(52u32.to_be_bytes()); // paletteEntryLabelsArrayOffset
+        for color in 0..6u8 {
+            data.extend([color, color, color, 0xFF]); // colorRecords
+        }
+        data.extend(256u16.to_be_bytes()); // paletteEntryLabels[0]
+        data.extend(NO_LABEL.to_be_bytes()); // paletteEntryLabels[1]
+        data.extend(258u16.to_be_bytes()); // paletteEntryLabels[2]
+
+        let mut name_ids = vec![];
+        let subset = subset_table(&data, &[0, 2], &mut name_ids).unwrap();
+
+        let mut expected = Vec::new();
+        expected.extend(1u16.to_be_bytes()); // version
+        expected.extend(2u16.to_be_bytes()); // numPaletteEntries
+        expected.extend(2u16.to_be_bytes()); // numPalettes
+        expected.extend(4u16.to_be_bytes()); // numColorRecords
+        expected.extend(28u32.to_be_bytes()); // colorRecordsArrayOffset
+        expected.extend(0u16.to_be_bytes()); // colorRecordIndices[0]
+        expected.extend(2u16.to_be_bytes()); // colorRecordIndices[1]
+        expected.extend(0u32.to_be_bytes()); // paletteTypesArrayOffset
+        expected.extend(0u32.to_be_bytes()); // paletteLabelsArrayOffset
+        expected.extend(44u32.to_be_bytes()); // paletteEntryLabelsArrayOffset
+        for color in [0, 2, 3, 5u8] {
+            expected.extend([color, color, color, 0xFF]); // colorRecords
+        }
+        expected.extend(256u16.to_be_bytes()); // paletteEntryLabels[0]
+        expected.extend(258u16.to_be_bytes()); // paletteEntryLabels[1]
+
+        assert_eq!(subset, expected);
+        assert_eq!(name_ids, [256, 258]);
+    }
+}
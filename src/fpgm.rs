@@ -0,0 +1,524 @@
+//! The `fpgm` table contains the function definitions of a TrueType font, which are
+//! called by the instructions in the `prep` table and in the glyphs. Heavily hinted
+//! fonts can define a lot of functions, of which a subset only needs a few.
+//!
+//! To find out which functions are needed, we run a simplified interpreter over the
+//! programs that can be executed: The top level of `fpgm`, `prep` and the instructions
+//! of each glyph in the subset. It only keeps track of the values on the stack that are
+//! known statically and follows calls into the functions. The bodies of the functions
+//! that are never called are then removed. Each function definition itself stays in
+//! place, so that the numbering of the functions doesn't change.
+//!
+//! Since a function could in principle be called with any computed number, the
+//! analysis gives up as soon as the number of a called function is not known, or when
+//! it encounters instructions that it can't follow, like jumps. It also gives up
+//! after a fixed number of instructions, so that malicious programs can't make it
+//! run for long. In that case, the table is kept as is.
+
+use super::*;
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::ops::Range;
+
+pub fn subset(ctx: &mut Context) -> Result<()> {
+    let fpgm = ctx.expect_table(Tag::FPGM).ok_or(MalformedFont)?;
+    let prep = ctx.expect_table(Tag::PREP).unwrap_or_default();
+
+    let subsetted = glyf::subsetted_glyphs(ctx).and_then(|glyphs| {
+        let mut programs = vec![prep];
+        for glyph_data in glyphs.iter().filter(|data| !data.is_empty()) {
+            programs.push(glyf::instructions(glyph_data)?);
+        }
+
+        subset_functions(fpgm, &programs)
+    });
+
+    match subsetted {
+        Some(data) => ctx.push(Tag::FPGM, data),
+        None => ctx.push(Tag::FPGM, fpgm),
+    }
+
+    Ok(())
+}
+
+/// Remove the bodies of all functions that can't be called from the top level of
+/// `fpgm` or one of the programs. Returns `None` if this can't be determined.
+fn subset_functions(fpgm: &[u8], programs: &[&[u8]]) -> Option<Vec<u8>> {
+    let mut analyzer = Analyzer::new(fpgm);
+
+    // Values read from the storage area are the ones written anywhere in the
+    // previous run or zero, so we repeat until those don't change anymore.
+    let mut converged = false;
+    analyzer.budget = MAX_INSTRUCTIONS;
+    for _ in 0..MAX_RUNS {
+        analyzer.writes = Storage::default();
+        analyzer.lp = 1;
+        analyzer.run(fpgm, &mut vec![], 0, true)?;
+        for program in programs {
+            analyzer.lp = 1;
+            analyzer.run(program, &mut vec![], 0, false)?;
+        }
+
+        let writes = std::mem::take(&mut analyzer.writes);
+        if writes == analyzer.storage {
+            converged = true;
+            break;
+        }
+        analyzer.storage = writes;
+    }
+
+    if !converged {
+        return None;
+    }
+
+    // Keep the definitions, but drop the bodies of unused functions.
+    let mut w = Writer::with_capacity(fpgm.len());
+    let mut last = 0;
+    for (number, body) in &analyzer.definitions {
+        if !analyzer.reachable.contains(number) {
+            w.extend(fpgm.get(last..body.start)?);
+            last = body.end;
+        }
+    }
+    w.extend(fpgm.get(last..)?);
+
+    Some(w.finish())
+}
+
+/// How often the programs are run at most until the storage area is stable.
+const MAX_RUNS: usize = 8;
+/// How many instructions are interpreted at most over all runs. Analyzing all
+/// glyphs of a heavily hinted font like Roboto takes about 40,000, while a
+/// malicious font that exhausts the budget takes a few milliseconds in a release
+/// build.
+const MAX_INSTRUCTIONS: usize = 1_000_000;
+/// How deeply function calls can be nested.
+const MAX_CALL_DEPTH: u8 = 64;
+/// How many times a function can be called by a single `LOOPCALL`.
+const MAX_LOOP_COUNT: i32 = 1024;
+/// How large the stack can get.
+const MAX_STACK_SIZE: usize = 16384;
+
+/// A value on the stack of the interpreter.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Value {
+    /// A value that is known statically.
+    Known(i32),
+    /// A value that depends on the state of the rasterizer.
+    Unknown,
+}
+
+impl Value {
+    fn known(self) -> Option<i32> {
+        match self {
+            Value::Known(v) => Some(v),
+            Value::Unknown => None,
+        }
+    }
+
+    fn join(self, other: Self) -> Self {
+        if self == other {
+            self
+        } else {
+            Value::Unknown
+        }
+    }
+}
+
+/// The values in the storage area.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+struct Storage {
+    values: FxHashMap<i32, Value>,
+    /// Whether a value was written to an unknown location.
+    clobbered: bool,
+}
+
+impl Storage {
+    /// Read a location, which may not have been written yet when it is read.
+    /// Locations that were never written read as zero.
+    fn read(&self, location: Value) -> Value {
+        match location {
+            Value::Known(l) if !self.clobbered => self
+                .values
+                .get(&l)
+                .map_or(Value::Known(0), |&value| value.join(Value::Known(0))),
+            _ => Value::Unknown,
+        }
+    }
+
+    fn write(&mut self, location: Value, value: Value) {
+        match location {
+            Value::Known(l) => {
+                let entry = self.values.entry(l).or_insert(value);
+                *entry = entry.join(value);
+            }
+            Value::Unknown => self.clobbered = true,
+        }
+    }
+}
+
+struct Analyzer<'a> {
+    fpgm: &'a [u8],
+    /// The number and byte range of the body of each function definition.
+    definitions: Vec<(i32, Range<usize>)>,
+    /// The body of each function.
+    functions: FxHashMap<i32, Range<usize>>,
+    /// The functions that can be called.
+    reachable: FxHashSet<i32>,
+    /// The values in the storage area that are read.
+    storage: Storage,
+    /// The values that are written to the storage area in the current run.
+    writes: Storage,
+    /// How many more instructions may be interpreted in the current run.
+    budget: usize,
+    /// The number of points that some instructions take, set by `SLOOP`.
+    lp: usize,
+}
+
+impl<'a> Analyzer<'a> {
+    fn new(fpgm: &'a [u8]) -> Self {
+        Self {
+            fpgm,
+            definitions: vec![],
+            functions: FxHashMap::default(),
+            reachable: FxHashSet::default(),
+            storage: Storage::default(),
+            writes: Storage::default(),
+            budget: 0,
+            lp: 1,
+        }
+    }
+
+    /// Interpret a program. `top_level` is set for the top level of `fpgm`, the
+    /// only place where functions may be defined.
+    fn run(
+        &mut self,
+        code: &'a [u8],
+        stack: &mut Vec<Value>,
+        depth: u8,
+        top_level: bool,
+    ) -> Option<()> {
+        if depth > MAX_CALL_DEPTH {
+            return None;
+        }
+
+        let mut pc = 0;
+
+        while pc < code.len() {
+            self.budget = self.budget.checked_sub(1)?;
+            if stack.len() > MAX_STACK_SIZE {
+                return None;
+            }
+
+            let op = code[pc];
+            let next = next_instruction(code, pc)?;
+
+            match op {
+                // NPUSHB, NPUSHW, PUSHB, PUSHW
+                0x40 | 0x41 | 0xB0..=0xBF => {
+                    let (words, start) = match op {
+                        0x40 => (false, pc + 2),
+                        0x41 => (true, pc + 2),
+                        0xB0..=0xB7 => (false, pc + 1),
+                        _ => (true, pc + 1),
+                    };
+
+                    let data = code.get(start..next)?;
+                    if words {
+                        stack.extend(data.chunks_exact(2).map(|chunk| {
+                            Value::Known(i16::from_be_bytes([chunk[0], chunk[1]]) as i32)
+                        }));
+                    } else {
+                        stack.extend(data.iter().map(|&b| Value::Known(b as i32)));
+                    }
+                }
+                // DUP
+                0x20 => {
+                    let v = stack.last().copied().unwrap_or(Value::Unknown);
+                    stack.push(v);
+                }
+                // CLEAR
+                0x22 => stack.clear(),
+                // SWAP
+                0x23 => {
+                    let (a, b) = (pop(stack), pop(stack));
+                    stack.extend([a, b]);
+                }
+                // CINDEX, MINDEX
+                0x25 | 0x26 => {
+                    let index = pop(stack).known()?;
+                    let i = stack.len().checked_sub(usize::try_from(index).ok()?)?;
+                    let v = if op == 0x25 { *stack.get(i)? } else { stack.remove(i) };
+                    stack.push(v);
+                }
+                // ROLL
+                0x8A => {
+                    let (a, b, c) = (pop(stack), pop(stack), pop(stack));
+                    stack.extend([b, a, c]);
+                }
+                // ADD, SUB
+                0x60 | 0x61 => {
+                    let (b, a) = (pop(stack), pop(stack));
+                    stack.push(match (a, b) {
+                        (Value::Known(a), Value::Known(b)) if op == 0x60 => {
+                            Value::Known(a.wrapping_add(b))
+                        }
+                        (Value::Known(a), Value::Known(b)) => {
+                            Value::Known(a.wrapping_sub(b))
+                        }
+                        _ => Value::Unknown,
+                    });
+                }
+                // WS
+                0x42 => {
+                    let (value, location) = (pop(stack), pop(stack));
+                    self.writes.write(location, value);
+                }
+                // RS
+                0x43 => {
+                    let location = pop(stack);
+                    stack.push(self.storage.read(location));
+                }
+                // SLOOP
+                0x17 => self.lp = usize::try_from(pop(stack).known()?).ok()?,
+                // SHP, IP, ALIGNRP, FLIPPT
+                0x32 | 0x33 | 0x39 | 0x3C | 0x80 => {
+                    pop_n(stack, self.lp);
+                    self.lp = 1;
+                }
+                // SHPIX
+                0x38 => {
+                    pop_n(stack, self.lp + 1);
+                    self.lp = 1;
+                }
+                // DELTAP1, DELTAP2, DELTAP3, DELTAC1, DELTAC2, DELTAC3
+                0x5D | 0x71..=0x75 => {
+                    let n = usize::try_from(pop(stack).known()?).ok()?;
+                    pop_n(stack, 2 * n);
+                }
+                // IF
+                0x58 => {
+                    let condition = pop(stack);
+                    let (else_pc, eif_pc) = find_else_and_eif(code, next)?;
+                    let then_part = code.get(next..else_pc.unwrap_or(eif_pc))?;
+                    let else_part = match else_pc {
+                        Some(else_pc) => {
+                            code.get(next_instruction(code, else_pc)?..eif_pc)?
+                        }
+                        None => &[],
+                    };
+
+                    match condition {
+                        Value::Known(0) => self.run(else_part, stack, depth, false)?,
+                        Value::Known(_) => self.run(then_part, stack, depth, false)?,
+                        Value::Unknown => {
+                            let mut other = stack.clone();
+                            let lp = self.lp;
+                            self.run(then_part, stack, depth, false)?;
+                            let then_lp = std::mem::replace(&mut self.lp, lp);
+                            self.run(else_part, &mut other, depth, false)?;
+                            if self.lp != then_lp {
+                                return None;
+                            }
+
+                            if stack.len() == other.len() {
+                                for (a, b) in stack.iter_mut().zip(other) {
+                                    *a = a.join(b);
+                                }
+                            } else {
+                                // The branches consumed a different number of
+                                // values, so we don't know which value is where
+                                // anymore. Values below are unknown, too, since
+                                // popping from an empty stack is unknown.
+                                let len = stack.len().min(other.len());
+                                stack.clear();
+                                stack.resize(len, Value::Unknown);
+                            }
+                        }
+                    }
+
+                    pc = next_instruction(code, eif_pc)?;
+                    continue;
+                }
+                // CALL, LOOPCALL
+                0x2B | 0x2A => {
+                    let number = pop(stack).known()?;
+                    let count = if op == 0x2A { pop(stack).known()? } else { 1 };
+                    if count > MAX_LOOP_COUNT {
+                        return None;
+                    }
+
+                    let body = self.functions.get(&number)?.clone();
+                    self.reachable.insert(number);
+                    let body = self.fpgm.get(body)?;
+                    for _ in 0..count {
+                        self.run(body, stack, depth + 1, false)?;
+                    }
+                }
+                // FDEF
+                0x2C if top_level => {
+                    let number = pop(stack).known()?;
+                    let end = find_endf(code, next)?;
+                    if !self.functions.contains_key(&number) {
+                        self.definitions.push((number, next..end));
+                    }
+                    self.functions.insert(number, next..end);
+                    pc = next_instruction(code, end)?;
+                    continue;
+                }
+                // FDEF and IDEF outside of the top level of `fpgm`, ELSE and EIF without
+                // an IF, jumps and instructions that may be defined by the font.
+                0x2C | 0x89 | 0x1B | 0x59 | 0x2D | 0x1C | 0x78 | 0x79 => return None,
+                _ => {
+                    let (pops, pushes) = stack_effect(op)?;
+                    pop_n(stack, pops);
+                    stack.extend(std::iter::repeat_n(Value::Unknown, pushes));
+                }
+            }
+
+            pc = next;
+        }
+
+        Some(())
+    }
+}
+
+/// Pop a value from the stack. Popping from an empty stack is an error in the
+/// program, which we don't need to care about.
+fn pop(stack: &mut Vec<Value>) -> Value {
+    stack.pop().unwrap_or(Value::Unknown)
+}
+
+/// Pop `n` values from the stack.
+fn pop_n(stack: &mut Vec<Value>, n: usize) {
+    stack.truncate(stack.len().saturating_sub(n));
+}
+
+/// The number of values that an instruction without special handling pops from
+/// the stack and pushes to it. Returns `None` for undefined instructions.
+fn stack_effect(op: u8) -> Option<(usize, usize)> {
+    Some(match op {
+        // SVTCA, SPVTCA, SFVTCA, SFVTPV, RTG, RTHG, IUP, RTDG, FLIPON, FLIPOFF,
+        // ROFF, RUTG, RDTG
+        0x00..=0x05
+        | 0x0E
+        | 0x18
+        | 0x19
+        | 0x30
+        | 0x31
+        | 0x3D
+        | 0x4D
+        | 0x4E
+        | 0x7A
+        | 0x7C
+        | 0x7D => (0, 0),
+        // SPVTL, SFVTL, SPVFS, SFVFS
+        0x06..=0x0B => (2, 0),
+        // GPV, GFV
+        0x0C | 0x0D => (0, 2),
+        // ISECT
+        0x0F => (5, 0),
+        // SRP0-2, SZP0-2, SZPS, SMD, SCVTCI, SSWCI, SSW, POP, UTP, MDAP, SHC, SHZ,
+        // DEBUG, SDB, SDS, SROUND, S45ROUND, SANGW, AA, SCANCTRL, SCANTYPE, MDRP
+        0x10..=0x16
+        | 0x1A
+        | 0x1D..=0x1F
+        | 0x21
+        | 0x29
+        | 0x2E
+        | 0x2F
+        | 0x34..=0x37
+        | 0x4F
+        | 0x5E
+        | 0x5F
+        | 0x76
+        | 0x77
+        | 0x7E
+        | 0x7F
+        | 0x85
+        | 0x8D
+        | 0xC0..=0xDF => (1, 0),
+        // DEPTH, MPPEM, MPS, GETDATA
+        0x24 | 0x4B | 0x4C | 0x92 => (0, 1),
+        // ALIGNPTS, MSIRP, MIAP, WCVTP, SCFS, WCVTF, FLIPRGON, FLIPRGOFF, SDPVTL,
+        // INSTCTRL, MIRP
+        0x27
+        | 0x3A
+        | 0x3B
+        | 0x3E
+        | 0x3F
+        | 0x44
+        | 0x48
+        | 0x70
+        | 0x81
+        | 0x82
+        | 0x86
+        | 0x87
+        | 0x8E
+        | 0xE0..=0xFF => (2, 0),
+        // RCVT, GC, ODD, EVEN, NOT, ABS, NEG, FLOOR, CEILING, ROUND, NROUND, GETINFO
+        0x45..=0x47 | 0x56 | 0x57 | 0x5C | 0x64..=0x6F | 0x88 => (1, 1),
+        // MD, LT, LTEQ, GT, GTEQ, EQ, NEQ, AND, OR, DIV, MUL, MAX, MIN
+        0x49 | 0x4A | 0x50..=0x55 | 0x5A | 0x5B | 0x62 | 0x63 | 0x8B | 0x8C => (2, 1),
+        _ => return None,
+    })
+}
+
+/// Returns the offset of the instruction after the one at `pc`.
+fn next_instruction(code: &[u8], pc: usize) -> Option<usize> {
+    let op = *code.get(pc)?;
+    let len = match op {
+        // NPUSHB
+        0x40 => 2 + *code.get(pc + 1)? as usize,
+        // NPUSHW
+        0x41 => 2 + 2 * *code.get(pc + 1)? as usize,
+        // PUSHB
+        0xB0..=0xB7 => 2 + (op - 0xB0) as usize,
+        // PUSHW
+        0xB8..=0xBF => 3 + 2 * (op - 0xB8) as usize,
+        _ => 1,
+    };
+
+    let next = pc + len;
+    (next <= code.len()).then_some(next)
+}
+
+/// Find the `ELSE` (if any) and `EIF` that belong to an `IF` whose body starts
+/// at `pc`.
+fn find_else_and_eif(code: &[u8], mut pc: usize) -> Option<(Option<usize>, usize)> {
+    let mut nesting = 0;
+    let mut else_pc = None;
+
+    while pc < code.len() {
+        match code[pc] {
+            // IF
+            0x58 => nesting += 1,
+            // ELSE
+            0x1B if nesting == 0 => else_pc = Some(pc),
+            // EIF
+            0x59 if nesting == 0 => return Some((else_pc, pc)),
+            0x59 => nesting -= 1,
+            _ => {}
+        }
+
+        pc = next_instruction(code, pc)?;
+    }
+
+    None
+}
+
+/// Find the `ENDF` of a function whose body starts at `pc`.
+fn find_endf(code: &[u8], mut pc: usize) -> Option<usize> {
+    while pc < code.len() {
+        match code[pc] {
+            // ENDF
+            0x2D => return Some(pc),
+            // Nested FDEF or IDEF
+            0x2C | 0x89 => return None,
+            _ => {}
+        }
+
+        pc = next_instruction(code, pc)?;
+    }
+
+    None
+}
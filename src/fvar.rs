@@ -0,0 +1,86 @@
+//! The `fvar` table defines the variation axes and named instances of a font. It
+//! doesn't depend on the glyphs, so it can be kept as is, but we need to make sure
+//! that the `name` table keeps the names it refers to. When partially instancing,
+//! pinned axes are removed, the ranges of the remaining axes are updated, and named
+//! instances that lie outside of the new limits are dropped.
+
+use super::*;
+use skrifa::raw::types::Fixed;
+use skrifa::raw::{FontData, FontRead};
+use skrifa::MetadataProvider;
+use write_fonts::dump_table;
+use write_fonts::from_obj::ToOwnedTable;
+use write_fonts::tables::fvar::Fvar;
+
+pub fn subset(ctx: &mut Context) -> Result<()> {
+    let fvar = ctx.expect_table(Tag::FVAR).ok_or(MalformedFont)?;
+    let mut fvar: Fvar = skrifa::raw::tables::fvar::Fvar::read(FontData::new(fvar))
+        .map_err(|_| MalformedFont)?
+        .to_owned_table();
+    let arrays = &mut fvar.axis_instance_arrays;
+
+    if let Interjector::Instancer(instancer) = &ctx.interjector {
+        let axes = instancer.axes();
+
+        arrays.instances.retain(|instance| {
+            axes.iter().zip(&instance.coordinates).all(|(axis, &coord)| {
+                Fixed::from_f64(axis.user.0) <= coord
+                    && coord <= Fixed::from_f64(axis.user.2)
+            })
+        });
+
+        for instance in &mut arrays.instances {
+            instance.coordinates = axes
+                .iter()
+                .zip(&instance.coordinates)
+                .filter(|(axis, _)| !axis.is_pinned())
+                .map(|(_, &coord)| coord)
+                .collect();
+        }
+
+        arrays.axes = axes
+            .iter()
+            .zip(std::mem::take(&mut arrays.axes))
+            .filter(|(axis, _)| !axis.is_pinned())
+            .map(|(axis, mut record)| {
+                record.min_value = Fixed::from_f64(axis.user.0);
+                record.default_value = Fixed::from_f64(axis.user.1);
+                record.max_value = Fixed::from_f64(axis.user.2);
+                record
+            })
+            .collect();
+    }
+
+    for instance in &arrays.instances {
+        ctx.name_ids.push(instance.subfamily_name_id.to_u16());
+        ctx.name_ids
+            .extend(instance.post_script_name_id.map(|id| id.to_u16()));
+    }
+
+    ctx.name_ids
+        .extend(arrays.axes.iter().map(|record| record.axis_name_id.to_u16()));
+    ctx.push(Tag::FVAR, dump_table(&fvar).map_err(|_| Error::SubsetError)?);
+
+    Ok(())
+}
+
+/// Read the named instances of a font, together with their names.
+pub fn named_instances(data: &[u8], index: u32) -> Result<Vec<NamedInstance>> {
+    let font = skrifa::FontRef::from_index(data, index).map_err(|_| MalformedFont)?;
+    let tags = font.axes().iter().map(|axis| Tag(axis.tag().into_bytes()));
+    let name = |id| {
+        font.localized_strings(id)
+            .english_or_first()
+            .map(|name| name.to_string())
+    };
+
+    Ok(font
+        .named_instances()
+        .iter()
+        .map(|instance| NamedInstance {
+            subfamily_name: name(instance.subfamily_name_id()),
+            postscript_name: instance.postscript_name_id().and_then(name),
+            coordinates: tags.clone().zip(instance.user_coords()).collect(),
+        })
+        .collect())
+}
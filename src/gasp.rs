@@ -0,0 +1,63 @@
+//! The `gasp` table tells rasterizers whether to apply grid-fitting and
+//! anti-aliasing for ranges of pixel sizes. It doesn't refer to any glyphs, so it
+//! is normally kept as is. See the [specification].
+//!
+//! Grid-fitting relies on the hinting of the font, though. If the hinting is
+//! removed, either explicitly or because a variable font is instanced, we replace
+//! the ranges with a single one that asks for smoothing at all sizes instead.
+//!
+//! [specification]: https://learn.microsoft.com/en-us/typography/opentype/spec/gasp
+
+use super::*;
+
+/// Use grayscale rendering.
+const DOGRAY: u16 = 0x0002;
+/// Use smoothing along multiple axes with ClearType. Only defined in version 1.
+const SYMMETRIC_SMOOTHING: u16 = 0x0008;
+
+pub fn subset(ctx: &mut Context) -> Result<()> {
+    let gasp = ctx.expect_table(Tag::GASP).ok_or(MalformedFont)?;
+    if ctx.options.strip_hinting || !ctx.interjector.is_dummy() {
+        ctx.push(Tag::GASP, unhinted(gasp)?);
+    } else {
+        ctx.push(Tag::GASP, gasp);
+    }
+    Ok(())
+}
+
+/// Write a `gasp` table for a font without hinting, in the same version.
+fn unhinted(gasp: &[u8]) -> Result<Vec<u8>> {
+    let version = Reader::new(gasp).read::<u16>().ok_or(MalformedFont)?;
+    let flags = if version >= 1 { DOGRAY | SYMMETRIC_SMOOTHING } else { DOGRAY };
+
+    let mut w = Writer::new();
+    w.write::<u16>(version);
+    w.write::<u16>(1);
+    w.write::<u16>(0xFFFF);
+    w.write::<u16>(flags);
+    Ok(w.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unhinted_version_1() {
+        let mut data = Vec::new();
+        data.extend(1u16.to_be_bytes()); // version
+        data.extend(2u16.to_be_bytes()); // numRanges
+        data.extend(8u16.to_be_bytes()); // rangeMaxPPEM
+        data.extend(0x000Au16.to_be_bytes()); // rangeGaspBehavior
+        data.extend(0xFFFFu16.to_be_bytes()); // rangeMaxPPEM
+        data.extend(0x000Fu16.to_be_bytes()); // rangeGaspBehavior
+
+        let mut expected = Vec::new();
+        expected.extend(1u16.to_be_bytes()); // version
+        expected.extend(1u16.to_be_bytes()); // numRanges
+        expected.extend(0xFFFFu16.to_be_bytes()); // rangeMaxPPEM
+        expected.extend(0x000Au16.to_be_bytes()); // rangeGaspBehavior
+
+        assert_eq!(unhinted(&data).unwrap(), expected);
+    }
+}
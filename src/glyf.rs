@@ -52,6 +52,8 @@ pub fn subset(ctx: &mut Context) -> Result<()> {
     let mut maxp_data = MaxpData::default();
     #[allow(unused_mut)]
     let mut hmtx_data = Vec::new();
+    #[cfg(feature = "variable-fonts")]
+    let mut glyph_variations = Vec::new();
 
     subset_with(ctx, |old_gid, ctx| {
         let data = match &ctx.interjector {
@@ -60,9 +62,30 @@ pub fn subset(ctx: &mut Context) -> Result<()> {
             }
             #[cfg(feature = "variable-fonts")]
             Interjector::Skrifa(s) => {
-                let (advance, lsb, data) =
-                    s.interject(&mut maxp_data, old_gid).ok_or(MalformedFont)?;
+                let glyph_data = table.glyph_data(old_gid).ok_or(MalformedFont)?;
+                let is_composite = glyph_data.len() >= 2
+                    && i16::from_be_bytes([glyph_data[0], glyph_data[1]]) < 0;
+
+                // Composite glyphs keep their structure, so that the components
+                // don't have to be duplicated into each glyph that uses them.
+                // Simple glyphs keep their points, so that components attached
+                // to them by point numbers stay in place.
+                let (advance, lsb, data) = if is_composite {
+                    s.interject_composite(old_gid, glyph_data)
+                } else {
+                    s.interject_simple(&mut maxp_data, old_gid, glyph_data)
+                }
+                .ok_or(MalformedFont)?;
+                hmtx_data.push((advance, lsb));
+                Cow::Owned(data)
+            }
+            #[cfg(feature = "variable-fonts")]
+            Interjector::Instancer(instancer) => {
+                let glyph_data = table.glyph_data(old_gid).ok_or(MalformedFont)?;
+                let (advance, lsb, data, variations) =
+                    instancer.instance_glyph(&mut maxp_data, old_gid, glyph_data)?;
                 hmtx_data.push((advance, lsb));
+                glyph_variations.push(variations);
                 Cow::Owned(data)
             }
         };
@@ -70,7 +93,16 @@ pub fn subset(ctx: &mut Context) -> Result<()> {
         Ok(data)
     })?;
 
-    if ctx.interjector.is_skrifa() {
+    #[cfg(feature = "variable-fonts")]
+    if let Interjector::Instancer(_) = &ctx.interjector {
+        ctx.custom_gvar_data = Some(glyph_variations);
+    }
+
+    if !ctx.interjector.is_dummy() {
+        // The statistics of composite glyphs depend on their components, so they
+        // can only be computed once all glyphs are instanced.
+        let glyphs = subsetted_glyphs(ctx).ok_or(MalformedFont)?;
+        update_maxp_data(&glyphs, &mut maxp_data).ok_or(MalformedFont)?;
         ctx.custom_maxp_data = Some(maxp_data);
         ctx.custom_hmtx_data = Some(hmtx_data);
     }
@@ -167,8 +199,11 @@ fn subset_glyf_entries<'a>(
         let mut r = Reader::new(&glyph_data);
         let num_contours = r.read::<i16>().ok_or(MalformedFont)?;
 
+        let strip_hinting = ctx.options.strip_hinting;
         let glyph_data = if num_contours < 0 {
-            Cow::Owned(remap_component_glyph(&ctx.mapper, &glyph_data)?)
+            Cow::Owned(remap_component_glyph(&ctx.mapper, &glyph_data, strip_hinting)?)
+        } else if strip_hinting {
+            Cow::Owned(strip_simple_instructions(&glyph_data).ok_or(MalformedFont)?)
         } else {
             // Simple glyphs don't need any subsetting.
             glyph_data
@@ -187,7 +222,32 @@ fn subset_glyf_entries<'a>(
     Ok(glyf_entries)
 }
 
-fn remap_component_glyph(mapper: &GlyphRemapper, data: &[u8]) -> Result<Vec<u8>> {
+/// Remove the instructions of a simple glyph.
+fn strip_simple_instructions(data: &[u8]) -> Option<Vec<u8>> {
+    let mut r = Reader::new(data);
+    let num_contours = r.read::<i16>()?;
+
+    // numberOfContours, xMin, yMin, xMax, yMax and endPtsOfContours
+    let header_len = 10 + 2 * num_contours as usize;
+    let header = data.get(..header_len)?;
+    r.jump(header_len);
+    let instructions_len = r.read::<u16>()?;
+    r.skip_bytes(instructions_len as usize);
+
+    let mut w = Writer::with_capacity(data.len());
+    w.extend(header);
+    w.write::<u16>(0);
+    w.extend(r.tail()?);
+    Some(w.finish())
+}
+
+/// Remap the components of a composite glyph to their new glyph IDs, optionally
+/// removing its instructions.
+fn remap_component_glyph(
+    mapper: &GlyphRemapper,
+    data: &[u8],
+    strip_instructions: bool,
+) -> Result<Vec<u8>> {
     let mut r = Reader::new(data);
     let mut w = Writer::with_capacity(data.len());
 
@@ -210,7 +270,11 @@ fn remap_component_glyph(mapper: &GlyphRemapper, data: &[u8]) -> Result<Vec<u8>>
     let mut done;
 
     loop {
-        let flags = r.read::<u16>().ok_or(MalformedFont)?;
+        let mut flags = r.read::<u16>().ok_or(MalformedFont)?;
+        let has_instructions = flags & WE_HAVE_INSTRUCTIONS != 0;
+        if strip_instructions {
+            flags &= !WE_HAVE_INSTRUCTIONS;
+        }
         w.write(flags);
         let old_component = r.read::<u16>().ok_or(MalformedFont)?;
         let new_component = mapper.get(old_component).ok_or(MalformedFont)?;
@@ -238,7 +302,7 @@ fn remap_component_glyph(mapper: &GlyphRemapper, data: &[u8]) -> Result<Vec<u8>>
         done = flags & MORE_COMPONENTS == 0;
 
         if done {
-            if flags & WE_HAVE_INSTRUCTIONS != 0 {
+            if has_instructions && !strip_instructions {
                 w.write(r.tail().ok_or(MalformedFont)?);
             }
 
@@ -249,8 +313,154 @@ fn remap_component_glyph(mapper: &GlyphRemapper, data: &[u8]) -> Result<Vec<u8>>
     Ok(w.finish())
 }
 
+/// Returns the data of each glyph in the subsetted `glyf` table, in the new
+/// glyph order.
+pub(crate) fn subsetted_glyphs<'b>(ctx: &'b Context) -> Option<Vec<&'b [u8]>> {
+    let glyf = ctx.subsetted_table(Tag::GLYF)?;
+    let mut loca = Reader::new(ctx.subsetted_table(Tag::LOCA)?);
+    let mut read_offset = || {
+        if ctx.long_loca {
+            loca.read::<u32>().map(|offset| offset as usize)
+        } else {
+            loca.read::<u16>().map(|offset| 2 * offset as usize)
+        }
+    };
+
+    let mut glyphs = Vec::with_capacity(ctx.mapper.num_gids() as usize);
+    let mut start = read_offset()?;
+    for _ in 0..ctx.mapper.num_gids() {
+        let end = read_offset()?;
+        glyphs.push(glyf.get(start..end)?);
+        start = end;
+    }
+
+    Some(glyphs)
+}
+
+/// Update the glyph statistics of the `maxp` data with the given glyphs. Components
+/// of composite glyphs are resolved with the glyph IDs in the same list.
+pub(crate) fn update_maxp_data(glyphs: &[&[u8]], maxp_data: &mut MaxpData) -> Option<()> {
+    for glyph_data in glyphs.iter().filter(|data| !data.is_empty()) {
+        let mut r = Reader::new(glyph_data);
+        let num_contours = r.read::<i16>()?;
+
+        if num_contours >= 0 {
+            let (points, contours) = simple_stats(glyph_data)?;
+            maxp_data.max_points = maxp_data.max_points.max(points);
+            maxp_data.max_contours = maxp_data.max_contours.max(contours);
+        } else {
+            let (points, contours, depth) = composite_stats(glyphs, glyph_data, 1)?;
+            maxp_data.max_composite_points = maxp_data.max_composite_points.max(points);
+            maxp_data.max_composite_contours =
+                maxp_data.max_composite_contours.max(contours);
+            maxp_data.max_component_elements = maxp_data
+                .max_component_elements
+                .max(component_glyphs(glyph_data)?.count() as u16);
+            maxp_data.max_component_depth = maxp_data.max_component_depth.max(depth);
+        }
+    }
+
+    Some(())
+}
+
+/// Returns the number of points and contours of a simple glyph.
+fn simple_stats(glyph_data: &[u8]) -> Option<(u16, u16)> {
+    let mut r = Reader::new(glyph_data);
+    let num_contours = r.read::<i16>()?;
+    if num_contours <= 0 {
+        return Some((0, 0));
+    }
+
+    // xMin, yMin, xMax, yMax and all but the last contour end.
+    r.skip_bytes(8 + 2 * (num_contours as usize - 1));
+    let last_end = r.read::<u16>()?;
+    Some((last_end.checked_add(1)?, num_contours as u16))
+}
+
+/// Returns the total number of points and contours of the simple glyphs a
+/// composite glyph consists of, as well as its nesting depth.
+fn composite_stats(
+    glyphs: &[&[u8]],
+    glyph_data: &[u8],
+    depth: u16,
+) -> Option<(u16, u16, u16)> {
+    // Protect against cyclic components.
+    if depth > 64 {
+        return None;
+    }
+
+    let (mut points, mut contours, mut max_depth) = (0u16, 0u16, depth);
+    for component in component_glyphs(glyph_data)? {
+        let data = *glyphs.get(component as usize)?;
+        if data.is_empty() {
+            continue;
+        }
+
+        let (p, c) = if Reader::new(data).read::<i16>()? < 0 {
+            let (p, c, d) = composite_stats(glyphs, data, depth + 1)?;
+            max_depth = max_depth.max(d);
+            (p, c)
+        } else {
+            simple_stats(data)?
+        };
+
+        points = points.saturating_add(p);
+        contours = contours.saturating_add(c);
+    }
+
+    Some((points, contours, max_depth))
+}
+
+/// Returns the instructions of a glyph.
+pub(crate) fn instructions(glyph_data: &[u8]) -> Option<&[u8]> {
+    let mut r = Reader::new(glyph_data);
+    let num_contours = r.read::<i16>()?;
+
+    // xMin, yMin, xMax, yMax
+    r.skip_bytes(8);
+
+    if num_contours >= 0 {
+        // endPtsOfContours
+        r.skip_bytes(2 * num_contours as usize);
+        let len = r.read::<u16>()?;
+        return r.read_bytes(len as usize);
+    }
+
+    const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+    const WE_HAVE_A_SCALE: u16 = 0x0008;
+    const MORE_COMPONENTS: u16 = 0x0020;
+    const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+    const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+    const WE_HAVE_INSTRUCTIONS: u16 = 0x0100;
+
+    loop {
+        let flags = r.read::<u16>()?;
+        // glyphIndex and the arguments.
+        r.skip_bytes(if flags & ARG_1_AND_2_ARE_WORDS != 0 { 6 } else { 4 });
+
+        if flags & WE_HAVE_A_SCALE != 0 {
+            r.skip_bytes(2);
+        } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+            r.skip_bytes(4);
+        } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+            r.skip_bytes(8);
+        }
+
+        if flags & MORE_COMPONENTS == 0 {
+            if flags & WE_HAVE_INSTRUCTIONS == 0 {
+                return Some(&[]);
+            }
+
+            let len = r.read::<u16>()?;
+            return r.read_bytes(len as usize);
+        }
+    }
+}
+
 /// Returns an iterator over the component glyphs of a glyph.
-fn component_glyphs(glyph_data: &[u8]) -> Option<impl Iterator<Item = u16> + '_> {
+pub(crate) fn component_glyphs(
+    glyph_data: &[u8],
+) -> Option<impl Iterator<Item = u16> + '_> {
     let mut r = Reader::new(glyph_data);
 
     // Number of contours
@@ -300,3 +510,99 @@ fn component_glyphs(glyph_data: &[u8]) -> Option<impl Iterator<Item = u16> + '_>
         Some(component)
     }))
 }
+
+/// Rewrite a component glyph so that it has the given bounding box and each of its
+/// components is moved by the corresponding offset delta. This is used when instancing
+/// variable fonts, where component offsets can be varied via `gvar`.
+///
+/// Instructions are dropped, since we don't keep any hinting tables when instancing.
+#[cfg(feature = "variable-fonts")]
+pub(crate) fn instance_component_glyph(
+    data: &[u8],
+    bbox: [i16; 4],
+    deltas: &[(i32, i32)],
+) -> Option<Vec<u8>> {
+    let mut r = Reader::new(data);
+    let mut w = Writer::with_capacity(data.len());
+
+    // number of contours
+    w.write(r.read::<i16>()?);
+
+    // xMin, yMin, xMax, yMax
+    r.skip_bytes(8);
+    w.write(bbox);
+
+    const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+    const ARGS_ARE_XY_VALUES: u16 = 0x0002;
+    const WE_HAVE_A_SCALE: u16 = 0x0008;
+    const MORE_COMPONENTS: u16 = 0x0020;
+    const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+    const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+    const WE_HAVE_INSTRUCTIONS: u16 = 0x0100;
+
+    let mut index = 0;
+
+    loop {
+        let flags = r.read::<u16>()? & !WE_HAVE_INSTRUCTIONS;
+        let component = r.read::<u16>()?;
+
+        if flags & ARGS_ARE_XY_VALUES != 0 {
+            let (x, y) = if flags & ARG_1_AND_2_ARE_WORDS != 0 {
+                (i32::from(r.read::<i16>()?), i32::from(r.read::<i16>()?))
+            } else {
+                (i32::from(r.read::<u8>()? as i8), i32::from(r.read::<u8>()? as i8))
+            };
+
+            let (dx, dy) = deltas.get(index).copied().unwrap_or_default();
+            let x = i16::try_from(x + dx).ok()?;
+            let y = i16::try_from(y + dy).ok()?;
+
+            // Use the smallest possible encoding for the new offsets.
+            match (i8::try_from(x), i8::try_from(y)) {
+                (Ok(x), Ok(y)) => {
+                    w.write(flags & !ARG_1_AND_2_ARE_WORDS);
+                    w.write(component);
+                    w.write(x as u8);
+                    w.write(y as u8);
+                }
+                _ => {
+                    w.write(flags | ARG_1_AND_2_ARE_WORDS);
+                    w.write(component);
+                    w.write(x);
+                    w.write(y);
+                }
+            }
+        } else {
+            // Point numbers are not affected by variations, since instanced glyphs
+            // keep their points.
+            w.write(flags);
+            w.write(component);
+
+            if flags & ARG_1_AND_2_ARE_WORDS != 0 {
+                w.write(r.read::<u32>()?);
+            } else {
+                w.write(r.read::<u16>()?);
+            }
+        }
+
+        if flags & WE_HAVE_A_SCALE != 0 {
+            w.write(r.read::<F2Dot14>()?);
+        } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+            w.write(r.read::<F2Dot14>()?);
+            w.write(r.read::<F2Dot14>()?);
+        } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+            w.write(r.read::<F2Dot14>()?);
+            w.write(r.read::<F2Dot14>()?);
+            w.write(r.read::<F2Dot14>()?);
+            w.write(r.read::<F2Dot14>()?);
+        }
+
+        if flags & MORE_COMPONENTS == 0 {
+            break;
+        }
+
+        index += 1;
+    }
+
+    Some(w.finish())
+}
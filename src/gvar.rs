@@ -0,0 +1,120 @@
+//! The `gvar` table contains the variations of the glyph outlines. The variation data
+//! of a glyph only refers to its own points (or to its components), so subsetting
+//! the table works similarly to `glyf`: We need to extract the variation data of all
+//! requested glyphs, write it in the order of the new glyphs, and update the offsets.
+//! The shared tuples can be copied as is.
+//!
+//! When partially instancing, the table is instead rebuilt from the remaining
+//! variations of each glyph.
+
+use super::*;
+use crate::Error::{OverflowError, SubsetError};
+use write_fonts::dump_table;
+use write_fonts::tables::gvar::{GlyphVariations, Gvar};
+
+pub fn subset(ctx: &mut Context) -> Result<()> {
+    if let Interjector::Instancer(instancer) = &ctx.interjector {
+        let variations = ctx
+            .custom_gvar_data
+            .take()
+            .ok_or(SubsetError)?
+            .into_iter()
+            .enumerate()
+            .map(|(gid, deltas)| {
+                GlyphVariations::new(skrifa::GlyphId::new(gid as u32), deltas)
+            })
+            .collect();
+
+        let gvar =
+            Gvar::new(variations, instancer.axis_count()).map_err(|_| SubsetError)?;
+        ctx.push(Tag::GVAR, dump_table(&gvar).map_err(|_| SubsetError)?);
+        return Ok(());
+    }
+
+    let gvar = ctx.expect_table(Tag::GVAR).ok_or(MalformedFont)?;
+    let mut r = Reader::new(gvar);
+
+    let version = r.read::<u32>().ok_or(MalformedFont)?;
+    let axis_count = r.read::<u16>().ok_or(MalformedFont)?;
+    let shared_tuple_count = r.read::<u16>().ok_or(MalformedFont)?;
+    let shared_tuples_offset = r.read::<u32>().ok_or(MalformedFont)? as usize;
+    let glyph_count = r.read::<u16>().ok_or(MalformedFont)?;
+    let flags = r.read::<u16>().ok_or(MalformedFont)?;
+    let data_offset = r.read::<u32>().ok_or(MalformedFont)? as usize;
+    let long = flags & 1 != 0;
+
+    let mut offsets = Vec::with_capacity(glyph_count as usize + 1);
+    for _ in 0..=glyph_count {
+        let offset = if long {
+            r.read::<u32>().ok_or(MalformedFont)? as usize
+        } else {
+            2 * r.read::<u16>().ok_or(MalformedFont)? as usize
+        };
+
+        offsets.push(data_offset.checked_add(offset).ok_or(MalformedFont)?);
+    }
+
+    let shared_tuples_len = shared_tuple_count as usize * axis_count as usize * 2;
+    let shared_tuples = gvar
+        .get(shared_tuples_offset..shared_tuples_offset + shared_tuples_len)
+        .ok_or(MalformedFont)?;
+
+    let mut entries = vec![];
+    for old_gid in ctx.mapper.remapped_gids() {
+        // Glyphs without any variations have empty data.
+        let entry = match offsets.get(old_gid as usize..old_gid as usize + 2) {
+            Some(&[start, end]) if start < end => {
+                gvar.get(start..end).ok_or(MalformedFont)?
+            }
+            _ => &[],
+        };
+
+        entries.push(entry);
+    }
+
+    // Entries need to be padded to an even length for short offsets.
+    let size = entries
+        .iter()
+        .map(|entry| entry.len() + entry.len() % 2)
+        .sum::<usize>();
+    let long = size > 2 * (u16::MAX as usize);
+    let num_glyphs = u16::try_from(entries.len()).map_err(|_| OverflowError)?;
+
+    let offsets_len = (entries.len() + 1) * if long { 4 } else { 2 };
+    let shared_tuples_offset = 20 + offsets_len;
+    let data_offset = shared_tuples_offset + shared_tuples.len();
+
+    let mut sub_gvar = Writer::new();
+    sub_gvar.write::<u32>(version);
+    sub_gvar.write::<u16>(axis_count);
+    sub_gvar.write::<u16>(shared_tuple_count);
+    sub_gvar.write::<u32>(shared_tuples_offset as u32);
+    sub_gvar.write::<u16>(num_glyphs);
+    sub_gvar.write::<u16>(long as u16);
+    sub_gvar.write::<u32>(u32::try_from(data_offset).map_err(|_| OverflowError)?);
+
+    let mut offset = 0;
+    for entry in entries.iter().map(|entry| entry.len()).chain(std::iter::once(0)) {
+        if long {
+            sub_gvar.write::<u32>(u32::try_from(offset).map_err(|_| OverflowError)?);
+        } else {
+            sub_gvar.write::<u16>((offset / 2) as u16);
+        }
+
+        offset += if long { entry } else { entry + entry % 2 };
+    }
+
+    sub_gvar.extend(shared_tuples);
+
+    for entry in entries {
+        sub_gvar.extend(entry);
+
+        if !long {
+            sub_gvar.align(2);
+        }
+    }
+
+    ctx.push(Tag::GVAR, sub_gvar.finish());
+
+    Ok(())
+}
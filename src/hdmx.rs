@@ -0,0 +1,79 @@
+//! The `hdmx` table contains the advance widths of the glyphs in pixels, as
+//! they result from hinting, for a couple of pixel sizes. Each record covers all
+//! glyphs, so we simply rewrite the widths in the order of the new glyph IDs and
+//! recompute the maximum width. See the [specification].
+//!
+//! [specification]: https://learn.microsoft.com/en-us/typography/opentype/spec/hdmx
+
+use super::*;
+use crate::Error::OverflowError;
+
+pub fn subset(ctx: &mut Context) -> Result<()> {
+    let hdmx = ctx.expect_table(Tag::HDMX).ok_or(MalformedFont)?;
+    let num_glyphs = ctx.face.num_glyphs().ok_or(MalformedFont)?;
+    let data = subset_table(hdmx, num_glyphs, &ctx.mapper)?;
+    ctx.push(Tag::HDMX, data);
+    Ok(())
+}
+
+fn subset_table(hdmx: &[u8], num_glyphs: u16, mapper: &GlyphRemapper) -> Result<Vec<u8>> {
+    let mut r = Reader::new(hdmx);
+    let version = r.read::<u16>().ok_or(MalformedFont)?;
+    let num_records = r.read::<u16>().ok_or(MalformedFont)?;
+    let record_size = r.read::<u32>().ok_or(MalformedFont)? as usize;
+    if record_size < 2 + usize::from(num_glyphs) {
+        return Err(MalformedFont);
+    }
+
+    // Records are padded to a multiple of four bytes.
+    let new_record_size = (2 + usize::from(mapper.num_gids())).next_multiple_of(4);
+
+    let mut w = Writer::new();
+    w.write::<u16>(version);
+    w.write::<u16>(num_records);
+    w.write::<u32>(u32::try_from(new_record_size).map_err(|_| OverflowError)?);
+
+    for _ in 0..num_records {
+        let record = r.read_bytes(record_size).ok_or(MalformedFont)?;
+        let widths = mapper
+            .remapped_gids()
+            .map(|gid| record.get(2 + usize::from(gid)).copied())
+            .collect::<Option<Vec<_>>>()
+            .ok_or(MalformedFont)?;
+
+        // pixelSize, maxWidth
+        w.write::<u8>(record[0]);
+        w.write::<u8>(widths.iter().copied().max().unwrap_or_default());
+        w.extend(&widths);
+        w.align(4);
+    }
+
+    Ok(w.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subset_records() {
+        let mut data = Vec::new();
+        data.extend(0u16.to_be_bytes()); // version
+        data.extend(2u16.to_be_bytes()); // numRecords
+        data.extend(8u32.to_be_bytes()); // sizeDeviceRecord
+        data.extend([12, 9, 5, 6, 9, 7, 0, 0]); // pixelSize, maxWidth, widths
+        data.extend([24, 18, 10, 12, 18, 14, 0, 0]); // pixelSize, maxWidth, widths
+
+        let mapper = GlyphRemapper::new_from_glyphs(&[3, 1]);
+        let subset = subset_table(&data, 4, &mapper).unwrap();
+
+        let mut expected = Vec::new();
+        expected.extend(0u16.to_be_bytes()); // version
+        expected.extend(2u16.to_be_bytes()); // numRecords
+        expected.extend(8u32.to_be_bytes()); // sizeDeviceRecord
+        expected.extend([12, 7, 5, 7, 6, 0, 0, 0]); // pixelSize, maxWidth, widths
+        expected.extend([24, 14, 10, 14, 12, 0, 0, 0]); // pixelSize, maxWidth, widths
+
+        assert_eq!(subset, expected);
+    }
+}
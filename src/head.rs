@@ -1,6 +1,8 @@
 //! The `head` table mostly contains information that can be reused from the
 //! old table, except for the `loca` format, which depends on the size of the
-//! glyph data. The checksum will be recalculated in the very end.
+//! glyph data. The checksum will be recalculated in the very end. When instancing,
+//! the style flags are updated to the ones of the instance. The bounding box is
+//! recomputed from the outlines in the subset when instancing or if requested.
 
 use super::*;
 
@@ -9,6 +11,53 @@ pub fn subset(ctx: &mut Context) -> Result<()> {
     let index_to_loc = head.get_mut(50..52).ok_or(MalformedFont)?;
     index_to_loc[0] = 0;
     index_to_loc[1] = ctx.long_loca as u8;
+
+    #[cfg(feature = "variable-fonts")]
+    if let Some((bold, italic)) = ctx.style.as_ref().and_then(|style| style.bold_italic) {
+        let mac_style = head.get_mut(44..46).ok_or(MalformedFont)?;
+        let mut flags = u16::from_be_bytes([mac_style[0], mac_style[1]]);
+        flags = (flags & !0b11) | bold as u16 | (italic as u16) << 1;
+        mac_style.copy_from_slice(&flags.to_be_bytes());
+    }
+
+    // Bitmap fonts don't have outlines to compute the bounding box from.
+    let recompute = ctx.options.recompute_bbox || !ctx.interjector.is_dummy();
+    if recompute && ctx.flavor != FontFlavor::Bitmap {
+        let bbox = match ctx.flavor {
+            FontFlavor::Cff => ctx.bbox,
+            _ => glyf_bbox(ctx),
+        }
+        .ok_or(MalformedFont)?;
+        let fields = head.get_mut(36..44).ok_or(MalformedFont)?;
+        for (field, value) in fields.chunks_exact_mut(2).zip(bbox) {
+            field.copy_from_slice(&value.to_be_bytes());
+        }
+    }
+
     ctx.push(Tag::HEAD, head);
     Ok(())
 }
+
+/// Compute the union of the bounding boxes of all glyphs in the subsetted `glyf`
+/// table. The bounding box of each glyph is stored in its header.
+fn glyf_bbox(ctx: &Context) -> Option<[i16; 4]> {
+    let mut bbox: Option<[i16; 4]> = None;
+    for glyph_data in glyf::subsetted_glyphs(ctx)? {
+        // Empty glyphs don't have a bounding box.
+        if glyph_data.is_empty() {
+            continue;
+        }
+
+        let mut r = Reader::new(glyph_data);
+        r.read::<i16>()?;
+        let [x_min, y_min, x_max, y_max] = [r.read()?, r.read()?, r.read()?, r.read()?];
+        bbox = Some(match bbox {
+            Some(b) => {
+                [b[0].min(x_min), b[1].min(y_min), b[2].max(x_max), b[3].max(y_max)]
+            }
+            None => [x_min, y_min, x_max, y_max],
+        });
+    }
+
+    Some(bbox.unwrap_or_default())
+}
@@ -6,7 +6,8 @@
 //! when rewriting the table.
 //! While doing so, we also rewrite the `hhea` table, which contains
 //! the number of glyphs that contain both, advance width and
-//! left side bearing metrics.
+//! left side bearing metrics, as well as the global metrics that need to be
+//! updated when instancing.
 
 // The parsing logic was taken from ttf-parser.
 
@@ -22,7 +23,7 @@ pub fn subset(ctx: &mut Context) -> Result<()> {
         match &ctx.interjector {
             Interjector::Dummy(_) => extract_metrics(hmtx, &mut new_metrics, ctx)?,
             #[cfg(feature = "variable-fonts")]
-            Interjector::Skrifa(_) => {
+            Interjector::Skrifa(_) | Interjector::Instancer(_) => {
                 new_metrics = ctx.custom_hmtx_data.take().ok_or(Error::SubsetError)?
             }
         }
@@ -61,7 +62,15 @@ pub fn subset(ctx: &mut Context) -> Result<()> {
     sub_hhea.extend(hhea.get(..hhea.len() - 2).ok_or(MalformedFont)?);
     sub_hhea.write::<u16>(last_advance_width_index + 1);
 
-    ctx.push(Tag::HHEA, sub_hhea.finish());
+    #[allow(unused_mut)]
+    let mut sub_hhea = sub_hhea.finish();
+
+    // When instancing, the ascender, descender and line gap, as well as the caret
+    // metrics, might vary.
+    #[cfg(feature = "variable-fonts")]
+    mvar::apply(ctx, &mut sub_hhea, mvar::HHEA_FIELDS)?;
+
+    ctx.push(Tag::HHEA, sub_hhea);
 
     Ok(())
 }
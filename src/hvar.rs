@@ -0,0 +1,115 @@
+//! The `HVAR` table contains the variations of the horizontal metrics. It consists of
+//! an item variation store and a couple of mappings from glyph IDs to items in the
+//! store. To subset it, we collect the deltas of all requested glyphs, build a new
+//! (smaller) item variation store from them, and write new mappings in the order of
+//! the new glyphs.
+//!
+//! When partially instancing, the deltas of each glyph are additionally rebased onto
+//! the new axis limits. The deltas that apply at the new default location have already
+//! been baked into `hmtx` via the phantom points of `gvar`.
+
+use super::*;
+use crate::instancer::{ot_round, read_store_item, region};
+use crate::Error::SubsetError;
+use skrifa::raw::tables::variations::{DeltaSetIndexMap, ItemVariationStore};
+use skrifa::raw::{FontData, FontRead};
+use write_fonts::dump_table;
+use write_fonts::tables::hvar::Hvar;
+use write_fonts::tables::variations::ivs_builder::VariationStoreBuilder;
+
+pub fn subset(ctx: &mut Context) -> Result<()> {
+    let table = ctx.expect_table(Tag::HVAR).ok_or(MalformedFont)?;
+    let mut r = Reader::new(table);
+
+    // Version
+    r.read::<u32>().ok_or(MalformedFont)?;
+    let store_offset = r.read::<u32>().ok_or(MalformedFont)?;
+    let store = ItemVariationStore::read(FontData::new(
+        table.get(store_offset as usize..).ok_or(MalformedFont)?,
+    ))
+    .map_err(|_| MalformedFont)?;
+
+    // The advance mapping and the side bearing mappings.
+    let mut mappings = vec![];
+    for _ in 0..3 {
+        let offset = r.read::<u32>().ok_or(MalformedFont)? as usize;
+        mappings.push(if offset == 0 {
+            None
+        } else {
+            let data = table.get(offset..).ok_or(MalformedFont)?;
+            Some(DeltaSetIndexMap::read(FontData::new(data)).map_err(|_| MalformedFont)?)
+        });
+    }
+
+    let axis_count = match &ctx.interjector {
+        Interjector::Instancer(instancer) => instancer.axis_count(),
+        _ => {
+            let regions = store.variation_region_list().map_err(|_| MalformedFont)?;
+            regions.axis_count()
+        }
+    };
+
+    let mut builder = VariationStoreBuilder::new(axis_count);
+    let mut ids = vec![vec![]; 3];
+
+    for old_gid in ctx.mapper.remapped_gids() {
+        for (i, mapping) in mappings.iter().enumerate() {
+            let (outer, inner) = match mapping {
+                Some(mapping) => {
+                    let index = mapping.get(old_gid as u32).map_err(|_| MalformedFont)?;
+                    (index.outer, index.inner)
+                }
+                // Without an advance mapping, the glyph ID is used as the inner
+                // index directly.
+                None if i == 0 => (0, old_gid),
+                None => continue,
+            };
+
+            let variations =
+                read_store_item(&store, outer, inner).ok_or(MalformedFont)?;
+
+            let deltas = match &ctx.interjector {
+                Interjector::Instancer(instancer) => instancer
+                    .instance_tuples(variations, 1)
+                    .1
+                    .iter()
+                    .map(|var| (instancer.region(var), ot_round(var.deltas[0].0) as i32))
+                    .collect(),
+                _ => variations
+                    .iter()
+                    .map(|var| {
+                        (region(var.tents.iter().copied()), var.deltas[0].0 as i32)
+                    })
+                    .collect(),
+            };
+
+            ids[i].push(builder.add_deltas(deltas));
+        }
+    }
+
+    let (store, remapping) = builder.build();
+    let mut mappings = mappings
+        .iter()
+        .zip(ids)
+        .map(|(mapping, ids)| {
+            if mapping.is_none() && ids.is_empty() {
+                return Ok(None);
+            }
+
+            ids.into_iter()
+                .map(|id| remapping.get(id).map(u32::from))
+                .collect::<Option<_>>()
+                .map(Some)
+                .ok_or(SubsetError)
+        })
+        .collect::<Result<Vec<_>>>()?
+        .into_iter();
+
+    let mut next = || mappings.next().flatten();
+    let data =
+        dump_table(&Hvar::new(store, next(), next(), next())).map_err(|_| SubsetError)?;
+
+    ctx.push(Tag::HVAR, data);
+
+    Ok(())
+}
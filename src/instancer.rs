@@ -0,0 +1,946 @@
+//! Partial instancing of variable fonts.
+//!
+//! When partially instancing a font, some axes are pinned to a single value (which removes
+//! them from the font), while other axes are restricted to a smaller range but stay
+//! variable. This requires "rebasing" each tuple variation onto the new axis limits, which
+//! might scale it, drop it or split it into multiple new tuples. Everything that ends up
+//! applying unconditionally is merged into the new default outlines and metrics.
+//!
+//! The algorithm was ported from the `varLib.instancer` module of fontTools.
+
+use crate::glyf::instance_component_glyph;
+use crate::interjector::skrifa::SkrifaInterjector;
+use crate::Error::{MalformedFont, OverflowError};
+use crate::{AxisLimit, MaxpData, Result, Tag};
+use kurbo::{Point, Vec2};
+use skrifa::raw::tables::glyf::{Anchor, CurvePoint, Glyph};
+use skrifa::raw::tables::variations::ItemVariationStore;
+use skrifa::raw::types::F2Dot14;
+use skrifa::raw::{FontData, FontRead, TableProvider};
+use skrifa::{FontRef, GlyphId};
+use write_fonts::dump_table;
+use write_fonts::tables::glyf::{Contour, SimpleGlyph};
+use write_fonts::tables::gvar::iup::iup_delta_optimize;
+use write_fonts::tables::gvar::{GlyphDelta, GlyphDeltas, Tent};
+use write_fonts::tables::variations::{RegionAxisCoordinates, VariationRegion};
+
+/// A `(lower, peak, upper)` region of a tuple variation on a single axis,
+/// in normalized coordinates.
+pub(crate) type Triple = (f64, f64, f64);
+
+/// A tuple variation whose deltas have been resolved for all points.
+#[derive(Debug, Clone)]
+pub(crate) struct TupleVariation {
+    /// The region on each axis of the font, or `None` if the axis doesn't
+    /// participate in the variation.
+    pub(crate) tents: Vec<Option<Triple>>,
+    /// The deltas of each point. Variation stores only have a single
+    /// delta, which is stored as the x value.
+    pub(crate) deltas: Vec<(f64, f64)>,
+}
+
+/// A variation axis of the font, with the limits requested by the user.
+pub(crate) struct Axis {
+    /// The tag of the axis.
+    pub(crate) tag: Tag,
+    /// The original minimum, default and maximum value of the axis in user coordinates.
+    pub(crate) range: (f64, f64, f64),
+    /// The new minimum, default and maximum value of the axis in user coordinates.
+    pub(crate) user: (f64, f64, f64),
+    /// The new limits of the axis in normalized coordinates.
+    pub(crate) limit: NormalizedLimit,
+    /// The new limits of the axis in normalized coordinates, without
+    /// applying the `avar` mapping.
+    pub(crate) unmapped: NormalizedLimit,
+}
+
+impl Axis {
+    /// Whether the axis is pinned to a single value and thus removed from the font.
+    pub(crate) fn is_pinned(&self) -> bool {
+        self.user.0 == self.user.2
+    }
+
+    /// Whether the axis keeps its original limits.
+    pub(crate) fn is_identity(&self) -> bool {
+        self.user == self.range
+    }
+}
+
+/// Partially instances the outlines and metrics of a TrueType variable font.
+pub(crate) struct Instancer<'a> {
+    font_ref: FontRef<'a>,
+    /// Draws the outlines at the new default location.
+    skrifa: SkrifaInterjector<'a>,
+    /// All axes of the font, in the order of the `fvar` table.
+    axes: Vec<Axis>,
+    /// Whether the font has version 2 of `avar`, whose mapping can't be
+    /// expressed with segment maps.
+    avar2: bool,
+}
+
+impl<'a> Instancer<'a> {
+    pub(crate) fn new(
+        data: &'a [u8],
+        index: u32,
+        limits: &[(Tag, AxisLimit)],
+    ) -> Result<Self> {
+        let font_ref = FontRef::from_index(data, index).map_err(|_| MalformedFont)?;
+
+        let avar2 = font_ref.avar().is_ok_and(|avar| avar.var_store().is_some());
+        let segment_maps = match font_ref.avar() {
+            Ok(avar) => avar
+                .axis_segment_maps()
+                .iter()
+                .map(|maps| {
+                    let maps = maps.ok()?;
+                    Some(
+                        maps.axis_value_maps()
+                            .iter()
+                            .map(|map| {
+                                (
+                                    map.from_coordinate().to_f32() as f64,
+                                    map.to_coordinate().to_f32() as f64,
+                                )
+                            })
+                            .collect::<Vec<_>>(),
+                    )
+                })
+                .collect::<Option<Vec<_>>>()
+                .ok_or(MalformedFont)?,
+            Err(_) => vec![],
+        };
+
+        let mut axes = vec![];
+
+        if let Ok(fvar) = font_ref.fvar() {
+            let records = fvar.axes().map_err(|_| MalformedFont)?;
+            for (i, record) in records.iter().enumerate() {
+                let tag = Tag(record.axis_tag().into_bytes());
+                let axis = (
+                    record.min_value().to_f64(),
+                    record.default_value().to_f64(),
+                    record.max_value().to_f64(),
+                );
+
+                let clamp = |v: f32| (v as f64).clamp(axis.0, axis.2);
+                let user = match limits.iter().find(|(t, _)| *t == tag).map(|l| l.1) {
+                    Some(AxisLimit::Pin(v)) => (clamp(v), clamp(v), clamp(v)),
+                    Some(AxisLimit::Range { min, default, max }) => {
+                        let (min, max) = (clamp(min.min(max)), clamp(min.max(max)));
+                        (min, clamp(default).clamp(min, max), max)
+                    }
+                    None => axis,
+                };
+
+                let maps = segment_maps.get(i).map(Vec::as_slice).unwrap_or_default();
+                let normalize = |maps: &[(f64, f64)]| {
+                    let n = |v| {
+                        round_f2dot14(piecewise_linear_map(
+                            normalize_value(v, axis),
+                            maps,
+                        ))
+                    };
+                    NormalizedLimit {
+                        min: n(user.0),
+                        default: n(user.1),
+                        max: n(user.2),
+                        distance_negative: axis.1 - axis.0,
+                        distance_positive: axis.2 - axis.1,
+                    }
+                };
+
+                let limit = normalize(maps);
+                let unmapped = normalize(&[]);
+                axes.push(Axis { tag, range: axis, user, limit, unmapped });
+            }
+        }
+
+        let location = axes
+            .iter()
+            .map(|axis| (axis.tag, axis.user.1 as f32))
+            .collect::<Vec<_>>();
+        let skrifa =
+            SkrifaInterjector::new(data, index, &location).ok_or(MalformedFont)?;
+
+        Ok(Self { font_ref, skrifa, axes, avar2 })
+    }
+
+    /// Return the location of the instance if all axes are pinned, in which case
+    /// the font can be fully instanced instead.
+    pub(crate) fn pinned_location(&self) -> Option<Vec<(Tag, f32)>> {
+        self.axes
+            .iter()
+            .map(|axis| axis.is_pinned().then_some((axis.tag, axis.user.1 as f32)))
+            .collect()
+    }
+
+    /// Whether none of the axes are restricted, in which case the variations
+    /// can be kept as they are.
+    pub(crate) fn is_identity(&self) -> bool {
+        self.axes.iter().all(Axis::is_identity)
+    }
+
+    /// Whether the font can be partially instanced. This isn't the case for
+    /// fonts with version 2 of `avar`.
+    pub(crate) fn is_supported(&self) -> bool {
+        !self.avar2
+    }
+
+    /// All axes of the original font, including the pinned ones.
+    pub(crate) fn axes(&self) -> &[Axis] {
+        &self.axes
+    }
+
+    /// The number of axes that remain in the font.
+    pub(crate) fn axis_count(&self) -> u16 {
+        self.axes.iter().filter(|axis| !axis.is_pinned()).count() as u16
+    }
+
+    /// Rebase the given tuple variations onto the new axis limits.
+    ///
+    /// Returns the deltas that apply at the new default location and the
+    /// remaining tuple variations.
+    pub(crate) fn instance_tuples(
+        &self,
+        mut variations: Vec<TupleVariation>,
+        len: usize,
+    ) -> (Vec<(f64, f64)>, Vec<TupleVariation>) {
+        for (i, axis) in self.axes.iter().enumerate() {
+            if axis.is_identity() {
+                continue;
+            }
+
+            variations = variations
+                .into_iter()
+                .flat_map(|var| change_axis_limit(var, i, axis.limit))
+                .collect();
+        }
+
+        let mut default = vec![(0.0, 0.0); len];
+        let mut merged: Vec<TupleVariation> = vec![];
+
+        for var in variations {
+            let target = if var.tents.iter().all(Option::is_none) {
+                &mut default
+            } else if let Some(other) =
+                merged.iter_mut().find(|other| same_tents(&other.tents, &var.tents))
+            {
+                &mut other.deltas
+            } else {
+                merged.push(var);
+                continue;
+            };
+
+            for (a, b) in target.iter_mut().zip(var.deltas) {
+                a.0 += b.0;
+                a.1 += b.1;
+            }
+        }
+
+        (default, merged)
+    }
+
+    /// Return the tents of the remaining axes of a tuple variation.
+    pub(crate) fn tents(&self, var: &TupleVariation) -> Vec<Tent> {
+        self.remaining(var)
+            .map(|tent| match tent {
+                Some((lower, peak, upper)) => Tent::new(
+                    F2Dot14::from_f32(peak as f32),
+                    Some((
+                        F2Dot14::from_f32(lower as f32),
+                        F2Dot14::from_f32(upper as f32),
+                    )),
+                ),
+                None => Tent::new(F2Dot14::ZERO, None),
+            })
+            .collect()
+    }
+
+    /// Return the variation region of a tuple variation over the remaining axes.
+    pub(crate) fn region(&self, var: &TupleVariation) -> VariationRegion {
+        region(self.remaining(var))
+    }
+
+    fn remaining<'b>(
+        &'b self,
+        var: &'b TupleVariation,
+    ) -> impl Iterator<Item = Option<Triple>> + 'b {
+        self.axes
+            .iter()
+            .zip(&var.tents)
+            .filter(|(axis, _)| !axis.is_pinned())
+            .map(|(_, tent)| *tent)
+    }
+
+    /// Instance a glyph at the new default location.
+    ///
+    /// Returns the advance width, the left side bearing, the new glyph description
+    /// and the remaining variations of the glyph. Fails with an overflow error if
+    /// the instanced coordinates, deltas or advance width don't fit into 16 bits.
+    pub(crate) fn instance_glyph(
+        &self,
+        maxp_data: &mut MaxpData,
+        glyph: u16,
+        data: &[u8],
+    ) -> Result<(u16, i16, Vec<u8>, Vec<GlyphDeltas>)> {
+        let glyph_id = GlyphId::new(glyph as u32);
+        let hmtx = self.font_ref.hmtx().map_err(|_| MalformedFont)?;
+        let advance = hmtx.advance(glyph_id).ok_or(MalformedFont)?;
+        let lsb = hmtx.side_bearing(glyph_id).unwrap_or_default();
+
+        let outline = if data.is_empty() {
+            None
+        } else {
+            Some(Glyph::read(FontData::new(data)).map_err(|_| MalformedFont)?)
+        };
+
+        let mut coords = vec![];
+        let mut ends = vec![];
+        let mut on_curve = vec![];
+        let mut x_min = 0;
+
+        match &outline {
+            Some(Glyph::Simple(simple)) => {
+                for point in simple.points() {
+                    coords.push((point.x as f64, point.y as f64));
+                    on_curve.push(point.on_curve);
+                }
+
+                ends.extend(
+                    simple.end_pts_of_contours().iter().map(|e| e.get() as usize),
+                );
+                x_min = simple.x_min();
+            }
+            Some(Glyph::Composite(composite)) => {
+                for component in composite.components() {
+                    coords.push(match component.anchor {
+                        Anchor::Offset { x, y } => (x as f64, y as f64),
+                        Anchor::Point { .. } => (0.0, 0.0),
+                    });
+                }
+
+                x_min = composite.x_min();
+            }
+            None => {}
+        }
+
+        // The phantom points. We don't keep any vertical metrics, so the last
+        // two can be ignored.
+        let left = x_min as f64 - lsb as f64;
+        coords.push((left, 0.0));
+        coords.push((left + advance as f64, 0.0));
+        coords.push((0.0, 0.0));
+        coords.push((0.0, 0.0));
+
+        let is_composite = matches!(outline, Some(Glyph::Composite(_)));
+        let mut variations = vec![];
+
+        let gvar = self.font_ref.gvar().map_err(|_| MalformedFont)?;
+        if let Some(var_data) =
+            gvar.glyph_variation_data(glyph_id).map_err(|_| MalformedFont)?
+        {
+            for tuple in var_data.tuples() {
+                let peak = tuple.peak();
+                let intermediate =
+                    tuple.intermediate_start().zip(tuple.intermediate_end());
+
+                let tents = (0..self.axes.len())
+                    .map(|i| {
+                        let peak = peak.get(i)?.to_f32() as f64;
+                        if peak == 0.0 {
+                            return None;
+                        }
+
+                        Some(match &intermediate {
+                            Some((start, end)) => (
+                                start.get(i)?.to_f32() as f64,
+                                peak,
+                                end.get(i)?.to_f32() as f64,
+                            ),
+                            None => (peak.min(0.0), peak, peak.max(0.0)),
+                        })
+                    })
+                    .collect();
+
+                let mut deltas = vec![None; coords.len()];
+                for delta in tuple.deltas() {
+                    if let Some(slot) = deltas.get_mut(delta.position as usize) {
+                        *slot = Some((delta.x_delta as f64, delta.y_delta as f64));
+                    }
+                }
+
+                let deltas = if is_composite {
+                    deltas.into_iter().map(Option::unwrap_or_default).collect()
+                } else {
+                    infer_deltas(&deltas, &coords, &ends)
+                };
+
+                variations.push(TupleVariation { tents, deltas });
+            }
+        }
+
+        let (default, variations) = self.instance_tuples(variations, coords.len());
+        let new_coords = coords
+            .iter()
+            .zip(&default)
+            .map(|(c, d)| (ot_round(c.0 + d.0), ot_round(c.1 + d.1)))
+            .collect::<Vec<_>>();
+        let num_points = new_coords.len() - 4;
+
+        let (bbox, data) = match &outline {
+            Some(Glyph::Simple(_)) => {
+                let mut start = 0;
+                let contours = ends
+                    .iter()
+                    .map(|&end| {
+                        let contour = (start..=end)
+                            .map(|i| {
+                                let (x, y) = new_coords[i];
+                                Some(CurvePoint::new(to_i16(x)?, to_i16(y)?, on_curve[i]))
+                            })
+                            .collect::<Option<Vec<_>>>()?;
+                        start = end + 1;
+                        Some(Contour::from(contour))
+                    })
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or(OverflowError)?;
+
+                let mut simple = SimpleGlyph { contours, ..SimpleGlyph::default() };
+                simple.recompute_bounding_box();
+
+                maxp_data.max_points = maxp_data.max_points.max(num_points as u16);
+                maxp_data.max_contours = maxp_data.max_contours.max(ends.len() as u16);
+
+                let bbox = simple.bbox;
+                (bbox.x_min, dump_table(&simple).map_err(|_| MalformedFont)?)
+            }
+            Some(Glyph::Composite(_)) => {
+                let offsets = coords
+                    .iter()
+                    .zip(&new_coords)
+                    .take(num_points)
+                    .map(|(old, new)| ((new.0 - old.0) as i32, (new.1 - old.1) as i32))
+                    .collect::<Vec<_>>();
+
+                let bbox = self.skrifa.bbox(glyph_id).ok_or(MalformedFont)?;
+                let data = instance_component_glyph(data, bbox, &offsets)
+                    .ok_or(MalformedFont)?;
+
+                (bbox[0], data)
+            }
+            None => (0, vec![]),
+        };
+
+        let left = new_coords[num_points].0;
+        let right = new_coords[num_points + 1].0;
+        // Like fontTools, we clamp negative advances to zero.
+        let advance = to_u16((right - left).max(0.0)).ok_or(OverflowError)?;
+        let lsb = to_i16(bbox as f64 - left).ok_or(OverflowError)?;
+
+        // The rounded deltas must fit into the `gvar` table, so we check them
+        // before dropping the variations that have no effect anymore.
+        let rounded = variations
+            .iter()
+            .map(|var| {
+                var.deltas
+                    .iter()
+                    .map(|d| Some((to_i16(ot_round(d.0))?, to_i16(ot_round(d.1))?)))
+                    .collect::<Option<Vec<_>>>()
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or(OverflowError)?;
+
+        let deltas = variations
+            .iter()
+            .zip(rounded)
+            .filter_map(|(var, rounded)| {
+                if rounded.iter().all(|d| *d == (0, 0)) {
+                    return None;
+                }
+
+                let deltas = if is_composite {
+                    rounded.iter().map(|&(x, y)| GlyphDelta::required(x, y)).collect()
+                } else {
+                    iup_delta_optimize(
+                        rounded
+                            .iter()
+                            .map(|&(x, y)| Vec2::new(x as f64, y as f64))
+                            .collect(),
+                        new_coords.iter().map(|&(x, y)| Point::new(x, y)).collect(),
+                        0.5,
+                        &ends,
+                    )
+                    .ok()?
+                };
+
+                Some(GlyphDeltas::new(self.tents(var), deltas))
+            })
+            .collect();
+
+        Ok((advance, lsb, data, deltas))
+    }
+}
+
+/// The new limits of an axis in normalized coordinates, together with the
+/// distances between the original minimum, default and maximum in user
+/// coordinates.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct NormalizedLimit {
+    pub(crate) min: f64,
+    pub(crate) default: f64,
+    pub(crate) max: f64,
+    pub(crate) distance_negative: f64,
+    pub(crate) distance_positive: f64,
+}
+
+impl NormalizedLimit {
+    fn is_pinned(&self) -> bool {
+        self.min == self.max
+    }
+
+    fn reverse_negate(self) -> Self {
+        Self {
+            min: -self.max,
+            default: -self.default,
+            max: -self.min,
+            distance_negative: self.distance_positive,
+            distance_positive: self.distance_negative,
+        }
+    }
+
+    /// Map a normalized value from the original axis range into the new one.
+    pub(crate) fn renormalize(&self, v: f64) -> f64 {
+        if v == self.default {
+            return 0.0;
+        }
+
+        if self.default < 0.0 {
+            return -self.reverse_negate().renormalize(-v);
+        }
+
+        if v > self.default {
+            return (v - self.default) / (self.max - self.default);
+        }
+
+        if self.min >= 0.0 {
+            return (v - self.default) / (self.default - self.min);
+        }
+
+        let total_distance =
+            self.distance_negative * -self.min + self.distance_positive * self.default;
+        let v_distance = if v >= 0.0 {
+            (self.default - v) * self.distance_positive
+        } else {
+            -v * self.distance_negative + self.distance_positive * self.default
+        };
+
+        -v_distance / total_distance
+    }
+}
+
+/// Read the deltas of an item in an item variation store as tuple variations.
+pub(crate) fn read_store_item(
+    store: &ItemVariationStore,
+    outer: u16,
+    inner: u16,
+) -> Option<Vec<TupleVariation>> {
+    let regions = store.variation_region_list().ok()?.variation_regions();
+    let data = store.item_variation_data().get(outer as usize)?.ok()?;
+
+    data.region_indexes()
+        .iter()
+        .zip(data.delta_set(inner))
+        .map(|(index, delta)| {
+            let region = regions.get(index.get() as usize).ok()?;
+            let tents = region
+                .region_axes()
+                .iter()
+                .map(|axis| {
+                    let peak = axis.peak_coord().to_f32() as f64;
+                    (peak != 0.0).then(|| {
+                        (
+                            axis.start_coord().to_f32() as f64,
+                            peak,
+                            axis.end_coord().to_f32() as f64,
+                        )
+                    })
+                })
+                .collect();
+
+            Some(TupleVariation { tents, deltas: vec![(delta as f64, 0.0)] })
+        })
+        .collect()
+}
+
+/// Create a variation region from the tents of a tuple variation.
+pub(crate) fn region(tents: impl Iterator<Item = Option<Triple>>) -> VariationRegion {
+    VariationRegion::new(
+        tents
+            .map(|tent| {
+                let (lower, peak, upper) = tent.unwrap_or_default();
+                RegionAxisCoordinates::new(
+                    F2Dot14::from_f32(lower as f32),
+                    F2Dot14::from_f32(peak as f32),
+                    F2Dot14::from_f32(upper as f32),
+                )
+            })
+            .collect(),
+    )
+}
+
+/// Restrict a single axis of a tuple variation to a new limit.
+fn change_axis_limit(
+    mut var: TupleVariation,
+    axis: usize,
+    limit: NormalizedLimit,
+) -> Vec<TupleVariation> {
+    let Some(tent) = var.tents[axis] else {
+        return vec![var];
+    };
+
+    let (lower, peak, upper) = tent;
+    if !(lower <= peak && peak <= upper) || (lower < 0.0 && upper > 0.0) {
+        return vec![];
+    }
+
+    let solutions = if limit.is_pinned() {
+        let scalar = support_scalar(limit.default, tent);
+        if scalar == 0.0 {
+            vec![]
+        } else {
+            vec![(scalar, None)]
+        }
+    } else {
+        rebase_tent(tent, limit)
+    };
+
+    let last = solutions.len().saturating_sub(1);
+    let mut out = Vec::with_capacity(solutions.len());
+
+    for (i, (scalar, tent)) in solutions.into_iter().enumerate() {
+        let mut new =
+            if i == last { std::mem::take(&mut var.deltas) } else { var.deltas.clone() };
+        for delta in &mut new {
+            delta.0 *= scalar;
+            delta.1 *= scalar;
+        }
+
+        let mut tents = var.tents.clone();
+        tents[axis] = tent;
+        out.push(TupleVariation { tents, deltas: new });
+    }
+
+    out
+}
+
+const EPSILON: f64 = 1.0 / (1 << 14) as f64;
+
+/// Compute the scalar of a tent at a value on a single axis.
+fn support_scalar(v: f64, (lower, peak, upper): Triple) -> f64 {
+    if peak == 0.0 || lower > peak || peak > upper || (lower < 0.0 && upper > 0.0) {
+        return 1.0;
+    }
+
+    if v == peak {
+        1.0
+    } else if v <= lower || upper <= v {
+        0.0
+    } else if v < peak {
+        (v - lower) / (peak - lower)
+    } else {
+        (v - upper) / (peak - upper)
+    }
+}
+
+/// Solve how to represent a tent under new axis limits.
+///
+/// Returns a list of scalars with which the deltas of the tuple need to be
+/// multiplied and the new tents for the resulting tuples. A `None` tent means
+/// that the deltas apply unconditionally. All values are in normalized
+/// coordinates.
+fn rebase_tent(tent: Triple, limit: NormalizedLimit) -> Vec<(f64, Option<Triple>)> {
+    let n = |v: f64| limit.renormalize(v);
+    solve(tent, limit)
+        .into_iter()
+        .filter(|(scalar, _)| *scalar != 0.0)
+        .map(|(scalar, tent)| (scalar, tent.map(|(l, p, u)| (n(l), n(p), n(u)))))
+        .collect()
+}
+
+fn solve(tent: Triple, limit: NormalizedLimit) -> Vec<(f64, Option<Triple>)> {
+    let NormalizedLimit {
+        min: axis_min, default: axis_def, max: axis_max, ..
+    } = limit;
+    let (mut lower, peak, mut upper) = tent;
+
+    // Mirror the problem such that `axis_def <= peak`.
+    if axis_def > peak {
+        let reverse = |(l, p, u): Triple| (-u, -p, -l);
+        return solve(reverse(tent), limit.reverse_negate())
+            .into_iter()
+            .map(|(scalar, tent)| (scalar, tent.map(reverse)))
+            .collect();
+    }
+
+    // The whole tent falls outside the new limit, so we can drop it.
+    if axis_max <= lower && axis_max < peak {
+        return vec![];
+    }
+
+    // Only the peak and the outermost bound fall outside the new limit. We
+    // move them to the limit and scale the deltas accordingly.
+    if axis_max < peak {
+        let mult = support_scalar(axis_max, tent);
+        let tent = (lower, axis_max, axis_max);
+        return solve(tent, limit)
+            .into_iter()
+            .map(|(scalar, tent)| (scalar * mult, tent))
+            .collect();
+    }
+
+    // lower <= axis_def <= peak <= axis_max
+
+    let gain = support_scalar(axis_def, tent);
+    let mut out = vec![(gain, None)];
+
+    // First, the positive side. `out_gain` is the scalar of `axis_max` at
+    // the tent.
+    let out_gain = support_scalar(axis_max, tent);
+
+    if gain >= out_gain {
+        // The gain is at least the out gain, so the down-slope of the tent
+        // crosses the axis into negative. We have to split it.
+        let crossing = peak + (1.0 - gain) * (upper - peak);
+        out.push((1.0 - gain, Some((lower.max(axis_def), peak, crossing))));
+
+        if upper >= axis_max {
+            // One tent is enough for the part after the crossing point.
+            out.push((out_gain - gain, Some((crossing, axis_max, axis_max))));
+        } else {
+            // Two tents are needed to keep the delta down all the way to the
+            // end. A peak cannot fall on the axis default, so we nudge it.
+            if upper == axis_def {
+                upper += EPSILON;
+            }
+
+            out.push((-gain, Some((crossing, upper, axis_max))));
+            out.push((-gain, Some((upper, axis_max, axis_max))));
+        }
+    } else {
+        // The new limit doesn't fit, so we need to chop the tent into two,
+        // since a triangle with part of one side cut off cannot be
+        // represented as a triangle itself.
+        out.push((1.0 - gain, Some((axis_def.max(lower), peak, axis_max))));
+
+        // Don't add a dirac delta.
+        if peak < axis_max {
+            out.push((out_gain - gain, Some((peak, axis_max, axis_max))));
+        }
+    }
+
+    // Now, the negative side.
+    if lower <= axis_min {
+        // The lower bound extends beyond the new minimum, so we just chop it.
+        let scalar = support_scalar(axis_min, tent);
+        out.push((scalar - gain, Some((axis_min, axis_min, axis_def))));
+    } else {
+        // The lower bound lies between the new minimum and the default. We
+        // add two tents to keep the delta down all the way to the end. A peak
+        // cannot fall on the axis default, so we nudge it.
+        if lower == axis_def {
+            lower -= EPSILON;
+        }
+
+        out.push((-gain, Some((axis_min, lower, axis_def))));
+        out.push((-gain, Some((axis_min, axis_min, lower))));
+    }
+
+    out
+}
+
+/// Infer the deltas of points that are not explicitly referenced by a tuple
+/// variation, as described in the `gvar` specification.
+pub(crate) fn infer_deltas(
+    deltas: &[Option<(f64, f64)>],
+    coords: &[(f64, f64)],
+    ends: &[usize],
+) -> Vec<(f64, f64)> {
+    let mut out = deltas.iter().map(|d| d.unwrap_or_default()).collect::<Vec<_>>();
+    let mut start = 0;
+
+    for &end in ends {
+        let Some(range) = deltas.get(start..=end) else {
+            break;
+        };
+
+        let explicit = range
+            .iter()
+            .enumerate()
+            .filter_map(|(i, d)| d.map(|_| start + i))
+            .collect::<Vec<_>>();
+
+        if !explicit.is_empty() {
+            for i in start..=end {
+                if deltas[i].is_some() {
+                    continue;
+                }
+
+                // Find the closest explicit points before and after this one,
+                // wrapping around the contour.
+                let next =
+                    explicit.iter().copied().find(|&j| j > i).unwrap_or(explicit[0]);
+                let prev = explicit
+                    .iter()
+                    .rev()
+                    .copied()
+                    .find(|&j| j < i)
+                    .unwrap_or(explicit[explicit.len() - 1]);
+
+                let (p1, p2) = (coords[prev], coords[next]);
+                let (d1, d2) = (out[prev], out[next]);
+                out[i] = (
+                    interpolate(coords[i].0, p1.0, p2.0, d1.0, d2.0),
+                    interpolate(coords[i].1, p1.1, p2.1, d1.1, d2.1),
+                );
+            }
+        }
+
+        start = end + 1;
+    }
+
+    out
+}
+
+/// Interpolate the delta of a coordinate from two reference coordinates.
+fn interpolate(x: f64, mut x1: f64, mut x2: f64, mut d1: f64, mut d2: f64) -> f64 {
+    if x1 == x2 {
+        return if d1 == d2 { d1 } else { 0.0 };
+    }
+
+    if x1 > x2 {
+        std::mem::swap(&mut x1, &mut x2);
+        std::mem::swap(&mut d1, &mut d2);
+    }
+
+    if x <= x1 {
+        d1
+    } else if x >= x2 {
+        d2
+    } else {
+        d1 + (x - x1) * (d2 - d1) / (x2 - x1)
+    }
+}
+
+/// Whether two tuple variations have the same region once serialized.
+fn same_tents(a: &[Option<Triple>], b: &[Option<Triple>]) -> bool {
+    let f2dot14 = |t: &Option<Triple>| {
+        t.map(|(l, p, u)| [l, p, u].map(|v| F2Dot14::from_f32(v as f32).to_bits()))
+    };
+
+    a.iter().map(f2dot14).eq(b.iter().map(f2dot14))
+}
+
+/// Normalize a user coordinate to the default normalization of an axis.
+fn normalize_value(v: f64, (min, default, max): (f64, f64, f64)) -> f64 {
+    let v = v.clamp(min, max);
+    if v == default || min == max {
+        0.0
+    } else if (v < default && min != default) || (v > default && max == default) {
+        (v - default) / (default - min)
+    } else {
+        (v - default) / (max - default)
+    }
+}
+
+/// Apply an `avar` segment map to a normalized coordinate.
+pub(crate) fn piecewise_linear_map(v: f64, maps: &[(f64, f64)]) -> f64 {
+    let (Some(first), Some(last)) = (maps.first(), maps.last()) else {
+        return v;
+    };
+
+    if let Some(&(_, to)) = maps.iter().find(|(from, _)| *from == v) {
+        return to;
+    }
+
+    if v < first.0 {
+        return v + first.1 - first.0;
+    }
+
+    if v > last.0 {
+        return v + last.1 - last.0;
+    }
+
+    let i = maps.iter().position(|(from, _)| *from > v).unwrap_or(maps.len() - 1);
+    let (a, b) = (maps[i - 1], maps[i]);
+    a.1 + (b.1 - a.1) * (v - a.0) / (b.0 - a.0)
+}
+
+/// Round a value to the precision of an `F2Dot14`.
+pub(crate) fn round_f2dot14(v: f64) -> f64 {
+    F2Dot14::from_f32(v as f32).to_f32() as f64
+}
+
+/// Round a value in the same way as fontTools.
+pub(crate) fn ot_round(v: f64) -> f64 {
+    (v + 0.5).floor()
+}
+
+/// Convert a rounded value to an `i16`. Returns `None` if it is out of range.
+pub(crate) fn to_i16(v: f64) -> Option<i16> {
+    (f64::from(i16::MIN)..=f64::from(i16::MAX))
+        .contains(&v)
+        .then_some(v as i16)
+}
+
+/// Convert a rounded value to a `u16`. Returns `None` if it is out of range.
+fn to_u16(v: f64) -> Option<u16> {
+    (0.0..=f64::from(u16::MAX)).contains(&v).then_some(v as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limit(min: f64, default: f64, max: f64) -> NormalizedLimit {
+        NormalizedLimit {
+            min,
+            default,
+            max,
+            distance_negative: 1.0,
+            distance_positive: 1.0,
+        }
+    }
+
+    #[test]
+    fn rebase_tent_restricts_range() {
+        // The tent is cut off at the new maximum and needs to be split.
+        assert_eq!(
+            rebase_tent((0.0, 1.0, 1.0), limit(0.0, 0.0, 0.5)),
+            vec![(0.5, Some((0.0, 1.0, 1.0)))]
+        );
+
+        // The new default lies within the tent, so part of it is applied to
+        // the default.
+        assert_eq!(
+            rebase_tent((0.0, 1.0, 1.0), limit(0.0, 0.5, 1.0)),
+            vec![
+                (0.5, None),
+                (0.5, Some((0.0, 1.0, 1.0))),
+                (-0.5, Some((-1.0, -1.0, 0.0)))
+            ]
+        );
+
+        // The tent lies completely outside of the new range.
+        assert_eq!(rebase_tent((0.5, 1.0, 1.0), limit(-1.0, 0.0, 0.5)), vec![]);
+    }
+
+    #[test]
+    fn conversions_check_range() {
+        assert_eq!(to_i16(ot_round(-32768.4)), Some(i16::MIN));
+        assert_eq!(to_i16(ot_round(32766.5)), Some(i16::MAX));
+        assert_eq!(to_i16(ot_round(-32768.6)), None);
+        assert_eq!(to_i16(ot_round(32767.5)), None);
+        assert_eq!(to_u16(ot_round(65534.5)), Some(u16::MAX));
+        assert_eq!(to_u16(ot_round(65535.5)), None);
+    }
+}
@@ -4,27 +4,29 @@ pub(crate) enum Interjector<'a> {
     Dummy(PhantomData<&'a ()>),
     #[cfg(feature = "variable-fonts")]
     Skrifa(skrifa::SkrifaInterjector<'a>),
+    #[cfg(feature = "variable-fonts")]
+    Instancer(crate::instancer::Instancer<'a>),
 }
 
 impl Interjector<'_> {
-    pub fn is_skrifa(&self) -> bool {
-        match self {
-            Self::Dummy(_) => false,
-            #[cfg(feature = "variable-fonts")]
-            Self::Skrifa(_) => true,
-        }
+    pub fn is_dummy(&self) -> bool {
+        matches!(self, Self::Dummy(_))
     }
 }
 
 #[cfg(feature = "variable-fonts")]
 pub(crate) mod skrifa {
+    use crate::glyf::{component_glyphs, instance_component_glyph};
+    use crate::instancer::{infer_deltas, ot_round, to_i16};
     use crate::{MaxpData, Tag};
     use kurbo::{BezPath, CubicBez};
     use skrifa::instance::{Location, LocationRef};
     use skrifa::outline::{DrawSettings, OutlinePen};
     use skrifa::prelude::Size;
+    use skrifa::raw::tables::glyf::{CurvePoint, Glyph};
+    use skrifa::raw::{FontData, FontRead, TableProvider};
     use skrifa::{FontRef, GlyphId, MetadataProvider};
-    use write_fonts::tables::glyf::SimpleGlyph;
+    use write_fonts::tables::glyf::{Contour, SimpleGlyph};
     use write_fonts::{dump_table, FontWrite, TableWriter};
 
     pub(crate) struct SkrifaInterjector<'a> {
@@ -49,6 +51,11 @@ pub(crate) mod skrifa {
         pub(crate) fn is_default_location(&self) -> bool {
             LocationRef::from(&self.location).is_default()
         }
+
+        /// The normalized coordinates of the requested location.
+        pub(crate) fn coords(&self) -> &[skrifa::raw::types::F2Dot14] {
+            self.location.coords()
+        }
     }
 
     impl<'a> SkrifaInterjector<'a> {
@@ -58,25 +65,11 @@ pub(crate) mod skrifa {
             maxp_data: &'b mut MaxpData,
             glyph: u16,
         ) -> Option<(u16, i16, Vec<u8>)> {
-            let outlines = self.font_ref.outline_glyphs();
-            let metrics = self.font_ref.glyph_metrics(Size::unscaled(), &self.location);
-
-            let mut outline_builder = OutlinePath::new();
             let glyph_id = GlyphId::new(glyph as u32);
-
-            if let Some(outline_glyph) = outlines.get(glyph_id) {
-                outline_glyph
-                    .draw(
-                        DrawSettings::unhinted(Size::unscaled(), &self.location),
-                        &mut outline_builder,
-                    )
-                    .ok()?;
-            }
-
-            let path = outline_builder.path;
+            let path = self.draw(glyph_id)?;
 
             let simple_glyph = SimpleGlyph::from_bezpath(&path).ok()?;
-            let advance = metrics.advance_width(glyph_id)?.round() as u16;
+            let advance = self.advance(glyph_id)?;
 
             // We derive the LSB from the resulting bounding box rather than
             // from the font's metrics, because the latter does not always agree
@@ -106,6 +99,191 @@ pub(crate) mod skrifa {
 
             Some((advance, lsb, data))
         }
+
+        /// Return the description of a simple TrueType glyph in the `glyf` outline
+        /// format.
+        ///
+        /// Unlike [`Self::interject`], this doesn't redraw the glyph. Instead, its
+        /// points are kept and only moved according to the `gvar` deltas at the
+        /// requested location. This way, the point numbers stay the same, which
+        /// composite glyphs can use to attach their components.
+        pub(crate) fn interject_simple(
+            &self,
+            maxp_data: &mut MaxpData,
+            glyph: u16,
+            data: &[u8],
+        ) -> Option<(u16, i16, Vec<u8>)> {
+            let glyph_id = GlyphId::new(glyph as u32);
+            let advance = self.advance(glyph_id)?;
+
+            let simple = match Glyph::read(FontData::new(data)) {
+                Ok(Glyph::Simple(simple)) if simple.num_points() > 0 => simple,
+                _ if data.is_empty() => return Some((advance, 0, vec![])),
+                _ => return None,
+            };
+
+            let coords = simple
+                .points()
+                .map(|point| (point.x as f64, point.y as f64))
+                .collect::<Vec<_>>();
+            let on_curve =
+                simple.points().map(|point| point.on_curve).collect::<Vec<_>>();
+            let ends = simple
+                .end_pts_of_contours()
+                .iter()
+                .map(|end| end.get() as usize)
+                .collect::<Vec<_>>();
+
+            let mut new_coords = coords.clone();
+            if let Ok(gvar) = self.font_ref.gvar() {
+                if let Some(var_data) = gvar.glyph_variation_data(glyph_id).ok()? {
+                    for (tuple, scalar) in var_data.active_tuples_at(self.coords()) {
+                        // The deltas of the phantom points are not needed, since
+                        // the advance is taken from the metrics.
+                        let mut deltas = vec![None; coords.len()];
+                        for delta in tuple.deltas() {
+                            if let Some(slot) = deltas.get_mut(delta.position as usize) {
+                                let p = delta.apply_scalar::<f32>(scalar);
+                                *slot = Some((p.x as f64, p.y as f64));
+                            }
+                        }
+
+                        let deltas = infer_deltas(&deltas, &coords, &ends);
+                        for (c, d) in new_coords.iter_mut().zip(deltas) {
+                            c.0 += d.0;
+                            c.1 += d.1;
+                        }
+                    }
+                }
+            }
+
+            let mut start = 0;
+            let contours = ends
+                .iter()
+                .map(|&end| {
+                    let contour = (start..=end)
+                        .map(|i| {
+                            let (x, y) = *new_coords.get(i)?;
+                            let (x, y) = (to_i16(ot_round(x))?, to_i16(ot_round(y))?);
+                            Some(CurvePoint::new(x, y, on_curve[i]))
+                        })
+                        .collect::<Option<Vec<_>>>()?;
+                    start = end + 1;
+                    Some(Contour::from(contour))
+                })
+                .collect::<Option<Vec<_>>>()?;
+
+            let mut simple_glyph = SimpleGlyph { contours, ..SimpleGlyph::default() };
+            simple_glyph.recompute_bounding_box();
+
+            maxp_data.max_points = maxp_data.max_points.max(coords.len() as u16);
+            maxp_data.max_contours = maxp_data.max_contours.max(ends.len() as u16);
+
+            // See the comment in `interject` for why we use `xMin` as the LSB.
+            let lsb = simple_glyph.bbox.x_min;
+            let data = dump_table(&simple_glyph).ok()?;
+
+            Some((advance, lsb, data))
+        }
+
+        /// Return the description of a composite glyph in the `glyf` outline format.
+        ///
+        /// Unlike [`Self::interject`], this doesn't flatten the glyph into a simple
+        /// glyph. Instead, the composite structure is preserved and only the
+        /// component offsets are moved according to the `gvar` deltas at the
+        /// requested location. The component glyphs themselves are instanced
+        /// separately, since they are part of the glyph closure.
+        pub(crate) fn interject_composite(
+            &self,
+            glyph: u16,
+            data: &[u8],
+        ) -> Option<(u16, i16, Vec<u8>)> {
+            let glyph_id = GlyphId::new(glyph as u32);
+            let advance = self.advance(glyph_id)?;
+            let bbox = self.bbox(glyph_id)?;
+
+            let num_components = component_glyphs(data)?.count();
+            let deltas = self.component_deltas(glyph_id, num_components)?;
+            let data = instance_component_glyph(data, bbox, &deltas)?;
+
+            // See the comment in `interject` for why we use `xMin` as the LSB.
+            Some((advance, bbox[0], data))
+        }
+
+        /// Draw the outline of a glyph at the requested location.
+        fn draw(&self, glyph_id: GlyphId) -> Option<BezPath> {
+            let outlines = self.font_ref.outline_glyphs();
+            let mut outline_builder = OutlinePath::new();
+
+            if let Some(outline_glyph) = outlines.get(glyph_id) {
+                outline_glyph
+                    .draw(
+                        DrawSettings::unhinted(Size::unscaled(), &self.location),
+                        &mut outline_builder,
+                    )
+                    .ok()?;
+            }
+
+            Some(outline_builder.path)
+        }
+
+        /// Return the bounding box of a glyph at the requested location.
+        pub(crate) fn bbox(&self, glyph_id: GlyphId) -> Option<[i16; 4]> {
+            let path = self.draw(glyph_id)?;
+            if path.is_empty() {
+                return Some([0; 4]);
+            }
+
+            let rect = path.control_box();
+            Some([
+                rect.x0.round() as i16,
+                rect.y0.round() as i16,
+                rect.x1.round() as i16,
+                rect.y1.round() as i16,
+            ])
+        }
+
+        /// Return the advance width of a glyph at the requested location.
+        fn advance(&self, glyph_id: GlyphId) -> Option<u16> {
+            let metrics = self.font_ref.glyph_metrics(Size::unscaled(), &self.location);
+            Some(metrics.advance_width(glyph_id)?.round() as u16)
+        }
+
+        /// Return the rounded offset deltas of each component of a composite glyph
+        /// at the requested location.
+        ///
+        /// In `gvar`, the "points" of a composite glyph are its component offsets,
+        /// followed by the four phantom points.
+        fn component_deltas(
+            &self,
+            glyph_id: GlyphId,
+            num_components: usize,
+        ) -> Option<Vec<(i32, i32)>> {
+            let mut deltas = vec![(0.0f32, 0.0f32); num_components];
+
+            if let Ok(gvar) = self.font_ref.gvar() {
+                let coords = LocationRef::from(&self.location).coords();
+
+                if let Some(var_data) = gvar.glyph_variation_data(glyph_id).ok()? {
+                    for (tuple, scalar) in var_data.active_tuples_at(coords) {
+                        for delta in tuple.deltas() {
+                            if let Some(d) = deltas.get_mut(delta.position as usize) {
+                                let p = delta.apply_scalar::<f32>(scalar);
+                                d.0 += p.x;
+                                d.1 += p.y;
+                            }
+                        }
+                    }
+                }
+            }
+
+            Some(
+                deltas
+                    .into_iter()
+                    .map(|(x, y)| (x.round() as i32, y.round() as i32))
+                    .collect(),
+            )
+        }
     }
 
     pub(crate) struct OutlinePath {
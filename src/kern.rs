@@ -0,0 +1,223 @@
+//! The legacy `kern` table contains kerning pairs for older fonts that don't have a
+//! `GPOS` table. There are two versions of the table header, one by Microsoft and one
+//! by Apple, but both consist of a list of subtables. We only subset subtables in
+//! format 0, which is a sorted list of glyph pairs and is by far the most common one.
+//! Subtables in other formats refer to glyphs in a way that we can't remap yet, so
+//! they are dropped. Pairs are only kept if both glyphs are in the subset and then
+//! sorted again by their new glyph IDs.
+//!
+//! See the [Microsoft](https://learn.microsoft.com/en-us/typography/opentype/spec/kern)
+//! and [Apple](https://developer.apple.com/fonts/TrueType-Reference-Manual/RM06/Chap6kern.html)
+//! specifications.
+
+use super::*;
+
+pub fn subset(ctx: &mut Context) -> Result<()> {
+    let kern = ctx.expect_table(Tag::KERN).ok_or(MalformedFont)?;
+    if let Some(data) = subset_table(kern, &ctx.mapper)? {
+        ctx.push(Tag::KERN, data);
+    }
+    Ok(())
+}
+
+/// Subset a `kern` table. Returns `None` if no pairs are left.
+fn subset_table(data: &[u8], mapper: &GlyphRemapper) -> Result<Option<Vec<u8>>> {
+    let mut r = Reader::new(data);
+    let apple = r.peak::<u16>().ok_or(MalformedFont)? == 1;
+
+    let count = if apple {
+        r.skip::<u32>();
+        r.read::<u32>().ok_or(MalformedFont)?
+    } else {
+        r.skip::<u16>();
+        u32::from(r.read::<u16>().ok_or(MalformedFont)?)
+    };
+
+    let mut subtables = vec![];
+    for _ in 0..count {
+        let start = r.offset();
+        let (len, coverage, tuple_index, format) = if apple {
+            let len = r.read::<u32>().ok_or(MalformedFont)? as usize;
+            let coverage = r.read::<u8>().ok_or(MalformedFont)?;
+            let format = r.read::<u8>().ok_or(MalformedFont)?;
+            let tuple_index = r.read::<u16>().ok_or(MalformedFont)?;
+            (len, coverage, tuple_index, format)
+        } else {
+            r.skip::<u16>();
+            let len = usize::from(r.read::<u16>().ok_or(MalformedFont)?);
+            let format = r.read::<u8>().ok_or(MalformedFont)?;
+            let coverage = r.read::<u8>().ok_or(MalformedFont)?;
+            (len, coverage, 0, format)
+        };
+
+        // The length of format 0 subtables often overflows in the Microsoft
+        // version, so we only rely on it to skip subtables in other formats.
+        if format != 0 {
+            r.jump(start.checked_add(len).ok_or(MalformedFont)?);
+            continue;
+        }
+
+        let num_pairs = r.read::<u16>().ok_or(MalformedFont)?;
+        r.skip::<u16>(); // searchRange
+        r.skip::<u16>(); // entrySelector
+        r.skip::<u16>(); // rangeShift
+
+        let mut pairs = vec![];
+        for _ in 0..num_pairs {
+            let left = r.read::<u16>().ok_or(MalformedFont)?;
+            let right = r.read::<u16>().ok_or(MalformedFont)?;
+            let value = r.read::<i16>().ok_or(MalformedFont)?;
+            if let (Some(left), Some(right)) = (mapper.get(left), mapper.get(right)) {
+                pairs.push((left, right, value));
+            }
+        }
+
+        if !pairs.is_empty() {
+            pairs.sort_unstable_by_key(|&(left, right, _)| (left, right));
+            subtables.push((coverage, tuple_index, pairs));
+        }
+    }
+
+    if subtables.is_empty() {
+        return Ok(None);
+    }
+
+    let mut w = Writer::new();
+    if apple {
+        w.write::<u32>(0x00010000);
+        w.write::<u32>(subtables.len() as u32);
+    } else {
+        w.write::<u16>(0);
+        w.write::<u16>(subtables.len() as u16);
+    }
+
+    for (coverage, tuple_index, pairs) in subtables {
+        // Since all pairs are from a single subtable, there can't be more than
+        // `u16::MAX` of them. The lengths and the search parameters derived from
+        // their number can be larger than that, though.
+        let num_pairs = pairs.len() as u32;
+        let len = 14 + 6 * num_pairs;
+        if apple {
+            w.write::<u32>(len + 2);
+            w.write::<u8>(coverage);
+            w.write::<u8>(0);
+            w.write::<u16>(tuple_index);
+        } else {
+            // Like in the original table, the length of a large subtable is
+            // truncated. Readers don't rely on it, see above.
+            w.write::<u16>(0);
+            w.write::<u16>(len as u16);
+            w.write::<u8>(0);
+            w.write::<u8>(coverage);
+        }
+
+        // With many pairs, the search range and range shift don't fit into 16
+        // bits, so we saturate them.
+        let entry_selector = num_pairs.ilog2();
+        let search_range = (1 << entry_selector) * 6;
+        let saturate = |v: u32| u16::try_from(v).unwrap_or(u16::MAX);
+        w.write::<u16>(num_pairs as u16);
+        w.write::<u16>(saturate(search_range));
+        w.write::<u16>(entry_selector as u16);
+        w.write::<u16>(saturate(num_pairs * 6 - search_range));
+
+        for (left, right, value) in pairs {
+            w.write::<u16>(left);
+            w.write::<u16>(right);
+            w.write::<i16>(value);
+        }
+    }
+
+    Ok(Some(w.finish()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subset_format_0() {
+        let mut data = Vec::new();
+        data.extend(0u16.to_be_bytes()); // version
+        data.extend(1u16.to_be_bytes()); // nTables
+        data.extend(0u16.to_be_bytes()); // version
+        data.extend(32u16.to_be_bytes()); // length
+        data.extend(0x0001u16.to_be_bytes()); // coverage
+        data.extend(3u16.to_be_bytes()); // nPairs
+        data.extend(12u16.to_be_bytes()); // searchRange
+        data.extend(1u16.to_be_bytes()); // entrySelector
+        data.extend(6u16.to_be_bytes()); // rangeShift
+        for (left, right, value) in [(1u16, 2u16, -10i16), (2, 1, 20), (3, 1, 30)] {
+            data.extend(left.to_be_bytes());
+            data.extend(right.to_be_bytes());
+            data.extend(value.to_be_bytes());
+        }
+
+        // Glyph 3 is not in the subset, and glyphs 1 and 2 swap their order.
+        let mapper = GlyphRemapper::new_from_glyphs(&[0, 2, 1]);
+        let subset = subset_table(&data, &mapper).unwrap().unwrap();
+
+        let mut expected = Vec::new();
+        expected.extend(0u16.to_be_bytes()); // version
+        expected.extend(1u16.to_be_bytes()); // nTables
+        expected.extend(0u16.to_be_bytes()); // version
+        expected.extend(26u16.to_be_bytes()); // length
+        expected.extend(0x0001u16.to_be_bytes()); // coverage
+        expected.extend(2u16.to_be_bytes()); // nPairs
+        expected.extend(12u16.to_be_bytes()); // searchRange
+        expected.extend(1u16.to_be_bytes()); // entrySelector
+        expected.extend(0u16.to_be_bytes()); // rangeShift
+        for (left, right, value) in [(1u16, 2u16, 20i16), (2, 1, -10)] {
+            expected.extend(left.to_be_bytes());
+            expected.extend(right.to_be_bytes());
+            expected.extend(value.to_be_bytes());
+        }
+
+        assert_eq!(subset, expected);
+
+        // Without any pairs, the table is dropped.
+        let mapper = GlyphRemapper::new_from_glyphs(&[0, 3]);
+        assert_eq!(subset_table(&data, &mapper).unwrap(), None);
+    }
+
+    #[test]
+    fn subset_many_pairs() {
+        // 20000 pairs don't fit into the 16-bit length, search range and range
+        // shift of a subtable.
+        let pairs =
+            (0..200u16).flat_map(|left| (0..100u16).map(move |right| (left, right)));
+        let table = |apple: bool| {
+            let mut data = Vec::new();
+            if apple {
+                data.extend(0x00010000u32.to_be_bytes()); // version
+                data.extend(1u32.to_be_bytes()); // nTables
+                data.extend(120016u32.to_be_bytes()); // length
+                data.extend(0u16.to_be_bytes()); // coverage, format
+                data.extend(0u16.to_be_bytes()); // tupleIndex
+            } else {
+                data.extend(0u16.to_be_bytes()); // version
+                data.extend(1u16.to_be_bytes()); // nTables
+                data.extend(0u16.to_be_bytes()); // version
+                data.extend((120014u32 as u16).to_be_bytes()); // length
+                data.extend(0x0001u16.to_be_bytes()); // coverage
+            }
+            data.extend(20000u16.to_be_bytes()); // nPairs
+            data.extend(u16::MAX.to_be_bytes()); // searchRange
+            data.extend(14u16.to_be_bytes()); // entrySelector
+            data.extend(21696u16.to_be_bytes()); // rangeShift
+            for (left, right) in pairs.clone() {
+                data.extend(left.to_be_bytes());
+                data.extend(right.to_be_bytes());
+                data.extend(1i16.to_be_bytes());
+            }
+            data
+        };
+
+        // Keeping all glyphs reproduces the table.
+        let mapper = GlyphRemapper::new_from_glyphs(&(0..200).collect::<Vec<_>>());
+        for apple in [false, true] {
+            let data = table(apple);
+            assert_eq!(subset_table(&data, &mapper).unwrap(), Some(data));
+        }
+    }
+}
@@ -0,0 +1,242 @@
+//! The `BASE` table defines the positions of the baselines of each script, as
+//! well as the minimum and maximum extent of lines, for horizontal and vertical
+//! layout. See the [specification].
+//!
+//! The only thing in it that refers to glyphs are baseline coordinates that are
+//! adjusted by the position of a contour point of a reference glyph. If the
+//! reference glyph is not part of the subset, we turn them into plain coordinates.
+//! Everything else is kept as is.
+//!
+//! [specification]: https://learn.microsoft.com/en-us/typography/opentype/spec/base
+
+use super::*;
+
+/// Subset the `BASE` table.
+pub fn subset(ctx: &mut Context) -> Result<()> {
+    let data = ctx.expect_table(Tag::BASE).ok_or(MalformedFont)?;
+    let node = table(&ctx.mapper, data).ok_or(MalformedFont)?;
+    let data = node.pack().ok_or(OverflowError)?;
+    ctx.push(Tag::BASE, data);
+    Ok(())
+}
+
+fn table(mapper: &GlyphRemapper, data: &[u8]) -> Option<Node> {
+    let mut r = Reader::new(data);
+    let major_version = r.read::<u16>()?;
+    let minor_version = r.read::<u16>()?;
+    if major_version != 1 {
+        return None;
+    }
+
+    let mut node = Node::new();
+    node.write::<u16>(major_version);
+    node.write::<u16>(minor_version);
+
+    // The horizontal and vertical axis.
+    for _ in 0..2 {
+        node.link_opt(match r.read::<u16>()? {
+            0 => None,
+            offset => Some(axis_node(mapper, data.get(offset as usize..)?)?),
+        });
+    }
+
+    if minor_version >= 1 {
+        node.link32_opt(match r.read::<u32>()? {
+            0 => None,
+            offset => Some(item_variation_store_node(data.get(offset as usize..)?)?),
+        });
+    }
+
+    Some(node)
+}
+
+fn axis_node(mapper: &GlyphRemapper, data: &[u8]) -> Option<Node> {
+    let mut r = Reader::new(data);
+    let tag_list = r.read::<u16>()?;
+    let script_list = r.read::<u16>()?;
+
+    let mut node = Node::new();
+    node.link_opt(match tag_list {
+        0 => None,
+        offset => {
+            let data = data.get(offset as usize..)?;
+            let count = Reader::new(data).read::<u16>()?;
+            Some(Node::from_bytes(data.get(..2 + 4 * usize::from(count))?))
+        }
+    });
+    node.link(script_list_node(mapper, data.get(script_list as usize..)?)?);
+    Some(node)
+}
+
+fn script_list_node(mapper: &GlyphRemapper, data: &[u8]) -> Option<Node> {
+    let mut r = Reader::new(data);
+    let count = r.read::<u16>()?;
+
+    let mut node = Node::new();
+    node.write::<u16>(count);
+    for _ in 0..count {
+        node.write::<Tag>(r.read::<Tag>()?);
+        let offset = r.read::<u16>()?;
+        node.link(script_node(mapper, data.get(offset as usize..)?)?);
+    }
+
+    Some(node)
+}
+
+fn script_node(mapper: &GlyphRemapper, data: &[u8]) -> Option<Node> {
+    let mut r = Reader::new(data);
+    let values = r.read::<u16>()?;
+    let default_min_max = r.read::<u16>()?;
+    let count = r.read::<u16>()?;
+
+    let mut node = Node::new();
+    node.link_opt(match values {
+        0 => None,
+        offset => Some(values_node(mapper, data.get(offset as usize..)?)?),
+    });
+    node.link_opt(match default_min_max {
+        0 => None,
+        offset => Some(min_max_node(mapper, data.get(offset as usize..)?)?),
+    });
+    node.write::<u16>(count);
+    for _ in 0..count {
+        node.write::<Tag>(r.read::<Tag>()?);
+        let offset = r.read::<u16>()?;
+        node.link(min_max_node(mapper, data.get(offset as usize..)?)?);
+    }
+
+    Some(node)
+}
+
+fn values_node(mapper: &GlyphRemapper, data: &[u8]) -> Option<Node> {
+    let mut r = Reader::new(data);
+    let default_index = r.read::<u16>()?;
+    let count = r.read::<u16>()?;
+
+    let mut node = Node::new();
+    node.write::<u16>(default_index);
+    node.write::<u16>(count);
+    for _ in 0..count {
+        node.link(coord_node(mapper, data, r.read::<u16>()?)?);
+    }
+
+    Some(node)
+}
+
+fn min_max_node(mapper: &GlyphRemapper, data: &[u8]) -> Option<Node> {
+    let mut r = Reader::new(data);
+    let coord = |r: &mut Reader| match r.read::<u16>()? {
+        0 => Some(None),
+        offset => coord_node(mapper, data, offset).map(Some),
+    };
+
+    let mut node = Node::new();
+    node.link_opt(coord(&mut r)?);
+    node.link_opt(coord(&mut r)?);
+
+    // The extents for specific features.
+    let count = r.read::<u16>()?;
+    node.write::<u16>(count);
+    for _ in 0..count {
+        node.write::<Tag>(r.read::<Tag>()?);
+        node.link_opt(coord(&mut r)?);
+        node.link_opt(coord(&mut r)?);
+    }
+
+    Some(node)
+}
+
+/// Write a `BaseCoord` table with a remapped reference glyph.
+fn coord_node(mapper: &GlyphRemapper, data: &[u8], offset: u16) -> Option<Node> {
+    let data = data.get(offset as usize..)?;
+    let mut r = Reader::new(data);
+    let format = r.read::<u16>()?;
+    let coordinate = r.read::<i16>()?;
+
+    let mut node = Node::new();
+    match format {
+        1 => {
+            node.write::<u16>(1);
+            node.write::<i16>(coordinate);
+        }
+        2 => {
+            let glyph = r.read::<u16>()?;
+            let point = r.read::<u16>()?;
+            match mapper.get(glyph) {
+                Some(glyph) => {
+                    node.write::<u16>(2);
+                    node.write::<i16>(coordinate);
+                    node.write::<u16>(glyph);
+                    node.write::<u16>(point);
+                }
+                None => {
+                    node.write::<u16>(1);
+                    node.write::<i16>(coordinate);
+                }
+            }
+        }
+        3 => {
+            node.write::<u16>(3);
+            node.write::<i16>(coordinate);
+            match r.read::<u16>()? {
+                0 => node.write::<u16>(0),
+                offset => node.link(device_node(data, offset)?),
+            }
+        }
+        _ => return None,
+    }
+
+    Some(node)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remap_reference_glyphs() {
+        let coord = |glyph: u16| {
+            let mut node = Node::new();
+            node.write::<[u16; 4]>([2, 100, glyph, 3]);
+            node
+        };
+
+        let mut values = Node::new();
+        values.write::<[u16; 2]>([0, 2]);
+        values.link(coord(5));
+        values.link(coord(7));
+
+        let mut script = Node::new();
+        script.link(values);
+        script.write::<[u16; 2]>([0, 0]);
+
+        let mut script_list = Node::new();
+        script_list.write::<u16>(1);
+        script_list.write(Tag::new(b"latn"));
+        script_list.link(script);
+
+        let mut axis = Node::new();
+        axis.write::<u16>(0);
+        axis.link(script_list);
+
+        let mut root = Node::new();
+        root.write::<[u16; 2]>([1, 0]);
+        root.link(axis);
+        root.write::<u16>(0);
+        let data = root.pack().unwrap();
+
+        let mapper = GlyphRemapper::new_from_glyphs(&[7]);
+        let subset = table(&mapper, &data).unwrap().pack().unwrap();
+
+        let mut expected = Vec::new();
+        expected.extend([0, 1, 0, 0, 0, 8, 0, 0]); // header
+        expected.extend([0, 0, 0, 4]); // axis
+        expected.extend([0, 1, b'l', b'a', b't', b'n', 0, 8]); // script list
+        expected.extend([0, 6, 0, 0, 0, 0]); // script
+        expected.extend([0, 0, 0, 2, 0, 8, 0, 12]); // values
+        expected.extend([0, 1, 0, 100]); // coordinate of glyph 5
+        expected.extend([0, 2, 0, 100, 0, 1, 0, 3]); // coordinate of glyph 7
+
+        assert_eq!(subset, expected);
+    }
+}
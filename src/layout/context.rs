@@ -0,0 +1,427 @@
+//! Contextual lookups apply other lookups at certain positions of a sequence of
+//! glyphs. They have the same structure in `GSUB` and `GPOS`.
+
+use super::*;
+
+/// Subset a sequence context subtable.
+pub fn subset(s: &Subsetter, data: &[u8]) -> Option<Option<Node>> {
+    let mut r = Reader::new(data);
+    let format = r.read::<u16>()?;
+
+    let mut node = Node::new();
+    node.write::<u16>(format);
+    match format {
+        1 => {
+            let coverage = Coverage::parse_at(data, r.read::<u16>()?)?;
+            let count = r.read::<u16>()?;
+            let offsets = r.read_array16::<u16>(count)?;
+            let glyph = |glyph| s.glyph(glyph);
+            let Some((coverage, rule_sets)) =
+                glyph_rule_sets(s, &coverage, offsets, |o| {
+                    rule_set_node(data, o, |r| context_rule_node(s, r, &glyph))
+                })?
+            else {
+                return Some(None);
+            };
+
+            node.link(coverage);
+            node.write::<u16>(rule_sets.len() as u16);
+            for rule_set in rule_sets {
+                node.link(rule_set);
+            }
+        }
+        2 => {
+            let Some(coverage) = s.coverage(&Coverage::parse_at(data, r.read::<u16>()?)?)
+            else {
+                return Some(None);
+            };
+            let class_def = ClassDef::parse_at(data, r.read::<u16>()?)?;
+            let count = r.read::<u16>()?;
+            let offsets = r.read_array16::<u16>(count)?;
+
+            node.link(coverage);
+            node.link(s.class_def(&class_def));
+            node.write::<u16>(count);
+            for offset in offsets {
+                node.link_opt(match offset {
+                    0 => None,
+                    _ => rule_set_node(data, offset, |r| context_rule_node(s, r, &Some))?,
+                });
+            }
+        }
+        3 => {
+            let glyph_count = r.read::<u16>()?;
+            let lookup_count = r.read::<u16>()?;
+            let Some(coverages) =
+                coverages(s, data, r.read_array16::<u16>(glyph_count)?)?
+            else {
+                return Some(None);
+            };
+            let records = s.lookup_records(&mut r, lookup_count)?;
+
+            node.write::<u16>(glyph_count);
+            node.write::<u16>(records.len() as u16);
+            for coverage in coverages {
+                node.link(coverage);
+            }
+            write_lookup_records(&mut node, &records);
+        }
+        _ => return None,
+    }
+
+    Some(Some(node))
+}
+
+/// Subset a chained sequence context subtable.
+pub fn subset_chained(s: &Subsetter, data: &[u8]) -> Option<Option<Node>> {
+    let mut r = Reader::new(data);
+    let format = r.read::<u16>()?;
+
+    let mut node = Node::new();
+    node.write::<u16>(format);
+    match format {
+        1 => {
+            let coverage = Coverage::parse_at(data, r.read::<u16>()?)?;
+            let count = r.read::<u16>()?;
+            let offsets = r.read_array16::<u16>(count)?;
+            let glyph = |glyph| s.glyph(glyph);
+            let Some((coverage, rule_sets)) =
+                glyph_rule_sets(s, &coverage, offsets, |o| {
+                    rule_set_node(data, o, |r| {
+                        chained_context_rule_node(s, r, [&glyph, &glyph, &glyph])
+                    })
+                })?
+            else {
+                return Some(None);
+            };
+
+            node.link(coverage);
+            node.write::<u16>(rule_sets.len() as u16);
+            for rule_set in rule_sets {
+                node.link(rule_set);
+            }
+        }
+        2 => {
+            let Some(coverage) = s.coverage(&Coverage::parse_at(data, r.read::<u16>()?)?)
+            else {
+                return Some(None);
+            };
+
+            node.link(coverage);
+            for _ in 0..3 {
+                let class_def = ClassDef::parse_at(data, r.read::<u16>()?)?;
+                node.link(s.class_def(&class_def));
+            }
+
+            let count = r.read::<u16>()?;
+            node.write::<u16>(count);
+            for offset in r.read_array16::<u16>(count)? {
+                node.link_opt(match offset {
+                    0 => None,
+                    _ => rule_set_node(data, offset, |r| {
+                        chained_context_rule_node(s, r, [&Some, &Some, &Some])
+                    })?,
+                });
+            }
+        }
+        3 => {
+            let mut sequences = vec![];
+            for _ in 0..3 {
+                let count = r.read::<u16>()?;
+                let offsets = r.read_array16::<u16>(count)?;
+                let Some(coverages) = coverages(s, data, offsets)? else {
+                    return Some(None);
+                };
+                sequences.push(coverages);
+            }
+
+            let lookup_count = r.read::<u16>()?;
+            let records = s.lookup_records(&mut r, lookup_count)?;
+
+            for coverages in sequences {
+                node.write::<u16>(coverages.len() as u16);
+                for coverage in coverages {
+                    node.link(coverage);
+                }
+            }
+            node.write::<u16>(records.len() as u16);
+            write_lookup_records(&mut node, &records);
+        }
+        _ => return None,
+    }
+
+    Some(Some(node))
+}
+
+/// The indices of all lookups that a sequence context subtable refers to.
+pub fn nested_lookups(data: &[u8]) -> Option<Vec<u16>> {
+    let mut r = Reader::new(data);
+    match r.read::<u16>()? {
+        format @ (1 | 2) => {
+            // coverageOffset
+            r.skip::<u16>();
+            if format == 2 {
+                // classDefOffset
+                r.skip::<u16>();
+            }
+
+            let count = r.read::<u16>()?;
+            let mut lookups = vec![];
+            for offset in r.read_array16::<u16>(count)? {
+                if offset != 0 {
+                    lookups
+                        .extend(rule_set(data, offset, |r| context_rule(r, &|_| true))?);
+                }
+            }
+            Some(lookups)
+        }
+        3 => {
+            let glyph_count = r.read::<u16>()?;
+            let lookup_count = r.read::<u16>()?;
+            r.skip_bytes(glyph_count as usize * u16::SIZE);
+            lookup_records(&mut r, lookup_count)
+        }
+        _ => None,
+    }
+}
+
+/// The indices of all lookups that a chained sequence context subtable refers to.
+pub fn nested_chained_lookups(data: &[u8]) -> Option<Vec<u16>> {
+    let mut r = Reader::new(data);
+    match r.read::<u16>()? {
+        format @ (1 | 2) => {
+            // coverageOffset
+            r.skip::<u16>();
+            if format == 2 {
+                // backtrackClassDefOffset, inputClassDefOffset, lookaheadClassDefOffset
+                r.skip_bytes(3 * u16::SIZE);
+            }
+
+            let count = r.read::<u16>()?;
+            let mut lookups = vec![];
+            for offset in r.read_array16::<u16>(count)? {
+                if offset != 0 {
+                    let any = |_| true;
+                    lookups.extend(rule_set(data, offset, |r| {
+                        chained_context_rule(r, &any, &any, &any)
+                    })?);
+                }
+            }
+            Some(lookups)
+        }
+        3 => {
+            for _ in 0..3 {
+                let count = r.read::<u16>()?;
+                r.skip_bytes(count as usize * u16::SIZE);
+            }
+            let lookup_count = r.read::<u16>()?;
+            lookup_records(&mut r, lookup_count)
+        }
+        _ => None,
+    }
+}
+
+/// Collect the lookups of all rules in the rule set at the given offset for
+/// which `rule` matches.
+pub fn rule_set(
+    data: &[u8],
+    offset: u16,
+    rule: impl Fn(&mut Reader) -> Option<Option<Vec<u16>>>,
+) -> Option<Vec<u16>> {
+    let data = data.get(offset as usize..)?;
+    let mut r = Reader::new(data);
+    let count = r.read::<u16>()?;
+
+    let mut lookups = vec![];
+    for offset in r.read_array16::<u16>(count)? {
+        let mut r = Reader::new(data.get(offset as usize..)?);
+        lookups.extend(rule(&mut r)?.into_iter().flatten());
+    }
+
+    Some(lookups)
+}
+
+/// Returns the lookups of a (class-based) sequence rule if all of its input glyphs
+/// or classes match.
+pub fn context_rule(
+    r: &mut Reader,
+    input: &dyn Fn(u16) -> bool,
+) -> Option<Option<Vec<u16>>> {
+    let glyph_count = r.read::<u16>()?;
+    let lookup_count = r.read::<u16>()?;
+    let sequence = r.read_array16::<u16>(glyph_count.checked_sub(1)?)?;
+    if !sequence.into_iter().all(input) {
+        return Some(None);
+    }
+
+    lookup_records(r, lookup_count).map(Some)
+}
+
+/// Returns the lookups of a (class-based) chained sequence rule if all of its
+/// glyphs or classes match.
+pub fn chained_context_rule(
+    r: &mut Reader,
+    backtrack: &dyn Fn(u16) -> bool,
+    input: &dyn Fn(u16) -> bool,
+    lookahead: &dyn Fn(u16) -> bool,
+) -> Option<Option<Vec<u16>>> {
+    let count = r.read::<u16>()?;
+    let mut matches = r.read_array16::<u16>(count)?.into_iter().all(backtrack);
+    let count = r.read::<u16>()?;
+    matches &= r.read_array16::<u16>(count.checked_sub(1)?)?.into_iter().all(input);
+    let count = r.read::<u16>()?;
+    matches &= r.read_array16::<u16>(count)?.into_iter().all(lookahead);
+    if !matches {
+        return Some(None);
+    }
+
+    let lookup_count = r.read::<u16>()?;
+    lookup_records(r, lookup_count).map(Some)
+}
+
+/// Read the lookup indices of sequence lookup records.
+pub fn lookup_records(r: &mut Reader, count: u16) -> Option<Vec<u16>> {
+    let mut lookups = vec![];
+    for _ in 0..count {
+        // sequenceIndex
+        r.skip::<u16>();
+        lookups.push(r.read::<u16>()?);
+    }
+
+    Some(lookups)
+}
+
+/// Subset the rule sets of a glyph-based subtable, which belong to the glyphs of
+/// the coverage table. Returns `None` if no rule set is left.
+#[allow(clippy::type_complexity)]
+fn glyph_rule_sets(
+    s: &Subsetter,
+    coverage: &Coverage,
+    offsets: LazyArray16<u16>,
+    rule_set: impl Fn(u16) -> Option<Option<Node>>,
+) -> Option<Option<(Node, Vec<Node>)>> {
+    let mut glyphs = vec![];
+    let mut rule_sets = vec![];
+    for (glyph, offset) in s.retain(coverage, offsets) {
+        if let Some(node) = rule_set(offset)? {
+            glyphs.push(glyph);
+            rule_sets.push(node);
+        }
+    }
+
+    if glyphs.is_empty() {
+        return Some(None);
+    }
+
+    Some(Some((coverage_node(&glyphs), rule_sets)))
+}
+
+/// Subset the rule set at the given offset. Returns `None` if no rule is left.
+fn rule_set_node(
+    data: &[u8],
+    offset: u16,
+    rule: impl Fn(&mut Reader) -> Option<Option<Node>>,
+) -> Option<Option<Node>> {
+    let data = data.get(offset as usize..)?;
+    let mut r = Reader::new(data);
+    let count = r.read::<u16>()?;
+
+    let mut rules = vec![];
+    for offset in r.read_array16::<u16>(count)? {
+        let mut r = Reader::new(data.get(offset as usize..)?);
+        rules.extend(rule(&mut r)?);
+    }
+
+    if rules.is_empty() {
+        return Some(None);
+    }
+
+    let mut node = Node::new();
+    node.write::<u16>(rules.len() as u16);
+    for rule in rules {
+        node.link(rule);
+    }
+
+    Some(Some(node))
+}
+
+/// Subset a (class-based) sequence rule. The input sequence is mapped with `map`
+/// and the rule is dropped if that fails for any of its glyphs.
+fn context_rule_node(
+    s: &Subsetter,
+    r: &mut Reader,
+    map: &dyn Fn(u16) -> Option<u16>,
+) -> Option<Option<Node>> {
+    let glyph_count = r.read::<u16>()?;
+    let lookup_count = r.read::<u16>()?;
+    let sequence = r.read_array16::<u16>(glyph_count.checked_sub(1)?)?;
+    let Some(sequence) = sequence.into_iter().map(map).collect::<Option<Vec<_>>>() else {
+        return Some(None);
+    };
+    let records = s.lookup_records(r, lookup_count)?;
+
+    let mut node = Node::new();
+    node.write::<u16>(glyph_count);
+    node.write::<u16>(records.len() as u16);
+    node.write(sequence.as_slice());
+    write_lookup_records(&mut node, &records);
+    Some(Some(node))
+}
+
+/// Subset a (class-based) chained sequence rule. The backtrack, input and
+/// lookahead sequences are mapped with the respective function in `maps`.
+fn chained_context_rule_node(
+    s: &Subsetter,
+    r: &mut Reader,
+    maps: [&dyn Fn(u16) -> Option<u16>; 3],
+) -> Option<Option<Node>> {
+    let mut sequences = vec![];
+    for (i, map) in maps.into_iter().enumerate() {
+        let count = r.read::<u16>()?;
+        // The first glyph of the input sequence is given by the coverage.
+        let len = if i == 1 { count.checked_sub(1)? } else { count };
+        let sequence = r.read_array16::<u16>(len)?;
+        let Some(sequence) = sequence.into_iter().map(map).collect::<Option<Vec<_>>>()
+        else {
+            return Some(None);
+        };
+        sequences.push((count, sequence));
+    }
+
+    let lookup_count = r.read::<u16>()?;
+    let records = s.lookup_records(r, lookup_count)?;
+
+    let mut node = Node::new();
+    for (count, sequence) in sequences {
+        node.write::<u16>(count);
+        node.write(sequence.as_slice());
+    }
+    node.write::<u16>(records.len() as u16);
+    write_lookup_records(&mut node, &records);
+    Some(Some(node))
+}
+
+/// Subset the coverage tables of a format 3 subtable. Returns `None` if any of
+/// them becomes empty, since the subtable can't match anymore then.
+fn coverages(
+    s: &Subsetter,
+    data: &[u8],
+    offsets: LazyArray16<u16>,
+) -> Option<Option<Vec<Node>>> {
+    let mut coverages = vec![];
+    for offset in offsets {
+        match s.coverage(&Coverage::parse_at(data, offset)?) {
+            Some(coverage) => coverages.push(coverage),
+            None => return Some(None),
+        }
+    }
+
+    Some(Some(coverages))
+}
+
+fn write_lookup_records(node: &mut Node, records: &[(u16, u16)]) {
+    for &(sequence_index, lookup_index) in records {
+        node.write::<u16>(sequence_index);
+        node.write::<u16>(lookup_index);
+    }
+}
@@ -0,0 +1,205 @@
+//! The `GDEF` table provides glyph classes, attachment points, ligature carets and
+//! mark glyph sets, all of which refer to glyphs. Lookups refer to mark glyph sets
+//! by index, so all of them are kept, even if they become empty.
+
+use super::*;
+
+/// Subset the `GDEF` table.
+pub fn subset(ctx: &mut Context) -> Result<()> {
+    let data = ctx.expect_table(Tag::GDEF).ok_or(MalformedFont)?;
+    let s = Subsetter {
+        mapper: &ctx.mapper,
+        lookups: FxHashMap::default(),
+        kinds: LookupKinds::new(Tag::GDEF),
+    };
+
+    let node = table(&s, data).ok_or(MalformedFont)?;
+    let data = node.pack().ok_or(OverflowError)?;
+    ctx.push(Tag::GDEF, data);
+    Ok(())
+}
+
+fn table(s: &Subsetter, data: &[u8]) -> Option<Node> {
+    let mut r = Reader::new(data);
+    let major_version = r.read::<u16>()?;
+    let minor_version = r.read::<u16>()?;
+    if major_version != 1 {
+        return None;
+    }
+
+    let glyph_class_def = r.read::<u16>()?;
+    let attach_list = r.read::<u16>()?;
+    let lig_caret_list = r.read::<u16>()?;
+    let mark_attach_class_def = r.read::<u16>()?;
+
+    let mut node = Node::new();
+    node.write::<u16>(major_version);
+    node.write::<u16>(minor_version);
+    node.link_opt(class_def(s, data, glyph_class_def)?);
+    node.link_opt(match attach_list {
+        0 => None,
+        offset => attach_list_node(s, data.get(offset as usize..)?)?,
+    });
+    node.link_opt(match lig_caret_list {
+        0 => None,
+        offset => lig_caret_list_node(s, data.get(offset as usize..)?)?,
+    });
+    node.link_opt(class_def(s, data, mark_attach_class_def)?);
+
+    if minor_version >= 2 {
+        node.link_opt(match r.read::<u16>()? {
+            0 => None,
+            offset => Some(mark_glyph_sets_node(s, data.get(offset as usize..)?)?),
+        });
+    }
+
+    if minor_version >= 3 {
+        node.link32_opt(match r.read::<u32>()? {
+            0 => None,
+            offset => Some(item_variation_store_node(data.get(offset as usize..)?)?),
+        });
+    }
+
+    Some(node)
+}
+
+/// Subset the class definition at the given offset, which may be null.
+fn class_def(s: &Subsetter, data: &[u8], offset: u16) -> Option<Option<Node>> {
+    match offset {
+        0 => Some(None),
+        _ => Some(Some(s.class_def(&ClassDef::parse_at(data, offset)?))),
+    }
+}
+
+/// Subset an attachment point list. Returns `None` if no glyph is left.
+fn attach_list_node(s: &Subsetter, data: &[u8]) -> Option<Option<Node>> {
+    let mut r = Reader::new(data);
+    let coverage = Coverage::parse_at(data, r.read::<u16>()?)?;
+    let count = r.read::<u16>()?;
+    let retained = s.retain(&coverage, r.read_array16::<u16>(count)?);
+    if retained.is_empty() {
+        return Some(None);
+    }
+
+    let glyphs = retained.iter().map(|&(glyph, _)| glyph).collect::<Vec<_>>();
+    let mut node = Node::new();
+    node.link(coverage_node(&glyphs));
+    node.write::<u16>(retained.len() as u16);
+    for (_, offset) in retained {
+        let mut r = Reader::new(data.get(offset as usize..)?);
+        let count = r.read::<u16>()?;
+        let mut point = Node::new();
+        point.write::<u16>(count);
+        point.write(r.read_bytes(count as usize * u16::SIZE)?);
+        node.link(point);
+    }
+
+    Some(Some(node))
+}
+
+/// Subset a ligature caret list. Returns `None` if no glyph is left.
+fn lig_caret_list_node(s: &Subsetter, data: &[u8]) -> Option<Option<Node>> {
+    let mut r = Reader::new(data);
+    let coverage = Coverage::parse_at(data, r.read::<u16>()?)?;
+    let count = r.read::<u16>()?;
+    let retained = s.retain(&coverage, r.read_array16::<u16>(count)?);
+    if retained.is_empty() {
+        return Some(None);
+    }
+
+    let glyphs = retained.iter().map(|&(glyph, _)| glyph).collect::<Vec<_>>();
+    let mut node = Node::new();
+    node.link(coverage_node(&glyphs));
+    node.write::<u16>(retained.len() as u16);
+    for (_, offset) in retained {
+        let data = data.get(offset as usize..)?;
+        let mut r = Reader::new(data);
+        let count = r.read::<u16>()?;
+
+        let mut lig_glyph = Node::new();
+        lig_glyph.write::<u16>(count);
+        for offset in r.read_array16::<u16>(count)? {
+            lig_glyph.link(caret_value_node(data.get(offset as usize..)?)?);
+        }
+        node.link(lig_glyph);
+    }
+
+    Some(Some(node))
+}
+
+/// Copy a caret value table.
+fn caret_value_node(data: &[u8]) -> Option<Node> {
+    let mut r = Reader::new(data);
+    match r.read::<u16>()? {
+        // A coordinate or a contour point.
+        1 | 2 => Some(Node::from_bytes(data.get(..4)?)),
+        // A coordinate with a device table.
+        3 => {
+            let mut node = Node::from_bytes(data.get(..4)?);
+            // coordinate
+            r.skip::<i16>();
+            node.link(device_node(data, r.read::<u16>()?)?);
+            Some(node)
+        }
+        _ => None,
+    }
+}
+
+/// Subset the mark glyph sets.
+fn mark_glyph_sets_node(s: &Subsetter, data: &[u8]) -> Option<Node> {
+    let mut r = Reader::new(data);
+    let format = r.read::<u16>()?;
+    let count = r.read::<u16>()?;
+
+    let mut node = Node::new();
+    node.write::<u16>(format);
+    node.write::<u16>(count);
+    for _ in 0..count {
+        let coverage = Coverage::parse(data.get(r.read::<u32>()? as usize..)?)?;
+        node.link32(s.coverage(&coverage).unwrap_or_else(|| coverage_node(&[])));
+    }
+
+    Some(node)
+}
+
+/// Copy an item variation store. The device tables of the other layout tables
+/// may refer to it, so we keep it as is.
+pub fn item_variation_store_node(data: &[u8]) -> Option<Node> {
+    let mut r = Reader::new(data);
+    let format = r.read::<u16>()?;
+    let region_list = data.get(r.read::<u32>()? as usize..)?;
+    let count = r.read::<u16>()?;
+
+    let mut node = Node::new();
+    node.write::<u16>(format);
+
+    let mut regions = Reader::new(region_list);
+    let axis_count = regions.read::<u16>()? as usize;
+    let region_count = regions.read::<u16>()? as usize;
+    // Each region has a start, peak and end coordinate for each axis.
+    let len = 4 + axis_count * region_count * 6;
+    node.link32(Node::from_bytes(region_list.get(..len)?));
+
+    node.write::<u16>(count);
+    for _ in 0..count {
+        let data = data.get(r.read::<u32>()? as usize..)?;
+        let mut r = Reader::new(data);
+        let item_count = r.read::<u16>()? as usize;
+        let word_delta_count = r.read::<u16>()?;
+        let region_index_count = r.read::<u16>()? as usize;
+
+        // The first deltas of each row are words (or 32-bit if the high bit is
+        // set), the others are bytes (or words).
+        let words = (word_delta_count & 0x7FFF) as usize;
+        let row_size = if word_delta_count & 0x8000 != 0 {
+            4 * words + 2 * region_index_count.checked_sub(words)?
+        } else {
+            2 * words + region_index_count.checked_sub(words)?
+        };
+
+        let len = 6 + 2 * region_index_count + item_count * row_size;
+        node.link32(Node::from_bytes(data.get(..len)?));
+    }
+
+    Some(node)
+}
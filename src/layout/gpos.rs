@@ -0,0 +1,322 @@
+//! The `GPOS` table positions glyphs relative to each other, for example for
+//! kerning and to attach marks to base glyphs.
+
+use super::*;
+
+const SINGLE: u16 = 1;
+const PAIR: u16 = 2;
+const CURSIVE: u16 = 3;
+const MARK_TO_BASE: u16 = 4;
+const MARK_TO_LIGATURE: u16 = 5;
+const MARK_TO_MARK: u16 = 6;
+
+/// Subset a `GPOS` subtable of the given lookup type. Returns `None` in the inner
+/// option if nothing is left of it.
+pub fn subset(s: &Subsetter, kind: u16, data: &[u8]) -> Option<Option<Node>> {
+    let mut r = Reader::new(data);
+    let format = r.read::<u16>()?;
+
+    let mut node = Node::new();
+    node.write::<u16>(format);
+    match (kind, format) {
+        (SINGLE, 1) => {
+            let Some(coverage) = s.coverage(&Coverage::parse_at(data, r.read::<u16>()?)?)
+            else {
+                return Some(None);
+            };
+            let value_format = r.read::<u16>()?;
+
+            node.link(coverage);
+            node.write::<u16>(value_format);
+            value_record(&mut node, &mut r, data, value_format)?;
+        }
+        (SINGLE, 2) => {
+            let coverage = Coverage::parse_at(data, r.read::<u16>()?)?;
+            let value_format = r.read::<u16>()?;
+            let count = r.read::<u16>()?;
+            let size = value_record_size(value_format);
+            let records =
+                (0..count).map(|_| r.read_bytes(size)).collect::<Option<Vec<_>>>()?;
+
+            let retained = s.retain(&coverage, records);
+            if retained.is_empty() {
+                return Some(None);
+            }
+
+            let glyphs = retained.iter().map(|&(glyph, _)| glyph).collect::<Vec<_>>();
+            node.link(coverage_node(&glyphs));
+            node.write::<u16>(value_format);
+            node.write::<u16>(retained.len() as u16);
+            for (_, record) in retained {
+                value_record(&mut node, &mut Reader::new(record), data, value_format)?;
+            }
+        }
+        (PAIR, 1) => {
+            let coverage = Coverage::parse_at(data, r.read::<u16>()?)?;
+            let formats = [r.read::<u16>()?, r.read::<u16>()?];
+            let count = r.read::<u16>()?;
+            let offsets = r.read_array16::<u16>(count)?;
+
+            let mut glyphs = vec![];
+            let mut sets = vec![];
+            for (glyph, offset) in s.retain(&coverage, offsets) {
+                let set = data.get(offset as usize..)?;
+                if let Some(set) = pair_set_node(s, set, formats)? {
+                    glyphs.push(glyph);
+                    sets.push(set);
+                }
+            }
+
+            if glyphs.is_empty() {
+                return Some(None);
+            }
+
+            node.link(coverage_node(&glyphs));
+            node.write(formats);
+            node.write::<u16>(sets.len() as u16);
+            for set in sets {
+                node.link(set);
+            }
+        }
+        (PAIR, 2) => {
+            let Some(coverage) = s.coverage(&Coverage::parse_at(data, r.read::<u16>()?)?)
+            else {
+                return Some(None);
+            };
+            let formats = [r.read::<u16>()?, r.read::<u16>()?];
+            let class_def1 = ClassDef::parse_at(data, r.read::<u16>()?)?;
+            let class_def2 = ClassDef::parse_at(data, r.read::<u16>()?)?;
+            let class1_count = r.read::<u16>()?;
+            let class2_count = r.read::<u16>()?;
+
+            node.link(coverage);
+            node.write(formats);
+            node.link(s.class_def(&class_def1));
+            node.link(s.class_def(&class_def2));
+            node.write::<u16>(class1_count);
+            node.write::<u16>(class2_count);
+            for _ in 0..u32::from(class1_count) * u32::from(class2_count) {
+                for format in formats {
+                    value_record(&mut node, &mut r, data, format)?;
+                }
+            }
+        }
+        (CURSIVE, 1) => {
+            let coverage = Coverage::parse_at(data, r.read::<u16>()?)?;
+            let count = r.read::<u16>()?;
+            let records = (0..count)
+                .map(|_| Some([r.read::<u16>()?, r.read::<u16>()?]))
+                .collect::<Option<Vec<_>>>()?;
+
+            let retained = s.retain(&coverage, records);
+            if retained.is_empty() {
+                return Some(None);
+            }
+
+            let glyphs = retained.iter().map(|&(glyph, _)| glyph).collect::<Vec<_>>();
+            node.link(coverage_node(&glyphs));
+            node.write::<u16>(retained.len() as u16);
+            for (_, anchors) in retained {
+                for offset in anchors {
+                    node.link_opt(anchor(data, offset)?);
+                }
+            }
+        }
+        (MARK_TO_BASE | MARK_TO_LIGATURE | MARK_TO_MARK, 1) => {
+            let mark_coverage = Coverage::parse_at(data, r.read::<u16>()?)?;
+            let base_coverage = Coverage::parse_at(data, r.read::<u16>()?)?;
+            let class_count = r.read::<u16>()?;
+            let mark_array = data.get(r.read::<u16>()? as usize..)?;
+            let base_array = data.get(r.read::<u16>()? as usize..)?;
+
+            let Some((mark_glyphs, mark_array)) =
+                mark_array_node(s, &mark_coverage, mark_array)?
+            else {
+                return Some(None);
+            };
+
+            // The base array of a mark-to-ligature subtable has an additional
+            // level for the components of the ligatures.
+            let bases = if kind == MARK_TO_LIGATURE {
+                ligature_array_node(s, &base_coverage, base_array, class_count)?
+            } else {
+                base_array_node(s, &base_coverage, base_array, class_count)?
+            };
+            let Some((base_glyphs, base_array)) = bases else {
+                return Some(None);
+            };
+
+            node.link(coverage_node(&mark_glyphs));
+            node.link(coverage_node(&base_glyphs));
+            node.write::<u16>(class_count);
+            node.link(mark_array);
+            node.link(base_array);
+        }
+        _ => return None,
+    }
+
+    Some(Some(node))
+}
+
+/// The size of a value record with the given format.
+fn value_record_size(format: u16) -> usize {
+    (format & 0xFF).count_ones() as usize * u16::SIZE
+}
+
+/// Copy a value record. Its device tables are at offsets from the start of `data`.
+fn value_record(node: &mut Node, r: &mut Reader, data: &[u8], format: u16) -> Option<()> {
+    for bit in 0..8 {
+        if format & (1 << bit) == 0 {
+            continue;
+        }
+
+        let value = r.read::<u16>()?;
+        // The first four values are placements and advances, the other four are
+        // offsets to device tables.
+        match (bit, value) {
+            (0..4, _) | (_, 0) => node.write::<u16>(value),
+            _ => node.link(device_node(data, value)?),
+        }
+    }
+
+    Some(())
+}
+
+/// Subset a pair set, keeping the pairs whose second glyph is in the subset.
+/// Returns `None` if no pair is left.
+fn pair_set_node(s: &Subsetter, data: &[u8], formats: [u16; 2]) -> Option<Option<Node>> {
+    let mut r = Reader::new(data);
+    let count = r.read::<u16>()?;
+    let size = value_record_size(formats[0]) + value_record_size(formats[1]);
+
+    let mut pairs = vec![];
+    for _ in 0..count {
+        let second = r.read::<u16>()?;
+        let records = r.read_bytes(size)?;
+        if let Some(second) = s.glyph(second) {
+            pairs.push((second, records));
+        }
+    }
+
+    if pairs.is_empty() {
+        return Some(None);
+    }
+
+    pairs.sort_by_key(|&(second, _)| second);
+
+    let mut node = Node::new();
+    node.write::<u16>(pairs.len() as u16);
+    for (second, records) in pairs {
+        node.write::<u16>(second);
+        let mut r = Reader::new(records);
+        for format in formats {
+            value_record(&mut node, &mut r, data, format)?;
+        }
+    }
+
+    Some(Some(node))
+}
+
+/// Copy the anchor table at the given offset, which may be null.
+fn anchor(data: &[u8], offset: u16) -> Option<Option<Node>> {
+    match offset {
+        0 => Some(None),
+        _ => anchor_node(data, offset).map(Some),
+    }
+}
+
+/// Subset a mark array. Returns the new glyph IDs of the marks that are left
+/// together with the array, or `None` if no mark is left.
+fn mark_array_node(
+    s: &Subsetter,
+    coverage: &Coverage,
+    data: &[u8],
+) -> Option<Option<(Vec<u16>, Node)>> {
+    let mut r = Reader::new(data);
+    let count = r.read::<u16>()?;
+    let records = (0..count)
+        .map(|_| Some((r.read::<u16>()?, r.read::<u16>()?)))
+        .collect::<Option<Vec<_>>>()?;
+
+    let retained = s.retain(coverage, records);
+    if retained.is_empty() {
+        return Some(None);
+    }
+
+    let mut node = Node::new();
+    node.write::<u16>(retained.len() as u16);
+    for &(_, (class, offset)) in &retained {
+        node.write::<u16>(class);
+        node.link_opt(anchor(data, offset)?);
+    }
+
+    let glyphs = retained.into_iter().map(|(glyph, _)| glyph).collect();
+    Some(Some((glyphs, node)))
+}
+
+/// Subset a base or mark2 array, where each record has an anchor for each mark
+/// class. Returns `None` if no base is left.
+fn base_array_node(
+    s: &Subsetter,
+    coverage: &Coverage,
+    data: &[u8],
+    class_count: u16,
+) -> Option<Option<(Vec<u16>, Node)>> {
+    let mut r = Reader::new(data);
+    let count = r.read::<u16>()?;
+    let records = (0..count)
+        .map(|_| r.read_array16::<u16>(class_count))
+        .collect::<Option<Vec<_>>>()?;
+
+    let retained = s.retain(coverage, records);
+    if retained.is_empty() {
+        return Some(None);
+    }
+
+    let mut node = Node::new();
+    node.write::<u16>(retained.len() as u16);
+    for &(_, anchors) in &retained {
+        for offset in anchors {
+            node.link_opt(anchor(data, offset)?);
+        }
+    }
+
+    let glyphs = retained.into_iter().map(|(glyph, _)| glyph).collect();
+    Some(Some((glyphs, node)))
+}
+
+/// Subset a ligature array, where each ligature has a base array for its
+/// components. Returns `None` if no ligature is left.
+fn ligature_array_node(
+    s: &Subsetter,
+    coverage: &Coverage,
+    data: &[u8],
+    class_count: u16,
+) -> Option<Option<(Vec<u16>, Node)>> {
+    let mut r = Reader::new(data);
+    let count = r.read::<u16>()?;
+    let offsets = r.read_array16::<u16>(count)?;
+
+    let retained = s.retain(coverage, offsets);
+    if retained.is_empty() {
+        return Some(None);
+    }
+
+    let mut node = Node::new();
+    node.write::<u16>(retained.len() as u16);
+    for &(_, offset) in &retained {
+        let data = data.get(offset as usize..)?;
+        let mut r = Reader::new(data);
+        let count = r.read::<u16>()?;
+
+        let mut attach = Node::new();
+        attach.write::<u16>(count);
+        for _ in 0..u32::from(count) * u32::from(class_count) {
+            attach.link_opt(anchor(data, r.read::<u16>()?)?);
+        }
+        node.link(attach);
+    }
+
+    let glyphs = retained.into_iter().map(|(glyph, _)| glyph).collect();
+    Some(Some((glyphs, node)))
+}
@@ -0,0 +1,477 @@
+//! The `GSUB` table describes how glyphs are substituted during text shaping, for
+//! example to form ligatures. If the subset is going to be shaped again, it needs to
+//! contain all glyphs that can be produced by the substitutions from the glyphs in
+//! the subset, so we compute the closure of the subset under those substitutions.
+//!
+//! We start with the lookups that are referenced by a feature. Contextual lookups
+//! only apply other lookups to certain positions, so we add the lookups they refer
+//! to once the glyphs of the context can all be in the subset. Each lookup is then
+//! applied to all glyphs in the subset, regardless of their position in the text.
+//! This may add a few glyphs that can't really be produced, but it never misses one.
+
+use super::context::{chained_context_rule, context_rule, lookup_records, rule_set};
+use super::*;
+
+const SINGLE: u16 = 1;
+const MULTIPLE: u16 = 2;
+const ALTERNATE: u16 = 3;
+const LIGATURE: u16 = 4;
+const CONTEXT: u16 = 5;
+const CHAINED_CONTEXT: u16 = 6;
+const REVERSE_CHAINED_CONTEXT: u16 = 8;
+
+/// Add all glyphs to the subset that can be produced by the substitutions in the
+/// `GSUB` table from the glyphs in the subset. Only the features and scripts that
+/// are kept with the given options are considered.
+pub fn closure(
+    face: &Face,
+    mapper: &mut GlyphRemapper,
+    options: &SubsetOptions,
+) -> Result<()> {
+    let Some(data) = face.table(Tag::GSUB) else {
+        return Ok(());
+    };
+
+    let table = LayoutTable::parse(data).ok_or(MalformedFont)?;
+    let num_glyphs = face.num_glyphs().ok_or(MalformedFont)?;
+    let features = table.features(options).ok_or(MalformedFont)?;
+    let lookups = table.feature_lookups(&features).ok_or(MalformedFont)?;
+    let mut closure = Closure {
+        table,
+        set: GlyphSet::new(mapper, num_glyphs),
+        lookups,
+    };
+    closure.run().ok_or(MalformedFont)
+}
+
+struct Closure<'a, 'b> {
+    table: LayoutTable<'a>,
+    set: GlyphSet<'b>,
+    /// The lookups that can be applied.
+    lookups: BTreeSet<u16>,
+}
+
+impl Closure<'_, '_> {
+    /// Apply the lookups until no more glyphs or lookups are added.
+    fn run(&mut self) -> Option<()> {
+        loop {
+            let before = (self.set.len(), self.lookups.len());
+
+            for index in self.lookups.clone() {
+                let lookup = self.table.lookup(index)?;
+                for subtable in lookup.subtables {
+                    self.subtable(lookup.kind, subtable)?;
+                }
+            }
+
+            if (self.set.len(), self.lookups.len()) == before {
+                return Some(());
+            }
+        }
+    }
+
+    fn subtable(&mut self, kind: u16, data: &[u8]) -> Option<()> {
+        let mut r = Reader::new(data);
+        let format = r.read::<u16>()?;
+
+        match (kind, format) {
+            (SINGLE, 1) => {
+                let coverage = Coverage::parse_at(data, r.read::<u16>()?)?;
+                let delta = r.read::<i16>()?;
+                for glyph in coverage.glyphs() {
+                    if self.set.contains(glyph) {
+                        self.set.insert(glyph.wrapping_add_signed(delta));
+                    }
+                }
+            }
+            (SINGLE, 2) => {
+                let coverage = Coverage::parse_at(data, r.read::<u16>()?)?;
+                let count = r.read::<u16>()?;
+                let substitutes = r.read_array16::<u16>(count)?;
+                for (glyph, substitute) in coverage.glyphs().zip(substitutes) {
+                    if self.set.contains(glyph) {
+                        self.set.insert(substitute);
+                    }
+                }
+            }
+            // Both have a list of glyphs for each covered glyph.
+            (MULTIPLE | ALTERNATE, 1) => {
+                let coverage = Coverage::parse_at(data, r.read::<u16>()?)?;
+                let count = r.read::<u16>()?;
+                let offsets = r.read_array16::<u16>(count)?;
+                for (glyph, offset) in coverage.glyphs().zip(offsets) {
+                    if self.set.contains(glyph) {
+                        let mut r = Reader::new(data.get(offset as usize..)?);
+                        let count = r.read::<u16>()?;
+                        for glyph in r.read_array16::<u16>(count)? {
+                            self.set.insert(glyph);
+                        }
+                    }
+                }
+            }
+            (LIGATURE, 1) => {
+                let coverage = Coverage::parse_at(data, r.read::<u16>()?)?;
+                let count = r.read::<u16>()?;
+                let offsets = r.read_array16::<u16>(count)?;
+                for (glyph, offset) in coverage.glyphs().zip(offsets) {
+                    if self.set.contains(glyph) {
+                        self.ligature_set(data.get(offset as usize..)?)?;
+                    }
+                }
+            }
+            (CONTEXT, 1) => {
+                let coverage = Coverage::parse_at(data, r.read::<u16>()?)?;
+                let count = r.read::<u16>()?;
+                let offsets = r.read_array16::<u16>(count)?;
+                for (glyph, offset) in coverage.glyphs().zip(offsets) {
+                    if self.set.contains(glyph) {
+                        let glyphs = |glyph| self.set.contains(glyph);
+                        let lookups =
+                            rule_set(data, offset, |r| context_rule(r, &glyphs))?;
+                        self.lookups.extend(lookups);
+                    }
+                }
+            }
+            (CONTEXT, 2) => {
+                let coverage = Coverage::parse_at(data, r.read::<u16>()?)?;
+                let class_def = ClassDef::parse_at(data, r.read::<u16>()?)?;
+                let count = r.read::<u16>()?;
+                let offsets = r.read_array16::<u16>(count)?;
+
+                let first = self.first_classes(&coverage, &class_def);
+                let classes = class_def.classes(&self.set);
+                let input = |class| classes.contains(&class);
+                for (class, offset) in (0..).zip(offsets) {
+                    if offset != 0 && first.contains(&class) {
+                        let lookups =
+                            rule_set(data, offset, |r| context_rule(r, &input))?;
+                        self.lookups.extend(lookups);
+                    }
+                }
+            }
+            (CONTEXT, 3) => {
+                let glyph_count = r.read::<u16>()?;
+                let lookup_count = r.read::<u16>()?;
+                let coverages = r.read_array16::<u16>(glyph_count)?;
+                if self.coverages_intersect(data, coverages)? {
+                    self.lookups.extend(lookup_records(&mut r, lookup_count)?);
+                }
+            }
+            (CHAINED_CONTEXT, 1) => {
+                let coverage = Coverage::parse_at(data, r.read::<u16>()?)?;
+                let count = r.read::<u16>()?;
+                let offsets = r.read_array16::<u16>(count)?;
+                for (glyph, offset) in coverage.glyphs().zip(offsets) {
+                    if self.set.contains(glyph) {
+                        let glyphs = |glyph| self.set.contains(glyph);
+                        let lookups = rule_set(data, offset, |r| {
+                            chained_context_rule(r, &glyphs, &glyphs, &glyphs)
+                        })?;
+                        self.lookups.extend(lookups);
+                    }
+                }
+            }
+            (CHAINED_CONTEXT, 2) => {
+                let coverage = Coverage::parse_at(data, r.read::<u16>()?)?;
+                let backtrack_class_def = ClassDef::parse_at(data, r.read::<u16>()?)?;
+                let input_class_def = ClassDef::parse_at(data, r.read::<u16>()?)?;
+                let lookahead_class_def = ClassDef::parse_at(data, r.read::<u16>()?)?;
+                let count = r.read::<u16>()?;
+                let offsets = r.read_array16::<u16>(count)?;
+
+                let first = self.first_classes(&coverage, &input_class_def);
+                let backtrack_classes = backtrack_class_def.classes(&self.set);
+                let input_classes = input_class_def.classes(&self.set);
+                let lookahead_classes = lookahead_class_def.classes(&self.set);
+                let backtrack = |class| backtrack_classes.contains(&class);
+                let input = |class| input_classes.contains(&class);
+                let lookahead = |class| lookahead_classes.contains(&class);
+                for (class, offset) in (0..).zip(offsets) {
+                    if offset != 0 && first.contains(&class) {
+                        let lookups = rule_set(data, offset, |r| {
+                            chained_context_rule(r, &backtrack, &input, &lookahead)
+                        })?;
+                        self.lookups.extend(lookups);
+                    }
+                }
+            }
+            (CHAINED_CONTEXT, 3) => {
+                let mut matches = true;
+                for _ in 0..3 {
+                    let count = r.read::<u16>()?;
+                    let coverages = r.read_array16::<u16>(count)?;
+                    matches &= self.coverages_intersect(data, coverages)?;
+                }
+
+                let lookup_count = r.read::<u16>()?;
+                if matches {
+                    self.lookups.extend(lookup_records(&mut r, lookup_count)?);
+                }
+            }
+            (REVERSE_CHAINED_CONTEXT, 1) => {
+                let coverage = Coverage::parse_at(data, r.read::<u16>()?)?;
+                let mut matches = true;
+                for _ in 0..2 {
+                    let count = r.read::<u16>()?;
+                    let coverages = r.read_array16::<u16>(count)?;
+                    matches &= self.coverages_intersect(data, coverages)?;
+                }
+
+                let count = r.read::<u16>()?;
+                let substitutes = r.read_array16::<u16>(count)?;
+                if matches {
+                    for (glyph, substitute) in coverage.glyphs().zip(substitutes) {
+                        if self.set.contains(glyph) {
+                            self.set.insert(substitute);
+                        }
+                    }
+                }
+            }
+            _ => return None,
+        }
+
+        Some(())
+    }
+
+    /// Add the ligatures of a ligature set whose components are all in the subset.
+    fn ligature_set(&mut self, data: &[u8]) -> Option<()> {
+        let mut r = Reader::new(data);
+        let count = r.read::<u16>()?;
+        for offset in r.read_array16::<u16>(count)? {
+            let mut r = Reader::new(data.get(offset as usize..)?);
+            let ligature = r.read::<u16>()?;
+            let count = r.read::<u16>()?;
+            let components = r.read_array16::<u16>(count.checked_sub(1)?)?;
+            if components.into_iter().all(|glyph| self.set.contains(glyph)) {
+                self.set.insert(ligature);
+            }
+        }
+
+        Some(())
+    }
+
+    /// The classes of the glyphs in the subset that are covered by `coverage`.
+    fn first_classes(&self, coverage: &Coverage, class_def: &ClassDef) -> BTreeSet<u16> {
+        coverage
+            .glyphs()
+            .filter(|&glyph| self.set.contains(glyph))
+            .map(|glyph| class_def.get(glyph))
+            .collect()
+    }
+
+    /// Whether each of the coverage tables contains a glyph in the subset.
+    fn coverages_intersect(
+        &self,
+        data: &[u8],
+        offsets: LazyArray16<u16>,
+    ) -> Option<bool> {
+        for offset in offsets {
+            if !Coverage::parse_at(data, offset)?.intersects(&self.set) {
+                return Some(false);
+            }
+        }
+
+        Some(true)
+    }
+}
+
+/// Subset a `GSUB` subtable of the given lookup type. Returns `None` in the inner
+/// option if nothing is left of it.
+pub fn subset(s: &Subsetter, kind: u16, data: &[u8]) -> Option<Option<Node>> {
+    let mut r = Reader::new(data);
+    let format = r.read::<u16>()?;
+    let coverage = Coverage::parse_at(data, r.read::<u16>()?)?;
+
+    let mut node = Node::new();
+    match (kind, format) {
+        (SINGLE, 1 | 2) => {
+            let substitutes = match format {
+                1 => {
+                    let delta = r.read::<i16>()?;
+                    coverage
+                        .glyphs()
+                        .map(|glyph| glyph.wrapping_add_signed(delta))
+                        .collect()
+                }
+                _ => {
+                    let count = r.read::<u16>()?;
+                    r.read_array16::<u16>(count)?.into_iter().collect::<Vec<_>>()
+                }
+            };
+
+            let pairs = s
+                .retain(&coverage, substitutes)
+                .into_iter()
+                .filter_map(|(glyph, substitute)| Some((glyph, s.glyph(substitute)?)))
+                .collect::<Vec<_>>();
+            if pairs.is_empty() {
+                return Some(None);
+            }
+
+            let glyphs = pairs.iter().map(|&(glyph, _)| glyph).collect::<Vec<_>>();
+
+            // Use a delta if it is the same for all glyphs.
+            let delta =
+                |&(glyph, substitute): &(u16, u16)| substitute.wrapping_sub(glyph);
+            let uniform = pairs.windows(2).all(|w| delta(&w[0]) == delta(&w[1]));
+            if uniform {
+                node.write::<u16>(1);
+                node.link(coverage_node(&glyphs));
+                node.write::<u16>(delta(&pairs[0]));
+            } else {
+                node.write::<u16>(2);
+                node.link(coverage_node(&glyphs));
+                node.write::<u16>(pairs.len() as u16);
+                for (_, substitute) in pairs {
+                    node.write::<u16>(substitute);
+                }
+            }
+        }
+        (MULTIPLE | ALTERNATE, 1) => {
+            let count = r.read::<u16>()?;
+            let offsets = r.read_array16::<u16>(count)?;
+
+            let mut glyphs = vec![];
+            let mut sets = vec![];
+            for (glyph, offset) in s.retain(&coverage, offsets) {
+                let mut r = Reader::new(data.get(offset as usize..)?);
+                let count = r.read::<u16>()?;
+                let set = r.read_array16::<u16>(count)?.into_iter().map(|g| s.glyph(g));
+                // A sequence must be substituted as a whole, but alternates can
+                // be dropped individually.
+                let set = if kind == MULTIPLE {
+                    match set.collect::<Option<Vec<_>>>() {
+                        Some(set) => set,
+                        None => continue,
+                    }
+                } else {
+                    let set = set.flatten().collect::<Vec<_>>();
+                    if set.is_empty() {
+                        continue;
+                    }
+                    set
+                };
+
+                glyphs.push(glyph);
+                sets.push(set);
+            }
+
+            if glyphs.is_empty() {
+                return Some(None);
+            }
+
+            node.write::<u16>(1);
+            node.link(coverage_node(&glyphs));
+            node.write::<u16>(sets.len() as u16);
+            for set in sets {
+                let mut child = Node::new();
+                child.write::<u16>(set.len() as u16);
+                child.write(set.as_slice());
+                node.link(child);
+            }
+        }
+        (LIGATURE, 1) => {
+            let count = r.read::<u16>()?;
+            let offsets = r.read_array16::<u16>(count)?;
+
+            let mut glyphs = vec![];
+            let mut sets = vec![];
+            for (glyph, offset) in s.retain(&coverage, offsets) {
+                if let Some(set) = ligature_set_node(s, data.get(offset as usize..)?)? {
+                    glyphs.push(glyph);
+                    sets.push(set);
+                }
+            }
+
+            if glyphs.is_empty() {
+                return Some(None);
+            }
+
+            node.write::<u16>(1);
+            node.link(coverage_node(&glyphs));
+            node.write::<u16>(sets.len() as u16);
+            for set in sets {
+                node.link(set);
+            }
+        }
+        (REVERSE_CHAINED_CONTEXT, 1) => {
+            let mut sequences = vec![];
+            for _ in 0..2 {
+                let count = r.read::<u16>()?;
+                let mut coverages = vec![];
+                for offset in r.read_array16::<u16>(count)? {
+                    match s.coverage(&Coverage::parse_at(data, offset)?) {
+                        Some(coverage) => coverages.push(coverage),
+                        None => return Some(None),
+                    }
+                }
+                sequences.push(coverages);
+            }
+
+            let count = r.read::<u16>()?;
+            let substitutes = r.read_array16::<u16>(count)?;
+            let pairs = s
+                .retain(&coverage, substitutes)
+                .into_iter()
+                .filter_map(|(glyph, substitute)| Some((glyph, s.glyph(substitute)?)))
+                .collect::<Vec<_>>();
+            if pairs.is_empty() {
+                return Some(None);
+            }
+
+            let glyphs = pairs.iter().map(|&(glyph, _)| glyph).collect::<Vec<_>>();
+            node.write::<u16>(1);
+            node.link(coverage_node(&glyphs));
+            for coverages in sequences {
+                node.write::<u16>(coverages.len() as u16);
+                for coverage in coverages {
+                    node.link(coverage);
+                }
+            }
+            node.write::<u16>(pairs.len() as u16);
+            for (_, substitute) in pairs {
+                node.write::<u16>(substitute);
+            }
+        }
+        _ => return None,
+    }
+
+    Some(Some(node))
+}
+
+/// Subset a ligature set, keeping the ligatures whose glyphs are all in the
+/// subset. Returns `None` if no ligature is left.
+fn ligature_set_node(s: &Subsetter, data: &[u8]) -> Option<Option<Node>> {
+    let mut r = Reader::new(data);
+    let count = r.read::<u16>()?;
+
+    let mut ligatures = vec![];
+    for offset in r.read_array16::<u16>(count)? {
+        let mut r = Reader::new(data.get(offset as usize..)?);
+        let ligature = r.read::<u16>()?;
+        let count = r.read::<u16>()?;
+        let components = r.read_array16::<u16>(count.checked_sub(1)?)?;
+        let components = components.into_iter().map(|glyph| s.glyph(glyph));
+        if let (Some(ligature), Some(components)) =
+            (s.glyph(ligature), components.collect::<Option<Vec<_>>>())
+        {
+            let mut node = Node::new();
+            node.write::<u16>(ligature);
+            node.write::<u16>(count);
+            node.write(components.as_slice());
+            ligatures.push(node);
+        }
+    }
+
+    if ligatures.is_empty() {
+        return Some(None);
+    }
+
+    let mut node = Node::new();
+    node.write::<u16>(ligatures.len() as u16);
+    for ligature in ligatures {
+        node.link(ligature);
+    }
+
+    Some(Some(node))
+}
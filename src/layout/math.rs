@@ -0,0 +1,416 @@
+//! The `MATH` table provides the constants and per-glyph data for laying out
+//! mathematical formulas: italic corrections, top accent positions, math kerning
+//! and the larger variants and assemblies of stretchy glyphs. See the
+//! [specification].
+//!
+//! The variants and the parts of the assemblies of the glyphs in the subset are
+//! added to it by the closure. To subset the table, the per-glyph records are
+//! filtered by their coverage tables and sorted by the new glyph IDs, like in the
+//! other layout tables. Variants and assemblies whose glyphs are not part of the
+//! subset are dropped.
+//!
+//! [specification]: https://learn.microsoft.com/en-us/typography/opentype/spec/math
+
+use super::*;
+
+/// The number of `MathValueRecord`s in the `MathConstants` table.
+const NUM_CONSTANT_RECORDS: usize = 51;
+
+/// Add all variants and assembly parts of the glyphs in the subset to it.
+pub fn closure(face: &Face, mapper: &mut GlyphRemapper) -> Result<()> {
+    let Some(data) = face.table(Tag::MATH) else {
+        return Ok(());
+    };
+
+    let num_glyphs = face.num_glyphs().ok_or(MalformedFont)?;
+    let mut set = GlyphSet::new(mapper, num_glyphs);
+    table_closure(data, &mut set).ok_or(MalformedFont)
+}
+
+fn table_closure(data: &[u8], set: &mut GlyphSet) -> Option<()> {
+    let mut r = Reader::new(data);
+    // majorVersion, minorVersion, mathConstantsOffset, mathGlyphInfoOffset
+    r.skip_bytes(8);
+    let variants = match r.read::<u16>()? {
+        0 => return Some(()),
+        offset => data.get(offset as usize..)?,
+    };
+
+    let mut constructions = vec![];
+    for (coverage, offsets) in Variants::parse(variants)?.directions {
+        constructions.extend(coverage.glyphs().zip(offsets));
+    }
+
+    let mut i = 0;
+    while i < set.len() {
+        let glyph = set.glyphs[i];
+        for &(_, offset) in constructions.iter().filter(|&&(g, _)| g == glyph) {
+            let construction = Construction::parse(variants.get(offset as usize..)?)?;
+            for variant in construction.variants {
+                set.insert(variant.glyph);
+            }
+            if let Some(assembly) = construction.assembly {
+                for part in Assembly::parse(assembly)?.parts {
+                    set.insert(part.glyph);
+                }
+            }
+        }
+        i += 1;
+    }
+
+    Some(())
+}
+
+/// Subset the `MATH` table.
+pub fn subset(ctx: &mut Context) -> Result<()> {
+    let data = ctx.expect_table(Tag::MATH).ok_or(MalformedFont)?;
+    let s = Subsetter {
+        mapper: &ctx.mapper,
+        lookups: FxHashMap::default(),
+        kinds: LookupKinds::new(Tag::GDEF),
+    };
+
+    let node = table(&s, data).ok_or(MalformedFont)?;
+    let data = node.pack().ok_or(OverflowError)?;
+    ctx.push(Tag::MATH, data);
+    Ok(())
+}
+
+fn table(s: &Subsetter, data: &[u8]) -> Option<Node> {
+    let mut r = Reader::new(data);
+    let major_version = r.read::<u16>()?;
+    let minor_version = r.read::<u16>()?;
+    if major_version != 1 {
+        return None;
+    }
+
+    let subtable = |offset: u16| match offset {
+        0 => Some(None),
+        offset => data.get(offset as usize..).map(Some),
+    };
+
+    let constants = subtable(r.read::<u16>()?)?;
+    let glyph_info = subtable(r.read::<u16>()?)?;
+    let variants = subtable(r.read::<u16>()?)?;
+
+    let mut node = Node::new();
+    node.write::<u16>(major_version);
+    node.write::<u16>(minor_version);
+    node.link_opt(match constants {
+        Some(data) => Some(constants_node(data)?),
+        None => None,
+    });
+    node.link_opt(match glyph_info {
+        Some(data) => Some(glyph_info_node(s, data)?),
+        None => None,
+    });
+    node.link_opt(match variants {
+        Some(data) => Some(variants_node(s, data)?),
+        None => None,
+    });
+    Some(node)
+}
+
+/// Copy the `MathConstants` table, which doesn't refer to any glyphs.
+fn constants_node(data: &[u8]) -> Option<Node> {
+    let mut r = Reader::new(data);
+    let mut node = Node::new();
+    // scriptPercentScaleDown, scriptScriptPercentScaleDown,
+    // delimitedSubFormulaMinHeight, displayOperatorMinHeight
+    node.extend(r.read_bytes(8)?);
+    for _ in 0..NUM_CONSTANT_RECORDS {
+        value_record(&mut node, &mut r, data)?;
+    }
+    // radicalDegreeBottomRaisePercent
+    node.write::<i16>(r.read::<i16>()?);
+    Some(node)
+}
+
+fn glyph_info_node(s: &Subsetter, data: &[u8]) -> Option<Node> {
+    let mut r = Reader::new(data);
+    let mut subtable = || match r.read::<u16>()? {
+        0 => Some(None),
+        offset => data.get(offset as usize..).map(Some),
+    };
+
+    let italics_correction = subtable()?;
+    let top_accent_attachment = subtable()?;
+    let extended_shapes = subtable()?;
+    let kern_info = subtable()?;
+
+    let mut node = Node::new();
+    for data in [italics_correction, top_accent_attachment] {
+        node.link_opt(match data {
+            Some(data) => value_records_node(s, data)?,
+            None => None,
+        });
+    }
+    node.link_opt(match extended_shapes {
+        Some(data) => s.coverage(&Coverage::parse(data)?),
+        None => None,
+    });
+    node.link_opt(match kern_info {
+        Some(data) => kern_info_node(s, data)?,
+        None => None,
+    });
+    Some(node)
+}
+
+/// Subset a table with a coverage and a `MathValueRecord` for each covered glyph,
+/// like `MathItalicsCorrectionInfo` and `MathTopAccentAttachment`.
+fn value_records_node(s: &Subsetter, data: &[u8]) -> Option<Option<Node>> {
+    let mut r = Reader::new(data);
+    let coverage = Coverage::parse_at(data, r.read::<u16>()?)?;
+    let count = r.read::<u16>()?;
+    let records = (0..count).map(|_| r.read_bytes(4)).collect::<Option<Vec<_>>>()?;
+
+    let retained = s.retain(&coverage, records);
+    if retained.is_empty() {
+        return Some(None);
+    }
+
+    let glyphs = retained.iter().map(|&(glyph, _)| glyph).collect::<Vec<_>>();
+    let mut node = Node::new();
+    node.link(coverage_node(&glyphs));
+    node.write::<u16>(retained.len() as u16);
+    for (_, record) in retained {
+        value_record(&mut node, &mut Reader::new(record), data)?;
+    }
+
+    Some(Some(node))
+}
+
+fn kern_info_node(s: &Subsetter, data: &[u8]) -> Option<Option<Node>> {
+    let mut r = Reader::new(data);
+    let coverage = Coverage::parse_at(data, r.read::<u16>()?)?;
+    let count = r.read::<u16>()?;
+    // The top right, top left, bottom right and bottom left kerns.
+    let records = (0..count)
+        .map(|_| r.read_array16::<u16>(4))
+        .collect::<Option<Vec<_>>>()?;
+
+    let retained = s.retain(&coverage, records);
+    if retained.is_empty() {
+        return Some(None);
+    }
+
+    let glyphs = retained.iter().map(|&(glyph, _)| glyph).collect::<Vec<_>>();
+    let mut node = Node::new();
+    node.link(coverage_node(&glyphs));
+    node.write::<u16>(retained.len() as u16);
+    for (_, offsets) in retained {
+        for offset in offsets {
+            node.link_opt(match offset {
+                0 => None,
+                offset => Some(kern_node(data.get(offset as usize..)?)?),
+            });
+        }
+    }
+
+    Some(Some(node))
+}
+
+/// Copy a `MathKern` table, which has one more kern value than correction
+/// heights.
+fn kern_node(data: &[u8]) -> Option<Node> {
+    let mut r = Reader::new(data);
+    let count = r.read::<u16>()?;
+    let mut node = Node::new();
+    node.write::<u16>(count);
+    for _ in 0..2 * usize::from(count) + 1 {
+        value_record(&mut node, &mut r, data)?;
+    }
+    Some(node)
+}
+
+fn variants_node(s: &Subsetter, data: &[u8]) -> Option<Node> {
+    let variants = Variants::parse(data)?;
+
+    let mut coverages = vec![];
+    let mut constructions = vec![];
+    for (coverage, offsets) in &variants.directions {
+        let mut glyphs = vec![];
+        let mut nodes = vec![];
+        for (glyph, offset) in s.retain(coverage, *offsets) {
+            let construction = Construction::parse(data.get(offset as usize..)?)?;
+            if let Some(node) = construction_node(s, &construction)? {
+                glyphs.push(glyph);
+                nodes.push(node);
+            }
+        }
+
+        coverages.push((!glyphs.is_empty()).then(|| coverage_node(&glyphs)));
+        constructions.push(nodes);
+    }
+
+    let mut node = Node::new();
+    node.write::<u16>(variants.min_connector_overlap);
+    for coverage in coverages {
+        node.link_opt(coverage);
+    }
+    for nodes in &constructions {
+        node.write::<u16>(nodes.len() as u16);
+    }
+    for construction in constructions.into_iter().flatten() {
+        node.link(construction);
+    }
+
+    Some(node)
+}
+
+/// Subset a `MathGlyphConstruction` table. Returns `None` in the inner option if
+/// neither a variant nor the assembly is left of it.
+fn construction_node(s: &Subsetter, construction: &Construction) -> Option<Option<Node>> {
+    let assembly = match construction.assembly {
+        Some(data) => assembly_node(s, data)?,
+        None => None,
+    };
+
+    let variants = construction
+        .variants
+        .into_iter()
+        .filter_map(|variant| Some((s.glyph(variant.glyph)?, variant.advance)))
+        .collect::<Vec<_>>();
+
+    if assembly.is_none() && variants.is_empty() {
+        return Some(None);
+    }
+
+    let mut node = Node::new();
+    node.link_opt(assembly);
+    node.write::<u16>(variants.len() as u16);
+    for (glyph, advance) in variants {
+        node.write::<u16>(glyph);
+        node.write::<u16>(advance);
+    }
+
+    Some(Some(node))
+}
+
+/// Subset a `GlyphAssembly` table. Returns `None` in the inner option if any of
+/// its parts is not part of the subset.
+fn assembly_node(s: &Subsetter, data: &[u8]) -> Option<Option<Node>> {
+    let assembly = Assembly::parse(data)?;
+
+    let mut node = Node::new();
+    value_record(&mut node, &mut Reader::new(data), data)?;
+    node.write::<u16>(assembly.parts.len());
+    for part in assembly.parts {
+        let Some(glyph) = s.glyph(part.glyph) else {
+            return Some(None);
+        };
+        node.write::<u16>(glyph);
+        node.extend(part.rest);
+    }
+
+    Some(Some(node))
+}
+
+/// Copy a `MathValueRecord`, whose device table offset is relative to `data`.
+fn value_record(node: &mut Node, r: &mut Reader, data: &[u8]) -> Option<()> {
+    node.write::<i16>(r.read::<i16>()?);
+    match r.read::<u16>()? {
+        0 => node.write::<u16>(0),
+        offset => node.link(device_node(data, offset)?),
+    }
+    Some(())
+}
+
+/// A parsed `MathVariants` table.
+struct Variants<'a> {
+    min_connector_overlap: u16,
+    /// The vertical and horizontal coverage with the offsets of the
+    /// `MathGlyphConstruction` tables of the covered glyphs.
+    directions: [(Coverage<'a>, LazyArray16<'a, u16>); 2],
+}
+
+impl<'a> Variants<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut r = Reader::new(data);
+        let min_connector_overlap = r.read::<u16>()?;
+        let vertical = r.read::<u16>()?;
+        let horizontal = r.read::<u16>()?;
+        let vertical_count = r.read::<u16>()?;
+        let horizontal_count = r.read::<u16>()?;
+
+        // A null coverage offset means that no glyph is covered.
+        let coverage = |offset: u16| match offset {
+            0 => Some(Coverage::Glyphs(LazyArray16::default())),
+            offset => Coverage::parse_at(data, offset),
+        };
+
+        let directions = [
+            (coverage(vertical)?, r.read_array16::<u16>(vertical_count)?),
+            (coverage(horizontal)?, r.read_array16::<u16>(horizontal_count)?),
+        ];
+
+        Some(Self { min_connector_overlap, directions })
+    }
+}
+
+/// A parsed `MathGlyphConstruction` table.
+struct Construction<'a> {
+    /// The `GlyphAssembly` table.
+    assembly: Option<&'a [u8]>,
+    /// The variant glyphs with their advance.
+    variants: LazyArray16<'a, Variant>,
+}
+
+impl<'a> Construction<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut r = Reader::new(data);
+        let assembly = match r.read::<u16>()? {
+            0 => None,
+            offset => Some(data.get(offset as usize..)?),
+        };
+        let count = r.read::<u16>()?;
+        let variants = r.read_array16::<Variant>(count)?;
+        Some(Self { assembly, variants })
+    }
+}
+
+/// A variant of a glyph.
+#[derive(Debug, Copy, Clone)]
+struct Variant {
+    glyph: u16,
+    advance: u16,
+}
+
+impl Readable<'_> for Variant {
+    const SIZE: usize = 4;
+
+    fn read(r: &mut Reader) -> Option<Self> {
+        Some(Self { glyph: r.read::<u16>()?, advance: r.read::<u16>()? })
+    }
+}
+
+/// A parsed `GlyphAssembly` table.
+struct Assembly<'a> {
+    parts: LazyArray16<'a, Part<'a>>,
+}
+
+impl<'a> Assembly<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut r = Reader::new(data);
+        // italicsCorrection
+        r.skip_bytes(4);
+        let count = r.read::<u16>()?;
+        Some(Self { parts: r.read_array16::<Part>(count)? })
+    }
+}
+
+/// A part of a glyph assembly.
+#[derive(Debug, Copy, Clone)]
+struct Part<'a> {
+    glyph: u16,
+    /// The connector lengths, the full advance and the flags, which stay the same.
+    rest: &'a [u8],
+}
+
+impl<'a> Readable<'a> for Part<'a> {
+    const SIZE: usize = 10;
+
+    fn read(r: &mut Reader<'a>) -> Option<Self> {
+        Some(Self { glyph: r.read::<u16>()?, rest: r.read_bytes(8)? })
+    }
+}
@@ -0,0 +1,1140 @@
+//! The OpenType layout tables `GSUB`, `GPOS` and `GDEF` share a couple of common
+//! structures, most importantly coverage tables, class definitions and the list of
+//! lookups that features refer to. See the [common table formats] in the OpenType
+//! specification.
+//!
+//! To subset `GSUB` and `GPOS`, we first determine the features that are kept,
+//! depending on the features and scripts requested in the options. Then, we subset
+//! all lookups that are reachable from those features. Each subtable keeps only the
+//! records of glyphs that are part of the subset, and lookups that end up without
+//! any subtables are removed. Since the new glyph IDs can be in any order, coverage
+//! tables and the records that belong to them are sorted again.
+//!
+//! [common table formats]: https://learn.microsoft.com/en-us/typography/opentype/spec/chapter2
+
+mod base;
+mod context;
+mod gdef;
+mod gpos;
+mod gsub;
+mod math;
+
+pub use base::subset as subset_base;
+pub use gdef::{item_variation_store_node, subset as subset_gdef};
+pub use gsub::closure as gsub_closure;
+pub use math::{closure as math_closure, subset as subset_math};
+
+use super::*;
+use crate::pack::Node;
+use crate::Error::OverflowError;
+use rustc_hash::FxHashMap;
+use std::collections::BTreeSet;
+
+/// Subset a `GSUB` or `GPOS` table.
+pub fn subset(ctx: &mut Context, tag: Tag) -> Result<()> {
+    let data = ctx.expect_table(tag).ok_or(MalformedFont)?;
+    let table = LayoutTable::parse(data).ok_or(MalformedFont)?;
+    let kinds = LookupKinds::new(tag);
+
+    let features = table.features(&ctx.options).ok_or(MalformedFont)?;
+    let candidates = table.reachable_lookups(&features, kinds).ok_or(MalformedFont)?;
+
+    // The new indices of the lookups depend on which lookups are empty in the
+    // subset, so we need to find that out first.
+    let mut s = Subsetter {
+        mapper: &ctx.mapper,
+        lookups: candidates.iter().map(|&index| (index, index)).collect(),
+        kinds,
+    };
+    let mut kept = vec![];
+    for &index in &candidates {
+        let lookup = table.lookup(index).ok_or(MalformedFont)?;
+        if !s.lookup(&lookup).ok_or(MalformedFont)?.is_empty() {
+            kept.push(index);
+        }
+    }
+    s.lookups = kept.iter().copied().zip(0..).collect();
+
+    let mut lookups = vec![];
+    for &index in &kept {
+        let lookup = table.lookup(index).ok_or(MalformedFont)?;
+        let subtables = s
+            .lookup(&lookup)
+            .ok_or(MalformedFont)?
+            .into_iter()
+            .map(|node| node.pack().ok_or(OverflowError))
+            .collect::<Result<_>>()?;
+        lookups.push(SubsetLookup { lookup, subtables });
+    }
+
+    let mut name_ids = vec![];
+    let (feature_list, feature_map) = s
+        .feature_list(&table, &features, &mut name_ids)
+        .ok_or(MalformedFont)?;
+    let script_list = s
+        .script_list(&table, &feature_map, &ctx.options)
+        .ok_or(MalformedFont)?;
+    let feature_variations = match table.feature_variations {
+        Some(data) => s.feature_variations(data, &feature_map).ok_or(MalformedFont)?,
+        None => None,
+    };
+
+    let data = write_table(
+        script_list.pack().ok_or(OverflowError)?,
+        feature_list.pack().ok_or(OverflowError)?,
+        &lookups,
+        kinds.extension,
+        feature_variations
+            .map(|node| node.pack().ok_or(OverflowError))
+            .transpose()?,
+    )
+    .ok_or(OverflowError)?;
+
+    ctx.name_ids.extend(name_ids);
+    ctx.push(tag, data);
+    Ok(())
+}
+
+/// The header of a `GSUB` or `GPOS` table.
+struct LayoutTable<'a> {
+    script_list: &'a [u8],
+    feature_list: &'a [u8],
+    lookup_list: &'a [u8],
+    feature_variations: Option<&'a [u8]>,
+}
+
+impl<'a> LayoutTable<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut r = Reader::new(data);
+        let major_version = r.read::<u16>()?;
+        let minor_version = r.read::<u16>()?;
+        if major_version != 1 {
+            return None;
+        }
+
+        let script_list = data.get(r.read::<u16>()? as usize..)?;
+        let feature_list = data.get(r.read::<u16>()? as usize..)?;
+        let lookup_list = data.get(r.read::<u16>()? as usize..)?;
+        let feature_variations = match (minor_version, r.read::<u32>()) {
+            (1.., Some(offset)) if offset != 0 => Some(data.get(offset as usize..)?),
+            _ => None,
+        };
+
+        Some(Self {
+            script_list,
+            feature_list,
+            lookup_list,
+            feature_variations,
+        })
+    }
+
+    /// The indices of the features that are kept with the given options. If only
+    /// some scripts are kept, so are the features they refer to.
+    fn features(&self, options: &SubsetOptions) -> Option<BTreeSet<u16>> {
+        let referenced = match &options.layout_scripts {
+            Some(scripts) => {
+                let mut referenced = BTreeSet::new();
+                for (tag, script) in self.scripts()? {
+                    if scripts.contains(&tag) {
+                        for lang_sys in script.lang_systems()? {
+                            referenced.extend(lang_sys.features());
+                        }
+                    }
+                }
+                Some(referenced)
+            }
+            None => None,
+        };
+
+        let mut features = BTreeSet::new();
+        for (index, (tag, _)) in (0..).zip(self.feature_records()?) {
+            let requested = options
+                .layout_features
+                .as_ref()
+                .is_none_or(|features| features.contains(&tag));
+            let referenced = referenced.as_ref().is_none_or(|set| set.contains(&index));
+            if requested && referenced {
+                features.insert(index);
+            }
+        }
+
+        Some(features)
+    }
+
+    /// The tags and tables of the features.
+    fn feature_records(&self) -> Option<Vec<(Tag, &'a [u8])>> {
+        records(self.feature_list)
+    }
+
+    /// The tags and tables of the scripts.
+    fn scripts(&self) -> Option<Vec<(Tag, Script<'a>)>> {
+        Some(
+            records(self.script_list)?
+                .into_iter()
+                .map(|(tag, data)| (tag, Script(data)))
+                .collect(),
+        )
+    }
+
+    /// The indices of all lookups that are referenced by the given features,
+    /// including the alternate features of the feature variations.
+    fn feature_lookups(&self, features: &BTreeSet<u16>) -> Option<BTreeSet<u16>> {
+        let mut lookups = BTreeSet::new();
+
+        for (index, (_, feature)) in (0..).zip(self.feature_records()?) {
+            if features.contains(&index) {
+                lookups.extend(Feature::parse(feature)?.lookups);
+            }
+        }
+
+        if let Some(data) = self.feature_variations {
+            for (_, substitutions) in feature_variation_records(data)? {
+                for (index, feature) in feature_substitutions(substitutions)? {
+                    if features.contains(&index) {
+                        lookups.extend(Feature::parse(feature)?.lookups);
+                    }
+                }
+            }
+        }
+
+        Some(lookups)
+    }
+
+    /// The lookups referenced by the given features and all lookups that are
+    /// referenced by those from contextual subtables.
+    fn reachable_lookups(
+        &self,
+        features: &BTreeSet<u16>,
+        kinds: LookupKinds,
+    ) -> Option<BTreeSet<u16>> {
+        let mut lookups = self.feature_lookups(features)?;
+        let mut queue = lookups.iter().copied().collect::<Vec<_>>();
+
+        while let Some(index) = queue.pop() {
+            let lookup = self.lookup(index)?;
+            for subtable in &lookup.subtables {
+                let nested = match lookup.kind {
+                    kind if kind == kinds.context => context::nested_lookups(subtable)?,
+                    kind if kind == kinds.chained_context => {
+                        context::nested_chained_lookups(subtable)?
+                    }
+                    _ => continue,
+                };
+
+                for index in nested {
+                    if lookups.insert(index) {
+                        queue.push(index);
+                    }
+                }
+            }
+        }
+
+        Some(lookups)
+    }
+
+    /// Get the lookup with the given index.
+    fn lookup(&self, index: u16) -> Option<Lookup<'a>> {
+        let mut r = Reader::new(self.lookup_list);
+        let count = r.read::<u16>()?;
+        let offsets = r.read_array16::<u16>(count)?;
+        Lookup::parse(self.lookup_list.get(offsets.get(index)? as usize..)?)
+    }
+}
+
+/// Read a list of tag records with 16-bit offsets from the start of `data`, which
+/// begins with the number of records.
+fn records(data: &[u8]) -> Option<Vec<(Tag, &[u8])>> {
+    let mut r = Reader::new(data);
+    let count = r.read::<u16>()?;
+    (0..count)
+        .map(|_| {
+            let tag = r.read::<Tag>()?;
+            let offset = r.read::<u16>()?;
+            Some((tag, data.get(offset as usize..)?))
+        })
+        .collect()
+}
+
+/// A script table.
+struct Script<'a>(&'a [u8]);
+
+impl<'a> Script<'a> {
+    /// The default language system, if any.
+    fn default_lang_sys(&self) -> Option<Option<LangSys<'a>>> {
+        match Reader::new(self.0).read::<u16>()? {
+            0 => Some(None),
+            offset => LangSys::parse(self.0.get(offset as usize..)?).map(Some),
+        }
+    }
+
+    /// The language systems with a tag.
+    fn tagged(&self) -> Option<Vec<(Tag, LangSys<'a>)>> {
+        let mut r = Reader::new(self.0);
+        // defaultLangSysOffset
+        r.skip::<u16>();
+        let count = r.read::<u16>()?;
+        (0..count)
+            .map(|_| {
+                let tag = r.read::<Tag>()?;
+                let offset = r.read::<u16>()?;
+                Some((tag, LangSys::parse(self.0.get(offset as usize..)?)?))
+            })
+            .collect()
+    }
+
+    /// All language systems, including the default one.
+    fn lang_systems(&self) -> Option<Vec<LangSys<'a>>> {
+        let tagged = self.tagged()?.into_iter().map(|(_, lang_sys)| lang_sys);
+        Some(self.default_lang_sys()?.into_iter().chain(tagged).collect())
+    }
+}
+
+/// A language system table.
+struct LangSys<'a> {
+    required: Option<u16>,
+    features: LazyArray16<'a, u16>,
+}
+
+impl<'a> LangSys<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut r = Reader::new(data);
+        // lookupOrderOffset
+        r.skip::<u16>();
+        let required = r.read::<u16>()?;
+        let count = r.read::<u16>()?;
+        let features = r.read_array16::<u16>(count)?;
+        Some(Self {
+            required: (required != 0xFFFF).then_some(required),
+            features,
+        })
+    }
+
+    /// The indices of all features of the language system.
+    fn features(&self) -> impl Iterator<Item = u16> + 'a {
+        self.required.into_iter().chain(self.features)
+    }
+}
+
+/// A feature table.
+struct Feature<'a> {
+    params: Option<&'a [u8]>,
+    lookups: LazyArray16<'a, u16>,
+}
+
+impl<'a> Feature<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut r = Reader::new(data);
+        let params = r.read::<u16>()?;
+        let count = r.read::<u16>()?;
+        let lookups = r.read_array16::<u16>(count)?;
+        let params = match params {
+            0 => None,
+            offset => Some(data.get(offset as usize..)?),
+        };
+        Some(Self { params, lookups })
+    }
+}
+
+/// A record of a feature variations table, consisting of an optional condition
+/// set and a feature table substitution table.
+type FeatureVariationRecord<'a> = (Option<&'a [u8]>, &'a [u8]);
+
+/// Read the records of a feature variations table.
+fn feature_variation_records(data: &[u8]) -> Option<Vec<FeatureVariationRecord<'_>>> {
+    let mut r = Reader::new(data);
+    // version
+    r.skip::<u32>();
+    let count = r.read::<u32>()?;
+
+    let mut records = vec![];
+    for _ in 0..count {
+        let condition_set = r.read::<u32>()?;
+        let substitutions = r.read::<u32>()?;
+        if substitutions == 0 {
+            continue;
+        }
+
+        let condition_set = match condition_set {
+            0 => None,
+            offset => Some(data.get(offset as usize..)?),
+        };
+        records.push((condition_set, data.get(substitutions as usize..)?));
+    }
+
+    Some(records)
+}
+
+/// Read the feature indices and alternate feature tables of a feature table
+/// substitution table.
+fn feature_substitutions(data: &[u8]) -> Option<Vec<(u16, &[u8])>> {
+    let mut r = Reader::new(data);
+    // version
+    r.skip::<u32>();
+    let count = r.read::<u16>()?;
+    (0..count)
+        .map(|_| {
+            let index = r.read::<u16>()?;
+            let offset = r.read::<u32>()?;
+            Some((index, data.get(offset as usize..)?))
+        })
+        .collect()
+}
+
+/// A lookup, consisting of subtables of the same type.
+struct Lookup<'a> {
+    kind: u16,
+    flag: u16,
+    subtables: Vec<&'a [u8]>,
+    mark_filtering_set: Option<u16>,
+}
+
+impl<'a> Lookup<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut r = Reader::new(data);
+        let mut kind = r.read::<u16>()?;
+        let flag = r.read::<u16>()?;
+        let count = r.read::<u16>()?;
+
+        let mut subtables = vec![];
+        for offset in r.read_array16::<u16>(count)? {
+            subtables.push(data.get(offset as usize..)?);
+        }
+
+        let mark_filtering_set = match flag & USE_MARK_FILTERING_SET {
+            0 => None,
+            _ => Some(r.read::<u16>()?),
+        };
+
+        // Resolve extension subtables, which allow for 32-bit offsets. All of them
+        // must have the same type.
+        if kind == GSUB_EXTENSION || kind == GPOS_EXTENSION {
+            let mut extension_kind = None;
+            for subtable in &mut subtables {
+                let mut r = Reader::new(subtable);
+                // format
+                r.skip::<u16>();
+                let kind = r.read::<u16>()?;
+                if *extension_kind.get_or_insert(kind) != kind {
+                    return None;
+                }
+                *subtable = subtable.get(r.read::<u32>()? as usize..)?;
+            }
+            kind = extension_kind.unwrap_or(kind);
+        }
+
+        Some(Self { kind, flag, subtables, mark_filtering_set })
+    }
+}
+
+/// The lookup flag that indicates that a mark filtering set is present.
+const USE_MARK_FILTERING_SET: u16 = 0x0010;
+
+const GSUB_EXTENSION: u16 = 7;
+const GPOS_EXTENSION: u16 = 9;
+
+/// The lookup types that `GSUB` and `GPOS` share, but with different numbers.
+#[derive(Debug, Copy, Clone)]
+struct LookupKinds {
+    context: u16,
+    chained_context: u16,
+    extension: u16,
+}
+
+impl LookupKinds {
+    fn new(tag: Tag) -> Self {
+        if tag == Tag::GPOS {
+            Self {
+                context: 7,
+                chained_context: 8,
+                extension: GPOS_EXTENSION,
+            }
+        } else {
+            Self {
+                context: 5,
+                chained_context: 6,
+                extension: GSUB_EXTENSION,
+            }
+        }
+    }
+}
+
+/// A subsetted lookup, with its packed subtables.
+struct SubsetLookup<'a> {
+    lookup: Lookup<'a>,
+    subtables: Vec<Vec<u8>>,
+}
+
+/// Write a `GSUB` or `GPOS` table from its parts.
+fn write_table(
+    script_list: Vec<u8>,
+    feature_list: Vec<u8>,
+    lookups: &[SubsetLookup],
+    extension: u16,
+    feature_variations: Option<Vec<u8>>,
+) -> Option<Vec<u8>> {
+    // Extension subtables need more space, so we only use them if the offsets to
+    // the subtables overflow otherwise.
+    let lookup_list = write_lookup_list(lookups, None)
+        .or_else(|| write_lookup_list(lookups, Some(extension)))?;
+
+    let header_len = if feature_variations.is_some() { 14 } else { 10 };
+    let script_list_offset = header_len;
+    let feature_list_offset = script_list_offset + script_list.len().next_multiple_of(2);
+    let lookup_list_offset = feature_list_offset + feature_list.len().next_multiple_of(2);
+    let feature_variations_offset =
+        lookup_list_offset + lookup_list.len().next_multiple_of(2);
+
+    let mut w = Writer::new();
+    w.write::<u16>(1);
+    w.write::<u16>(if feature_variations.is_some() { 1 } else { 0 });
+    w.write::<u16>(u16::try_from(script_list_offset).ok()?);
+    w.write::<u16>(u16::try_from(feature_list_offset).ok()?);
+    w.write::<u16>(u16::try_from(lookup_list_offset).ok()?);
+    if feature_variations.is_some() {
+        w.write::<u32>(u32::try_from(feature_variations_offset).ok()?);
+    }
+
+    w.extend(&script_list);
+    w.align(2);
+    w.extend(&feature_list);
+    w.align(2);
+    w.extend(&lookup_list);
+    if let Some(feature_variations) = feature_variations {
+        w.align(2);
+        w.extend(&feature_variations);
+    }
+
+    Some(w.finish())
+}
+
+/// Write the lookup list with the lookups and their subtables. If an extension
+/// lookup type is given, all subtables are wrapped into extension subtables.
+fn write_lookup_list(
+    lookups: &[SubsetLookup],
+    extension: Option<u16>,
+) -> Option<Vec<u8>> {
+    // The lookup tables come right after the list, followed by the extension
+    // subtables, if any, and finally the actual subtables.
+    let mut lookup_offsets = vec![];
+    let mut cursor = 2 + 2 * lookups.len();
+    for lookup in lookups {
+        lookup_offsets.push(cursor);
+        cursor += 6
+            + 2 * lookup.subtables.len()
+            + 2 * lookup.lookup.mark_filtering_set.iter().count();
+    }
+
+    let mut extension_offset = cursor;
+    if extension.is_some() {
+        cursor += 8 * lookups.iter().map(|lookup| lookup.subtables.len()).sum::<usize>();
+    }
+
+    let mut subtable_offsets = vec![];
+    for subtable in lookups.iter().flat_map(|lookup| &lookup.subtables) {
+        subtable_offsets.push(cursor);
+        cursor = (cursor + subtable.len()).next_multiple_of(2);
+    }
+
+    let mut w = Writer::with_capacity(cursor);
+    w.write::<u16>(u16::try_from(lookups.len()).ok()?);
+    for &offset in &lookup_offsets {
+        w.write::<u16>(u16::try_from(offset).ok()?);
+    }
+
+    let mut subtable_offsets = subtable_offsets.into_iter();
+    let mut extensions = vec![];
+    for (lookup, &start) in lookups.iter().zip(&lookup_offsets) {
+        w.write::<u16>(extension.unwrap_or(lookup.lookup.kind));
+        w.write::<u16>(lookup.lookup.flag);
+        w.write::<u16>(u16::try_from(lookup.subtables.len()).ok()?);
+        for _ in &lookup.subtables {
+            let mut offset = subtable_offsets.next()?;
+            if extension.is_some() {
+                extensions.push((extension_offset, offset, lookup.lookup.kind));
+                offset = extension_offset;
+                extension_offset += 8;
+            }
+            w.write::<u16>(u16::try_from(offset - start).ok()?);
+        }
+
+        if let Some(set) = lookup.lookup.mark_filtering_set {
+            w.write::<u16>(set);
+        }
+    }
+
+    for (start, target, kind) in extensions {
+        // format
+        w.write::<u16>(1);
+        w.write::<u16>(kind);
+        w.write::<u32>(u32::try_from(target - start).ok()?);
+    }
+
+    for subtable in lookups.iter().flat_map(|lookup| &lookup.subtables) {
+        w.extend(subtable);
+        w.align(2);
+    }
+
+    Some(w.finish())
+}
+
+/// Subsets the parts of layout tables that refer to glyphs and lookups.
+struct Subsetter<'a> {
+    mapper: &'a GlyphRemapper,
+    /// The new index of each kept lookup.
+    lookups: FxHashMap<u16, u16>,
+    kinds: LookupKinds,
+}
+
+impl Subsetter<'_> {
+    /// Subset the subtables of a lookup, dropping those that become empty.
+    fn lookup(&self, lookup: &Lookup) -> Option<Vec<Node>> {
+        let mut subtables = vec![];
+        for data in &lookup.subtables {
+            let subtable = match lookup.kind {
+                kind if kind == self.kinds.context => context::subset(self, data)?,
+                kind if kind == self.kinds.chained_context => {
+                    context::subset_chained(self, data)?
+                }
+                kind if self.kinds.extension == GPOS_EXTENSION => {
+                    gpos::subset(self, kind, data)?
+                }
+                kind => gsub::subset(self, kind, data)?,
+            };
+            subtables.extend(subtable);
+        }
+
+        Some(subtables)
+    }
+
+    /// Get the new ID of a glyph.
+    fn glyph(&self, glyph: u16) -> Option<u16> {
+        self.mapper.get(glyph)
+    }
+
+    /// Pair the glyphs of a coverage table that are in the subset with the records
+    /// that belong to them, sorted by their new glyph ID.
+    fn retain<T>(
+        &self,
+        coverage: &Coverage,
+        records: impl IntoIterator<Item = T>,
+    ) -> Vec<(u16, T)> {
+        let mut retained = coverage
+            .glyphs()
+            .zip(records)
+            .filter_map(|(glyph, record)| Some((self.glyph(glyph)?, record)))
+            .collect::<Vec<_>>();
+        retained.sort_by_key(|&(glyph, _)| glyph);
+        retained
+    }
+
+    /// Subset a coverage table. Returns `None` if none of its glyphs are in the
+    /// subset.
+    fn coverage(&self, coverage: &Coverage) -> Option<Node> {
+        let mut glyphs = coverage
+            .glyphs()
+            .filter_map(|glyph| self.glyph(glyph))
+            .collect::<Vec<_>>();
+        glyphs.sort();
+        (!glyphs.is_empty()).then(|| coverage_node(&glyphs))
+    }
+
+    /// Subset a class definition.
+    fn class_def(&self, class_def: &ClassDef) -> Node {
+        let mut classes = self
+            .mapper
+            .remapped_gids()
+            .filter_map(|glyph| Some((self.glyph(glyph)?, class_def.get(glyph))))
+            .filter(|&(_, class)| class != 0)
+            .collect::<Vec<_>>();
+        classes.sort();
+        class_def_node(&classes)
+    }
+
+    /// Read sequence lookup records and remap their lookup indices. Records that
+    /// refer to removed lookups are dropped.
+    fn lookup_records(&self, r: &mut Reader, count: u16) -> Option<Vec<(u16, u16)>> {
+        let mut records = vec![];
+        for _ in 0..count {
+            let sequence_index = r.read::<u16>()?;
+            let lookup_index = r.read::<u16>()?;
+            if let Some(&new) = self.lookups.get(&lookup_index) {
+                records.push((sequence_index, new));
+            }
+        }
+
+        Some(records)
+    }
+
+    /// Write the list of kept features. Returns the list and the new index of each
+    /// kept feature.
+    fn feature_list(
+        &self,
+        table: &LayoutTable,
+        features: &BTreeSet<u16>,
+        name_ids: &mut Vec<u16>,
+    ) -> Option<(Node, FxHashMap<u16, u16>)> {
+        let records = (0..)
+            .zip(table.feature_records()?)
+            .filter(|(index, _)| features.contains(index))
+            .collect::<Vec<_>>();
+
+        let mut node = Node::new();
+        let mut map = FxHashMap::default();
+        node.write::<u16>(u16::try_from(records.len()).ok()?);
+        for (new, (old, (tag, data))) in (0..).zip(records) {
+            map.insert(old, new);
+            let feature = Feature::parse(data)?;
+            let params =
+                feature.params.and_then(|data| feature_params(tag, data, name_ids));
+            node.write(tag);
+            node.link(self.feature(&feature, params)?);
+        }
+
+        Some((node, map))
+    }
+
+    /// Write a feature table with remapped lookup indices.
+    fn feature(&self, feature: &Feature, params: Option<Node>) -> Option<Node> {
+        let lookups = feature
+            .lookups
+            .into_iter()
+            .filter_map(|index| self.lookups.get(&index).copied())
+            .collect::<Vec<_>>();
+
+        let mut node = Node::new();
+        node.link_opt(params);
+        node.write::<u16>(u16::try_from(lookups.len()).ok()?);
+        node.write(lookups.as_slice());
+        Some(node)
+    }
+
+    /// Write the list of kept scripts, with the feature indices remapped.
+    fn script_list(
+        &self,
+        table: &LayoutTable,
+        features: &FxHashMap<u16, u16>,
+        options: &SubsetOptions,
+    ) -> Option<Node> {
+        let scripts = table
+            .scripts()?
+            .into_iter()
+            .filter(|(tag, _)| {
+                options
+                    .layout_scripts
+                    .as_ref()
+                    .is_none_or(|scripts| scripts.contains(tag))
+            })
+            .collect::<Vec<_>>();
+
+        let mut node = Node::new();
+        node.write::<u16>(u16::try_from(scripts.len()).ok()?);
+        for (tag, script) in scripts {
+            let default = script.default_lang_sys()?;
+            let tagged = script.tagged()?;
+
+            let mut script = Node::new();
+            script.link_opt(default.map(|lang_sys| lang_sys_node(&lang_sys, features)));
+            script.write::<u16>(u16::try_from(tagged.len()).ok()?);
+            for (tag, lang_sys) in tagged {
+                script.write(tag);
+                script.link(lang_sys_node(&lang_sys, features));
+            }
+
+            node.write(tag);
+            node.link(script);
+        }
+
+        Some(node)
+    }
+
+    /// Subset a feature variations table. Returns `None` if no variations are left.
+    fn feature_variations(
+        &self,
+        data: &[u8],
+        features: &FxHashMap<u16, u16>,
+    ) -> Option<Option<Node>> {
+        let mut records = vec![];
+        for (condition_set, substitutions) in feature_variation_records(data)? {
+            // We can't copy conditions we don't know, so we drop such records.
+            let condition_set = match condition_set {
+                Some(data) => match condition_set_node(data) {
+                    Some(node) => Some(node),
+                    None => continue,
+                },
+                None => None,
+            };
+
+            let substitutions = feature_substitutions(substitutions)?
+                .into_iter()
+                .filter_map(|(index, data)| Some((*features.get(&index)?, data)))
+                .collect::<Vec<_>>();
+
+            let mut node = Node::new();
+            // version
+            node.write::<u16>(1);
+            node.write::<u16>(0);
+            node.write::<u16>(u16::try_from(substitutions.len()).ok()?);
+            for (index, data) in substitutions {
+                node.write::<u16>(index);
+                node.link32(self.feature(&Feature::parse(data)?, None)?);
+            }
+
+            records.push((condition_set, node));
+        }
+
+        if records.is_empty() {
+            return Some(None);
+        }
+
+        let mut node = Node::new();
+        // version
+        node.write::<u16>(1);
+        node.write::<u16>(0);
+        node.write::<u32>(u32::try_from(records.len()).ok()?);
+        for (condition_set, substitutions) in records {
+            node.link32_opt(condition_set);
+            node.link32(substitutions);
+        }
+
+        Some(Some(node))
+    }
+}
+
+/// Write a language system table with remapped feature indices.
+fn lang_sys_node(lang_sys: &LangSys, features: &FxHashMap<u16, u16>) -> Node {
+    let indices = lang_sys
+        .features
+        .into_iter()
+        .filter_map(|index| features.get(&index).copied())
+        .collect::<Vec<_>>();
+    let required = lang_sys.required.and_then(|index| features.get(&index).copied());
+
+    let mut node = Node::new();
+    // lookupOrderOffset
+    node.write::<u16>(0);
+    node.write::<u16>(required.unwrap_or(0xFFFF));
+    node.write::<u16>(indices.len() as u16);
+    node.write(indices.as_slice());
+    node
+}
+
+/// Copy the parameters of a feature, whose format depends on the feature, and
+/// collect the name IDs they refer to. Returns `None` for unknown parameters.
+fn feature_params(tag: Tag, data: &[u8], name_ids: &mut Vec<u16>) -> Option<Node> {
+    let mut r = Reader::new(data);
+    let len = match tag.get() {
+        b"size" => {
+            // designSize, subfamilyIdentifier
+            r.skip_bytes(4);
+            name_ids.push(r.read::<u16>()?);
+            10
+        }
+        [b's', b's', ..] => {
+            // version
+            r.skip::<u16>();
+            name_ids.push(r.read::<u16>()?);
+            4
+        }
+        [b'c', b'v', ..] => {
+            // format
+            r.skip::<u16>();
+            for _ in 0..3 {
+                name_ids.push(r.read::<u16>()?);
+            }
+            let count = r.read::<u16>()?;
+            let first = r.read::<u16>()?;
+            name_ids.extend((0..count).map(|i| first.saturating_add(i)));
+            let char_count = r.read::<u16>()?;
+            14 + 3 * char_count as usize
+        }
+        _ => return None,
+    };
+
+    Some(Node::from_bytes(data.get(..len)?))
+}
+
+/// Copy a condition set. Returns `None` if it contains an unknown condition.
+fn condition_set_node(data: &[u8]) -> Option<Node> {
+    let mut r = Reader::new(data);
+    let count = r.read::<u16>()?;
+
+    let mut node = Node::new();
+    node.write::<u16>(count);
+    for _ in 0..count {
+        let condition = data.get(r.read::<u32>()? as usize..)?;
+        // Only format 1, the axis range condition, is defined.
+        if Reader::new(condition).read::<u16>()? != 1 {
+            return None;
+        }
+        node.link32(Node::from_bytes(condition.get(..8)?));
+    }
+
+    Some(node)
+}
+
+/// Write a coverage table for sorted glyphs in the format that needs less space.
+fn coverage_node(glyphs: &[u16]) -> Node {
+    let ranges = ranges(glyphs.iter().map(|&glyph| (glyph, ())));
+
+    let mut node = Node::new();
+    if 3 * ranges.len() < glyphs.len() {
+        node.write::<u16>(2);
+        node.write::<u16>(ranges.len() as u16);
+        let mut index = 0;
+        for (start, end, ()) in ranges {
+            node.write::<u16>(start);
+            node.write::<u16>(end);
+            node.write::<u16>(index);
+            index += end - start + 1;
+        }
+    } else {
+        node.write::<u16>(1);
+        node.write::<u16>(glyphs.len() as u16);
+        node.write(glyphs);
+    }
+
+    node
+}
+
+/// Write a class definition for glyphs with a class other than 0, sorted by their
+/// glyph ID, in the format that needs less space.
+fn class_def_node(classes: &[(u16, u16)]) -> Node {
+    let ranges = ranges(classes.iter().copied());
+    let span = match (classes.first(), classes.last()) {
+        (Some(&(first, _)), Some(&(last, _))) => Some((first, last)),
+        _ => None,
+    };
+
+    let mut node = Node::new();
+    match span {
+        Some((first, last))
+            if 6 + 2 * (last - first + 1) as usize <= 4 + 6 * ranges.len() =>
+        {
+            node.write::<u16>(1);
+            node.write::<u16>(first);
+            node.write::<u16>(last - first + 1);
+            let mut classes = classes.iter().peekable();
+            for glyph in first..=last {
+                match classes.next_if(|&&(g, _)| g == glyph) {
+                    Some(&(_, class)) => node.write::<u16>(class),
+                    None => node.write::<u16>(0),
+                }
+            }
+        }
+        _ => {
+            node.write::<u16>(2);
+            node.write::<u16>(ranges.len() as u16);
+            for (start, end, class) in ranges {
+                node.write::<u16>(start);
+                node.write::<u16>(end);
+                node.write::<u16>(class);
+            }
+        }
+    }
+
+    node
+}
+
+/// Group sorted glyphs with values into ranges of consecutive glyphs with the same
+/// value.
+fn ranges<T: Copy + PartialEq>(
+    items: impl Iterator<Item = (u16, T)>,
+) -> Vec<(u16, u16, T)> {
+    let mut ranges: Vec<(u16, u16, T)> = vec![];
+    for (glyph, value) in items {
+        match ranges.last_mut() {
+            Some((_, end, v)) if end.checked_add(1) == Some(glyph) && *v == value => {
+                *end = glyph
+            }
+            _ => ranges.push((glyph, glyph, value)),
+        }
+    }
+    ranges
+}
+
+/// Copy a device or variation index table.
+fn device_node(data: &[u8], offset: u16) -> Option<Node> {
+    let data = data.get(offset as usize..)?;
+    let mut r = Reader::new(data);
+    let start_size = r.read::<u16>()?;
+    let end_size = r.read::<u16>()?;
+    let len = match r.read::<u16>()? {
+        // The deltas take 2, 4 or 8 bits each.
+        format @ 1..=3 => {
+            let count = end_size.checked_sub(start_size)? as usize + 1;
+            6 + 2 * (count << format).div_ceil(16)
+        }
+        _ => 6,
+    };
+
+    Some(Node::from_bytes(data.get(..len)?))
+}
+
+/// Copy an anchor table.
+fn anchor_node(data: &[u8], offset: u16) -> Option<Node> {
+    let data = data.get(offset as usize..)?;
+    let mut r = Reader::new(data);
+    match r.read::<u16>()? {
+        1 => Some(Node::from_bytes(data.get(..6)?)),
+        2 => Some(Node::from_bytes(data.get(..8)?)),
+        3 => {
+            let mut node = Node::from_bytes(data.get(..6)?);
+            // xCoordinate, yCoordinate
+            r.skip_bytes(4);
+            for _ in 0..2 {
+                match r.read::<u16>()? {
+                    0 => node.write::<u16>(0),
+                    offset => node.link(device_node(data, offset)?),
+                }
+            }
+            Some(node)
+        }
+        _ => None,
+    }
+}
+
+/// A coverage table, which defines a sorted set of glyphs. The index of a glyph
+/// in this set is its coverage index.
+#[derive(Clone)]
+enum Coverage<'a> {
+    Glyphs(LazyArray16<'a, u16>),
+    Ranges(LazyArray16<'a, RangeRecord>),
+}
+
+impl<'a> Coverage<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut r = Reader::new(data);
+        let format = r.read::<u16>()?;
+        let count = r.read::<u16>()?;
+        match format {
+            1 => Some(Self::Glyphs(r.read_array16(count)?)),
+            2 => Some(Self::Ranges(r.read_array16(count)?)),
+            _ => None,
+        }
+    }
+
+    /// Parse the coverage table at the given offset from the start of `data`.
+    fn parse_at(data: &'a [u8], offset: u16) -> Option<Self> {
+        Self::parse(data.get(offset as usize..)?)
+    }
+
+    /// Iterate over the glyphs of the coverage, in the order of their index.
+    fn glyphs(&self) -> impl Iterator<Item = u16> + 'a {
+        let (glyphs, ranges) = match self {
+            Self::Glyphs(glyphs) => (Some(*glyphs), None),
+            Self::Ranges(ranges) => (None, Some(*ranges)),
+        };
+
+        glyphs
+            .into_iter()
+            .flatten()
+            .chain(ranges.into_iter().flatten().flat_map(|range| range.start..=range.end))
+    }
+
+    /// Whether any of the glyphs is contained in `set`.
+    fn intersects(&self, set: &GlyphSet) -> bool {
+        self.glyphs().any(|glyph| set.contains(glyph))
+    }
+}
+
+/// A range of glyphs in a coverage table or class definition.
+#[derive(Debug, Copy, Clone)]
+struct RangeRecord {
+    start: u16,
+    end: u16,
+    value: u16,
+}
+
+impl Readable<'_> for RangeRecord {
+    const SIZE: usize = 3 * u16::SIZE;
+
+    fn read(r: &mut Reader) -> Option<Self> {
+        Some(Self { start: r.read()?, end: r.read()?, value: r.read()? })
+    }
+}
+
+/// A class definition, which assigns a class to glyphs. Glyphs that are not
+/// assigned a class are in class 0.
+enum ClassDef<'a> {
+    Array { start: u16, classes: LazyArray16<'a, u16> },
+    Ranges(LazyArray16<'a, RangeRecord>),
+}
+
+impl<'a> ClassDef<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut r = Reader::new(data);
+        match r.read::<u16>()? {
+            1 => {
+                let start = r.read::<u16>()?;
+                let count = r.read::<u16>()?;
+                Some(Self::Array { start, classes: r.read_array16(count)? })
+            }
+            2 => {
+                let count = r.read::<u16>()?;
+                Some(Self::Ranges(r.read_array16(count)?))
+            }
+            _ => None,
+        }
+    }
+
+    /// Parse the class definition at the given offset from the start of `data`.
+    /// A null offset means that all glyphs are in class 0.
+    fn parse_at(data: &'a [u8], offset: u16) -> Option<Self> {
+        if offset == 0 {
+            return Some(Self::Ranges(LazyArray16::default()));
+        }
+
+        Self::parse(data.get(offset as usize..)?)
+    }
+
+    /// Get the class of a glyph.
+    fn get(&self, glyph: u16) -> u16 {
+        match self {
+            Self::Array { start, classes } => glyph
+                .checked_sub(*start)
+                .and_then(|index| classes.get(index))
+                .unwrap_or(0),
+            Self::Ranges(ranges) => ranges
+                .into_iter()
+                .find(|range| range.start <= glyph && glyph <= range.end)
+                .map(|range| range.value)
+                .unwrap_or(0),
+        }
+    }
+
+    /// The classes of the glyphs in `set`.
+    fn classes(&self, set: &GlyphSet) -> BTreeSet<u16> {
+        set.glyphs.iter().map(|&glyph| self.get(glyph)).collect()
+    }
+}
+
+/// A set of glyphs that can grow while it is iterated over.
+struct GlyphSet<'a> {
+    mapper: &'a mut GlyphRemapper,
+    /// The glyphs in the set, in the order they were added.
+    glyphs: Vec<u16>,
+    /// The number of glyphs in the font. Glyphs beyond this are ignored.
+    num_glyphs: u16,
+}
+
+impl<'a> GlyphSet<'a> {
+    fn new(mapper: &'a mut GlyphRemapper, num_glyphs: u16) -> Self {
+        let glyphs = mapper.remapped_gids().collect();
+        Self { mapper, glyphs, num_glyphs }
+    }
+
+    fn contains(&self, glyph: u16) -> bool {
+        self.mapper.get(glyph).is_some()
+    }
+
+    fn insert(&mut self, glyph: u16) {
+        if glyph < self.num_glyphs && !self.contains(glyph) {
+            self.mapper.remap(glyph);
+            self.glyphs.push(glyph);
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.glyphs.len()
+    }
+}
@@ -4,21 +4,22 @@ in PDFs. You can in general expect very good results in terms of font size, as m
 that can be subsetted are also subsetted.
 
 # Scope
-**Note that the resulting font subsets will most likely be unusable in any other contexts than PDF writing,
-since a lot of information will be removed from the font which is not necessary in PDFs, but is
-necessary in other contexts.** This is on purpose, and for now, there are no plans to expand the
-scope of this crate to become a general purpose subsetter, as this is a massive undertaking and
-will make the already complex codebase even more complex.
-
-In the future,
+**By default, the resulting font subsets will most likely be unusable in any other contexts than PDF
+writing, since a lot of information will be removed from the font which is not necessary in PDFs,
+but is necessary in other contexts.** To get a valid font that can be installed or used as a web
+font, use [`subset_with_options`] with [`SubsetOptions::general`]. It keeps the `cmap` and `OS/2`
+tables, the glyph names, the layout tables and all names, subsetted to the glyphs in the subset.
+
+Even so, this crate is not meant to be a full replacement for general-purpose subsetters like
+the one of fonttools. In the future,
 [skera](https://github.com/googlefonts/fontations/tree/main/skera) will hopefully fill the gap
 of a general-purpose subsetter in the Rust ecosystem.
 
 # Notes
 A couple of important notes if you want to use this crate in combination with your own pdf writer:
 
-- You must write your fonts as a CID font. This is because we remove the `cmap` table from the font,
-  so you must provide your own cmap table in the PDF.
+- You must write your fonts as a CID font. This is because we remove the `cmap` table from the font
+  (unless [`SubsetProfile::General`] is used), so you must provide your own cmap table in the PDF.
 - Copyright information in the font will be retained.
 - When writing a CID font in PDF, CIDs must be used to address glyphs. This can be pretty tricky,
   because the meaning of CID depends on the type of font you are embedding (see the PDF specification
@@ -72,23 +73,53 @@ resulting font is 1 KB.
 #![deny(unsafe_code)]
 #![deny(missing_docs)]
 
+#[cfg(feature = "variable-fonts")]
+mod avar;
+mod bitmap;
 mod cff;
 #[cfg(feature = "variable-fonts")]
 mod cff2;
+mod cmap;
+mod colr;
+mod cpal;
+mod fpgm;
+#[cfg(feature = "variable-fonts")]
+mod fvar;
+mod gasp;
 mod glyf;
+#[cfg(feature = "variable-fonts")]
+mod gvar;
+mod hdmx;
 mod head;
 mod hmtx;
+#[cfg(feature = "variable-fonts")]
+mod hvar;
+#[cfg(feature = "variable-fonts")]
+mod instancer;
 mod interjector;
+mod kern;
+mod layout;
+mod ltsh;
 mod maxp;
+#[cfg(feature = "variable-fonts")]
+mod mvar;
 mod name;
+mod os2;
+mod pack;
 mod post;
 mod read;
 mod remapper;
+mod sbix;
+#[cfg(feature = "variable-fonts")]
+mod stat;
+#[cfg(feature = "variable-fonts")]
+mod style;
+mod svg;
 mod write;
 
 use crate::interjector::Interjector;
 use crate::maxp::MaxpData;
-use crate::read::{Readable, Reader};
+use crate::read::{LazyArray16, Readable, Reader};
 pub use crate::remapper::GlyphRemapper;
 use crate::write::{Writeable, Writer};
 use crate::Error::{MalformedFont, Unimplemented, UnknownKind};
@@ -104,9 +135,240 @@ use std::str::FromStr;
 /// - The `index` is only relevant if the data contains a font collection
 ///   (`.ttc` or `.otc` file). Otherwise, it should be 0.
 ///
-/// CFF2 fonts are not supported.
+/// CFF2 fonts are not supported. Fonts without outlines are supported if they have
+/// color bitmaps in the `CBDT` or `sbix` table.
 pub fn subset(data: &[u8], index: u32, mapper: &GlyphRemapper) -> Result<Vec<u8>> {
-    subset_inner(data, index, &[], false, mapper)
+    subset_with_options(data, index, mapper, &SubsetOptions::default())
+}
+
+/// Subset the font face to include only the necessary glyphs and tables, with
+/// additional options.
+///
+/// This does the same as [`subset`], but allows you to configure the subsetting
+/// process with [`SubsetOptions`].
+pub fn subset_with_options(
+    data: &[u8],
+    index: u32,
+    mapper: &GlyphRemapper,
+    options: &SubsetOptions,
+) -> Result<Vec<u8>> {
+    subset_inner(data, index, Variations::Default, mapper, options)
+}
+
+/// Add all glyphs to the mapper that can be produced from its glyphs by the
+/// substitutions in the `GSUB` table of the font, as well as their larger variants
+/// and assembly parts from the `MATH` table.
+///
+/// This is the same closure that is formed with [`SubsetOptions::layout_closure`],
+/// but it allows you to look up the new glyph IDs of the added glyphs in the mapper.
+/// Only the features and scripts that are kept with the given options are
+/// considered.
+pub fn layout_closure(
+    data: &[u8],
+    index: u32,
+    mapper: &mut GlyphRemapper,
+    options: &SubsetOptions,
+) -> Result<()> {
+    let face = parse(data, index)?;
+    layout::gsub_closure(&face, mapper, options)?;
+    layout::math_closure(&face, mapper)
+}
+
+/// Add the layer glyphs of the color glyphs in the mapper to it and return how to
+/// draw each color glyph with them.
+///
+/// PDF has no support for color fonts. Instead, a PDF writer can subset the font
+/// without its color tables and draw each color glyph as a stack of its layer
+/// glyphs, filled with their colors. The layer glyphs get new glyph IDs after the
+/// ones already in the mapper, and the returned glyph IDs are the new ones. The
+/// colors are taken from the `CPAL` palette with the given index. If there is no
+/// such palette, [`Error::UnknownPalette`] is returned.
+///
+/// Only the color glyphs of `COLR` version 0 are supported. Color glyphs that are
+/// only defined in version 1 are not returned.
+pub fn color_layers(
+    data: &[u8],
+    index: u32,
+    mapper: &mut GlyphRemapper,
+    palette: u16,
+) -> Result<Vec<ColorGlyph>> {
+    let face = parse(data, index)?;
+    colr::layers(&face, mapper, palette)
+}
+
+/// A color glyph, drawn as a stack of monochrome layers.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ColorGlyph {
+    /// The new glyph ID of the color glyph.
+    pub glyph: u16,
+    /// The layers, from bottom to top.
+    pub layers: Vec<ColorLayer>,
+}
+
+/// A layer of a color glyph.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct ColorLayer {
+    /// The new glyph ID of the layer glyph.
+    pub glyph: u16,
+    /// The color to fill the layer glyph with, in RGBA, or `None` to use the
+    /// color of the text.
+    pub color: Option<[u8; 4]>,
+}
+
+/// Options for the subsetting process.
+///
+/// More options may be added in the future, so the options can't be constructed
+/// with a struct expression. Start from [`SubsetOptions::default`] or
+/// [`SubsetOptions::general`] instead and set the fields you need.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct SubsetOptions {
+    /// Whether to recompute the bounding box in the `head` table (and the
+    /// `FontBBox` of a CFF font) from the glyphs in the subset. Otherwise, it
+    /// covers all glyphs of the original font.
+    ///
+    /// When a variable font is instanced, the bounding box is always recomputed.
+    pub recompute_bbox: bool,
+    /// Whether to remove the hinting of the font. Hinting is not needed for
+    /// high-resolution output, such as print.
+    ///
+    /// For TrueType fonts, this drops the `cvt `, `fpgm` and `prep` tables as well
+    /// as the instructions of each glyph. For CFF fonts, the stem hints and hint
+    /// masks are removed from the charstrings, and the hinting parameters from the
+    /// private dicts.
+    ///
+    /// When a variable font is instanced, the hinting is always removed.
+    pub strip_hinting: bool,
+    /// Whether to add all glyphs to the subset that can be produced from the
+    /// requested glyphs by the substitutions in the `GSUB` table, such as
+    /// ligatures, alternates and contextual substitutions. The variants and
+    /// assembly parts of stretchy glyphs in the `MATH` table are added as well.
+    ///
+    /// This is needed if text is going to be shaped with the subset, but not if
+    /// the glyphs are only addressed directly, like in a PDF. The added glyphs get
+    /// new glyph IDs after the ones of the requested glyphs. To find out which ones,
+    /// use [`layout_closure`] on the mapper before subsetting instead.
+    pub layout_closure: bool,
+    /// Whether to keep the OpenType layout tables `GSUB`, `GPOS` and `GDEF`,
+    /// subsetted to the glyphs in the subset. They are needed if text is going to
+    /// be shaped with the subset, for example in a web font, but not in a PDF.
+    /// The legacy `kern` table of older fonts and the `MATH` table of math fonts
+    /// are kept as well.
+    ///
+    /// The layout tables are dropped when a variable font is instanced, since
+    /// their variations are not instanced yet. The `kern` table is dropped as
+    /// well, since its values only fit the default instance.
+    ///
+    /// If the offsets in a subsetted `GSUB` or `GPOS` table don't fit into 16
+    /// bits even with extension subtables, [`Error::OverflowError`] is returned.
+    pub layout_tables: bool,
+    /// The features to keep in the layout tables, or `None` to keep all of them.
+    pub layout_features: Option<Vec<Tag>>,
+    /// The scripts to keep in the layout tables, or `None` to keep all of them.
+    /// Features that are only used by other scripts are removed as well.
+    pub layout_scripts: Option<Vec<Tag>>,
+    /// Whether to keep the color tables `COLR` and `CPAL`, the color bitmaps in
+    /// `CBLC`, `CBDT` and `sbix` and the SVG documents in `SVG `, subsetted to the
+    /// glyphs in the subset. The glyphs that the color glyphs are made of are
+    /// added to the subset, with new glyph IDs after the ones of the requested
+    /// glyphs.
+    ///
+    /// PDF has no support for color fonts, so this is only useful for other
+    /// outputs. For PDF, use [`color_layers`] instead. Fonts without outlines
+    /// always keep their color bitmaps, though.
+    ///
+    /// The `COLR` and `CPAL` tables are dropped when a variable font is instanced,
+    /// since their variations are not instanced yet.
+    ///
+    /// Subsetting gzip-compressed SVG documents requires the `svg-gzip` feature.
+    pub color_tables: bool,
+    /// Whether to keep the embedded bitmaps in `EBLC` and `EBDT` and their
+    /// scalings in `EBSC`, subsetted to the glyphs in the subset. The glyphs that
+    /// composite bitmaps are made of are added to the subset, with new glyph IDs
+    /// after the ones of the requested glyphs.
+    ///
+    /// These are mostly found in older CJK fonts, which use them to render small
+    /// sizes on low-resolution screens. They are not needed for high-resolution
+    /// output, such as print.
+    pub embedded_bitmaps: bool,
+    /// Whether to keep the device metrics of TrueType fonts in `hdmx`, `LTSH`
+    /// and `VDMX`, subsetted to the glyphs in the subset. They tell rasterizers
+    /// how hinting changes the metrics at small pixel sizes, so they are only
+    /// useful for font files that are installed or used on screen, not for PDF.
+    ///
+    /// Like the hinting they result from, they are dropped when the hinting is
+    /// removed or a variable font is instanced.
+    pub device_metrics: bool,
+    /// Whether to keep the `gasp` table, which tells rasterizers when to apply
+    /// grid-fitting and anti-aliasing. It affects how the font is rendered on
+    /// screen, for example in browsers, but is not needed for PDF.
+    ///
+    /// When the hinting is removed or a variable font is instanced, the table is
+    /// replaced with one that asks for smoothing at all sizes, since grid-fitting
+    /// needs the hinting.
+    pub gasp: bool,
+    /// Whether to keep the `BASE` table, subsetted to the glyphs in the subset.
+    /// It defines the baselines of each script and is needed for correct line
+    /// layout with mixed scripts, for example in desktop applications.
+    ///
+    /// The table is dropped when a variable font is instanced, since its
+    /// variations are not instanced yet.
+    pub base_table: bool,
+    /// Whether to keep the `STAT` table, which describes the style of the font
+    /// within its family, for example to link the styles in a font menu. It is
+    /// always kept if the font stays variable.
+    ///
+    /// This requires the `variable-fonts` feature.
+    pub stat_table: bool,
+    /// What the subset is going to be used for. This decides which of the
+    /// tables that are required by the OpenType specification are kept.
+    pub profile: SubsetProfile,
+}
+
+impl SubsetOptions {
+    /// Options for a general-purpose subset that can be installed or used as a
+    /// web font. Besides using [`SubsetProfile::General`], this keeps all the
+    /// optional tables that are needed to shape and render text with the subset,
+    /// and adds the glyphs that the substitutions can produce.
+    ///
+    /// When a variable font is instanced, the layout tables, `MATH`, `COLR`,
+    /// `CPAL` and `BASE` are still dropped, as described for the individual
+    /// options, since their variations are not instanced yet. The result is a
+    /// valid font, but text can't be shaped with it as well as with the original.
+    pub fn general() -> Self {
+        Self {
+            layout_closure: true,
+            layout_tables: true,
+            color_tables: true,
+            embedded_bitmaps: true,
+            device_metrics: true,
+            gasp: true,
+            base_table: true,
+            stat_table: true,
+            profile: SubsetProfile::General,
+            ..Self::default()
+        }
+    }
+}
+
+/// What a subset is going to be used for.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum SubsetProfile {
+    /// A font that is embedded in a PDF. The tables that PDF readers don't need
+    /// are removed, most notably the `cmap` and `OS/2` tables and most of the
+    /// names. Such fonts must be written as CID fonts, with the new glyph IDs as
+    /// CIDs and a PDF cmap to map them to text.
+    #[default]
+    Pdf,
+    /// A valid OpenType font that can be installed or used as a web font. The
+    /// `cmap` table is subsetted to the codepoints of the glyphs in the subset, and
+    /// the `OS/2` table and all names are kept.
+    ///
+    /// On its own, this doesn't keep the layout tables. Use
+    /// [`SubsetOptions::general`] to keep everything that is needed to shape text.
+    /// Like with the PDF profile, tables whose variations can't be instanced yet
+    /// are dropped when a variable font is instanced.
+    General,
 }
 
 /// Subset the font face to include only the necessary glyphs and tables, instantiated
@@ -116,6 +378,11 @@ pub fn subset(data: &[u8], index: u32, mapper: &GlyphRemapper) -> Result<Vec<u8>
 ///
 /// It is important to note that if you pass a CFF2 font, it will be converted to a TrueType
 /// font.
+///
+/// The names in the `name` table, the style flags and the `OS/2` weight and width class
+/// are updated to describe the instance. The new names are built from the `STAT` axis
+/// values at the given location, so they are only updated if every axis has a
+/// matching axis value.
 #[cfg(feature = "variable-fonts")]
 pub fn subset_with_variations(
     data: &[u8],
@@ -123,30 +390,258 @@ pub fn subset_with_variations(
     variation_coordinates: &[(Tag, f32)],
     mapper: &GlyphRemapper,
 ) -> Result<Vec<u8>> {
-    subset_inner(data, index, variation_coordinates, true, mapper)
+    subset_with_variations_and_options(
+        data,
+        index,
+        variation_coordinates,
+        mapper,
+        &SubsetOptions::default(),
+    )
 }
 
-fn subset_inner(
+/// Subset the font face instantiated to the given variation coordinates, with
+/// additional options.
+///
+/// This does the same as [`subset_with_variations`], but allows you to configure
+/// the subsetting process with [`SubsetOptions`]. Note that some tables are dropped
+/// when the font is instanced, even if the options ask for them to be kept, since
+/// their variations are not instanced yet.
+#[cfg(feature = "variable-fonts")]
+pub fn subset_with_variations_and_options(
     data: &[u8],
     index: u32,
     variation_coordinates: &[(Tag, f32)],
-    allow_cff2: bool,
     mapper: &GlyphRemapper,
+    options: &SubsetOptions,
+) -> Result<Vec<u8>> {
+    subset_inner(
+        data,
+        index,
+        Variations::Instance(variation_coordinates),
+        mapper,
+        options,
+    )
+}
+
+/// Subset the font face to include only the necessary glyphs and tables, while
+/// restricting the variation axes to the given limits.
+///
+/// Axes that are pinned to a single value are removed from the font, while the
+/// others stay variable within their new range. Axes that are not mentioned keep
+/// their full range. Values outside of the original range of an axis are clamped.
+///
+/// If all axes end up pinned, this behaves like [`subset_with_variations`]. If no axis
+/// is restricted, this behaves like [`subset_variable`]. Otherwise, the font is
+/// partially instanced. Keeping variations is only supported for fonts with TrueType
+/// outlines, and partial instancing is not supported for fonts with version 2 of the
+/// `avar` table.
+#[cfg(feature = "variable-fonts")]
+pub fn subset_with_axis_limits(
+    data: &[u8],
+    index: u32,
+    axis_limits: &[(Tag, AxisLimit)],
+    mapper: &GlyphRemapper,
+) -> Result<Vec<u8>> {
+    subset_with_axis_limits_and_options(
+        data,
+        index,
+        axis_limits,
+        mapper,
+        &SubsetOptions::default(),
+    )
+}
+
+/// Subset the font face while restricting the variation axes to the given limits,
+/// with additional options.
+///
+/// This does the same as [`subset_with_axis_limits`], but allows you to configure
+/// the subsetting process with [`SubsetOptions`]. Note that some tables are dropped
+/// when the font is partially instanced, even if the options ask for them to be
+/// kept, since their variations are not instanced yet.
+#[cfg(feature = "variable-fonts")]
+pub fn subset_with_axis_limits_and_options(
+    data: &[u8],
+    index: u32,
+    axis_limits: &[(Tag, AxisLimit)],
+    mapper: &GlyphRemapper,
+    options: &SubsetOptions,
+) -> Result<Vec<u8>> {
+    subset_inner(data, index, Variations::Limits(axis_limits), mapper, options)
+}
+
+/// Subset the font face to include only the necessary glyphs and tables, while
+/// keeping all of its variations.
+///
+/// The subset stays fully variable, which is useful when the font is not embedded in
+/// a PDF, but for example in a web page. Keeping variations is only supported for
+/// fonts with TrueType outlines. Fonts that aren't variable are subsetted like with
+/// [`subset`]. Since the vertical metrics are not kept, their variations in the
+/// `VVAR` table are dropped as well.
+#[cfg(feature = "variable-fonts")]
+pub fn subset_variable(
+    data: &[u8],
+    index: u32,
+    mapper: &GlyphRemapper,
+) -> Result<Vec<u8>> {
+    subset_variable_with_options(data, index, mapper, &SubsetOptions::default())
+}
+
+/// Subset the font face while keeping all of its variations, with additional
+/// options.
+///
+/// This does the same as [`subset_variable`], but allows you to configure the
+/// subsetting process with [`SubsetOptions`].
+#[cfg(feature = "variable-fonts")]
+pub fn subset_variable_with_options(
+    data: &[u8],
+    index: u32,
+    mapper: &GlyphRemapper,
+    options: &SubsetOptions,
+) -> Result<Vec<u8>> {
+    subset_inner(data, index, Variations::Limits(&[]), mapper, options)
+}
+
+/// Subset the font face to include only the necessary glyphs and tables, instantiated
+/// at one of its named instances.
+///
+/// This does the same as [`subset_with_variations`], with the coordinates of the
+/// selected named instance. Instead of the `STAT` axis values, the subfamily name of
+/// the instance is used to build the new names. The available named instances can be
+/// listed with [`named_instances`].
+#[cfg(feature = "variable-fonts")]
+pub fn subset_with_named_instance(
+    data: &[u8],
+    index: u32,
+    instance: InstanceSelector,
+    mapper: &GlyphRemapper,
+) -> Result<Vec<u8>> {
+    subset_with_named_instance_and_options(
+        data,
+        index,
+        instance,
+        mapper,
+        &SubsetOptions::default(),
+    )
+}
+
+/// Subset the font face instantiated at one of its named instances, with
+/// additional options.
+///
+/// This does the same as [`subset_with_named_instance`], but allows you to
+/// configure the subsetting process with [`SubsetOptions`]. Like with
+/// [`subset_with_variations_and_options`], some tables are dropped anyway.
+#[cfg(feature = "variable-fonts")]
+pub fn subset_with_named_instance_and_options(
+    data: &[u8],
+    index: u32,
+    instance: InstanceSelector,
+    mapper: &GlyphRemapper,
+    options: &SubsetOptions,
+) -> Result<Vec<u8>> {
+    let instances = named_instances(data, index)?;
+    let instance = match instance {
+        InstanceSelector::Index(i) => instances.get(i),
+        InstanceSelector::Name(name) => instances.iter().find(|instance| {
+            instance.subfamily_name.as_deref() == Some(name)
+                || instance.postscript_name.as_deref() == Some(name)
+        }),
+    }
+    .ok_or(Error::UnknownInstance)?;
+
+    let variations = Variations::Named {
+        location: &instance.coordinates,
+        subfamily_name: instance.subfamily_name.as_deref(),
+    };
+
+    subset_inner(data, index, variations, mapper, options)
+}
+
+/// List the named instances of a variable font, in the order in which they are
+/// defined in the `fvar` table.
+///
+/// Returns an empty list if the font isn't variable.
+#[cfg(feature = "variable-fonts")]
+pub fn named_instances(data: &[u8], index: u32) -> Result<Vec<NamedInstance>> {
+    fvar::named_instances(data, index)
+}
+
+/// A named instance of a variable font.
+#[cfg(feature = "variable-fonts")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamedInstance {
+    /// The subfamily name of the instance, for example "SemiBold Condensed".
+    pub subfamily_name: Option<String>,
+    /// The PostScript name of the instance, if the font defines one.
+    pub postscript_name: Option<String>,
+    /// The user coordinates of the instance.
+    pub coordinates: Vec<(Tag, f32)>,
+}
+
+/// Selects one of the named instances of a variable font.
+#[cfg(feature = "variable-fonts")]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum InstanceSelector<'a> {
+    /// The named instance at the given index in the list returned by
+    /// [`named_instances`].
+    Index(usize),
+    /// The named instance with the given subfamily or PostScript name.
+    Name(&'a str),
+}
+
+/// The new limits of a variation axis, in user coordinates.
+#[cfg(feature = "variable-fonts")]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AxisLimit {
+    /// Pin the axis to a single value, removing it from the font.
+    Pin(f32),
+    /// Restrict the axis to a smaller range, with a new default value.
+    Range {
+        /// The new minimum value of the axis.
+        min: f32,
+        /// The new default value of the axis.
+        default: f32,
+        /// The new maximum value of the axis.
+        max: f32,
+    },
+}
+
+/// How to deal with the variations of a font.
+#[derive(Copy, Clone)]
+enum Variations<'a> {
+    /// Keep the default location. CFF2 fonts are rejected.
+    Default,
+    /// Instance the font at the given location.
+    #[cfg_attr(not(feature = "variable-fonts"), allow(unused))]
+    Instance(&'a [(Tag, f32)]),
+    /// Instance the font at a named instance, updating its subfamily name.
+    #[cfg(feature = "variable-fonts")]
+    Named { location: &'a [(Tag, f32)], subfamily_name: Option<&'a str> },
+    /// Restrict the axes of the font to the given limits.
+    #[cfg(feature = "variable-fonts")]
+    Limits(&'a [(Tag, AxisLimit)]),
+}
+
+fn subset_inner(
+    data: &[u8],
+    index: u32,
+    variations: Variations,
+    mapper: &GlyphRemapper,
+    options: &SubsetOptions,
 ) -> Result<Vec<u8>> {
     let mapper = mapper.clone();
-    let context =
-        prepare_context(data, index, variation_coordinates, allow_cff2, mapper)?;
+    let context = prepare_context(data, index, variations, mapper, options)?;
     _subset(context)
 }
 
 fn prepare_context<'a>(
     data: &'a [u8],
     index: u32,
-    #[cfg_attr(not(feature = "variable-fonts"), allow(unused))]
-    variation_coordinates: &[(Tag, f32)],
-    allow_cff2: bool,
-    mut gid_remapper: GlyphRemapper,
+    variations: Variations,
+    gid_remapper: GlyphRemapper,
+    options: &SubsetOptions,
 ) -> Result<Context<'a>> {
+    let allow_cff2 = !matches!(variations, Variations::Default);
+
     let face = parse(data, index)?;
     let flavor = if face.table(Tag::GLYF).is_some() {
         FontFlavor::TrueType
@@ -160,49 +655,122 @@ fn prepare_context<'a>(
         } else {
             return Err(Unimplemented);
         }
+    } else if (face.table(Tag::CBLC).is_some() && face.table(Tag::CBDT).is_some())
+        || face.table(Tag::SBIX).is_some()
+    {
+        FontFlavor::Bitmap
     } else {
         return Err(UnknownKind);
     };
 
-    if flavor == FontFlavor::TrueType {
-        glyf::closure(&face, &mut gid_remapper)?;
-    }
-
-    let _ = variation_coordinates;
-
     #[cfg(not(feature = "variable-fonts"))]
     let interjector = Interjector::Dummy(PhantomData);
-    // For CFF, we _always_ want to do normal subsetting, since CFF cannot have variations.
+    // For CFF and bitmap fonts, we _always_ want to do normal subsetting, since they
+    // cannot have variations.
     // For TrueType, we prefer normal subsetting in case the requested variation coordinates
     // resolve to the default location. If we do have variations, we use `skrifa` to instance.
     // For CFF2, we _always_ use `skrifa` to instance.
+    // When restricting axis limits, we only need to partially instance if some
+    // axes remain variable. Otherwise, the font can be fully instanced. If no
+    // axis is restricted at all, the variations are kept as they are.
     #[cfg(feature = "variable-fonts")]
-    let interjector = if flavor == FontFlavor::Cff {
-        Interjector::Dummy(PhantomData)
+    let (interjector, variable, location) = match variations {
+        _ if matches!(flavor, FontFlavor::Cff | FontFlavor::Bitmap) => {
+            (Interjector::Dummy(PhantomData), false, vec![])
+        }
+        Variations::Default => {
+            (instance_interjector(data, index, flavor, &[])?, false, vec![])
+        }
+        Variations::Instance(location) | Variations::Named { location, .. } => (
+            instance_interjector(data, index, flavor, location)?,
+            false,
+            location.to_vec(),
+        ),
+        Variations::Limits(limits) => {
+            let instancer = instancer::Instancer::new(data, index, limits)?;
+            match instancer.pinned_location() {
+                Some(location) => (
+                    instance_interjector(data, index, flavor, &location)?,
+                    false,
+                    location,
+                ),
+                None if flavor == FontFlavor::Cff2 => return Err(Unimplemented),
+                None if instancer.is_identity() => {
+                    (Interjector::Dummy(PhantomData), true, vec![])
+                }
+                None if !instancer.is_supported() => return Err(Unimplemented),
+                None => {
+                    let location = instancer
+                        .axes()
+                        .iter()
+                        .map(|axis| (axis.tag, axis.user.1 as f32))
+                        .collect();
+                    (Interjector::Instancer(instancer), true, location)
+                }
+            }
+        }
+    };
+
+    // When instancing, the names and the style information of the font are updated
+    // to the ones of the instance.
+    #[cfg(feature = "variable-fonts")]
+    let (style, name_overrides) = if interjector.is_dummy() {
+        (None, vec![])
     } else {
-        let interjector = interjector::skrifa::SkrifaInterjector::new(
-            data,
-            index,
-            variation_coordinates,
-        )
-        .ok_or(MalformedFont)?;
+        let subfamily_name = match variations {
+            Variations::Named { subfamily_name, .. } => subfamily_name,
+            _ => None,
+        };
 
-        if flavor == FontFlavor::TrueType && interjector.is_default_location() {
-            Interjector::Dummy(PhantomData)
-        } else {
-            Interjector::Skrifa(interjector)
-        }
+        let (style, names) =
+            style::instance_style(data, index, &location, subfamily_name)?;
+        (Some(style), names)
     };
+    #[cfg(not(feature = "variable-fonts"))]
+    let name_overrides = vec![];
 
-    Ok(Context {
+    let mut ctx = Context {
         face,
         mapper: gid_remapper,
+        options: options.clone(),
         interjector,
         custom_maxp_data: None,
         custom_hmtx_data: None,
         flavor,
         tables: vec![],
         long_loca: false,
+        bbox: None,
+        name_ids: vec![],
+        name_overrides,
+        palette_entries: vec![],
+        codepoints: None,
+        #[cfg(feature = "variable-fonts")]
+        variable,
+        #[cfg(feature = "variable-fonts")]
+        custom_gvar_data: None,
+        #[cfg(feature = "variable-fonts")]
+        style,
+    };
+
+    ctx.closure()?;
+    Ok(ctx)
+}
+
+/// Create an interjector that fully instances the font at the given location.
+#[cfg(feature = "variable-fonts")]
+fn instance_interjector<'a>(
+    data: &'a [u8],
+    index: u32,
+    flavor: FontFlavor,
+    location: &[(Tag, f32)],
+) -> Result<Interjector<'a>> {
+    let interjector = interjector::skrifa::SkrifaInterjector::new(data, index, location)
+        .ok_or(MalformedFont)?;
+
+    Ok(if flavor == FontFlavor::TrueType && interjector.is_default_location() {
+        Interjector::Dummy(PhantomData)
+    } else {
+        Interjector::Skrifa(interjector)
     })
 }
 
@@ -211,12 +779,14 @@ fn _subset(mut ctx: Context) -> Result<Vec<u8>> {
     // https://learn.microsoft.com/en-us/typography/opentype/spec/otff#required-tables
     // but some of those are not strictly needed according to the PDF specification.
 
-    // Of the above tables, we are not including the following ones:
+    // Of the above tables, we are not including the following ones for PDF:
     // - CFF2: Since we don't support CFF2
     // - VORG: PDF doesn't use that table.
     // - CMAP: CID fonts in PDF define their own cmaps, so we don't need to include them in the font.
-    // - GASP: Not mandated by PDF specification, and ghostscript also seems to exclude them.
+    // - GASP: Not mandated by PDF specification, and ghostscript also seems to exclude them,
+    //   unless requested in the options.
     // - OS2: Not mandated by PDF specification, and ghostscript also seems to exclude them.
+    // General-purpose subsets must be valid fonts, so they keep CMAP and OS2.
 
     if ctx.flavor == FontFlavor::TrueType {
         // LOCA will be handled by GLYF
@@ -225,10 +795,17 @@ fn _subset(mut ctx: Context) -> Result<Vec<u8>> {
         // Only copy hinting tables if we don't interject because skrifa will
         // discard hinting information.
         // TODO: Add a test for this.
-        if !ctx.interjector.is_skrifa() {
+        if ctx.interjector.is_dummy() && !ctx.options.strip_hinting {
             ctx.process(Tag::CVT)?; // won't be subsetted.
-            ctx.process(Tag::FPGM)?; // won't be subsetted.
+            ctx.process(Tag::FPGM)?;
             ctx.process(Tag::PREP)?; // won't be subsetted.
+
+            // The device metrics are the result of hinting.
+            if ctx.options.device_metrics {
+                ctx.process(Tag::HDMX)?;
+                ctx.process(Tag::LTSH)?;
+                ctx.process(Tag::VDMX)?; // won't be subsetted.
+            }
         }
     } else if ctx.flavor == FontFlavor::Cff {
         ctx.process(Tag::CFF)?;
@@ -236,13 +813,105 @@ fn _subset(mut ctx: Context) -> Result<Vec<u8>> {
         ctx.process(Tag::CFF2)?;
     }
 
+    if ctx.options.gasp {
+        ctx.process(Tag::GASP)?;
+    }
+
+    if ctx.keeps_color_bitmaps() {
+        ctx.process(Tag::CBLC)?;
+        ctx.process(Tag::SBIX)?;
+    }
+
+    if ctx.options.color_tables {
+        ctx.process(Tag::SVG)?;
+    }
+
+    // EBSC must be processed after EBLC.
+    if ctx.keeps_embedded_bitmaps() {
+        ctx.process(Tag::EBLC)?;
+        ctx.process(Tag::EBSC)?;
+    }
+
     // Required tables.
     ctx.process(Tag::HEAD)?;
     ctx.process(Tag::HMTX)?;
     ctx.process(Tag::MAXP)?;
-    // NAME is also not strictly needed, and ghostscript removes it when subsetting.
-    // However, it contains copyright information which probably should not be removed...
-    // Even though it can free up a lot of space for some fonts.
+
+    // The layout tables are only needed for shaping. We don't instance their
+    // variations yet, so they are dropped when instancing.
+    if ctx.options.layout_tables && ctx.interjector.is_dummy() {
+        ctx.process(Tag::GDEF)?;
+        ctx.process(Tag::GSUB)?;
+        ctx.process(Tag::GPOS)?;
+        ctx.process(Tag::MATH)?;
+
+        // The legacy kerning table has no variations, but its values are only
+        // correct for the default instance.
+        ctx.process(Tag::KERN)?;
+    }
+
+    // The palettes only keep the entries that the color glyphs still use, so
+    // COLR must be processed first.
+    if ctx.keeps_colr() {
+        ctx.process(Tag::COLR)?;
+        ctx.process(Tag::CPAL)?;
+    }
+
+    // If the font stays variable, we need to keep the tables that describe
+    // the variations.
+    #[cfg(feature = "variable-fonts")]
+    if ctx.variable {
+        ctx.process(Tag::GVAR)?;
+        ctx.process(Tag::HVAR)?;
+        ctx.process(Tag::FVAR)?;
+        ctx.process(Tag::AVAR)?;
+
+        // We don't rebase the variations of the global metrics yet, so we can
+        // only keep them if the font isn't partially instanced. The vertical
+        // metrics aren't kept at all, so neither are their variations in `VVAR`.
+        if ctx.interjector.is_dummy() {
+            ctx.process(Tag::MVAR)?;
+
+            // The variations of the CVT are indexed by CVT entries, which are
+            // kept as they are.
+            if !ctx.options.strip_hinting {
+                ctx.process(Tag::CVAR)?; // won't be subsetted.
+            }
+        }
+    }
+
+    // The style attributes describe the font within its family, so they stay
+    // valid when instancing. They are always needed if the font stays variable.
+    #[cfg(feature = "variable-fonts")]
+    if ctx.variable || ctx.options.stat_table {
+        ctx.process(Tag::STAT)?;
+    }
+
+    // The baselines can have variations, which we don't instance yet.
+    if ctx.options.base_table && ctx.interjector.is_dummy() {
+        ctx.process(Tag::BASE)?;
+    }
+
+    // OS2 records the range of the codepoints in CMAP, so it must be processed
+    // after it.
+    if ctx.options.profile == SubsetProfile::General {
+        ctx.process(Tag::CMAP)?;
+    }
+
+    // OS2 is not needed for PDF (see above), but when instancing, it's the only
+    // place where the new weight and width of the font are recorded.
+    #[cfg(feature = "variable-fonts")]
+    let instanced = ctx.style.is_some();
+    #[cfg(not(feature = "variable-fonts"))]
+    let instanced = false;
+    if ctx.options.profile == SubsetProfile::General || instanced {
+        ctx.process(Tag::OS2)?;
+    }
+
+    // NAME is also not strictly needed for PDF, and ghostscript removes it when
+    // subsetting. However, it contains copyright information which probably should
+    // not be removed... Even though it can free up a lot of space for some fonts.
+    // General-purpose subsets keep all of the names.
     ctx.process(Tag::NAME)?;
     ctx.process(Tag::POST)?;
 
@@ -367,6 +1036,8 @@ struct Context<'a> {
     face: Face<'a>,
     /// A map from old gids to new gids, and the reverse
     mapper: GlyphRemapper,
+    /// The options for the subsetting process.
+    options: SubsetOptions,
     /// The font flavor.
     flavor: FontFlavor,
     /// Subsetted tables.
@@ -386,9 +1057,78 @@ struct Context<'a> {
     pub(crate) custom_hmtx_data: Option<Vec<(u16, i16)>>,
     /// Whether the long loca format was chosen.
     long_loca: bool,
+    /// The bounding box of the glyphs in the subset, if it was computed while
+    /// subsetting the CFF table.
+    bbox: Option<[i16; 4]>,
+    /// Name IDs referenced by other tables that need to be kept in the `name` table.
+    name_ids: Vec<u16>,
+    /// Name IDs whose strings should be replaced in the `name` table, or that
+    /// should be removed if the string is `None`.
+    name_overrides: Vec<name::Override>,
+    /// The entries of the `CPAL` palettes that the subsetted `COLR` table uses,
+    /// in their new order.
+    palette_entries: Vec<u16>,
+    /// The first and last codepoint in the subsetted `cmap` table.
+    codepoints: Option<(u32, u32)>,
+    /// Whether the subset stays a variable font, either with all of its variations
+    /// or partially instanced.
+    #[cfg(feature = "variable-fonts")]
+    variable: bool,
+    /// The remaining variations of each glyph when partially instancing.
+    #[cfg(feature = "variable-fonts")]
+    pub(crate) custom_gvar_data: Option<Vec<Vec<write_fonts::tables::gvar::GlyphDeltas>>>,
+    /// The new style of the font, if it was instanced.
+    #[cfg(feature = "variable-fonts")]
+    style: Option<style::Style>,
 }
 
 impl<'a> Context<'a> {
+    /// Add the glyphs that other glyphs refer to to the mapper. The color and
+    /// bitmap tables only contribute glyphs if they are kept, since nothing would
+    /// refer to the added glyphs otherwise.
+    fn closure(&mut self) -> Result<()> {
+        if self.options.layout_closure {
+            layout::gsub_closure(&self.face, &mut self.mapper, &self.options)?;
+            layout::math_closure(&self.face, &mut self.mapper)?;
+        }
+
+        if self.keeps_colr() {
+            colr::closure(&self.face, &mut self.mapper)?;
+        }
+
+        if self.keeps_color_bitmaps() {
+            sbix::closure(&self.face, &mut self.mapper)?;
+        }
+
+        if self.keeps_embedded_bitmaps() {
+            bitmap::closure(&self.face, &mut self.mapper)?;
+        }
+
+        // The components of composite glyphs, including the ones added above.
+        if self.flavor == FontFlavor::TrueType {
+            glyf::closure(&self.face, &mut self.mapper)?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether the `COLR` and `CPAL` tables are kept. Their variations are not
+    /// instanced yet, so they are dropped when instancing.
+    fn keeps_colr(&self) -> bool {
+        self.options.color_tables && self.interjector.is_dummy()
+    }
+
+    /// Whether the `CBLC`, `CBDT` and `sbix` tables are kept. Bitmap fonts don't
+    /// have any outlines, so the color bitmaps are the glyphs themselves.
+    fn keeps_color_bitmaps(&self) -> bool {
+        self.flavor == FontFlavor::Bitmap || self.options.color_tables
+    }
+
+    /// Whether the `EBLC`, `EBDT` and `EBSC` tables are kept.
+    fn keeps_embedded_bitmaps(&self) -> bool {
+        self.options.embedded_bitmaps
+    }
+
     /// Expect a table.
     fn expect_table(&self, tag: Tag) -> Option<&'a [u8]> {
         self.face.table(tag)
@@ -414,13 +1154,50 @@ impl<'a> Context<'a> {
             Tag::HMTX => hmtx::subset(self)?,
             Tag::POST => post::subset(self)?,
             Tag::MAXP => maxp::subset(self)?,
+            Tag::FPGM => fpgm::subset(self)?,
+            Tag::GASP => gasp::subset(self)?,
+            Tag::HDMX => hdmx::subset(self)?,
+            Tag::LTSH => ltsh::subset(self)?,
             Tag::NAME => name::subset(self)?,
+            Tag::BASE => layout::subset_base(self)?,
+            Tag::GDEF => layout::subset_gdef(self)?,
+            Tag::GSUB | Tag::GPOS => layout::subset(self, tag)?,
+            Tag::KERN => kern::subset(self)?,
+            Tag::MATH => layout::subset_math(self)?,
+            Tag::CBLC | Tag::EBLC => bitmap::subset(self, tag)?,
+            Tag::CBDT => panic!("handled by cblc"),
+            Tag::EBDT => panic!("handled by eblc"),
+            Tag::EBSC => bitmap::subset_ebsc(self)?,
+            Tag::SBIX => sbix::subset(self)?,
+            Tag::SVG => svg::subset(self)?,
+            Tag::COLR => colr::subset(self)?,
+            Tag::CPAL => cpal::subset(self)?,
+            Tag::CMAP => cmap::subset(self)?,
+            Tag::OS2 => os2::subset(self)?,
+            #[cfg(feature = "variable-fonts")]
+            Tag::GVAR => gvar::subset(self)?,
+            #[cfg(feature = "variable-fonts")]
+            Tag::HVAR => hvar::subset(self)?,
+            #[cfg(feature = "variable-fonts")]
+            Tag::FVAR => fvar::subset(self)?,
+            #[cfg(feature = "variable-fonts")]
+            Tag::AVAR => avar::subset(self)?,
+            #[cfg(feature = "variable-fonts")]
+            Tag::STAT => stat::subset(self)?,
             _ => self.push(tag, data),
         }
 
         Ok(())
     }
 
+    /// Get a table that was already subsetted.
+    fn subsetted_table(&self, tag: Tag) -> Option<&[u8]> {
+        self.tables
+            .iter()
+            .find(|(t, _)| *t == tag)
+            .map(|(_, data)| data.as_ref())
+    }
+
     /// Push a subsetted table.
     fn push(&mut self, tag: Tag, table: impl Into<Cow<'a, [u8]>>) {
         debug_assert!(
@@ -445,6 +1222,14 @@ impl<'a> Face<'a> {
         let end = start.checked_add(record.length as usize)?;
         self.data.get(start..end)
     }
+
+    /// Read the number of glyphs from the `maxp` table.
+    fn num_glyphs(&self) -> Option<u16> {
+        let mut r = Reader::new(self.table(Tag::MAXP)?);
+        // version
+        r.skip::<u32>();
+        r.read::<u16>()
+    }
 }
 
 /// Whether the font is a font collection or a single font.
@@ -480,6 +1265,8 @@ enum FontFlavor {
     Cff,
     /// CFF2 fonts using the `CFF2` table.
     Cff2,
+    /// Fonts without outlines, using color bitmaps in the `CBDT` or `sbix` table.
+    Bitmap,
 }
 
 impl Writeable for FontFlavor {
@@ -488,7 +1275,7 @@ impl Writeable for FontFlavor {
             // Important note: This is the magic for TrueType and not CFF2.
             // However, CFF2 fonts will be converted to TrueType as part of the subsetting
             // process, hence we write the same magic.
-            FontFlavor::TrueType | FontFlavor::Cff2 => 0x00010000,
+            FontFlavor::TrueType | FontFlavor::Cff2 | FontFlavor::Bitmap => 0x00010000,
             FontFlavor::Cff => 0x4F54544F,
         })
     }
@@ -540,6 +1327,9 @@ impl Tag {
     const FPGM: Self = Self(*b"fpgm");
     const CVT: Self = Self(*b"cvt ");
     const GASP: Self = Self(*b"gasp");
+    const HDMX: Self = Self(*b"hdmx");
+    const LTSH: Self = Self(*b"LTSH");
+    const VDMX: Self = Self(*b"VDMX");
 
     // CFF.
     const CFF: Self = Self(*b"CFF ");
@@ -556,6 +1346,23 @@ impl Tag {
     const CBLC: Self = Self(*b"CBLC");
     const SBIX: Self = Self(*b"sbix");
     const SVG: Self = Self(*b"SVG ");
+
+    // OpenType layout.
+    const BASE: Self = Self(*b"BASE");
+    const GDEF: Self = Self(*b"GDEF");
+    const GSUB: Self = Self(*b"GSUB");
+    const GPOS: Self = Self(*b"GPOS");
+    const KERN: Self = Self(*b"kern");
+    const MATH: Self = Self(*b"MATH");
+
+    // Font variations.
+    const AVAR: Self = Self(*b"avar");
+    const CVAR: Self = Self(*b"cvar");
+    const FVAR: Self = Self(*b"fvar");
+    const GVAR: Self = Self(*b"gvar");
+    const HVAR: Self = Self(*b"HVAR");
+    const MVAR: Self = Self(*b"MVAR");
+    const STAT: Self = Self(*b"STAT");
 }
 
 impl Readable<'_> for Tag {
@@ -637,6 +1444,7 @@ type Result<T> = std::result::Result<T, Error>;
 
 /// Parsing failed because the font face is malformed.
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum Error {
     /// The file contains an unknown kind of font.
     UnknownKind,
@@ -653,6 +1461,10 @@ pub enum Error {
     OverflowError,
     /// An error occurred while processing the CFF table.
     CFFError,
+    /// The requested named instance doesn't exist in the font.
+    UnknownInstance,
+    /// The requested color palette doesn't exist in the font.
+    UnknownPalette,
 }
 
 impl Display for Error {
@@ -664,6 +1476,8 @@ impl Display for Error {
             Self::SubsetError => f.write_str("subsetting of font failed"),
             Self::OverflowError => f.write_str("overflow occurred"),
             Self::CFFError => f.write_str("processing CFF table failed"),
+            Self::UnknownInstance => f.write_str("unknown named instance"),
+            Self::UnknownPalette => f.write_str("unknown color palette"),
         }
     }
 }
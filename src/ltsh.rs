@@ -0,0 +1,25 @@
+//! The `LTSH` table contains, for each glyph, the pixel size from which on its
+//! advance width scales linearly, despite hinting. We simply rewrite it in the
+//! order of the new glyph IDs. See the [specification].
+//!
+//! [specification]: https://learn.microsoft.com/en-us/typography/opentype/spec/ltsh
+
+use super::*;
+
+pub fn subset(ctx: &mut Context) -> Result<()> {
+    let ltsh = ctx.expect_table(Tag::LTSH).ok_or(MalformedFont)?;
+    let mut r = Reader::new(ltsh);
+    let version = r.read::<u16>().ok_or(MalformedFont)?;
+    let num_glyphs = r.read::<u16>().ok_or(MalformedFont)?;
+    let y_pels = r.read_bytes(usize::from(num_glyphs)).ok_or(MalformedFont)?;
+
+    let mut w = Writer::new();
+    w.write::<u16>(version);
+    w.write::<u16>(ctx.mapper.num_gids());
+    for gid in ctx.mapper.remapped_gids() {
+        w.write::<u8>(*y_pels.get(usize::from(gid)).ok_or(MalformedFont)?);
+    }
+
+    ctx.push(Tag::LTSH, w.finish());
+    Ok(())
+}
@@ -1,6 +1,9 @@
 //! The `maxp` table contains the number of glyphs (and some additional information
-//! depending on the version). All we need to do is rewrite the number of glyphs, the rest
-//! can be copied from the old table.
+//! depending on the version). For CFF fonts, all we need to do is rewrite the number of
+//! glyphs. For TrueType fonts, the statistics about the glyphs are recomputed from the
+//! subsetted `glyf` table, while the ones about the hinting programs are copied from the
+//! old table, unless the hinting is removed. Bitmap fonts don't have any outlines, so
+//! their table is copied apart from the number of glyphs.
 
 use super::*;
 
@@ -10,8 +13,7 @@ pub fn subset(ctx: &mut Context) -> Result<()> {
 
     let maxp = ctx.expect_table(Tag::MAXP).ok_or(MalformedFont)?;
     let mut r = Reader::new(maxp);
-    // Version
-    let _ = r.read::<u32>().ok_or(MalformedFont)?;
+    let old_version = r.read::<u32>().ok_or(MalformedFont)?;
     // number of glyphs
     r.read::<u16>().ok_or(MalformedFont)?;
 
@@ -20,6 +22,8 @@ pub fn subset(ctx: &mut Context) -> Result<()> {
         FontFlavor::Cff => POST_CFF_VERSION,
         // Since we convert to TrueType.
         FontFlavor::Cff2 => POST_TRUETYPE_VERSION,
+        // Bitmap fonts can have either version.
+        FontFlavor::Bitmap => old_version,
     };
 
     let mut sub_maxp = Writer::new();
@@ -27,23 +31,38 @@ pub fn subset(ctx: &mut Context) -> Result<()> {
     sub_maxp.write::<u16>(ctx.mapper.num_gids());
 
     if version == POST_TRUETYPE_VERSION {
-        if let Some(custom_data) = &ctx.custom_maxp_data {
-            sub_maxp.write::<u16>(custom_data.max_points);
-            sub_maxp.write::<u16>(custom_data.max_contours);
-            sub_maxp.write::<u16>(custom_data.max_composite_points);
-            sub_maxp.write::<u16>(custom_data.max_composite_contours);
-            sub_maxp.write::<u16>(custom_data.max_zones);
-            sub_maxp.write::<u16>(custom_data.max_twilight_points);
-            sub_maxp.write::<u16>(custom_data.max_storage);
-            sub_maxp.write::<u16>(custom_data.max_function_defs);
-            sub_maxp.write::<u16>(custom_data.max_instruction_defs);
-            sub_maxp.write::<u16>(custom_data.max_stack_elements);
-            sub_maxp.write::<u16>(custom_data.max_size_of_instructions);
-            sub_maxp.write::<u16>(custom_data.max_component_elements);
-            sub_maxp.write::<u16>(custom_data.max_component_depth);
-        } else {
-            sub_maxp.extend(r.tail().ok_or(MalformedFont)?);
-        }
+        let recomputed;
+        let custom_data = match &ctx.custom_maxp_data {
+            Some(custom_data) => custom_data,
+            None => {
+                let mut data = if ctx.options.strip_hinting {
+                    MaxpData { max_component_depth: 0, ..MaxpData::default() }
+                } else {
+                    MaxpData::read(&mut r).ok_or(MalformedFont)?
+                };
+                // Without outlines, there are no statistics to recompute.
+                if ctx.flavor != FontFlavor::Bitmap {
+                    let glyphs = glyf::subsetted_glyphs(ctx).ok_or(MalformedFont)?;
+                    glyf::update_maxp_data(&glyphs, &mut data).ok_or(MalformedFont)?;
+                }
+                recomputed = data;
+                &recomputed
+            }
+        };
+
+        sub_maxp.write::<u16>(custom_data.max_points);
+        sub_maxp.write::<u16>(custom_data.max_contours);
+        sub_maxp.write::<u16>(custom_data.max_composite_points);
+        sub_maxp.write::<u16>(custom_data.max_composite_contours);
+        sub_maxp.write::<u16>(custom_data.max_zones);
+        sub_maxp.write::<u16>(custom_data.max_twilight_points);
+        sub_maxp.write::<u16>(custom_data.max_storage);
+        sub_maxp.write::<u16>(custom_data.max_function_defs);
+        sub_maxp.write::<u16>(custom_data.max_instruction_defs);
+        sub_maxp.write::<u16>(custom_data.max_stack_elements);
+        sub_maxp.write::<u16>(custom_data.max_size_of_instructions);
+        sub_maxp.write::<u16>(custom_data.max_component_elements);
+        sub_maxp.write::<u16>(custom_data.max_component_depth);
     }
 
     ctx.push(Tag::MAXP, sub_maxp.finish());
@@ -54,8 +73,8 @@ pub fn subset(ctx: &mut Context) -> Result<()> {
 pub(crate) struct MaxpData {
     pub(crate) max_points: u16,
     pub(crate) max_contours: u16,
-    max_composite_points: u16,
-    max_composite_contours: u16,
+    pub(crate) max_composite_points: u16,
+    pub(crate) max_composite_contours: u16,
     max_zones: u16,
     max_twilight_points: u16,
     max_storage: u16,
@@ -63,8 +82,30 @@ pub(crate) struct MaxpData {
     max_instruction_defs: u16,
     max_stack_elements: u16,
     max_size_of_instructions: u16,
-    max_component_elements: u16,
-    max_component_depth: u16,
+    pub(crate) max_component_elements: u16,
+    pub(crate) max_component_depth: u16,
+}
+
+impl MaxpData {
+    /// Read the fields about the hinting programs from an old version 1.0 table,
+    /// resetting the statistics about the glyphs.
+    fn read(r: &mut Reader) -> Option<Self> {
+        let mut data = MaxpData { max_component_depth: 0, ..MaxpData::default() };
+        if r.at_end() {
+            return Some(data);
+        }
+
+        // maxPoints, maxContours, maxCompositePoints, maxCompositeContours
+        r.skip_bytes(8);
+        data.max_zones = r.read()?;
+        data.max_twilight_points = r.read()?;
+        data.max_storage = r.read()?;
+        data.max_function_defs = r.read()?;
+        data.max_instruction_defs = r.read()?;
+        data.max_stack_elements = r.read()?;
+        data.max_size_of_instructions = r.read()?;
+        Some(data)
+    }
 }
 
 impl Default for MaxpData {
@@ -82,7 +123,6 @@ impl Default for MaxpData {
             max_stack_elements: 0,
             max_size_of_instructions: 0,
             max_component_elements: 0,
-            // Could probably be 0 too since we only use simple glyphs when converting?
             max_component_depth: 1,
         }
     }
@@ -0,0 +1,82 @@
+//! The `MVAR` table contains the variations of global font metrics, like the
+//! ascender or the x-height, which are identified by tags. When instancing, we apply
+//! the deltas at the location of the instance to the static values in the `hhea`,
+//! `OS/2` and `post` tables.
+
+use super::*;
+use skrifa::raw::types::F2Dot14;
+use skrifa::raw::{FontData, FontRead};
+
+/// The fields of the `hhea` table that are varied by `MVAR`, with their offsets.
+pub const HHEA_FIELDS: &[(&[u8; 4], usize)] = &[
+    (b"hasc", 4),
+    (b"hdsc", 6),
+    (b"hlgp", 8),
+    (b"hcrs", 18),
+    (b"hcrn", 20),
+    (b"hcof", 22),
+];
+
+/// The fields of the `OS/2` table that are varied by `MVAR`, with their offsets.
+pub const OS2_FIELDS: &[(&[u8; 4], usize)] = &[
+    (b"sbxs", 10),
+    (b"sbys", 12),
+    (b"sbxo", 14),
+    (b"sbyo", 16),
+    (b"spxs", 18),
+    (b"spys", 20),
+    (b"spxo", 22),
+    (b"spyo", 24),
+    (b"strs", 26),
+    (b"stro", 28),
+    (b"hasc", 68),
+    (b"hdsc", 70),
+    (b"hlgp", 72),
+    (b"hcla", 74),
+    (b"hcld", 76),
+    (b"xhgt", 86),
+    (b"cpht", 88),
+];
+
+/// The fields of the `post` table that are varied by `MVAR`, with their offsets.
+pub const POST_FIELDS: &[(&[u8; 4], usize)] = &[(b"undo", 8), (b"unds", 10)];
+
+/// Apply the deltas at the location of the instance to the given fields of a table.
+/// Fields that lie outside of the table (because it has an older version) are
+/// skipped.
+pub fn apply(ctx: &Context, data: &mut [u8], fields: &[(&[u8; 4], usize)]) -> Result<()> {
+    let coords = match &ctx.interjector {
+        Interjector::Dummy(_) => return Ok(()),
+        Interjector::Skrifa(interjector) => interjector.coords().to_vec(),
+        Interjector::Instancer(instancer) => instancer
+            .axes()
+            .iter()
+            .map(|axis| F2Dot14::from_f32(axis.limit.default as f32))
+            .collect(),
+    };
+
+    let Some(mvar) = ctx.expect_table(Tag::MVAR) else { return Ok(()) };
+    let mvar = skrifa::raw::tables::mvar::Mvar::read(FontData::new(mvar))
+        .map_err(|_| MalformedFont)?;
+
+    for &(tag, offset) in fields {
+        let Some(field) = data.get_mut(offset..offset + 2) else { continue };
+        let Ok(delta) = mvar.metric_delta(skrifa::Tag::new(tag), &coords) else {
+            continue;
+        };
+
+        let bytes = [field[0], field[1]];
+        // The clipping ascent and descent are the only unsigned fields.
+        let value = if [b"hcla", b"hcld"].contains(&tag) {
+            (u16::from_be_bytes(bytes) as i32 + delta.to_i32()).clamp(0, u16::MAX as i32)
+                as u16
+        } else {
+            (i16::from_be_bytes(bytes) as i32 + delta.to_i32())
+                .clamp(i16::MIN as i32, i16::MAX as i32) as u16
+        };
+
+        field.copy_from_slice(&value.to_be_bytes());
+    }
+
+    Ok(())
+}
@@ -2,6 +2,13 @@ use super::*;
 use crate::Error::{MalformedFont, SubsetError};
 use std::collections::HashMap;
 
+/// The name ID of the font subfamily name.
+#[cfg_attr(not(feature = "variable-fonts"), allow(unused))]
+pub const SUBFAMILY: u16 = 2;
+
+/// A name ID and its new string, or `None` if the name should be removed.
+pub type Override = (u16, Option<String>);
+
 pub fn subset(ctx: &mut Context) -> Result<()> {
     let name = ctx.expect_table(Tag::NAME).ok_or(MalformedFont)?;
     let mut r = Reader::new(name);
@@ -15,8 +22,18 @@ pub fn subset(ctx: &mut Context) -> Result<()> {
         return Ok(());
     }
 
+    // General-purpose subsets keep all names, so there is nothing to do unless
+    // some of them are replaced.
+    let keep_all = ctx.options.profile == SubsetProfile::General;
+    if keep_all && ctx.name_overrides.is_empty() {
+        ctx.push(Tag::NAME, name);
+        return Ok(());
+    }
+
     let table = Table::parse(name).ok_or(MalformedFont)?;
-    let subsetted_table = subset_table(&table).ok_or(SubsetError)?;
+    let subsetted_table =
+        subset_table(&table, keep_all, &ctx.name_ids, &ctx.name_overrides)
+            .ok_or(SubsetError)?;
 
     let mut w = Writer::new();
     w.write(subsetted_table);
@@ -25,28 +42,78 @@ pub fn subset(ctx: &mut Context) -> Result<()> {
     Ok(())
 }
 
-pub fn subset_table<'a>(table: &Table<'a>) -> Option<Table<'a>> {
+/// Subset a name table. Besides the default Unicode names, the Unicode names with
+/// the given IDs are kept, or all names if `keep_all` is set. The strings of
+/// overridden names are replaced in the Unicode records, and added if they don't
+/// exist. Other records of overridden names are removed, since they can't hold the
+/// new string, as are all records of names overridden with `None`.
+pub fn subset_table<'a>(
+    table: &Table<'a>,
+    keep_all: bool,
+    name_ids: &[u16],
+    overrides: &[Override],
+) -> Option<Table<'a>> {
+    let overrides = overrides
+        .iter()
+        .map(|(id, name)| {
+            let name = name
+                .as_ref()
+                .map(|name| name.encode_utf16().flat_map(u16::to_be_bytes).collect());
+            (*id, name)
+        })
+        .collect::<Vec<(u16, Option<Vec<u8>>)>>();
+    let find_override = |id: u16| overrides.iter().find(|(i, _)| *i == id);
+
     let mut names = table
         .names
         .iter()
         .copied()
-        .filter(|record| {
-            record.is_unicode()
-                && [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 11, 12, 13, 14]
-                    .contains(&record.name_id)
+        .filter(|record| match find_override(record.name_id) {
+            Some((_, name)) => name.is_some() && record.is_unicode(),
+            None => {
+                keep_all
+                    || (record.is_unicode()
+                        && ([0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 11, 12, 13, 14]
+                            .contains(&record.name_id)
+                            || name_ids.contains(&record.name_id)))
+            }
         })
         .collect::<Vec<_>>();
 
+    for (id, name) in &overrides {
+        if name.is_some() && !names.iter().any(|record| record.name_id == *id) {
+            // Windows, Unicode BMP, English (United States).
+            names.push(NameRecord {
+                platform_id: 3,
+                encoding_id: 1,
+                language_id: 0x0409,
+                name_id: *id,
+                length: 0,
+                string_offset: 0,
+            });
+        }
+    }
+
+    names.sort_by_key(|record| {
+        (record.platform_id, record.encoding_id, record.language_id, record.name_id)
+    });
+
     let mut storage = Vec::new();
     let mut cur_storage_offset = 0;
 
     let mut name_deduplicator: HashMap<&[u8], u16> = HashMap::new();
 
     for record in &mut names {
-        let name = table.storage.get(
-            (record.string_offset as usize)
-                ..((record.string_offset + record.length) as usize),
-        )?;
+        let name = match find_override(record.name_id) {
+            Some((_, Some(name))) => name.as_slice(),
+            _ => table.storage.get(
+                (record.string_offset as usize)
+                    ..((record.string_offset + record.length) as usize),
+            )?,
+        };
+
+        record.length = u16::try_from(name.len()).ok()?;
+
         let offset = *name_deduplicator.entry(name).or_insert_with(|| {
             storage.extend(name);
             let offset = cur_storage_offset;
@@ -179,8 +246,72 @@ mod tests {
         data.extend([0, b'O', 0, b'K']); // storage
 
         let table = Table::parse(&data).unwrap();
-        let subset = subset_table(&table).unwrap();
+        let subset = subset_table(&table, false, &[], &[]).unwrap();
 
         assert_eq!(subset.storage.as_ref(), &[0, b'O', 0, b'K']);
     }
+
+    #[test]
+    fn remove_overridden_names() {
+        let mut data = Vec::new();
+        data.extend(0u16.to_be_bytes()); // version
+        data.extend(2u16.to_be_bytes()); // count
+        data.extend(30u16.to_be_bytes()); // storageOffset
+        for (id, offset) in [(1u16, 0u16), (16, 2)] {
+            data.extend(3u16.to_be_bytes()); // platformID
+            data.extend(1u16.to_be_bytes()); // encodingID
+            data.extend(0x0409u16.to_be_bytes()); // languageID
+            data.extend(id.to_be_bytes()); // nameID
+            data.extend(2u16.to_be_bytes()); // length
+            data.extend(offset.to_be_bytes()); // stringOffset
+        }
+        data.extend([0, b'A', 0, b'B']); // storage
+
+        let table = Table::parse(&data).unwrap();
+        let overrides = [(1, Some("C".into())), (16, None)];
+        let subset = subset_table(&table, true, &[], &overrides).unwrap();
+
+        assert_eq!(subset.names.len(), 1);
+        assert_eq!(subset.names[0].name_id, 1);
+        assert_eq!(subset.storage.as_ref(), &[0, b'C']);
+    }
+
+    #[test]
+    fn keep_all_names() {
+        let mut data = Vec::new();
+        data.extend(0u16.to_be_bytes()); // version
+        data.extend(3u16.to_be_bytes()); // count
+        data.extend(42u16.to_be_bytes()); // storageOffset
+        for (platform, encoding, language, id, offset) in
+            [(1u16, 0u16, 0u16, 1u16, 0u16), (1, 0, 0, 256, 2), (3, 1, 0x0409, 1, 4)]
+        {
+            data.extend(platform.to_be_bytes()); // platformID
+            data.extend(encoding.to_be_bytes()); // encodingID
+            data.extend(language.to_be_bytes()); // languageID
+            data.extend(id.to_be_bytes()); // nameID
+            data.extend(2u16.to_be_bytes()); // length
+            data.extend(offset.to_be_bytes()); // stringOffset
+        }
+        data.extend([b'A', b'?', b'B', b'?', 0, b'A']); // storage
+
+        let table = Table::parse(&data).unwrap();
+        let ids = |table: &Table| {
+            table
+                .names
+                .iter()
+                .map(|r| (r.platform_id, r.name_id))
+                .collect::<Vec<_>>()
+        };
+
+        let subset = subset_table(&table, false, &[256], &[]).unwrap();
+        assert_eq!(ids(&subset), [(3, 1)]);
+
+        let subset = subset_table(&table, true, &[], &[]).unwrap();
+        assert_eq!(ids(&subset), [(1, 1), (1, 256), (3, 1)]);
+
+        // The Mac record can't hold the new string.
+        let subset = subset_table(&table, true, &[], &[(1, Some("C".into()))]).unwrap();
+        assert_eq!(ids(&subset), [(1, 256), (3, 1)]);
+        assert_eq!(subset.storage.as_ref(), b"B?\0C");
+    }
 }
@@ -0,0 +1,55 @@
+//! The `OS/2` table contains metrics and style information. We keep it for
+//! general-purpose subsets, in which case the range of the codepoints is updated
+//! to the subsetted `cmap` table. When instancing, the weight and width class, the
+//! style flags and the global metrics are updated to the ones of the instance.
+
+use super::*;
+
+pub fn subset(ctx: &mut Context) -> Result<()> {
+    let mut os2 = ctx.expect_table(Tag::OS2).ok_or(MalformedFont)?.to_vec();
+
+    // The first and last character index can only hold BMP codepoints.
+    if let Some((first, last)) = ctx.codepoints {
+        let clamp = |c: u32| u16::try_from(c).unwrap_or(0xFFFF);
+        os2.get_mut(64..66)
+            .ok_or(MalformedFont)?
+            .copy_from_slice(&clamp(first).to_be_bytes());
+        os2.get_mut(66..68)
+            .ok_or(MalformedFont)?
+            .copy_from_slice(&clamp(last).to_be_bytes());
+    }
+
+    #[cfg(feature = "variable-fonts")]
+    if let Some(style) = &ctx.style {
+        if let Some(weight_class) = style.weight_class {
+            os2.get_mut(4..6)
+                .ok_or(MalformedFont)?
+                .copy_from_slice(&weight_class.to_be_bytes());
+        }
+
+        if let Some(width_class) = style.width_class {
+            os2.get_mut(6..8)
+                .ok_or(MalformedFont)?
+                .copy_from_slice(&width_class.to_be_bytes());
+        }
+
+        if let Some((bold, italic)) = style.bold_italic {
+            let fs_selection = os2.get_mut(62..64).ok_or(MalformedFont)?;
+            let mut flags = u16::from_be_bytes([fs_selection[0], fs_selection[1]]);
+
+            // Clear the italic, bold and regular bits.
+            flags &= !(1 | 1 << 5 | 1 << 6);
+            flags |= match (bold, italic) {
+                (false, false) => 1 << 6,
+                _ => (bold as u16) << 5 | italic as u16,
+            };
+
+            fs_selection.copy_from_slice(&flags.to_be_bytes());
+        }
+    }
+
+    #[cfg(feature = "variable-fonts")]
+    mvar::apply(ctx, &mut os2, mvar::OS2_FIELDS)?;
+    ctx.push(Tag::OS2, os2);
+    Ok(())
+}
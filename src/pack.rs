@@ -0,0 +1,159 @@
+//! Some tables, like the OpenType layout tables and `COLR`, consist of many small
+//! tables that refer to each other through offsets, some of which are only 16 or
+//! 24 bits wide. We build them as a graph of nodes and only lay them out in the end.
+
+use super::*;
+use crate::cff::U24;
+use rustc_hash::FxHashMap;
+use std::collections::VecDeque;
+
+/// A table that is being written, together with the tables it refers to.
+pub struct Node {
+    data: Writer,
+    links: Vec<Link>,
+}
+
+/// An offset from a node to another one.
+struct Link {
+    /// The position of the offset in the data of the parent node.
+    pos: usize,
+    /// The width of the offset in bytes.
+    width: u8,
+    node: Node,
+}
+
+impl Node {
+    pub fn new() -> Self {
+        Self { data: Writer::new(), links: vec![] }
+    }
+
+    /// Create a node that consists only of the given data.
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let mut node = Self::new();
+        node.extend(bytes);
+        node
+    }
+
+    /// Write raw bytes into the data.
+    pub fn extend(&mut self, bytes: &[u8]) {
+        self.data.extend(bytes);
+    }
+
+    /// Write `T` into the data.
+    pub fn write<T: Writeable>(&mut self, data: T) {
+        self.data.write(data);
+    }
+
+    /// Write a 16-bit offset to another node.
+    pub fn link(&mut self, node: Node) {
+        self.links.push(Link { pos: self.data.len(), width: 2, node });
+        self.data.write::<u16>(0);
+    }
+
+    /// Write a 16-bit offset to another node, or a null offset.
+    pub fn link_opt(&mut self, node: Option<Node>) {
+        match node {
+            Some(node) => self.link(node),
+            None => self.data.write::<u16>(0),
+        }
+    }
+
+    /// Write a 24-bit offset to another node.
+    pub fn link24(&mut self, node: Node) {
+        self.links.push(Link { pos: self.data.len(), width: 3, node });
+        self.data.write(U24(0));
+    }
+
+    /// Write a 32-bit offset to another node.
+    pub fn link32(&mut self, node: Node) {
+        self.links.push(Link { pos: self.data.len(), width: 4, node });
+        self.data.write::<u32>(0);
+    }
+
+    /// Write a 32-bit offset to another node, or a null offset.
+    pub fn link32_opt(&mut self, node: Option<Node>) {
+        match node {
+            Some(node) => self.link32(node),
+            None => self.data.write::<u32>(0),
+        }
+    }
+
+    /// Lay out the node and all nodes it refers to. Returns `None` if an offset
+    /// overflows.
+    ///
+    /// The nodes are placed in breadth-first order, so that the children of a node
+    /// end up close to it. Subgraphs that occur more than once are only written
+    /// once, as long as the earlier copy lies after the parent, since offsets
+    /// cannot be negative. This matters for tables like anchors with device tables,
+    /// which are typically shared by many glyphs.
+    pub fn pack(self) -> Option<Vec<u8>> {
+        let root = Interned::new(self, &mut FxHashMap::default());
+        let mut out = root.data;
+        let mut shared = FxHashMap::default();
+        let mut queue = VecDeque::new();
+        queue.extend(root.links.into_iter().map(|link| (0, link)));
+
+        while let Some((parent, (pos, width, node))) = queue.pop_front() {
+            let start = match shared.get(&node.id) {
+                Some(&start) if start >= parent => start,
+                _ => {
+                    let start = place(&mut out, &node.data);
+                    shared.insert(node.id, start);
+                    queue.extend(node.links.into_iter().map(|link| (start, link)));
+                    start
+                }
+            };
+
+            let offset = u32::try_from(start - parent).ok()?.to_be_bytes();
+            let width = usize::from(width);
+            if offset[..4 - width].iter().any(|&b| b != 0) {
+                return None;
+            }
+
+            let field = parent + pos;
+            out[field..field + width].copy_from_slice(&offset[4 - width..]);
+        }
+
+        Some(out)
+    }
+}
+
+/// A node with finished data and an ID that is the same for all nodes with the
+/// same data that refer to the same nodes in the same way.
+struct Interned {
+    id: u32,
+    data: Vec<u8>,
+    links: Vec<(usize, u8, Interned)>,
+}
+
+/// The data of a node together with the positions, widths and IDs of its links.
+type Key = (Vec<u8>, Vec<(usize, u8, u32)>);
+
+impl Interned {
+    fn new(node: Node, ids: &mut FxHashMap<Key, u32>) -> Self {
+        let data = node.data.finish();
+        let links = node
+            .links
+            .into_iter()
+            .map(|Link { pos, width, node }| (pos, width, Self::new(node, ids)))
+            .collect::<Vec<_>>();
+        let key = links
+            .iter()
+            .map(|&(pos, width, ref node)| (pos, width, node.id))
+            .collect();
+        let next = ids.len() as u32;
+        let id = *ids.entry((data.clone(), key)).or_insert(next);
+        Self { id, data, links }
+    }
+}
+
+/// Append data at a 2-byte boundary and return where it starts.
+fn place(out: &mut Vec<u8>, data: &[u8]) -> usize {
+    if out.len() % 2 != 0 {
+        out.push(0);
+    }
+
+    let start = out.len();
+    out.extend(data);
+    start
+}
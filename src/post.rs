@@ -2,7 +2,8 @@
 //! needed for some PostScript printers. Only version 2 table contains actual custom names,
 //! so this is the only version that we need to subset. All we need to do is to extract
 //! the strings for all requested glyphs and write them into a new `post` table in the
-//! given order.
+//! given order. When instancing, the italic angle and the underline metrics are updated
+//! to the ones of the instance.
 
 use super::*;
 use crate::read::LazyArray16;
@@ -14,6 +15,7 @@ pub fn subset(ctx: &mut Context) -> Result<()> {
 
     let version = r.read::<u32>().ok_or(MalformedFont)?;
     if version != 0x00020000 {
+        let post = update_header(ctx, post.into())?;
         ctx.push(Tag::POST, post);
         return Ok(());
     }
@@ -51,10 +53,31 @@ pub fn subset(ctx: &mut Context) -> Result<()> {
 
     sub_post.extend(&string_storage.finish());
 
-    ctx.push(Tag::POST, sub_post.finish());
+    let post = update_header(ctx, sub_post.finish().into())?;
+    ctx.push(Tag::POST, post);
     Ok(())
 }
 
+/// Update the italic angle and the underline metrics in the header when instancing.
+#[cfg_attr(not(feature = "variable-fonts"), allow(unused_mut, unused_variables))]
+fn update_header<'a>(ctx: &Context, mut post: Cow<'a, [u8]>) -> Result<Cow<'a, [u8]>> {
+    #[cfg(feature = "variable-fonts")]
+    if ctx.style.is_some() {
+        let post = post.to_mut();
+
+        if let Some(angle) = ctx.style.as_ref().and_then(|style| style.italic_angle) {
+            let angle = (angle * 65536.0).round() as i32;
+            post.get_mut(4..8)
+                .ok_or(MalformedFont)?
+                .copy_from_slice(&angle.to_be_bytes());
+        }
+
+        mvar::apply(ctx, post, mvar::POST_FIELDS)?;
+    }
+
+    Ok(post)
+}
+
 /// An iterator over glyph names.
 ///
 /// The `post` table doesn't provide the glyph names count,
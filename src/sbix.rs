@@ -0,0 +1,217 @@
+//! The `sbix` table contains bitmap images (usually PNGs) for the glyphs, grouped
+//! into strikes for one size each. Each strike has an offset array with one entry per
+//! glyph, similar to `loca`. Instead of an image, a glyph can have a `dupe` record,
+//! which refers to the image of another glyph. See the [specification].
+//!
+//! The glyphs that `dupe` records refer to are added to the subset by the closure.
+//! To subset the table, we then rewrite the offset array of each strike in the order
+//! of the new glyph IDs and remap the glyph IDs of the `dupe` records.
+//!
+//! [specification]: https://learn.microsoft.com/en-us/typography/opentype/spec/sbix
+
+use super::*;
+use crate::Error::OverflowError;
+
+/// The graphic type of glyphs that use the image of another glyph.
+const DUPE: &[u8; 4] = b"dupe";
+
+/// Add all glyphs to the subset whose images are used by glyphs in the subset.
+pub fn closure(face: &Face, mapper: &mut GlyphRemapper) -> Result<()> {
+    let Some(data) = face.table(Tag::SBIX) else {
+        return Ok(());
+    };
+
+    let num_glyphs = face.num_glyphs().ok_or(MalformedFont)?;
+    let table = Table::parse(data, num_glyphs).ok_or(MalformedFont)?;
+    table_closure(&table, mapper);
+    Ok(())
+}
+
+/// Add the glyphs that `dupe` records refer to until no new ones are found.
+fn table_closure(table: &Table, mapper: &mut GlyphRemapper) {
+    let mut queue = mapper.remapped_gids().collect::<Vec<_>>();
+
+    while let Some(glyph) = queue.pop() {
+        for strike in &table.strikes {
+            let Some(glyph_data) = strike.glyph_data(glyph) else { continue };
+            if let Some(dupe) = dupe(glyph_data) {
+                if mapper.get(dupe).is_none() {
+                    mapper.remap(dupe);
+                    queue.push(dupe);
+                }
+            }
+        }
+    }
+}
+
+pub fn subset(ctx: &mut Context) -> Result<()> {
+    let sbix = ctx.expect_table(Tag::SBIX).ok_or(MalformedFont)?;
+    let num_glyphs = ctx.face.num_glyphs().ok_or(MalformedFont)?;
+    let data = subset_table(sbix, num_glyphs, &ctx.mapper)?;
+    ctx.push(Tag::SBIX, data);
+    Ok(())
+}
+
+/// Subset an `sbix` table to the glyphs of the mapper.
+fn subset_table(sbix: &[u8], num_glyphs: u16, mapper: &GlyphRemapper) -> Result<Vec<u8>> {
+    let table = Table::parse(sbix, num_glyphs).ok_or(MalformedFont)?;
+
+    let mut strikes = vec![];
+    for strike in &table.strikes {
+        let mut offsets = Writer::new();
+        let mut glyphs = Writer::new();
+        let header_len = 4 + 4 * (usize::from(mapper.num_gids()) + 1);
+
+        for old_gid in mapper.remapped_gids() {
+            offsets.write::<u32>(
+                u32::try_from(header_len + glyphs.len()).map_err(|_| OverflowError)?,
+            );
+
+            let Some(glyph_data) = strike.glyph_data(old_gid) else { continue };
+            match dupe(glyph_data) {
+                Some(dupe) => {
+                    glyphs.extend(&glyph_data[..8]);
+                    glyphs.write::<u16>(mapper.get(dupe).ok_or(MalformedFont)?);
+                }
+                None => glyphs.extend(glyph_data),
+            }
+        }
+
+        offsets.write::<u32>(
+            u32::try_from(header_len + glyphs.len()).map_err(|_| OverflowError)?,
+        );
+
+        let mut w = Writer::new();
+        w.extend(strike.header);
+        w.extend(&offsets.finish());
+        w.extend(&glyphs.finish());
+        strikes.push(w.finish());
+    }
+
+    let mut w = Writer::new();
+    w.extend(table.header);
+    w.write::<u32>(strikes.len() as u32);
+
+    let mut offset = 8 + 4 * strikes.len();
+    for strike in &strikes {
+        w.write::<u32>(u32::try_from(offset).map_err(|_| OverflowError)?);
+        offset += strike.len();
+    }
+
+    for strike in strikes {
+        w.extend(&strike);
+    }
+
+    Ok(w.finish())
+}
+
+/// A parsed `sbix` table.
+struct Table<'a> {
+    /// The version and flags.
+    header: &'a [u8],
+    strikes: Vec<Strike<'a>>,
+}
+
+impl<'a> Table<'a> {
+    fn parse(data: &'a [u8], num_glyphs: u16) -> Option<Self> {
+        let mut r = Reader::new(data);
+        let header = r.read_bytes(4)?;
+        let count = r.read::<u32>()?;
+
+        let mut strikes = vec![];
+        for _ in 0..count {
+            let data = data.get(r.read::<u32>()? as usize..)?;
+            let mut r = Reader::new(data);
+            let header = r.read_bytes(4)?;
+            let offsets = r.read_array16::<u32>(num_glyphs.checked_add(1)?)?;
+            strikes.push(Strike { data, header, offsets });
+        }
+
+        Some(Self { header, strikes })
+    }
+}
+
+/// A strike of the `sbix` table.
+struct Strike<'a> {
+    data: &'a [u8],
+    /// The size and resolution.
+    header: &'a [u8],
+    /// The offsets of the glyph data, relative to the start of the strike.
+    offsets: LazyArray16<'a, u32>,
+}
+
+impl<'a> Strike<'a> {
+    /// The data of a glyph. Returns `None` if the glyph has no image.
+    fn glyph_data(&self, glyph: u16) -> Option<&'a [u8]> {
+        let start = self.offsets.get(glyph)? as usize;
+        let end = self.offsets.get(glyph.checked_add(1)?)? as usize;
+        if start == end {
+            return None;
+        }
+        self.data.get(start..end)
+    }
+}
+
+/// The glyph whose image the glyph data refers to, if it is a `dupe` record.
+fn dupe(glyph_data: &[u8]) -> Option<u16> {
+    // The glyph data starts with the origin and the graphic type.
+    let mut r = Reader::new(glyph_data);
+    r.skip_bytes(4);
+    if r.read::<[u8; 4]>()? == *DUPE {
+        r.read::<u16>()
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subset_dupe() {
+        let mut data = Vec::new();
+        data.extend(1u16.to_be_bytes()); // version
+        data.extend(1u16.to_be_bytes()); // flags
+        data.extend(1u32.to_be_bytes()); // numStrikes
+        data.extend(12u32.to_be_bytes()); // strikeOffsets[0]
+        data.extend(20u16.to_be_bytes()); // ppem
+        data.extend(72u16.to_be_bytes()); // ppi
+        for offset in [20u32, 20, 31, 41] {
+            data.extend(offset.to_be_bytes()); // glyphDataOffsets
+        }
+        data.extend([0, 0, 0, 0]); // originOffsetX, originOffsetY
+        data.extend(b"png "); // graphicType
+        data.extend([1, 2, 3]); // data
+        data.extend([0, 0, 0, 0]); // originOffsetX, originOffsetY
+        data.extend(DUPE); // graphicType
+        data.extend(1u16.to_be_bytes()); // data
+
+        let table = Table::parse(&data, 3).unwrap();
+        let mut mapper = GlyphRemapper::new();
+        mapper.remap(2);
+        table_closure(&table, &mut mapper);
+        assert_eq!(mapper.get(1), Some(2));
+
+        let subset = subset_table(&data, 3, &mapper).unwrap();
+
+        let mut expected = Vec::new();
+        expected.extend(1u16.to_be_bytes()); // version
+        expected.extend(1u16.to_be_bytes()); // flags
+        expected.extend(1u32.to_be_bytes()); // numStrikes
+        expected.extend(12u32.to_be_bytes()); // strikeOffsets[0]
+        expected.extend(20u16.to_be_bytes()); // ppem
+        expected.extend(72u16.to_be_bytes()); // ppi
+        for offset in [20u32, 20, 30, 41] {
+            expected.extend(offset.to_be_bytes()); // glyphDataOffsets
+        }
+        expected.extend([0, 0, 0, 0]); // originOffsetX, originOffsetY
+        expected.extend(DUPE); // graphicType
+        expected.extend(2u16.to_be_bytes()); // data
+        expected.extend([0, 0, 0, 0]); // originOffsetX, originOffsetY
+        expected.extend(b"png "); // graphicType
+        expected.extend([1, 2, 3]); // data
+
+        assert_eq!(subset, expected);
+    }
+}
@@ -0,0 +1,69 @@
+//! The `STAT` table describes the design attributes that distinguish the fonts of a
+//! family. It doesn't depend on the glyphs, so it can be kept as is, but we need to
+//! make sure that the `name` table keeps the names it refers to. When partially
+//! instancing, we drop all axis values that lie outside of the new axis limits. The
+//! design axes themselves are kept, since they can also describe axes that are not
+//! part of `fvar`.
+
+use super::*;
+use skrifa::raw::types::Fixed;
+use skrifa::raw::{FontData, FontRead};
+use write_fonts::dump_table;
+use write_fonts::from_obj::ToOwnedTable;
+use write_fonts::tables::stat::{AxisValue, Stat};
+
+pub fn subset(ctx: &mut Context) -> Result<()> {
+    let stat = ctx.expect_table(Tag::STAT).ok_or(MalformedFont)?;
+    let mut stat: Stat = skrifa::raw::tables::stat::Stat::read(FontData::new(stat))
+        .map_err(|_| MalformedFont)?
+        .to_owned_table();
+
+    // The new user limits of each design axis, if it is a restricted variation axis.
+    let limits = stat
+        .design_axes
+        .iter()
+        .map(|record| match &ctx.interjector {
+            Interjector::Instancer(instancer) => instancer
+                .axes()
+                .iter()
+                .find(|axis| axis.tag.0 == record.axis_tag.into_bytes())
+                .map(|axis| (Fixed::from_f64(axis.user.0), Fixed::from_f64(axis.user.2))),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    let contains = |index: u16, value: Fixed| match limits.get(index as usize) {
+        Some(Some((min, max))) => *min <= value && value <= *max,
+        _ => true,
+    };
+
+    if let Some(values) = stat.offset_to_axis_values.as_mut() {
+        values.retain(|value| match &**value {
+            AxisValue::Format1(value) => contains(value.axis_index, value.value),
+            AxisValue::Format2(value) => contains(value.axis_index, value.nominal_value),
+            AxisValue::Format3(value) => contains(value.axis_index, value.value),
+            AxisValue::Format4(value) => value
+                .axis_values
+                .iter()
+                .all(|record| contains(record.axis_index, record.value)),
+        });
+
+        ctx.name_ids.extend(values.iter().map(|value| {
+            match &**value {
+                AxisValue::Format1(value) => value.value_name_id,
+                AxisValue::Format2(value) => value.value_name_id,
+                AxisValue::Format3(value) => value.value_name_id,
+                AxisValue::Format4(value) => value.value_name_id,
+            }
+            .to_u16()
+        }));
+    }
+
+    ctx.name_ids
+        .extend(stat.design_axes.iter().map(|record| record.axis_name_id.to_u16()));
+    ctx.name_ids
+        .extend(stat.elided_fallback_name_id.map(|id| id.to_u16()));
+    ctx.push(Tag::STAT, dump_table(&stat).map_err(|_| Error::SubsetError)?);
+
+    Ok(())
+}
@@ -0,0 +1,302 @@
+//! When instancing a variable font, the naming and style information in the `name`,
+//! `head`, `OS/2` and `post` tables still describes the default instance. Here, we
+//! derive the new style from the location of the instance, similarly to the instancer
+//! of fontTools.
+//!
+//! The new subfamily name is either the one of the named instance, or built from the
+//! names of the `STAT` axis values at the location. It is then split into a legacy
+//! family/subfamily pair (name IDs 1 and 2), where the subfamily is one of "Regular",
+//! "Bold", "Italic" and "Bold Italic", and a typographic pair (name IDs 16 and 17),
+//! which is only needed if the two differ. The unique ID (name ID 3) is updated to
+//! the new names as well.
+
+use super::*;
+use skrifa::raw::tables::stat::AxisValueTableFlags;
+use skrifa::raw::types::{Fixed, NameId};
+use skrifa::raw::TableProvider;
+use skrifa::MetadataProvider;
+use write_fonts::from_obj::ToOwnedTable;
+use write_fonts::tables::stat::{AxisValue, Stat};
+
+/// The name ID of the font family name.
+const FAMILY: u16 = 1;
+/// The name ID of the unique font identifier.
+const UNIQUE_ID: u16 = 3;
+/// The name ID of the full font name.
+const FULL_NAME: u16 = 4;
+/// The name ID of the PostScript name.
+const POSTSCRIPT_NAME: u16 = 6;
+/// The name ID of the typographic family name.
+const TYPOGRAPHIC_FAMILY: u16 = 16;
+/// The name ID of the typographic subfamily name.
+const TYPOGRAPHIC_SUBFAMILY: u16 = 17;
+
+/// The style of an instance of a variable font.
+#[derive(Debug, Default, Clone)]
+pub struct Style {
+    /// The new weight class, if the font has a `wght` axis.
+    pub weight_class: Option<u16>,
+    /// The new width class, if the font has a `wdth` axis.
+    pub width_class: Option<u16>,
+    /// The new italic angle, if the font has a `slnt` axis.
+    pub italic_angle: Option<f32>,
+    /// Whether the instance is bold and whether it is italic, if the new names
+    /// could be determined.
+    pub bold_italic: Option<(bool, bool)>,
+}
+
+/// Determine the style of the instance at the given user location, together with
+/// its new names.
+pub fn instance_style(
+    data: &[u8],
+    index: u32,
+    location: &[(Tag, f32)],
+    subfamily_name: Option<&str>,
+) -> Result<(Style, Vec<name::Override>)> {
+    let font = skrifa::FontRef::from_index(data, index).map_err(|_| MalformedFont)?;
+
+    // The full location, with the coordinates clamped to the axis ranges.
+    let location = font
+        .axes()
+        .iter()
+        .map(|axis| {
+            let tag = Tag(axis.tag().into_bytes());
+            let value = location
+                .iter()
+                .find(|(t, _)| *t == tag)
+                .map(|(_, v)| v.clamp(axis.min_value(), axis.max_value()))
+                .unwrap_or(axis.default_value());
+            (tag, value)
+        })
+        .collect::<Vec<_>>();
+
+    let coord =
+        |tag: &[u8; 4]| location.iter().find(|(t, _)| t.get() == tag).map(|(_, v)| *v);
+
+    let mut style = Style {
+        weight_class: coord(b"wght").map(|v| v.clamp(1.0, 1000.0).round() as u16),
+        width_class: coord(b"wdth").map(width_class),
+        italic_angle: coord(b"slnt").map(|v| v.clamp(-90.0, 90.0)),
+        bold_italic: None,
+    };
+
+    let name = |id: u16| {
+        font.localized_strings(NameId::new(id))
+            .english_or_first()
+            .map(|name| name.to_string())
+    };
+
+    // The parts of the subfamily name, which are the names of the `STAT` axis
+    // values. The name of a named instance is only split up if it consists of
+    // them, in any order. Otherwise, it is kept whole, so that e.g. "Extra Bold"
+    // isn't mistaken for a bold style.
+    let parts = match subfamily_name {
+        Some(subfamily) => Some(
+            stat_subfamily(&font, &location, name)
+                .and_then(|mut parts| {
+                    parts.sort_by_key(|part| subfamily.find(part.as_str()));
+                    (parts.join(" ") == subfamily).then_some(parts)
+                })
+                .unwrap_or_else(|| vec![subfamily.to_string()]),
+        ),
+        None => stat_subfamily(&font, &location, name),
+    };
+
+    let Some((family, parts)) =
+        name(TYPOGRAPHIC_FAMILY).or_else(|| name(FAMILY)).zip(parts)
+    else {
+        return Ok((style, vec![]));
+    };
+
+    let subfamily = parts.join(" ");
+    let bold = parts
+        .iter()
+        .any(|part| ["Bold", "Bold Italic"].contains(&part.as_str()));
+    let italic = parts
+        .iter()
+        .any(|part| ["Italic", "Bold Italic"].contains(&part.as_str()));
+    style.bold_italic = Some((bold, italic));
+
+    let legacy_subfamily = match (bold, italic) {
+        (true, true) => "Bold Italic",
+        (true, false) => "Bold",
+        (false, true) => "Italic",
+        (false, false) => "Regular",
+    };
+
+    // The parts of the subfamily name that can't be expressed by the legacy
+    // subfamily name are moved into the legacy family name.
+    let rest = parts
+        .iter()
+        .filter(|part| {
+            !["Regular", "Bold", "Italic", "Bold Italic"].contains(&part.as_str())
+        })
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let full_name = format!("{family} {subfamily}");
+    let postscript_name = format!("{}-{}", postscript(&family), postscript(&subfamily));
+
+    // Like fontTools, we replace the old full or PostScript name in the unique ID.
+    // If it contains neither, we build a new one from the version, the vendor and
+    // the PostScript name.
+    let unique_id = name(UNIQUE_ID).map(|unique_id| {
+        [(FULL_NAME, &full_name), (POSTSCRIPT_NAME, &postscript_name)]
+            .into_iter()
+            .filter_map(|(id, new)| Some((name(id)?, new)))
+            .find(|(old, _)| unique_id.contains(old.as_str()))
+            .map(|(old, new)| unique_id.replace(&old, new))
+            .unwrap_or_else(|| {
+                let version = font.head().map(|head| head.font_revision().to_f32());
+                let vendor = font.os2().map(|os2| os2.ach_vend_id().to_string());
+                format!(
+                    "{:.3};{};{postscript_name}",
+                    version.unwrap_or(1.0),
+                    vendor
+                        .unwrap_or_default()
+                        .replace(|c: char| !c.is_ascii_uppercase(), "")
+                )
+            })
+    });
+
+    let mut names = vec![
+        (name::SUBFAMILY, Some(legacy_subfamily.to_string())),
+        (FULL_NAME, Some(full_name)),
+        (POSTSCRIPT_NAME, Some(postscript_name)),
+    ];
+
+    if let Some(unique_id) = unique_id {
+        names.push((UNIQUE_ID, Some(unique_id)));
+    }
+
+    // The typographic names of the variable font would contradict the new names,
+    // so they are removed if they aren't needed.
+    if rest.is_empty() {
+        names.push((FAMILY, Some(family)));
+        names.push((TYPOGRAPHIC_FAMILY, None));
+        names.push((TYPOGRAPHIC_SUBFAMILY, None));
+    } else {
+        names.push((FAMILY, Some(format!("{family} {rest}"))));
+        names.push((TYPOGRAPHIC_FAMILY, Some(family)));
+        names.push((TYPOGRAPHIC_SUBFAMILY, Some(subfamily)));
+    }
+
+    Ok((style, names))
+}
+
+/// Build the parts of the subfamily name of the instance at the given location from
+/// the names of the `STAT` axis values that match it. Returns `None` if there is no
+/// matching value for one of the axes.
+fn stat_subfamily(
+    font: &skrifa::FontRef,
+    location: &[(Tag, f32)],
+    name: impl Fn(u16) -> Option<String>,
+) -> Option<Vec<String>> {
+    let stat: Stat = font.stat().ok()?.to_owned_table();
+
+    // The location on each design axis, in the same precision as the `STAT` values.
+    let coords = stat
+        .design_axes
+        .iter()
+        .map(|record| {
+            location
+                .iter()
+                .find(|(tag, _)| tag.0 == record.axis_tag.into_bytes())
+                .map(|(_, v)| Fixed::from_f64(*v as f64))
+        })
+        .collect::<Vec<_>>();
+    let coord = |index: u16| coords.get(index as usize).copied().flatten();
+
+    // The matching axis value of each design axis, preferring values that
+    // combine multiple axes.
+    let mut matches = vec![None; coords.len()];
+    for value in stat.offset_to_axis_values.as_ref()?.iter() {
+        let (axes, flags, name_id) = match &**value {
+            AxisValue::Format1(v) if coord(v.axis_index) == Some(v.value) => {
+                (vec![v.axis_index], v.flags, v.value_name_id)
+            }
+            AxisValue::Format2(v)
+                if coord(v.axis_index).is_some_and(|c| {
+                    v.range_min_value <= c && c <= v.range_max_value
+                }) =>
+            {
+                (vec![v.axis_index], v.flags, v.value_name_id)
+            }
+            AxisValue::Format3(v) if coord(v.axis_index) == Some(v.value) => {
+                (vec![v.axis_index], v.flags, v.value_name_id)
+            }
+            AxisValue::Format4(v)
+                if v.axis_values.iter().all(|r| coord(r.axis_index) == Some(r.value)) =>
+            {
+                (
+                    v.axis_values.iter().map(|r| r.axis_index).collect(),
+                    v.flags,
+                    v.value_name_id,
+                )
+            }
+            _ => continue,
+        };
+
+        let combined = axes.len() > 1;
+        for axis in axes {
+            let Some(slot) = matches.get_mut(axis as usize) else { continue };
+            if slot.is_none() || combined {
+                *slot = Some((flags, name_id.to_u16()));
+            }
+        }
+    }
+
+    if coords
+        .iter()
+        .zip(&matches)
+        .any(|(coord, m)| coord.is_some() && m.is_none())
+    {
+        return None;
+    }
+
+    // Order the names by the axis ordering, skipping elidable names and the
+    // duplicates of values that combine multiple axes.
+    let mut order = (0..stat.design_axes.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&i| stat.design_axes[i].axis_ordering);
+
+    let mut ids = vec![];
+    for i in order {
+        if let Some((flags, id)) = matches[i] {
+            if !flags.contains(AxisValueTableFlags::ELIDABLE_AXIS_VALUE_NAME)
+                && !ids.contains(&id)
+            {
+                ids.push(id);
+            }
+        }
+    }
+
+    if ids.is_empty() {
+        let fallback = stat.elided_fallback_name_id.map(|id| id.to_u16());
+        return Some(vec![fallback.and_then(&name).unwrap_or_else(|| "Regular".into())]);
+    }
+
+    ids.into_iter().map(name).collect()
+}
+
+/// Map a `wdth` axis value, in percent of the normal width, to the closest
+/// width class.
+fn width_class(width: f32) -> u16 {
+    const WIDTHS: [f32; 9] = [50.0, 62.5, 75.0, 87.5, 100.0, 112.5, 125.0, 150.0, 200.0];
+
+    let mut class = 1;
+    for (i, w) in WIDTHS.iter().enumerate() {
+        if (w - width).abs() < (WIDTHS[class - 1] - width).abs() {
+            class = i + 1;
+        }
+    }
+
+    class as u16
+}
+
+/// Remove the characters that are not allowed in PostScript names.
+fn postscript(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_ascii_graphic() && !"[](){}<>/%".contains(*c))
+        .collect()
+}
@@ -0,0 +1,382 @@
+//! The `SVG ` table contains SVG documents for the glyphs. Each document covers a
+//! range of glyph IDs and contains an element with the ID `glyphN` for each glyph
+//! `N` in that range. See the [specification].
+//!
+//! To subset the table, we go through the new glyph IDs in order and group
+//! consecutive glyphs that are described by the same document into one record.
+//! The documents that are still used are rewritten such that the `glyphN` IDs and
+//! the references to them refer to the new glyph IDs. Compressed documents are
+//! decompressed for that and compressed again afterwards, which requires the
+//! `svg-gzip` feature. Documents are only written once, even if multiple records
+//! refer to them.
+//!
+//! [specification]: https://learn.microsoft.com/en-us/typography/opentype/spec/svg
+
+use super::*;
+use crate::Error::OverflowError;
+
+/// The magic bytes at the start of gzip-compressed documents.
+const GZIP: &[u8] = &[0x1F, 0x8B];
+
+/// The maximum size of a decompressed document, to protect against compression
+/// bombs.
+#[cfg(feature = "svg-gzip")]
+const MAX_DOCUMENT_SIZE: usize = 1 << 26;
+
+pub fn subset(ctx: &mut Context) -> Result<()> {
+    let svg = ctx.expect_table(Tag::SVG).ok_or(MalformedFont)?;
+    if let Some(data) = subset_table(svg, &ctx.mapper)? {
+        ctx.push(Tag::SVG, data);
+    }
+    Ok(())
+}
+
+/// Subset an `SVG ` table to the glyphs of the mapper. Returns `None` if no
+/// glyph of the subset has an SVG document.
+fn subset_table(svg: &[u8], mapper: &GlyphRemapper) -> Result<Option<Vec<u8>>> {
+    let table = Table::parse(svg).ok_or(MalformedFont)?;
+
+    // The records of the subsetted table as new glyph ranges and indices into
+    // `documents`.
+    let mut records: Vec<(u16, u16, usize)> = vec![];
+    // The indices of the used records of the original table, in order of first use.
+    let mut used: Vec<usize> = vec![];
+
+    for (new_gid, old_gid) in (0..).zip(mapper.remapped_gids()) {
+        let Some(index) = table.find(old_gid) else { continue };
+        let position = match used.iter().position(|&i| i == index) {
+            Some(position) => position,
+            None => {
+                used.push(index);
+                used.len() - 1
+            }
+        };
+
+        match records.last_mut() {
+            Some((_, end, last))
+                if end.checked_add(1) == Some(new_gid) && *last == position =>
+            {
+                *end = new_gid;
+            }
+            _ => records.push((new_gid, new_gid, position)),
+        }
+    }
+
+    if records.is_empty() {
+        return Ok(None);
+    }
+
+    let mut documents = vec![];
+    for &index in &used {
+        let document = table.document(index).ok_or(MalformedFont)?;
+        if document.starts_with(GZIP) {
+            documents.push(renumber_compressed(document, mapper)?);
+        } else {
+            documents.push(renumber(document, mapper));
+        }
+    }
+
+    let mut w = Writer::new();
+    w.write::<u16>(0);
+    w.write::<u32>(10);
+    w.write::<u32>(0);
+
+    // The document list, with offsets relative to its start.
+    w.write::<u16>(u16::try_from(records.len()).map_err(|_| OverflowError)?);
+    let mut offsets = vec![];
+    let mut offset = 2 + 12 * records.len();
+    for document in &documents {
+        offsets.push(u32::try_from(offset).map_err(|_| OverflowError)?);
+        offset += document.len();
+    }
+
+    for (start, end, position) in records {
+        w.write::<u16>(start);
+        w.write::<u16>(end);
+        w.write::<u32>(offsets[position]);
+        w.write::<u32>(
+            u32::try_from(documents[position].len()).map_err(|_| OverflowError)?,
+        );
+    }
+
+    for document in documents {
+        w.extend(&document);
+    }
+
+    Ok(Some(w.finish()))
+}
+
+/// Rewrite the `glyphN` IDs of a document and the references to them, like
+/// `href="#glyphN"` and `url(#glyphN)`, to the new glyph IDs. The IDs of glyphs
+/// that are not part of the subset are renamed to `unused-glyphN`, so that they
+/// can't clash with the new IDs.
+fn renumber(document: &[u8], mapper: &GlyphRemapper) -> Vec<u8> {
+    const GLYPH: &[u8] = b"glyph";
+
+    let mut out = Vec::with_capacity(document.len());
+    let mut i = 0;
+    while i < document.len() {
+        // The start of an ID, either in an `id` attribute or in a reference.
+        let start = if document[i] == b'#' {
+            Some(i + 1)
+        } else if document[i..].starts_with(b"id=")
+            && matches!(document.get(i + 3), Some(b'"' | b'\''))
+            && !document[..i].last().is_some_and(|&b| is_name_byte(b))
+        {
+            Some(i + 4)
+        } else {
+            None
+        };
+
+        if let Some(start) = start {
+            let rest = &document[start..];
+            if rest.starts_with(GLYPH) {
+                let digits = &rest[GLYPH.len()..];
+                let len = digits.iter().take_while(|b| b.is_ascii_digit()).count();
+                let gid = std::str::from_utf8(&digits[..len])
+                    .ok()
+                    .and_then(|n| n.parse::<u16>().ok())
+                    .filter(|_| !digits.get(len).is_some_and(|&b| is_name_byte(b)));
+
+                if let Some(gid) = gid {
+                    out.extend(&document[i..start]);
+                    match mapper.get(gid) {
+                        Some(new_gid) => out.extend(format!("glyph{new_gid}").as_bytes()),
+                        None => out.extend(format!("unused-glyph{gid}").as_bytes()),
+                    }
+                    i = start + GLYPH.len() + len;
+                    continue;
+                }
+            }
+        }
+
+        out.push(document[i]);
+        i += 1;
+    }
+
+    out
+}
+
+/// Whether the byte can be part of an XML name.
+fn is_name_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b':')
+}
+
+/// Renumber a gzip-compressed document, keeping it compressed.
+#[cfg(feature = "svg-gzip")]
+fn renumber_compressed(document: &[u8], mapper: &GlyphRemapper) -> Result<Vec<u8>> {
+    let document = decompress(document).ok_or(MalformedFont)?;
+    Ok(compress(&renumber(&document, mapper)))
+}
+
+/// Without the `svg-gzip` feature, compressed documents can't be renumbered.
+#[cfg(not(feature = "svg-gzip"))]
+fn renumber_compressed(_: &[u8], _: &GlyphRemapper) -> Result<Vec<u8>> {
+    Err(Unimplemented)
+}
+
+/// Decompress a gzip-compressed document.
+#[cfg(feature = "svg-gzip")]
+fn decompress(data: &[u8]) -> Option<Vec<u8>> {
+    const FEXTRA: u8 = 1 << 2;
+    const FNAME: u8 = 1 << 3;
+    const FCOMMENT: u8 = 1 << 4;
+    const FHCRC: u8 = 1 << 1;
+
+    let mut r = Reader::new(data);
+    // magic bytes
+    r.skip_bytes(2);
+    // Only deflate is defined as a compression method.
+    if r.read::<u8>()? != 8 {
+        return None;
+    }
+    let flags = r.read::<u8>()?;
+    // modification time, extra flags, operating system
+    r.skip_bytes(6);
+    if flags & FEXTRA != 0 {
+        let len = u16::from_le_bytes(r.read::<[u8; 2]>()?);
+        r.read_bytes(usize::from(len))?;
+    }
+    for flag in [FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            while r.read::<u8>()? != 0 {}
+        }
+    }
+    if flags & FHCRC != 0 {
+        r.skip::<u16>();
+    }
+
+    miniz_oxide::inflate::decompress_to_vec_with_limit(r.tail()?, MAX_DOCUMENT_SIZE).ok()
+}
+
+/// Compress a document with gzip.
+#[cfg(feature = "svg-gzip")]
+fn compress(data: &[u8]) -> Vec<u8> {
+    let mut w = Writer::new();
+    w.extend(GZIP);
+    // deflate, no flags, no modification time, no extra flags, unknown system
+    w.extend(&[8, 0, 0, 0, 0, 0, 0, 0xFF]);
+    w.extend(&miniz_oxide::deflate::compress_to_vec(data, 6));
+    w.extend(&crc32(data).to_le_bytes());
+    w.extend(&(data.len() as u32).to_le_bytes());
+    w.finish()
+}
+
+/// The CRC-32 checksum that gzip uses.
+#[cfg(feature = "svg-gzip")]
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB88320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+/// A parsed `SVG ` table.
+struct Table<'a> {
+    /// The document list, which the document offsets are relative to.
+    list: &'a [u8],
+    records: Vec<Record>,
+}
+
+impl<'a> Table<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut r = Reader::new(data);
+        // version
+        r.skip::<u16>();
+        let list = data.get(r.read::<u32>()? as usize..)?;
+        let mut r = Reader::new(list);
+        let count = r.read::<u16>()?;
+        let records = r.read_array16::<Record>(count)?.into_iter().collect();
+        Some(Self { list, records })
+    }
+
+    /// The index of the record whose glyph range contains the glyph.
+    fn find(&self, glyph: u16) -> Option<usize> {
+        let i = self.records.partition_point(|record| record.end < glyph);
+        self.records.get(i).filter(|record| record.start <= glyph).map(|_| i)
+    }
+
+    /// The document of the record at the given index.
+    fn document(&self, index: usize) -> Option<&'a [u8]> {
+        let record = self.records.get(index)?;
+        let start = record.offset as usize;
+        self.list.get(start..start.checked_add(record.len as usize)?)
+    }
+}
+
+/// A document record.
+#[derive(Debug, Copy, Clone)]
+struct Record {
+    start: u16,
+    end: u16,
+    /// The offset of the document, relative to the start of the document list.
+    offset: u32,
+    len: u32,
+}
+
+impl Readable<'_> for Record {
+    const SIZE: usize = 12;
+
+    fn read(r: &mut Reader) -> Option<Self> {
+        Some(Self {
+            start: r.read::<u16>()?,
+            end: r.read::<u16>()?,
+            offset: r.read::<u32>()?,
+            len: r.read::<u32>()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subset_documents() {
+        let first = b"<svg><g id=\"glyph1\"/><g id=\"glyph2\"/><g id=\"glyph3\"/></svg>";
+        let second = b"<svg><g id='glyph5'/></svg>";
+
+        let mut data = Vec::new();
+        data.extend(0u16.to_be_bytes()); // version
+        data.extend(10u32.to_be_bytes()); // svgDocumentListOffset
+        data.extend(0u32.to_be_bytes()); // reserved
+        data.extend(2u16.to_be_bytes()); // numEntries
+        data.extend(1u16.to_be_bytes()); // startGlyphID
+        data.extend(3u16.to_be_bytes()); // endGlyphID
+        data.extend(26u32.to_be_bytes()); // svgDocOffset
+        data.extend((first.len() as u32).to_be_bytes()); // svgDocLength
+        data.extend(5u16.to_be_bytes()); // startGlyphID
+        data.extend(5u16.to_be_bytes()); // endGlyphID
+        data.extend((26 + first.len() as u32).to_be_bytes()); // svgDocOffset
+        data.extend((second.len() as u32).to_be_bytes()); // svgDocLength
+        data.extend(first);
+        data.extend(second);
+
+        let mut mapper = GlyphRemapper::new();
+        for gid in [3, 4, 5, 1] {
+            mapper.remap(gid);
+        }
+
+        let subset = subset_table(&data, &mapper).unwrap().unwrap();
+
+        let first =
+            b"<svg><g id=\"glyph4\"/><g id=\"unused-glyph2\"/><g id=\"glyph1\"/></svg>";
+        let second = b"<svg><g id='glyph3'/></svg>";
+
+        let mut expected = Vec::new();
+        expected.extend(0u16.to_be_bytes()); // version
+        expected.extend(10u32.to_be_bytes()); // svgDocumentListOffset
+        expected.extend(0u32.to_be_bytes()); // reserved
+        expected.extend(3u16.to_be_bytes()); // numEntries
+        expected.extend(1u16.to_be_bytes()); // startGlyphID
+        expected.extend(1u16.to_be_bytes()); // endGlyphID
+        expected.extend(38u32.to_be_bytes()); // svgDocOffset
+        expected.extend((first.len() as u32).to_be_bytes()); // svgDocLength
+        expected.extend(3u16.to_be_bytes()); // startGlyphID
+        expected.extend(3u16.to_be_bytes()); // endGlyphID
+        expected.extend((38 + first.len() as u32).to_be_bytes()); // svgDocOffset
+        expected.extend((second.len() as u32).to_be_bytes()); // svgDocLength
+        expected.extend(4u16.to_be_bytes()); // startGlyphID
+        expected.extend(4u16.to_be_bytes()); // endGlyphID
+        expected.extend(38u32.to_be_bytes()); // svgDocOffset
+        expected.extend((first.len() as u32).to_be_bytes()); // svgDocLength
+        expected.extend(first);
+        expected.extend(second);
+
+        assert_eq!(subset, expected);
+    }
+
+    #[test]
+    fn renumber_references() {
+        let document = b"<svg><g id='glyph2'/><use href='#glyph3'/>\
+            <g id='glyph3' fill='url(#glyph2)'/><g id='glyph30'/><g data-id='glyph2'/></svg>";
+        let mapper = GlyphRemapper::new_from_glyphs(&[3]);
+        let expected = b"<svg><g id='unused-glyph2'/><use href='#glyph1'/>\
+            <g id='glyph1' fill='url(#unused-glyph2)'/><g id='unused-glyph30'/><g data-id='glyph2'/></svg>";
+        assert_eq!(
+            std::str::from_utf8(&renumber(document, &mapper)).unwrap(),
+            std::str::from_utf8(expected).unwrap(),
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "svg-gzip"))]
+    fn compressed_documents_unimplemented() {
+        let mapper = GlyphRemapper::new_from_glyphs(&[3]);
+        assert_eq!(renumber_compressed(GZIP, &mapper), Err(Unimplemented));
+    }
+
+    #[test]
+    #[cfg(feature = "svg-gzip")]
+    fn compress_roundtrip() {
+        let document = b"<svg><g id='glyph1'/></svg>".repeat(10);
+        let compressed = compress(&document);
+        assert!(compressed.starts_with(GZIP));
+        assert_eq!(decompress(&compressed).unwrap(), document);
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+    }
+}
@@ -10,12 +10,14 @@ FONT_DIR = ROOT / ".." / "fonts"
 SUBSETS_PATH = ROOT / "src" / "subsets.rs"
 FONT_TOOLS_PATH = ROOT / "src" / "font_tools.rs"
 CFF_PATH = ROOT / "src" / "cff.rs"
+AXIS_LIMITS_PATH = ROOT / "src" / "axis_limits.rs"
 
 
 def main():
     gen_font_tools_tests()
     gen_cff_tests()
     gen_subset_tests()
+    gen_axis_limits_tests()
 
 
 def gen_font_tools_tests():
@@ -97,6 +99,35 @@ def gen_subset_tests():
         file.write(test_string)
 
 
+def gen_axis_limits_tests():
+    test_string = f"// This file was auto-generated by `{Path(__file__).name}`, do not edit manually.\n\n"
+    test_string += "#![allow(non_snake_case)]\n\n"
+    test_string += f"use crate::*;\n\n"
+
+    counters = {}
+    with open(DATA_DIR / "axis_limits.tests") as file:
+        content = file.read().splitlines()
+        for line in content:
+            if line.startswith("//") or len(line.strip()) == 0:
+                continue
+
+            font_file, gids, limits, location = line.split(";")
+
+            if font_file not in counters:
+                counters[font_file] = 1
+
+            counter = counters[font_file]
+            counters[font_file] += 1
+
+            function_name = f"{font_name_to_function(font_file)}_{counter}"
+
+            test_string += '#[test] #[cfg(feature = "variable-fonts")] '
+            test_string += f'fn {function_name}() {{test_axis_limits("{font_file}", "{gids}", "{limits}", "{location}")}}\n'
+
+    with open(Path(AXIS_LIMITS_PATH), "w+") as file:
+        file.write(test_string)
+
+
 def font_name_to_function(font_name: str):
     camel_case_pattern = re.compile(r'(?<!^)(?=[A-Z])')
     font_name = camel_case_pattern.sub('_', font_name).lower()
@@ -0,0 +1,18 @@
+// This file was auto-generated by `gen-tests.py`, do not edit manually.
+
+#![allow(non_snake_case)]
+
+use crate::*;
+
+#[test] #[cfg(feature = "variable-fonts")] fn noto_sans_regular_var_1() {test_axis_limits("NotoSans-Regular_var.ttf", "10,40,58,201-205", "wdth=100", "wght=400")}
+#[test] #[cfg(feature = "variable-fonts")] fn noto_sans_regular_var_2() {test_axis_limits("NotoSans-Regular_var.ttf", "10,40,58,201-205", "wdth=100", "wght=750")}
+#[test] #[cfg(feature = "variable-fonts")] fn noto_sans_regular_var_3() {test_axis_limits("NotoSans-Regular_var.ttf", "10,40,58,201-205", "wdth=80", "wght=250")}
+#[test] #[cfg(feature = "variable-fonts")] fn noto_sans_regular_var_4() {test_axis_limits("NotoSans-Regular_var.ttf", "10,40,58,201-205", "wght=300:500:700", "wght=300")}
+#[test] #[cfg(feature = "variable-fonts")] fn noto_sans_regular_var_5() {test_axis_limits("NotoSans-Regular_var.ttf", "10,40,58,201-205", "wght=300:500:700", "wght=450")}
+#[test] #[cfg(feature = "variable-fonts")] fn noto_sans_regular_var_6() {test_axis_limits("NotoSans-Regular_var.ttf", "10,40,58,201-205", "wght=300:500:700", "wght=620,wdth=70")}
+#[test] #[cfg(feature = "variable-fonts")] fn noto_sans_regular_var_7() {test_axis_limits("NotoSans-Regular_var.ttf", "10,40,58,201-205", "wght=650,wdth=70:90:100", "wdth=75")}
+#[test] #[cfg(feature = "variable-fonts")] fn noto_sans_regular_var_8() {test_axis_limits("NotoSans-Regular_var.ttf", "*", "wght=200:400:800,wdth=62.5:100:100", "wght=700,wdth=80")}
+#[test] #[cfg(feature = "variable-fonts")] fn noto_sans_regular_var_9() {test_axis_limits("NotoSans-Regular_var.ttf", "10,40,58,201-205", "", "wght=400")}
+#[test] #[cfg(feature = "variable-fonts")] fn noto_sans_regular_var_10() {test_axis_limits("NotoSans-Regular_var.ttf", "10,40,58,201-205", "", "wght=730,wdth=65")}
+#[test] #[cfg(feature = "variable-fonts")] fn noto_sans_regular_var_11() {test_axis_limits("NotoSans-Regular_var.ttf", "*", "", "wght=120,wdth=90")}
+#[test] #[cfg(feature = "variable-fonts")] fn noto_sans_regular_var_12() {test_axis_limits("NotoSans-Regular_var.ttf", "3,200-230", "wght=100:400:900", "wght=850,wdth=75")}
@@ -6,7 +6,10 @@ use std::error::Error;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::str::FromStr;
-use subsetter::{subset, subset_with_variations, GlyphRemapper, Tag};
+use subsetter::{
+    subset, subset_with_options, subset_with_variations, GlyphRemapper, SubsetOptions,
+    Tag,
+};
 use ttf_parser::GlyphId;
 
 #[rustfmt::skip]
@@ -16,6 +19,8 @@ mod subsets;
 mod font_tools;
 #[rustfmt::skip]
 mod cff;
+#[rustfmt::skip]
+mod axis_limits;
 
 type Result<T> = std::result::Result<T, Box<dyn Error>>;
 
@@ -206,6 +211,77 @@ fn read_file(font_file: &str) -> Vec<u8> {
     std::fs::read(font_path).unwrap()
 }
 
+/// Add a table to a font.
+fn with_table(data: &[u8], tag: &[u8; 4], table: Vec<u8>) -> Vec<u8> {
+    let font = skrifa::FontRef::new(data).unwrap();
+    write_fonts::FontBuilder::new()
+        .add_raw(write_fonts::types::Tag::new(tag), table)
+        .copy_missing_tables(font)
+        .build()
+}
+
+/// Remove a table from a font.
+fn without_table(data: &[u8], tag: &[u8; 4]) -> Vec<u8> {
+    let font = skrifa::FontRef::new(data).unwrap();
+    let mut builder = write_fonts::FontBuilder::new();
+    for record in font.table_directory.table_records() {
+        if record.tag() != write_fonts::types::Tag::new(tag) {
+            builder.add_raw(record.tag(), font.table_data(record.tag()).unwrap());
+        }
+    }
+    builder.build()
+}
+
+/// Build an `sbix` table with one strike of 20 ppem for the given glyph data.
+fn sbix(num_glyphs: u16, images: &[(u16, &[u8; 4], &[u8])]) -> Vec<u8> {
+    let mut glyphs = vec![];
+    let mut offsets = vec![];
+    let header_len = 4 + 4 * (u32::from(num_glyphs) + 1);
+    for gid in 0..num_glyphs {
+        offsets.push(header_len + glyphs.len() as u32);
+        if let Some((_, kind, data)) = images.iter().find(|image| image.0 == gid) {
+            glyphs.extend([0, 0, 0, 0]); // originOffsetX, originOffsetY
+            glyphs.extend(*kind); // graphicType
+            glyphs.extend(*data);
+        }
+    }
+    offsets.push(header_len + glyphs.len() as u32);
+
+    let mut table = vec![];
+    table.extend(1u16.to_be_bytes()); // version
+    table.extend(1u16.to_be_bytes()); // flags
+    table.extend(1u32.to_be_bytes()); // numStrikes
+    table.extend(12u32.to_be_bytes()); // strikeOffsets[0]
+    table.extend(20u16.to_be_bytes()); // ppem
+    table.extend(72u16.to_be_bytes()); // ppi
+    for offset in offsets {
+        table.extend(offset.to_be_bytes()); // glyphDataOffsets
+    }
+    table.extend(glyphs);
+    table
+}
+
+#[cfg(feature = "svg-gzip")]
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x1F, 0x8B, 8, 0, 0, 0, 0, 0, 0, 0xFF];
+    out.extend(miniz_oxide::deflate::compress_to_vec(data, 6));
+    let crc = data.iter().fold(!0u32, |crc, &byte| {
+        (0..8).fold(crc ^ u32::from(byte), |crc, _| {
+            (crc >> 1) ^ (0xEDB88320 & (crc & 1).wrapping_neg())
+        })
+    });
+    out.extend((!crc).to_le_bytes());
+    out.extend((data.len() as u32).to_le_bytes());
+    out
+}
+
+#[cfg(feature = "svg-gzip")]
+fn gunzip(data: &[u8]) -> Vec<u8> {
+    // Our documents have no optional gzip header fields.
+    assert_eq!(data[..4], [0x1F, 0x8B, 8, 0]);
+    miniz_oxide::inflate::decompress_to_vec(&data[10..data.len() - 8]).unwrap()
+}
+
 fn get_test_context(font_file: &str, gids: &str) -> Result<TestContext> {
     let data = read_file(font_file);
     let face = ttf_parser::Face::parse(&data, 0).unwrap();
@@ -256,6 +332,1667 @@ fn parse_variations(input: &str) -> Vec<(Tag, f32)> {
         .collect()
 }
 
+#[cfg(feature = "variable-fonts")]
+fn parse_axis_limits(input: &str) -> Vec<(Tag, subsetter::AxisLimit)> {
+    input
+        .split(',')
+        .filter_map(|pair| {
+            let (tag, value) = pair.split_once('=')?;
+            let values = value
+                .split(':')
+                .map(|v| v.trim().parse::<f32>().unwrap())
+                .collect::<Vec<_>>();
+
+            let limit = match values[..] {
+                [v] => subsetter::AxisLimit::Pin(v),
+                [min, default, max] => subsetter::AxisLimit::Range { min, default, max },
+                _ => panic!("invalid axis limit {value}"),
+            };
+
+            Some((Tag::from_str(tag.trim()).unwrap(), limit))
+        })
+        .collect()
+}
+
+/// Partially instance a font and check that the outlines and advances of the
+/// subset at `location` match the ones of the original font at the same location.
+#[cfg(feature = "variable-fonts")]
+fn test_axis_limits(font_file: &str, gids: &str, limits: &str, location: &str) {
+    let data = read_file(font_file);
+    let old_face = skrifa::FontRef::from_index(&data, 0).unwrap();
+    let num_glyphs = old_face.maxp().unwrap().num_glyphs();
+    let gids = parse_gids(gids, num_glyphs);
+    let mapper = GlyphRemapper::new_from_glyphs(gids.as_slice());
+    let limits = parse_axis_limits(limits);
+    let subset = if limits.is_empty() {
+        subsetter::subset_variable(&data, 0, &mapper).unwrap()
+    } else {
+        subsetter::subset_with_axis_limits(&data, 0, &limits, &mapper).unwrap()
+    };
+    let new_face = skrifa::FontRef::from_index(&subset, 0).unwrap();
+
+    let location = parse_variations(location);
+    let pinned = limits.iter().filter_map(|(tag, limit)| match limit {
+        subsetter::AxisLimit::Pin(v) => Some((*tag, *v)),
+        _ => None,
+    });
+
+    let to_skrifa = |(tag, v): (Tag, f32)| (skrifa::Tag::new(tag.get()), v);
+    let old_location = old_face
+        .axes()
+        .location(pinned.chain(location.iter().copied()).map(to_skrifa));
+    let new_location = new_face.axes().location(location.iter().copied().map(to_skrifa));
+
+    let old_metrics = old_face.glyph_metrics(Size::unscaled(), &old_location);
+    let new_metrics = new_face.glyph_metrics(Size::unscaled(), &new_location);
+
+    for glyph in (0..num_glyphs).filter(|g| gids.contains(g)) {
+        let old_glyph = skrifa::GlyphId::new(glyph as u32);
+        let new_glyph = skrifa::GlyphId::new(mapper.get(glyph).unwrap() as u32);
+
+        let old_advance = old_metrics.advance_width(old_glyph).unwrap();
+        let new_advance = new_metrics.advance_width(new_glyph).unwrap();
+        assert!(
+            (old_advance - new_advance).abs() <= 1.0,
+            "advance of glyph {glyph} didn't match ({old_advance} vs {new_advance})."
+        );
+
+        let mut sink1 = Sink::default();
+        let mut sink2 = Sink::default();
+
+        let settings = DrawSettings::unhinted(Size::unscaled(), &old_location);
+        old_face
+            .outline_glyphs()
+            .get(old_glyph)
+            .unwrap()
+            .draw(settings, &mut sink1)
+            .unwrap();
+        let settings = DrawSettings::unhinted(Size::unscaled(), &new_location);
+        new_face
+            .outline_glyphs()
+            .get(new_glyph)
+            .unwrap()
+            .draw(settings, &mut sink2)
+            .unwrap();
+
+        // Allow for a small difference, since the new default coordinates as well as
+        // the rebased deltas are rounded.
+        assert!(
+            sink1.approx_eq(&sink2, 2.0),
+            "glyph {glyph} didn't match.\n{sink1:?}\n{sink2:?}"
+        );
+    }
+}
+
+#[test]
+#[cfg(feature = "variable-fonts")]
+fn named_instance() {
+    use subsetter::InstanceSelector;
+
+    let data = read_file("NotoSans-Regular_var.ttf");
+    let mapper = GlyphRemapper::new_from_glyphs(&[10, 40, 58]);
+    let instances = subsetter::named_instances(&data, 0).unwrap();
+    let index = instances
+        .iter()
+        .position(|instance| instance.subfamily_name.as_deref() == Some("Condensed Bold"))
+        .unwrap();
+
+    let by_index = subsetter::subset_with_named_instance(
+        &data,
+        0,
+        InstanceSelector::Index(index),
+        &mapper,
+    )
+    .unwrap();
+    let by_name = subsetter::subset_with_named_instance(
+        &data,
+        0,
+        InstanceSelector::Name("Condensed Bold"),
+        &mapper,
+    )
+    .unwrap();
+    let by_coords =
+        subset_with_variations(&data, 0, &instances[index].coordinates, &mapper).unwrap();
+
+    assert_eq!(by_index, by_name);
+
+    let face = ttf_parser::Face::parse(&by_name, 0).unwrap();
+    let reference = ttf_parser::Face::parse(&by_coords, 0).unwrap();
+    assert_eq!(
+        face.raw_face().table(ttf_parser::Tag::from_bytes(b"glyf")),
+        reference.raw_face().table(ttf_parser::Tag::from_bytes(b"glyf"))
+    );
+
+    assert_eq!(name(&face, ttf_parser::name_id::SUBFAMILY).as_deref(), Some("Bold"));
+    assert_eq!(
+        name(&face, ttf_parser::name_id::TYPOGRAPHIC_SUBFAMILY).as_deref(),
+        Some("Condensed Bold")
+    );
+
+    assert_eq!(
+        subsetter::subset_with_named_instance(
+            &data,
+            0,
+            InstanceSelector::Name("Heavy"),
+            &mapper
+        ),
+        Err(subsetter::Error::UnknownInstance)
+    );
+}
+
+#[test]
+#[cfg(feature = "variable-fonts")]
+fn non_ribbi_instance() {
+    use subsetter::InstanceSelector;
+
+    let data = read_file("Cantarell-VF.otf");
+    let mapper = GlyphRemapper::new_from_glyphs(&[10]);
+    let subset = subsetter::subset_with_named_instance(
+        &data,
+        0,
+        InstanceSelector::Name("Extra Bold"),
+        &mapper,
+    )
+    .unwrap();
+
+    // "Extra Bold" isn't one of the legacy subfamilies, so it is moved into the
+    // legacy family name.
+    let face = ttf_parser::Face::parse(&subset, 0).unwrap();
+    let name = |id| name(&face, id);
+    assert_eq!(
+        name(ttf_parser::name_id::FAMILY).as_deref(),
+        Some("Cantarell Extra Bold")
+    );
+    assert_eq!(name(ttf_parser::name_id::SUBFAMILY).as_deref(), Some("Regular"));
+    assert_eq!(
+        name(ttf_parser::name_id::UNIQUE_ID).as_deref(),
+        Some("0.303;ABAT;Cantarell-ExtraBold")
+    );
+    assert_eq!(
+        name(ttf_parser::name_id::TYPOGRAPHIC_FAMILY).as_deref(),
+        Some("Cantarell")
+    );
+    assert_eq!(
+        name(ttf_parser::name_id::TYPOGRAPHIC_SUBFAMILY).as_deref(),
+        Some("Extra Bold")
+    );
+
+    assert!(!face.is_bold());
+    assert!(face.is_regular());
+    let head = face.raw_face().table(ttf_parser::Tag::from_bytes(b"head")).unwrap();
+    assert_eq!(head[44..46], [0, 0]);
+    assert_eq!(face.weight().to_number(), 800);
+}
+
+#[test]
+#[cfg(feature = "variable-fonts")]
+fn instance_style() {
+    let data = read_file("NotoSans-Regular_var.ttf");
+    let mapper = GlyphRemapper::new_from_glyphs(&[10, 40, 58]);
+    let location = parse_variations("wght=700,wdth=75");
+    let subset = subset_with_variations(&data, 0, &location, &mapper).unwrap();
+    let face = ttf_parser::Face::parse(&subset, 0).unwrap();
+
+    assert_eq!(face.weight(), ttf_parser::Weight::Bold);
+    assert_eq!(face.width(), ttf_parser::Width::Condensed);
+    assert!(face.is_bold());
+    assert!(!face.is_italic());
+
+    let head = face.raw_face().table(ttf_parser::Tag::from_bytes(b"head")).unwrap();
+    assert_eq!(u16::from_be_bytes([head[44], head[45]]), 1);
+
+    let expected = [
+        (ttf_parser::name_id::FAMILY, "Noto Sans Condensed"),
+        (ttf_parser::name_id::SUBFAMILY, "Bold"),
+        (ttf_parser::name_id::FULL_NAME, "Noto Sans Bold Condensed"),
+        (ttf_parser::name_id::POST_SCRIPT_NAME, "NotoSans-BoldCondensed"),
+        (ttf_parser::name_id::TYPOGRAPHIC_FAMILY, "Noto Sans"),
+        (ttf_parser::name_id::TYPOGRAPHIC_SUBFAMILY, "Bold Condensed"),
+    ];
+
+    for (id, expected) in expected {
+        assert_eq!(name(&face, id).as_deref(), Some(expected));
+    }
+}
+
+#[test]
+#[cfg(feature = "variable-fonts")]
+fn instance_metrics() {
+    let data = read_file("NotoSans-Regular_var.ttf");
+    let mapper = GlyphRemapper::new_from_glyphs(&(0..300).collect::<Vec<_>>());
+
+    for location in ["wght=900,wdth=62.5", "wght=100"] {
+        let location = parse_variations(location);
+        let subset = subset_with_variations(&data, 0, &location, &mapper).unwrap();
+
+        let old_face = skrifa::FontRef::from_index(&data, 0).unwrap();
+        let new_face = skrifa::FontRef::from_index(&subset, 0).unwrap();
+        let old_location = old_face
+            .axes()
+            .location(location.iter().map(|(tag, v)| (skrifa::Tag::new(tag.get()), *v)));
+
+        let old_metrics = old_face.metrics(Size::unscaled(), &old_location);
+        let new_metrics = new_face.metrics(Size::unscaled(), LocationRef::default());
+
+        assert_eq!(old_metrics.ascent, new_metrics.ascent);
+        assert_eq!(old_metrics.descent, new_metrics.descent);
+        assert_eq!(old_metrics.leading, new_metrics.leading);
+        assert_eq!(old_metrics.x_height, new_metrics.x_height);
+        assert_eq!(old_metrics.cap_height, new_metrics.cap_height);
+        assert_eq!(old_metrics.underline, new_metrics.underline);
+        assert_eq!(old_metrics.strikeout, new_metrics.strikeout);
+
+        // The bounding box only covers the glyphs of the subset.
+        let bounds = new_metrics.bounds.unwrap();
+        let glyphs = new_face.glyph_metrics(Size::unscaled(), LocationRef::default());
+        let mut max = 0.0f32;
+        for glyph in 0..mapper.num_gids() {
+            let b = glyphs.bounds(skrifa::GlyphId::new(glyph as u32)).unwrap_or_default();
+            max = max.max(b.y_max);
+            assert!(bounds.x_min <= b.x_min && b.x_max <= bounds.x_max);
+            assert!(bounds.y_min <= b.y_min && b.y_max <= bounds.y_max);
+        }
+        assert_eq!(bounds.y_max, max);
+    }
+}
+
+#[test]
+#[cfg(feature = "variable-fonts")]
+fn instance_composite_offsets() {
+    use skrifa::raw::tables::glyf::{Anchor, Glyph};
+
+    let data = read_file("NotoSans-Regular_var.ttf");
+    let old_face = skrifa::FontRef::from_index(&data, 0).unwrap();
+    let glyph_id = old_face.charmap().map('é').unwrap().to_u32() as u16;
+    let Some(Glyph::Composite(old_glyph)) = glyph(&old_face, glyph_id) else {
+        panic!("é should be a composite glyph");
+    };
+    let mapper = GlyphRemapper::new_from_glyphs(&[glyph_id]);
+
+    for location in ["wght=900,wdth=62.5", "wght=100"] {
+        let location = parse_variations(location);
+        let subset = subset_with_variations(&data, 0, &location, &mapper).unwrap();
+        let new_face = skrifa::FontRef::from_index(&subset, 0).unwrap();
+        let old_location = old_face
+            .axes()
+            .location(location.iter().map(|(tag, v)| (skrifa::Tag::new(tag.get()), *v)));
+
+        let draw = |gid: u16| {
+            let mut sink = Sink::default();
+            let settings = DrawSettings::unhinted(Size::unscaled(), &old_location);
+            let outlines = old_face.outline_glyphs();
+            let glyph = outlines.get(skrifa::GlyphId::new(gid as u32)).unwrap();
+            glyph.draw(settings, &mut sink).unwrap();
+            sink.0
+        };
+
+        // The composite glyph is drawn as its components, one after the other, so
+        // the offset of a component at the location is the distance between its
+        // first point and the corresponding one in the composite.
+        let composite = draw(glyph_id);
+        let mut start = 0;
+        let mut expected = vec![];
+        for component in old_glyph.components() {
+            let component = draw(component.glyph.to_u16());
+            let (Inst::MoveTo(x1, y1), Inst::MoveTo(x2, y2)) =
+                (&composite[start], &component[0])
+            else {
+                panic!("component doesn't start with a move");
+            };
+            expected.push(((x1 - x2).round() as i16, (y1 - y2).round() as i16));
+            start += component.len();
+        }
+
+        let Some(Glyph::Composite(new_glyph)) = glyph(&new_face, 1) else {
+            panic!("é should stay a composite glyph");
+        };
+        let offsets = new_glyph
+            .components()
+            .map(|component| match component.anchor {
+                Anchor::Offset { x, y } => (x, y),
+                Anchor::Point { .. } => panic!("unexpected point anchor"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(offsets, expected, "{location:?}");
+        assert_ne!(
+            offsets,
+            old_glyph
+                .components()
+                .map(|component| match component.anchor {
+                    Anchor::Offset { x, y } => (x, y),
+                    Anchor::Point { .. } => panic!("unexpected point anchor"),
+                })
+                .collect::<Vec<_>>()
+        );
+
+        // The statistics of the composite glyph are recomputed from the
+        // instanced components.
+        let maxp = new_face.maxp().unwrap();
+        let (points, contours, _) = glyph_stats(&new_face, 1);
+        assert_eq!(maxp.max_composite_points(), Some(points));
+        assert_eq!(maxp.max_composite_contours(), Some(contours));
+        assert_eq!(maxp.max_component_elements(), Some(offsets.len() as u16));
+        assert_eq!(maxp.max_component_depth(), Some(1));
+    }
+}
+
+#[test]
+#[cfg(feature = "variable-fonts")]
+fn instance_point_anchored_composite() {
+    use skrifa::raw::tables::glyf::Glyph;
+
+    let data = read_file("NotoSans-Regular_var.ttf");
+    let face = skrifa::FontRef::from_index(&data, 0).unwrap();
+    let glyph_id = face.charmap().map('ó').unwrap();
+
+    // Attach the accent of `ó` to the last point of the `o` instead of using an
+    // offset.
+    let mut glyf = face
+        .table_data(skrifa::Tag::new(b"glyf"))
+        .unwrap()
+        .as_bytes()
+        .to_vec();
+    let loca = face.loca(None).unwrap();
+    let mut pos = loca.get_raw(glyph_id.to_u32() as usize).unwrap() as usize + 10;
+    let flags = u16::from_be_bytes([glyf[pos], glyf[pos + 1]]);
+    assert!(flags & 0x0020 != 0 && flags & 0x00C8 == 0, "expected two plain components");
+    let base = u16::from_be_bytes([glyf[pos + 2], glyf[pos + 3]]);
+    let Some(Glyph::Simple(simple)) = glyph(&face, base) else {
+        panic!("o should be a simple glyph");
+    };
+    let last = simple.num_points() as u8 - 1;
+    pos += if flags & 0x0001 != 0 { 8 } else { 6 };
+    let flags = u16::from_be_bytes([glyf[pos], glyf[pos + 1]]) & !0x0002;
+    glyf[pos..pos + 2].copy_from_slice(&flags.to_be_bytes());
+    if flags & 0x0001 != 0 {
+        glyf[pos + 4..pos + 8].copy_from_slice(&[0, last, 0, 0]);
+    } else {
+        glyf[pos + 4..pos + 6].copy_from_slice(&[last, 0]);
+    }
+    let data = with_table(&data, b"glyf", glyf);
+
+    let old_face = skrifa::FontRef::from_index(&data, 0).unwrap();
+    let mapper = GlyphRemapper::new_from_glyphs(&[glyph_id.to_u32() as u16]);
+    let location = parse_variations("wght=900,wdth=62.5");
+    let subset = subset_with_variations(&data, 0, &location, &mapper).unwrap();
+    let new_face = skrifa::FontRef::from_index(&subset, 0).unwrap();
+    let old_location = old_face
+        .axes()
+        .location(location.iter().map(|(tag, v)| (skrifa::Tag::new(tag.get()), *v)));
+
+    // The instanced `o` keeps its points, so the accent is attached to the same
+    // point as before.
+    let Some(Glyph::Composite(composite)) = glyph(&new_face, 1) else {
+        panic!("ó should stay a composite glyph");
+    };
+    let new_base = composite.components().next().unwrap().glyph.to_u16();
+    let on_curve = |face: &skrifa::FontRef, gid: u16| match glyph(face, gid) {
+        Some(Glyph::Simple(simple)) => {
+            simple.points().map(|p| p.on_curve).collect::<Vec<_>>()
+        }
+        _ => panic!("expected a simple glyph"),
+    };
+    assert_eq!(on_curve(&old_face, base), on_curve(&new_face, new_base));
+
+    let draw = |face: &skrifa::FontRef, gid, location: LocationRef| {
+        let mut sink = Sink::default();
+        let settings = DrawSettings::unhinted(Size::unscaled(), location);
+        let outlines = face.outline_glyphs();
+        outlines.get(gid).unwrap().draw(settings, &mut sink).unwrap();
+        sink
+    };
+    let old = draw(&old_face, glyph_id, (&old_location).into());
+    let new = draw(&new_face, skrifa::GlyphId::new(1), LocationRef::default());
+    assert!(old.approx_eq(&new, 1.0), "outline didn't match.\n{old:?}\n{new:?}");
+}
+
+#[test]
+fn recompute_bbox() {
+    let mut options = SubsetOptions::default();
+    options.recompute_bbox = true;
+
+    for (font_file, gids) in [
+        ("NotoSans-Regular.ttf", "68-70,200"),
+        ("LatinModernRoman-Regular.otf", "307,309,314,221"),
+        ("NewCMMath-Regular.otf", "803-806,950-952,5600-5602"),
+    ] {
+        let data = read_file(font_file);
+        let face = ttf_parser::Face::parse(&data, 0).unwrap();
+        let mapper =
+            GlyphRemapper::new_from_glyphs(&parse_gids(gids, face.number_of_glyphs()));
+        let subset = subset_with_options(&data, 0, &mapper, &options).unwrap();
+        let new_face = ttf_parser::Face::parse(&subset, 0).unwrap();
+        let bbox = new_face.global_bounding_box();
+        assert!(bbox.width() < face.global_bounding_box().width());
+
+        // The bounding box contains all points on the outlines and is contained
+        // in the bounding box of their control points.
+        let mut control_box: Option<ttf_parser::Rect> = None;
+        for glyph in 0..new_face.number_of_glyphs() {
+            let mut sink = Sink::default();
+            let Some(b) = new_face.outline_glyph(GlyphId(glyph), &mut sink) else {
+                continue;
+            };
+
+            for inst in &sink.0 {
+                let (x, y) = match *inst {
+                    Inst::MoveTo(x, y) | Inst::LineTo(x, y) => (x, y),
+                    Inst::QuadTo(_, _, x, y) | Inst::CurveTo(_, _, _, _, x, y) => (x, y),
+                    Inst::Close => continue,
+                };
+                assert!(bbox.x_min as f32 <= x && x <= bbox.x_max as f32);
+                assert!(bbox.y_min as f32 <= y && y <= bbox.y_max as f32);
+            }
+
+            control_box = Some(match control_box {
+                Some(c) => ttf_parser::Rect {
+                    x_min: c.x_min.min(b.x_min),
+                    y_min: c.y_min.min(b.y_min),
+                    x_max: c.x_max.max(b.x_max),
+                    y_max: c.y_max.max(b.y_max),
+                },
+                None => b,
+            });
+        }
+
+        let control_box = control_box.unwrap();
+        assert!(control_box.x_min <= bbox.x_min && bbox.x_max <= control_box.x_max);
+        assert!(control_box.y_min <= bbox.y_min && bbox.y_max <= control_box.y_max);
+    }
+}
+
+#[test]
+fn truetype_maxp() {
+    use skrifa::raw::tables::glyf::Glyph;
+
+    for (font_file, gids) in [
+        ("DejaVuSansMono.ttf", "140-155,100-105"),
+        ("NotoSans-Regular.ttf", "567-570,2345-2350"),
+        ("Roboto-Regular.ttf", "456,460-463"),
+    ] {
+        let ctx = get_test_context(font_file, gids).unwrap();
+        let face = skrifa::FontRef::from_index(&ctx.subset, 0).unwrap();
+        let maxp = face.maxp().unwrap();
+
+        let mut expected = [0u16; 6];
+        for gid in 0..maxp.num_glyphs() {
+            let (points, contours, depth) = glyph_stats(&face, gid);
+            match glyph(&face, gid) {
+                Some(Glyph::Simple(_)) => {
+                    expected[0] = expected[0].max(points);
+                    expected[1] = expected[1].max(contours);
+                }
+                Some(Glyph::Composite(composite)) => {
+                    expected[2] = expected[2].max(points);
+                    expected[3] = expected[3].max(contours);
+                    expected[4] = expected[4].max(composite.components().count() as u16);
+                    expected[5] = expected[5].max(depth);
+                }
+                None => {}
+            }
+        }
+
+        let actual = [
+            maxp.max_points(),
+            maxp.max_contours(),
+            maxp.max_composite_points(),
+            maxp.max_composite_contours(),
+            maxp.max_component_elements(),
+            maxp.max_component_depth(),
+        ]
+        .map(Option::unwrap);
+        assert_eq!(actual, expected, "maxp of {font_file} didn't match");
+    }
+}
+
+#[test]
+fn strip_hinting() {
+    use skrifa::raw::tables::glyf::Glyph;
+
+    let mut options = SubsetOptions::default();
+    options.strip_hinting = true;
+
+    for (font_file, gids) in [
+        ("ClickerScript-Regular.ttf", "5,8,10,100-104"),
+        ("DejaVuSansMono.ttf", "140-155,100-105"),
+        ("Roboto-Regular.ttf", "456,460-463"),
+        ("LatinModernRoman-Regular.otf", "307,309,314,221"),
+        ("NotoSansCJKsc-Bold-subset1.otf", "1"),
+        ("NewCMMath-Regular.otf", "803-806,950-952,5600-5602"),
+    ] {
+        let data = read_file(font_file);
+        let face = skrifa::FontRef::from_index(&data, 0).unwrap();
+        let mapper = GlyphRemapper::new_from_glyphs(&parse_gids(
+            gids,
+            face.maxp().unwrap().num_glyphs(),
+        ));
+        let subset = subset_with_options(&data, 0, &mapper, &options).unwrap();
+        let new_face = skrifa::FontRef::from_index(&subset, 0).unwrap();
+        let hinted = subset_with_options(&data, 0, &mapper, &SubsetOptions::default());
+        assert!(subset.len() < hinted.unwrap().len());
+
+        for tag in [b"cvt ", b"fpgm", b"prep"] {
+            assert!(new_face.table_data(skrifa::Tag::new(tag)).is_none());
+        }
+
+        let maxp = new_face.maxp().unwrap();
+        if maxp.version() == skrifa::raw::types::Version16Dot16::VERSION_1_0 {
+            assert_eq!(maxp.max_zones(), Some(1));
+            assert_eq!(maxp.max_function_defs(), Some(0));
+            assert_eq!(maxp.max_size_of_instructions(), Some(0));
+        }
+
+        for gid in 0..maxp.num_glyphs() {
+            match glyph(&new_face, gid) {
+                Some(Glyph::Simple(simple)) => assert!(simple.instructions().is_empty()),
+                Some(Glyph::Composite(composite)) => {
+                    assert!(composite.instructions().is_none())
+                }
+                None => {}
+            }
+        }
+
+        // The outlines stay the same.
+        let settings =
+            || DrawSettings::unhinted(Size::unscaled(), LocationRef::default());
+        for old_gid in mapper.remapped_gids() {
+            let new_gid = mapper.get(old_gid).unwrap();
+            let (mut sink1, mut sink2) = (Sink::default(), Sink::default());
+            let outlines = face.outline_glyphs();
+            let glyph1 = outlines.get(skrifa::GlyphId::new(old_gid as u32)).unwrap();
+            glyph1.draw(settings(), &mut sink1).unwrap();
+            let outlines = new_face.outline_glyphs();
+            let glyph2 = outlines.get(skrifa::GlyphId::new(new_gid as u32)).unwrap();
+            glyph2.draw(settings(), &mut sink2).unwrap();
+            assert_eq!(sink1, sink2);
+        }
+    }
+}
+
+#[test]
+fn layout_closure() {
+    for (font_file, ligature) in [
+        ("ClickerScript-Regular.ttf", "fi"),
+        ("NotoSans-Regular.ttf", "fi"),
+        ("LatinModernRoman-Regular.otf", "f_i"),
+    ] {
+        let data = read_file(font_file);
+        let face = ttf_parser::Face::parse(&data, 0).unwrap();
+        let gid = |c| face.glyph_index(c).unwrap().0;
+        let ligature = (0..face.number_of_glyphs())
+            .find(|&gid| face.glyph_name(GlyphId(gid)) == Some(ligature))
+            .unwrap();
+
+        // The ligature is only added if all of its components are in the subset.
+        let mut mapper = GlyphRemapper::new_from_glyphs(&[gid('f'), gid('a')]);
+        subsetter::layout_closure(&data, 0, &mut mapper, &SubsetOptions::default())
+            .unwrap();
+        assert_eq!(mapper.get(ligature), None, "{font_file}");
+
+        let mut mapper = GlyphRemapper::new_from_glyphs(&[gid('f'), gid('i')]);
+        let requested = mapper.clone();
+        subsetter::layout_closure(&data, 0, &mut mapper, &SubsetOptions::default())
+            .unwrap();
+        assert!(mapper.get(ligature).is_some(), "{font_file}");
+
+        // The subset contains the same glyphs, with the same IDs.
+        let mut options = SubsetOptions::default();
+        options.layout_closure = true;
+        let subset = subset_with_options(&data, 0, &requested, &options).unwrap();
+        let reference = subset_with_options(&data, 0, &mapper, &SubsetOptions::default());
+        assert_eq!(subset, reference.unwrap(), "{font_file}");
+    }
+}
+
+#[test]
+fn layout_tables() {
+    use ttf_parser::gsub::SubstitutionSubtable;
+
+    for (font_file, ligature) in [
+        ("ClickerScript-Regular.ttf", "fi"),
+        ("NotoSans-Regular.ttf", "fi"),
+        ("LatinModernRoman-Regular.otf", "f_i"),
+    ] {
+        let data = read_file(font_file);
+        let face = ttf_parser::Face::parse(&data, 0).unwrap();
+        let gid = |c| face.glyph_index(c).unwrap().0;
+        let ligature = (0..face.number_of_glyphs())
+            .find(|&gid| face.glyph_name(GlyphId(gid)) == Some(ligature))
+            .unwrap();
+
+        let mut mapper = GlyphRemapper::new_from_glyphs(&[gid('f'), gid('i')]);
+        let mut options = SubsetOptions::default();
+        options.layout_tables = true;
+        subsetter::layout_closure(&data, 0, &mut mapper, &options).unwrap();
+        let subset = subset_with_options(&data, 0, &mapper, &options).unwrap();
+        let new_face = ttf_parser::Face::parse(&subset, 0).unwrap();
+
+        // The ligature substitution still exists, with the new glyph IDs.
+        let f = GlyphId(mapper.get(gid('f')).unwrap());
+        let i = GlyphId(mapper.get(gid('i')).unwrap());
+        let fi = GlyphId(mapper.get(ligature).unwrap());
+        let gsub = new_face.tables().gsub.unwrap();
+        let found = gsub.lookups.into_iter().any(|lookup| {
+            lookup.subtables.into_iter::<SubstitutionSubtable>().any(|subtable| {
+                let SubstitutionSubtable::Ligature(subtable) = subtable else {
+                    return false;
+                };
+                let Some(index) = subtable.coverage.get(f) else {
+                    return false;
+                };
+                let set = subtable.ligature_sets.get(index).unwrap();
+                set.into_iter()
+                    .any(|lig| lig.glyph == fi && lig.components.into_iter().eq([i]))
+            })
+        });
+        assert!(found, "{font_file}");
+
+        // Without the `liga` feature, the ligature substitution is gone.
+        let features = new_face.tables().gsub.unwrap().features;
+        assert!(features
+            .into_iter()
+            .any(|f| f.tag == ttf_parser::Tag::from_bytes(b"liga")));
+        options.layout_features = Some(vec![Tag::new(b"kern")]);
+        let subset = subset_with_options(&data, 0, &mapper, &options).unwrap();
+        let new_face = ttf_parser::Face::parse(&subset, 0).unwrap();
+        let gsub = new_face.tables().gsub.unwrap();
+        assert_eq!(gsub.features.len(), 0, "{font_file}");
+        assert_eq!(gsub.lookups.len(), 0, "{font_file}");
+    }
+}
+
+#[test]
+fn subset_fpgm() {
+    use skrifa::outline::{Engine, HintingInstance};
+
+    let count_fdefs = |fpgm: &[u8]| fpgm.iter().filter(|&&b| b == 0x2C).count();
+
+    for (font_file, gids) in [
+        ("ClickerScript-Regular.ttf", "5,8,10,100-104"),
+        ("DejaVuSansMono.ttf", "140-155,100-105"),
+        ("Roboto-Regular.ttf", "456,460-463"),
+    ] {
+        let data = read_file(font_file);
+        let face = skrifa::FontRef::from_index(&data, 0).unwrap();
+        let mapper = GlyphRemapper::new_from_glyphs(&parse_gids(
+            gids,
+            face.maxp().unwrap().num_glyphs(),
+        ));
+        let subset = subset(&data, 0, &mapper).unwrap();
+        let new_face = skrifa::FontRef::from_index(&subset, 0).unwrap();
+
+        // Unused function bodies are removed, but the definitions are kept.
+        let fpgm = face.table_data(skrifa::Tag::new(b"fpgm")).unwrap();
+        let new_fpgm = new_face.table_data(skrifa::Tag::new(b"fpgm")).unwrap();
+        assert!(new_fpgm.len() < fpgm.len(), "fpgm of {font_file} wasn't subsetted");
+
+        // The pushed data in these fonts doesn't contain the `FDEF` opcode, so we
+        // can simply count the bytes.
+        assert_eq!(count_fdefs(new_fpgm.as_bytes()), count_fdefs(fpgm.as_bytes()));
+
+        // The hinted outlines stay the same.
+        for ppem in [9.0, 12.0, 16.0, 24.0, 48.0] {
+            let instance = |face: &skrifa::FontRef| {
+                HintingInstance::new(
+                    &face.outline_glyphs(),
+                    Size::new(ppem),
+                    LocationRef::default(),
+                    Engine::Interpreter,
+                )
+                .unwrap()
+            };
+            let (instance1, instance2) = (instance(&face), instance(&new_face));
+
+            for old_gid in mapper.remapped_gids() {
+                let new_gid = mapper.get(old_gid).unwrap();
+                let (mut sink1, mut sink2) = (Sink::default(), Sink::default());
+                let outlines = face.outline_glyphs();
+                let glyph1 = outlines.get(skrifa::GlyphId::new(old_gid as u32)).unwrap();
+                glyph1
+                    .draw(DrawSettings::hinted(&instance1, true), &mut sink1)
+                    .unwrap();
+                let outlines = new_face.outline_glyphs();
+                let glyph2 = outlines.get(skrifa::GlyphId::new(new_gid as u32)).unwrap();
+                glyph2
+                    .draw(DrawSettings::hinted(&instance2, true), &mut sink2)
+                    .unwrap();
+                assert_eq!(sink1, sink2, "glyph {old_gid} of {font_file} at {ppem}ppem");
+            }
+        }
+    }
+}
+
+#[test]
+#[cfg(feature = "svg-gzip")]
+fn svg_table() {
+    // A document that describes glyphs 68 and 69, and an unused glyph 1 whose ID
+    // would clash with the new ID of glyph 69.
+    let document = br##"<svg xmlns="http://www.w3.org/2000/svg"><defs><path id="glyph1" d="M0 0h10v10z"/></defs><g id="glyph68"><use href="#glyph1"/></g><g id="glyph69"><use href="#glyph68"/></g></svg>"##;
+    let document = gzip(document);
+
+    let mut svg = vec![];
+    svg.extend(0u16.to_be_bytes()); // version
+    svg.extend(10u32.to_be_bytes()); // svgDocumentListOffset
+    svg.extend(0u32.to_be_bytes()); // reserved
+    svg.extend(1u16.to_be_bytes()); // numEntries
+    svg.extend(1u16.to_be_bytes()); // startGlyphID
+    svg.extend(69u16.to_be_bytes()); // endGlyphID
+    svg.extend(14u32.to_be_bytes()); // svgDocOffset
+    svg.extend((document.len() as u32).to_be_bytes()); // svgDocLength
+    svg.extend(&document);
+
+    let data = read_file("NotoSans-Regular.ttf");
+    let data = with_table(&data, b"SVG ", svg);
+    let mapper = GlyphRemapper::new_from_glyphs(&[69, 68]);
+    let mut options = SubsetOptions::default();
+    options.color_tables = true;
+    let subset = subset_with_options(&data, 0, &mapper, &options).unwrap();
+
+    let face = ttf_parser::Face::parse(&subset, 0).unwrap();
+    let document = face.glyph_svg_image(GlyphId(1)).unwrap();
+    assert_eq!((document.start_glyph_id.0, document.end_glyph_id.0), (1, 2));
+    assert_eq!(
+        face.glyph_svg_image(GlyphId(2)).unwrap().data.as_ptr(),
+        document.data.as_ptr()
+    );
+    assert!(face.glyph_svg_image(GlyphId(0)).is_none());
+
+    // The document stays compressed and its IDs are unique.
+    let inflated = gunzip(document.data);
+    assert_eq!(
+        std::str::from_utf8(&inflated).unwrap(),
+        r##"<svg xmlns="http://www.w3.org/2000/svg"><defs><path id="unused-glyph1" d="M0 0h10v10z"/></defs><g id="glyph2"><use href="#unused-glyph1"/></g><g id="glyph1"><use href="#glyph2"/></g></svg>"##
+    );
+}
+
+#[test]
+fn colr_v1_table() {
+    let data = read_file("colr_1.ttf");
+    let font = skrifa::FontRef::new(&data).unwrap();
+    let base_glyphs = font
+        .colr()
+        .unwrap()
+        .base_glyph_list()
+        .unwrap()
+        .unwrap()
+        .base_glyph_paint_records()
+        .iter()
+        .map(|record| record.glyph_id().to_u16())
+        .collect::<Vec<_>>();
+    let mapper = GlyphRemapper::new_from_glyphs(&base_glyphs);
+    let mut options = SubsetOptions::default();
+    options.color_tables = true;
+    let subset = subset_with_options(&data, 0, &mapper, &options).unwrap();
+
+    // Every color glyph paints the same as before.
+    let old_face = ttf_parser::Face::parse(&data, 0).unwrap();
+    let new_face = ttf_parser::Face::parse(&subset, 0).unwrap();
+    for &gid in &base_glyphs {
+        let new_gid = mapper.get(gid).unwrap();
+        let paint = |face: &ttf_parser::Face, gid| {
+            let mut canvas = Canvas { face, ops: vec![] };
+            let black = ttf_parser::RgbaColor::new(0, 0, 0, 255);
+            face.paint_color_glyph(GlyphId(gid), 0, black, &mut canvas).unwrap();
+            canvas.ops
+        };
+        assert_eq!(paint(&old_face, gid), paint(&new_face, new_gid));
+    }
+}
+
+#[test]
+#[cfg(feature = "variable-fonts")]
+fn general_profile_instancing() {
+    let data = read_file("NotoSans-Regular_var.ttf");
+    let font = ttf_parser::Face::parse(&data, 0).unwrap();
+    let glyph = font.glyph_index('a').unwrap();
+
+    // A legacy kerning table with a single pair.
+    let mut kern = vec![];
+    kern.extend(0u16.to_be_bytes()); // version
+    kern.extend(1u16.to_be_bytes()); // nTables
+    kern.extend(0u16.to_be_bytes()); // version
+    kern.extend(20u16.to_be_bytes()); // length
+    kern.extend(1u16.to_be_bytes()); // coverage
+    kern.extend(1u16.to_be_bytes()); // nPairs
+    kern.extend(6u16.to_be_bytes()); // searchRange
+    kern.extend(0u16.to_be_bytes()); // entrySelector
+    kern.extend(0u16.to_be_bytes()); // rangeShift
+    kern.extend(glyph.0.to_be_bytes()); // left
+    kern.extend(glyph.0.to_be_bytes()); // right
+    kern.extend((-10i16).to_be_bytes()); // value
+    let data = with_table(&data, b"kern", kern);
+
+    let mapper = GlyphRemapper::new_from_glyphs(&[glyph.0]);
+    let options = SubsetOptions::general();
+    let subset = |limits| {
+        let limits = parse_axis_limits(limits);
+        subsetter::subset_with_axis_limits_and_options(
+            &data, 0, &limits, &mapper, &options,
+        )
+        .unwrap()
+    };
+
+    // Without restrictions, the font stays as it is.
+    let full = subset("");
+    let face = ttf_parser::Face::parse(&full, 0).unwrap();
+    assert!(face.tables().gsub.is_some());
+    assert!(face.tables().gpos.is_some());
+    assert!(face.tables().mvar.is_some());
+    let kern = face.tables().kern.unwrap().subtables.into_iter().next().unwrap();
+    assert_eq!(kern.glyphs_kerning(GlyphId(1), GlyphId(1)), Some(-10));
+
+    // When partially instancing, the tables whose variations can't be instanced
+    // yet are dropped, but the subset is still a valid font.
+    for limits in ["wght=400:500:700", "wght=500,wdth=100"] {
+        let instanced = subset(limits);
+        let face = ttf_parser::Face::parse(&instanced, 0).unwrap();
+        assert!(face.tables().gdef.is_none());
+        assert!(face.tables().gsub.is_none());
+        assert!(face.tables().gpos.is_none());
+        assert!(face.tables().mvar.is_none());
+        assert!(face.tables().kern.is_none());
+        assert!(face.tables().os2.is_some());
+        assert_eq!(face.glyph_index('a'), Some(GlyphId(1)));
+
+        // The names are updated to the instance, but none are lost.
+        let ids = |face: &ttf_parser::Face| {
+            face.names().into_iter().map(|name| name.name_id).collect::<Vec<_>>()
+        };
+        let new_ids = ids(&face);
+        assert!(ids(&font).iter().all(|id| new_ids.contains(id)));
+    }
+}
+
+#[test]
+#[cfg(feature = "variable-fonts")]
+fn colr_closure_instancing() {
+    let data = read_file("colr_1_variable.ttf");
+    let font = skrifa::FontRef::new(&data).unwrap();
+    let colr = font.colr().unwrap();
+    let base_glyphs = colr
+        .base_glyph_list()
+        .unwrap()
+        .unwrap()
+        .base_glyph_paint_records()
+        .iter()
+        .map(|record| record.glyph_id().to_u16())
+        .collect::<Vec<_>>();
+    let mapper = GlyphRemapper::new_from_glyphs(&base_glyphs);
+    let mut options = SubsetOptions::default();
+    options.color_tables = true;
+    let subset = |limits| {
+        let limits = parse_axis_limits(limits);
+        subsetter::subset_with_axis_limits_and_options(
+            &data, 0, &limits, &mapper, &options,
+        )
+        .unwrap()
+    };
+
+    // The paints of the color glyphs refer to other glyphs.
+    let full = subset("");
+    let face = ttf_parser::Face::parse(&full, 0).unwrap();
+    assert!(face.tables().colr.is_some());
+    assert!(face.number_of_glyphs() > mapper.num_gids());
+
+    // When instancing, the color tables are dropped, so their glyphs aren't needed.
+    let axis = font.axes().get(0).unwrap();
+    let instanced = subset(&format!("{}={}", axis.tag(), axis.max_value()));
+    let face = ttf_parser::Face::parse(&instanced, 0).unwrap();
+    assert!(face.tables().colr.is_none());
+    assert_eq!(face.number_of_glyphs(), mapper.num_gids());
+}
+
+#[test]
+fn sbix_closure() {
+    let data = read_file("NotoSans-Regular.ttf");
+    let num_glyphs = ttf_parser::Face::parse(&data, 0).unwrap().number_of_glyphs();
+    let images: &[(u16, &[u8; 4], &[u8])] =
+        &[(68, b"png ", &[1, 2, 3]), (69, b"dupe", &68u16.to_be_bytes())];
+    let data = with_table(&data, b"sbix", sbix(num_glyphs, images));
+    let mapper = GlyphRemapper::new_from_glyphs(&[69]);
+
+    // The image that the `dupe` record refers to is only needed if the table is
+    // kept.
+    let subset = subset(&data, 0, &mapper).unwrap();
+    let face = ttf_parser::Face::parse(&subset, 0).unwrap();
+    assert!(face.tables().sbix.is_none());
+    assert_eq!(face.number_of_glyphs(), 2);
+
+    let mut options = SubsetOptions::default();
+    options.color_tables = true;
+    let subset = subset_with_options(&data, 0, &mapper, &options).unwrap();
+    let face = ttf_parser::Face::parse(&subset, 0).unwrap();
+    assert!(face.tables().sbix.is_some());
+    assert_eq!(face.number_of_glyphs(), 3);
+}
+
+#[test]
+fn colr_v0_layers() {
+    use ttf_parser::colr::{Paint, Painter};
+
+    let data = read_file("colr_1.ttf");
+    let face = ttf_parser::Face::parse(&data, 0).unwrap();
+    let palettes = skrifa::FontRef::new(&data).unwrap().cpal().unwrap().num_palettes();
+    let base_glyph = 168;
+
+    for palette in 0..palettes {
+        let mut mapper = GlyphRemapper::new_from_glyphs(&[base_glyph]);
+        let glyphs = subsetter::color_layers(&data, 0, &mut mapper, palette).unwrap();
+        assert_eq!(glyphs.len(), 1);
+        assert_eq!(glyphs[0].glyph, 1);
+
+        // Drawing the layer glyphs of the subset in the given colors paints the
+        // same as the color glyph of the original font.
+        let black = ttf_parser::RgbaColor::new(0, 0, 0, 255);
+        let mut canvas = Canvas { face: &face, ops: vec![] };
+        face.paint_color_glyph(GlyphId(base_glyph), palette, black, &mut canvas)
+            .unwrap();
+
+        let subset = subset(&data, 0, &mapper).unwrap();
+        let new_face = ttf_parser::Face::parse(&subset, 0).unwrap();
+        let mut new_canvas = Canvas { face: &new_face, ops: vec![] };
+        for layer in &glyphs[0].layers {
+            let [r, g, b, a] = layer.color.unwrap_or([0, 0, 0, 255]);
+            let color = ttf_parser::RgbaColor::new(r, g, b, a);
+            new_canvas.outline_glyph(GlyphId(layer.glyph));
+            new_canvas.paint(Paint::Solid(color));
+        }
+
+        assert_eq!(new_canvas.ops, canvas.ops);
+        assert_eq!(new_face.number_of_glyphs(), 2 + glyphs[0].layers.len() as u16);
+    }
+
+    let mut mapper = GlyphRemapper::new_from_glyphs(&[base_glyph]);
+    assert_eq!(
+        subsetter::color_layers(&data, 0, &mut mapper, palettes),
+        Err(subsetter::Error::UnknownPalette)
+    );
+
+    // Without palettes, the layers have no colors to refer to.
+    let data = without_table(&data, b"CPAL");
+    let mut mapper = GlyphRemapper::new_from_glyphs(&[base_glyph]);
+    assert_eq!(
+        subsetter::color_layers(&data, 0, &mut mapper, 0),
+        Err(subsetter::Error::MalformedFont)
+    );
+
+    // Fonts without color glyphs have no layers.
+    let data = read_file("NotoSans-Regular.ttf");
+    let mut mapper = GlyphRemapper::new_from_glyphs(&[68]);
+    assert_eq!(subsetter::color_layers(&data, 0, &mut mapper, 0), Ok(vec![]));
+    assert_eq!(mapper.num_gids(), 2);
+}
+
+#[test]
+fn sbix_table() {
+    // The start of a PNG image, up to its size.
+    let png = |width: u32, height: u32| {
+        let mut data = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        data.extend(width.to_be_bytes());
+        data.extend(height.to_be_bytes());
+        data
+    };
+
+    let data = read_file("NotoSans-Regular.ttf");
+    let num_glyphs = ttf_parser::Face::parse(&data, 0).unwrap().number_of_glyphs();
+    let (a, c) = (png(10, 12), png(8, 8));
+    let images: &[(u16, &[u8; 4], &[u8])] =
+        &[(68, b"png ", &a), (69, b"dupe", &68u16.to_be_bytes()), (70, b"png ", &c)];
+    let data = with_table(&data, b"sbix", sbix(num_glyphs, images));
+    let mapper = GlyphRemapper::new_from_glyphs(&[70, 69]);
+    let mut options = SubsetOptions::default();
+    options.color_tables = true;
+    let subset = subset_with_options(&data, 0, &mapper, &options).unwrap();
+
+    let face = ttf_parser::Face::parse(&subset, 0).unwrap();
+    assert_eq!(face.number_of_glyphs(), 4);
+    assert!(face.glyph_raster_image(GlyphId(0), 20).is_none());
+
+    let image = |gid| {
+        let image = face.glyph_raster_image(GlyphId(gid), 20).unwrap();
+        assert_eq!(image.pixels_per_em, 20);
+        assert_eq!(image.format, ttf_parser::RasterImageFormat::PNG);
+        (image.width, image.height, image.data)
+    };
+    assert_eq!(image(1), (8, 8, c.as_slice()));
+    assert_eq!(image(3), (10, 12, a.as_slice()));
+
+    // The `dupe` record refers to the new glyph ID of its image.
+    assert_eq!(image(2).2.as_ptr(), image(3).2.as_ptr());
+}
+
+#[test]
+fn cbdt_bitmap_font() {
+    // Color bitmap fonts have the same structure as the embedded bitmaps, so we
+    // can turn a font with embedded bitmaps into one without outlines.
+    let otb = read_file("bitmap.otb");
+    let font = skrifa::FontRef::new(&otb).unwrap();
+    let mut builder = write_fonts::FontBuilder::new();
+    for record in font.table_directory.table_records() {
+        let mut table = font.table_data(record.tag()).unwrap().as_bytes().to_vec();
+        let tag = match &record.tag().to_be_bytes() {
+            b"EBLC" => *b"CBLC",
+            b"EBDT" => *b"CBDT",
+            tag => *tag,
+        };
+        if tag != record.tag().to_be_bytes() {
+            table[..4].copy_from_slice(&0x0003_0000u32.to_be_bytes()); // version
+        }
+        builder.add_raw(write_fonts::types::Tag::new(&tag), table);
+    }
+    let data = builder.build();
+
+    let mapper = GlyphRemapper::new_from_glyphs(&[5, 2]);
+    let subset = subset(&data, 0, &mapper).unwrap();
+
+    let face = ttf_parser::Face::parse(&data, 0).unwrap();
+    let new_face = ttf_parser::Face::parse(&subset, 0).unwrap();
+    assert!(new_face.tables().cbdt.is_some());
+    assert_eq!(new_face.number_of_glyphs(), 3);
+    for (old_gid, new_gid) in [(0, 0), (5, 1), (2, 2)] {
+        let image = face.glyph_raster_image(GlyphId(old_gid), 8).unwrap();
+        let new_image = new_face.glyph_raster_image(GlyphId(new_gid), 8).unwrap();
+        assert_eq!(new_image, image);
+        assert_eq!(
+            new_face.glyph_hor_advance(GlyphId(new_gid)),
+            face.glyph_hor_advance(GlyphId(old_gid))
+        );
+    }
+}
+
+#[test]
+fn embedded_bitmaps() {
+    // Add the embedded bitmaps of a bitmap font to a font with outlines.
+    let otb = read_file("bitmap.otb");
+    let font = skrifa::FontRef::new(&otb).unwrap();
+    let table = |tag| font.table_data(skrifa::Tag::new(tag)).unwrap().as_bytes().to_vec();
+    let data = read_file("NotoSans-Regular.ttf");
+    let data = with_table(&data, b"EBLC", table(b"EBLC"));
+    let data = with_table(&data, b"EBDT", table(b"EBDT"));
+    let mapper = GlyphRemapper::new_from_glyphs(&[5, 2]);
+
+    // The bitmaps are dropped by default.
+    let subset = subset(&data, 0, &mapper).unwrap();
+    let new_face = ttf_parser::Face::parse(&subset, 0).unwrap();
+    assert!(new_face.tables().ebdt.is_none());
+
+    let mut options = SubsetOptions::default();
+    options.embedded_bitmaps = true;
+    let subset = subset_with_options(&data, 0, &mapper, &options).unwrap();
+    let face = ttf_parser::Face::parse(&otb, 0).unwrap();
+    let new_face = ttf_parser::Face::parse(&subset, 0).unwrap();
+    assert_eq!(new_face.number_of_glyphs(), 3);
+    for (old_gid, new_gid) in [(0, 0), (5, 1), (2, 2)] {
+        let image = face.glyph_raster_image(GlyphId(old_gid), 8).unwrap();
+        let new_image = new_face.glyph_raster_image(GlyphId(new_gid), 8).unwrap();
+        assert_eq!(new_image, image);
+    }
+}
+
+#[test]
+fn base_table() {
+    use skrifa::raw::tables::base::BaseCoord;
+    use write_fonts::tables::base as write;
+    use write_fonts::types::Tag;
+
+    let data = read_file("NotoSans-Regular.ttf");
+    let face = ttf_parser::Face::parse(&data, 0).unwrap();
+    let gid = |c| face.glyph_index(c).unwrap().0;
+
+    // The ideographic baseline refers to a glyph that isn't part of the subset.
+    let values = write::BaseValues::new(
+        1,
+        vec![
+            write::BaseCoord::format_2(-120, gid('b'), 3),
+            write::BaseCoord::format_2(0, gid('a'), 1),
+        ],
+    );
+    let min_max = write::MinMax::new(
+        Some(write::BaseCoord::format_1(-200)),
+        Some(write::BaseCoord::format_1(800)),
+        vec![],
+    );
+    let script = write::BaseScript::new(Some(values), Some(min_max), vec![]);
+    let axis = write::Axis::new(
+        Some(write::BaseTagList::new(vec![Tag::new(b"ideo"), Tag::new(b"romn")])),
+        write::BaseScriptList::new(vec![write::BaseScriptRecord::new(
+            Tag::new(b"latn"),
+            script,
+        )]),
+    );
+    let base = write_fonts::dump_table(&write::Base::new(Some(axis), None)).unwrap();
+    let data = with_table(&data, b"BASE", base);
+
+    let mapper = GlyphRemapper::new_from_glyphs(&[gid('a')]);
+    let mut options = SubsetOptions::default();
+    options.base_table = true;
+    let subset = subset_with_options(&data, 0, &mapper, &options).unwrap();
+
+    let font = skrifa::FontRef::new(&subset).unwrap();
+    let axis = font.base().unwrap().horiz_axis().unwrap().unwrap();
+    let tags = axis.base_tag_list().unwrap().unwrap().baseline_tags().to_vec();
+    assert_eq!(tags, [Tag::new(b"ideo"), Tag::new(b"romn")]);
+
+    let list = axis.base_script_list().unwrap();
+    let record = &list.base_script_records()[0];
+    assert_eq!(record.base_script_tag(), Tag::new(b"latn"));
+    let script = record.base_script(list.offset_data()).unwrap();
+    let values = script.base_values().unwrap().unwrap();
+    assert_eq!(values.default_baseline_index(), 1);
+
+    // Coordinates that refer to removed glyphs become plain ones.
+    let coords = values.base_coords();
+    let Ok(BaseCoord::Format1(ideo)) = coords.get(0) else { panic!("expected format 1") };
+    assert_eq!(ideo.coordinate(), -120);
+    let Ok(BaseCoord::Format2(romn)) = coords.get(1) else { panic!("expected format 2") };
+    assert_eq!((romn.coordinate(), romn.reference_glyph()), (0, 1));
+    assert_eq!(romn.base_coord_point(), 1);
+
+    let min_max = script.default_min_max().unwrap().unwrap();
+    assert_eq!(min_max.min_coord().unwrap().unwrap().coordinate(), -200);
+    assert_eq!(min_max.max_coord().unwrap().unwrap().coordinate(), 800);
+}
+
+#[test]
+#[cfg(feature = "variable-fonts")]
+fn stat_table() {
+    use write_fonts::from_obj::ToOwnedTable;
+    use write_fonts::tables::stat::{AxisValue, Stat};
+
+    let data = read_file("Syne-Regular_subset.otf");
+    let mapper = GlyphRemapper::new_from_glyphs(&[1, 2]);
+    let mut options = SubsetOptions::default();
+    options.stat_table = true;
+    let subset = subset_with_options(&data, 0, &mapper, &options).unwrap();
+
+    // The style attributes are kept as they are.
+    let stat = |data| -> Stat {
+        skrifa::FontRef::new(data).unwrap().stat().unwrap().to_owned_table()
+    };
+    let new_stat = stat(&subset);
+    assert_eq!(new_stat, stat(&data));
+    assert!(!new_stat.design_axes.is_empty());
+
+    // And so are the names they refer to.
+    let new_face = ttf_parser::Face::parse(&subset, 0).unwrap();
+    let name_ids = new_stat
+        .design_axes
+        .iter()
+        .map(|axis| axis.axis_name_id.to_u16())
+        .chain(
+            new_stat
+                .offset_to_axis_values
+                .iter()
+                .flat_map(|values| values.iter())
+                .map(|value| {
+                    match &**value {
+                        AxisValue::Format1(value) => value.value_name_id,
+                        AxisValue::Format2(value) => value.value_name_id,
+                        AxisValue::Format3(value) => value.value_name_id,
+                        AxisValue::Format4(value) => value.value_name_id,
+                    }
+                    .to_u16()
+                }),
+        );
+    for id in name_ids {
+        assert!(name(&new_face, id).is_some(), "name {id} is missing");
+    }
+}
+
+#[test]
+fn fpgm_budget() {
+    // Function 0 runs 1000 instructions and function 1 is never called.
+    let mut fpgm = vec![0xB0, 0x00, 0x2C]; // PUSHB[0] 0, FDEF
+    for _ in 0..500 {
+        fpgm.extend([0xB0, 0x01, 0x21]); // PUSHB[0] 1, POP
+    }
+    fpgm.extend([0x2D, 0xB0, 0x01, 0x2C, 0xB0, 0x01, 0x21, 0x2D]);
+
+    let subset_fpgm = |calls: usize| {
+        let mut prep = vec![];
+        for _ in 0..calls {
+            prep.extend([0xB1, 100, 0x00, 0x2A]); // PUSHB[1] 100 0, LOOPCALL
+        }
+        let data = read_file("NotoSans-Regular.ttf");
+        let data = with_table(&data, b"fpgm", fpgm.clone());
+        let data = with_table(&data, b"prep", prep);
+        let mapper = GlyphRemapper::new_from_glyphs(&[5]);
+        let subset = subset(&data, 0, &mapper).unwrap();
+        let face = skrifa::FontRef::new(&subset).unwrap();
+        face.table_data(skrifa::Tag::new(b"fpgm"))
+            .unwrap()
+            .as_bytes()
+            .to_vec()
+    };
+
+    // The body of the unused function is removed.
+    assert_eq!(subset_fpgm(1).len(), fpgm.len() - 3);
+
+    // Once the analysis exceeds its budget, the table is kept as is.
+    assert_eq!(subset_fpgm(20), fpgm);
+}
+
+#[test]
+fn fpgm_storage() {
+    // Functions 0 and 1 with the same body.
+    let mut fpgm = vec![];
+    for number in 0..2 {
+        fpgm.extend([0xB0, number, 0x2C, 0xB0, 0x01, 0x21, 0x2D]); // FDEF, POP, ENDF
+    }
+
+    let subset_fpgm = |prep: &[u8]| {
+        let data = read_file("NotoSans-Regular.ttf");
+        let data = with_table(&data, b"fpgm", fpgm.clone());
+        let data = with_table(&data, b"prep", prep.to_vec());
+        let mapper = GlyphRemapper::new_from_glyphs(&[5]);
+        let subset = subset(&data, 0, &mapper).unwrap();
+        let face = skrifa::FontRef::new(&subset).unwrap();
+        face.table_data(skrifa::Tag::new(b"fpgm"))
+            .unwrap()
+            .as_bytes()
+            .to_vec()
+    };
+
+    // Storage location 0 is never written, so it reads as zero and only function
+    // 0 is called.
+    let mut prep = vec![0xB0, 0x00, 0x43, 0x2B]; // PUSHB[0] 0, RS, CALL
+    assert_eq!(subset_fpgm(&prep).len(), fpgm.len() - 3);
+
+    // Once it is written after the call, the called function could be either one.
+    prep.extend([0xB1, 0x00, 0x01, 0x42]); // PUSHB[1] 0 1, WS
+    assert_eq!(subset_fpgm(&prep), fpgm);
+}
+
+#[test]
+fn math_table() {
+    let data = read_file("NewCMMath-Regular.otf");
+    let face = ttf_parser::Face::parse(&data, 0).unwrap();
+    let gid = |c| face.glyph_index(c).unwrap().0;
+
+    let mut mapper =
+        GlyphRemapper::new_from_glyphs(&[gid('('), gid('\u{1D453}'), gid('∑')]);
+    let mut options = SubsetOptions::default();
+    options.layout_tables = true;
+    subsetter::layout_closure(&data, 0, &mut mapper, &options).unwrap();
+    let subset = subset_with_options(&data, 0, &mapper, &options).unwrap();
+    let new_face = ttf_parser::Face::parse(&subset, 0).unwrap();
+
+    let math = face.tables().math.unwrap();
+    let new_math = new_face.tables().math.unwrap();
+    let (constants, new_constants) =
+        (math.constants.unwrap(), new_math.constants.unwrap());
+    assert_eq!(constants.axis_height().value, new_constants.axis_height().value);
+    assert_eq!(
+        constants.radical_degree_bottom_raise_percent(),
+        new_constants.radical_degree_bottom_raise_percent()
+    );
+
+    let map = |glyph: GlyphId| GlyphId(mapper.get(glyph.0).unwrap());
+    let (info, new_info) = (math.glyph_info.unwrap(), new_math.glyph_info.unwrap());
+    let (variants, new_variants) = (math.variants.unwrap(), new_math.variants.unwrap());
+    let mut constructions = 0;
+
+    for old_gid in mapper.remapped_gids() {
+        let (glyph, new_glyph) = (GlyphId(old_gid), map(GlyphId(old_gid)));
+
+        let value = |values: Option<ttf_parser::math::MathValues>, glyph| {
+            values.and_then(|values| values.get(glyph)).map(|value| value.value)
+        };
+        assert_eq!(
+            value(info.italic_corrections, glyph),
+            value(new_info.italic_corrections, new_glyph)
+        );
+        assert_eq!(
+            value(info.top_accent_attachments, glyph),
+            value(new_info.top_accent_attachments, new_glyph)
+        );
+
+        let kern = |infos: Option<ttf_parser::math::KernInfos>, glyph| {
+            let kern = infos?.get(glyph)?.top_right?;
+            Some(
+                (0..=kern.count())
+                    .map(|i| kern.kern(i).unwrap().value)
+                    .collect::<Vec<_>>(),
+            )
+        };
+        assert_eq!(kern(info.kern_infos, glyph), kern(new_info.kern_infos, new_glyph));
+
+        // All variants and assembly parts were added by the closure.
+        let Some(construction) = variants.vertical_constructions.get(glyph) else {
+            continue;
+        };
+        let new_construction =
+            new_variants.vertical_constructions.get(new_glyph).unwrap();
+        assert!(construction
+            .variants
+            .into_iter()
+            .map(|v| (map(v.variant_glyph), v.advance_measurement))
+            .eq(new_construction
+                .variants
+                .into_iter()
+                .map(|v| (v.variant_glyph, v.advance_measurement))));
+        let parts = |assembly: Option<ttf_parser::math::GlyphAssembly>,
+                     map: &dyn Fn(GlyphId) -> GlyphId| {
+            assembly.map(|assembly| {
+                assembly
+                    .parts
+                    .into_iter()
+                    .map(|p| (map(p.glyph_id), p.full_advance))
+                    .collect::<Vec<_>>()
+            })
+        };
+        assert_eq!(
+            parts(construction.assembly, &map),
+            parts(new_construction.assembly, &|g| g)
+        );
+        constructions += 1;
+    }
+
+    assert!(constructions >= 2);
+}
+
+#[test]
+fn device_metrics() {
+    let data = read_file("Roboto-Regular.ttf");
+    let face = ttf_parser::Face::parse(&data, 0).unwrap();
+    let gids = "aWi".chars().map(|c| face.glyph_index(c).unwrap().0);
+    let mapper = GlyphRemapper::new_from_glyphs(&gids.collect::<Vec<_>>());
+
+    let hdmx = |data: &[u8]| {
+        let face = ttf_parser::RawFace::parse(data, 0).unwrap();
+        face.table(ttf_parser::Tag::from_bytes(b"hdmx")).map(<[u8]>::to_vec)
+    };
+
+    let subset = subset(&data, 0, &mapper).unwrap();
+    assert!(hdmx(&subset).is_none());
+
+    let mut options = SubsetOptions::default();
+    options.device_metrics = true;
+    let subset = subset_with_options(&data, 0, &mapper, &options).unwrap();
+    let (old, new) = (hdmx(&data).unwrap(), hdmx(&subset).unwrap());
+    let record_size = |hdmx: &[u8]| u32::from_be_bytes(hdmx[4..8].try_into().unwrap());
+    let (old_size, new_size) = (record_size(&old) as usize, record_size(&new) as usize);
+    assert_eq!(new_size, 8);
+    assert_eq!(old[2..4], new[2..4]);
+
+    let num_records = u16::from_be_bytes([old[2], old[3]]) as usize;
+    for i in 0..num_records {
+        let old_record = &old[8 + i * old_size..];
+        let new_record = &new[8 + i * new_size..];
+        assert_eq!(old_record[0], new_record[0]);
+        for (new_gid, old_gid) in mapper.remapped_gids().enumerate() {
+            assert_eq!(old_record[2 + old_gid as usize], new_record[2 + new_gid]);
+        }
+    }
+}
+
+#[test]
+fn gasp_table() {
+    let data = read_file("NotoSans-Regular.ttf");
+    let mapper = GlyphRemapper::new_from_glyphs(&[1, 2, 3]);
+    let gasp = |data: &[u8]| {
+        let face = ttf_parser::RawFace::parse(data, 0).unwrap();
+        face.table(ttf_parser::Tag::from_bytes(b"gasp")).map(<[u8]>::to_vec)
+    };
+
+    let subset = subset(&data, 0, &mapper).unwrap();
+    assert!(gasp(&subset).is_none());
+
+    let mut options = SubsetOptions::default();
+    options.gasp = true;
+    let subset = subset_with_options(&data, 0, &mapper, &options).unwrap();
+    assert_eq!(gasp(&subset), gasp(&data));
+
+    // Without hinting, only smoothing is requested.
+    options.strip_hinting = true;
+    let subset = subset_with_options(&data, 0, &mapper, &options).unwrap();
+    let gasp = gasp(&subset).unwrap();
+    assert_eq!(gasp[2..], [0, 1, 0xFF, 0xFF, 0, 0x0A]);
+}
+
+#[test]
+#[cfg(feature = "variable-fonts")]
+fn gasp_table_instancing() {
+    let data = read_file("NotoSans-Regular_var.ttf");
+    let mut gasp = vec![];
+    gasp.extend(1u16.to_be_bytes()); // version
+    gasp.extend(1u16.to_be_bytes()); // numRanges
+    gasp.extend(0xFFFFu16.to_be_bytes()); // rangeMaxPPEM
+    gasp.extend(0x000Fu16.to_be_bytes()); // rangeGaspBehavior
+    let data = with_table(&data, b"gasp", gasp);
+
+    let mapper = GlyphRemapper::new_from_glyphs(&[1, 2, 3]);
+    let mut options = SubsetOptions::default();
+    options.gasp = true;
+    let gasp = |data: &[u8]| {
+        let face = ttf_parser::RawFace::parse(data, 0).unwrap();
+        face.table(ttf_parser::Tag::from_bytes(b"gasp")).map(<[u8]>::to_vec)
+    };
+
+    // Instancing drops the hinting, so only smoothing is requested.
+    let unhinted = Some(vec![0, 1, 0, 1, 0xFF, 0xFF, 0, 0x0A]);
+    let variations = parse_variations("wght=700");
+    let subset = subsetter::subset_with_variations_and_options(
+        &data,
+        0,
+        &variations,
+        &mapper,
+        &options,
+    )
+    .unwrap();
+    assert_eq!(gasp(&subset), unhinted);
+
+    let subset = subsetter::subset_with_named_instance_and_options(
+        &data,
+        0,
+        subsetter::InstanceSelector::Index(0),
+        &mapper,
+        &options,
+    )
+    .unwrap();
+    assert_eq!(gasp(&subset), unhinted);
+
+    // Without the option, the table is dropped as before.
+    let subset = subset_with_variations(&data, 0, &variations, &mapper).unwrap();
+    assert!(gasp(&subset).is_none());
+}
+
+#[test]
+fn general_profile() {
+    let data = read_file("NotoSans-Regular.ttf");
+    let font = ttf_parser::Face::parse(&data, 0).unwrap();
+    let chars = ['a', 'b', 'c', 'é'];
+    let glyphs = chars.map(|c| font.glyph_index(c).unwrap().0);
+    let mapper = GlyphRemapper::new_from_glyphs(&glyphs);
+
+    // PDF subsets don't map codepoints.
+    let subset = subset(&data, 0, &mapper).unwrap();
+    let face = ttf_parser::Face::parse(&subset, 0).unwrap();
+    assert!(face.tables().cmap.is_none());
+    assert!(face.tables().os2.is_none());
+
+    let options = SubsetOptions::general();
+    let subset = subset_with_options(&data, 0, &mapper, &options).unwrap();
+    let face = ttf_parser::Face::parse(&subset, 0).unwrap();
+
+    for (c, glyph) in chars.into_iter().zip(glyphs) {
+        let new_glyph = face.glyph_index(c).map(|g| g.0);
+        assert_eq!(new_glyph, mapper.get(glyph), "{c}");
+    }
+    assert!(face.glyph_index('d').is_none());
+
+    let os2 = face.raw_face().table(ttf_parser::Tag::from_bytes(b"OS/2")).unwrap();
+    assert_eq!(os2[64..68], [0, b'a', 0, 0xE9]);
+
+    assert_eq!(face.names().len(), font.names().len());
+    assert!(face.tables().gsub.is_some());
+    assert!(face.tables().gpos.is_some());
+    assert_eq!(face.glyph_name(GlyphId(mapper.get(glyphs[0]).unwrap())), Some("a"));
+}
+
+#[test]
+fn layout_tables_all_glyphs() {
+    // Subsetting the mark attachment lookups of this font to all of its glyphs
+    // overflows unless shared anchors are written only once.
+    let data = read_file("NotoSans-Regular_var.ttf");
+    let font = ttf_parser::Face::parse(&data, 0).unwrap();
+    let glyphs = (0..font.number_of_glyphs()).collect::<Vec<_>>();
+    let mapper = GlyphRemapper::new_from_glyphs(&glyphs);
+
+    let options = SubsetOptions::general();
+    let subset = subset_with_options(&data, 0, &mapper, &options).unwrap();
+    let face = ttf_parser::Face::parse(&subset, 0).unwrap();
+    assert_eq!(face.number_of_glyphs(), font.number_of_glyphs());
+    assert!(face.tables().gsub.is_some());
+
+    let gpos = face.tables().gpos.unwrap();
+    assert_eq!(gpos.lookups.len(), font.tables().gpos.unwrap().lookups.len());
+}
+
+#[test]
+#[cfg(feature = "variable-fonts")]
+fn variable_subset() {
+    let data = read_file("NotoSans-Regular_var.ttf");
+    let font = ttf_parser::Face::parse(&data, 0).unwrap();
+    let glyph = font.glyph_index('a').unwrap();
+    let mapper = GlyphRemapper::new_from_glyphs(&[glyph.0]);
+
+    // The vertical metrics are never kept, so their variations are dropped, too.
+    let hvar = font.raw_face().table(ttf_parser::Tag::from_bytes(b"HVAR")).unwrap();
+    let data = with_table(&data, b"VVAR", hvar.to_vec());
+
+    let variable = subsetter::subset_variable(&data, 0, &mapper).unwrap();
+    let face = ttf_parser::Face::parse(&variable, 0).unwrap();
+    assert!(face.is_variable());
+    assert_eq!(face.variation_axes().len(), font.variation_axes().len());
+    assert!(face.tables().gvar.is_some());
+    assert!(face.tables().hvar.is_some());
+    assert!(face.tables().vhea.is_none());
+    assert!(face.tables().vmtx.is_none());
+    assert!(face.tables().vvar.is_none());
+
+    // The variations of the CVT are kept with the hinting tables. Their tuple
+    // variations are indexed by CVT entries, so they are copied as they are.
+    let cvar = [0, 1, 0, 0, 0, 0, 0, 8];
+    let data = with_table(&data, b"cvt ", vec![0, 10, 0, 20]);
+    let data = with_table(&data, b"cvar", cvar.to_vec());
+    let table = |data: &[u8], tag: &[u8; 4]| {
+        let face = skrifa::FontRef::new(data).unwrap();
+        face.table_data(skrifa::Tag::new(tag))
+            .map(|data| data.as_bytes().to_vec())
+    };
+    let variable = subsetter::subset_variable(&data, 0, &mapper).unwrap();
+    assert_eq!(table(&variable, b"cvar").as_deref(), Some(&cvar[..]));
+    assert!(table(&variable, b"cvt ").is_some());
+
+    let mut options = SubsetOptions::default();
+    options.strip_hinting = true;
+    let variable =
+        subsetter::subset_variable_with_options(&data, 0, &mapper, &options).unwrap();
+    assert_eq!(table(&variable, b"cvar"), None);
+    assert_eq!(table(&variable, b"cvt "), None);
+
+    // Fonts that aren't variable are subsetted as usual.
+    let data = read_file("NotoSans-Regular.ttf");
+    assert_eq!(
+        subsetter::subset_variable(&data, 0, &mapper).unwrap(),
+        subset(&data, 0, &mapper).unwrap(),
+    );
+}
+
+#[test]
+#[cfg(feature = "variable-fonts")]
+fn variable_subset_avar2() {
+    let data = read_file("NotoSans-Regular_var.ttf");
+    let font = ttf_parser::Face::parse(&data, 0).unwrap();
+    let glyph = font.glyph_index('a').unwrap();
+    let mapper = GlyphRemapper::new_from_glyphs(&[glyph.0]);
+
+    // An `avar` table of version 2 with identity segment maps and an empty
+    // item variation store.
+    let axis_count = font.variation_axes().len();
+    let mut avar = vec![0, 2, 0, 0, 0, 0];
+    avar.extend(axis_count.to_be_bytes());
+    avar.extend(std::iter::repeat_n(0, 2 * usize::from(axis_count)));
+    let store_offset = avar.len() as u32 + 8;
+    avar.extend(0u32.to_be_bytes());
+    avar.extend(store_offset.to_be_bytes());
+    avar.extend([0, 1, 0, 0, 0, 8, 0, 0]);
+    avar.extend(axis_count.to_be_bytes());
+    avar.extend([0, 0]);
+    let data = with_table(&data, b"avar", avar.clone());
+
+    // Keeping all variations doesn't need to touch the `avar` table.
+    let variable = subsetter::subset_variable(&data, 0, &mapper).unwrap();
+    let face = ttf_parser::Face::parse(&variable, 0).unwrap();
+    assert!(face.is_variable());
+    assert_eq!(
+        face.raw_face().table(ttf_parser::Tag::from_bytes(b"avar")),
+        Some(&avar[..])
+    );
+
+    // Neither does fully instancing it.
+    let limits = font
+        .variation_axes()
+        .into_iter()
+        .map(|axis| {
+            (Tag::new(&axis.tag.to_bytes()), subsetter::AxisLimit::Pin(axis.max_value))
+        })
+        .collect::<Vec<_>>();
+    let instance =
+        subsetter::subset_with_axis_limits(&data, 0, &limits, &mapper).unwrap();
+    let face = ttf_parser::Face::parse(&instance, 0).unwrap();
+    assert!(!face.is_variable());
+
+    // But partially instancing it does.
+    let limits = [(
+        Tag::new(b"wght"),
+        subsetter::AxisLimit::Range { min: 400.0, default: 400.0, max: 700.0 },
+    )];
+    assert_eq!(
+        subsetter::subset_with_axis_limits(&data, 0, &limits, &mapper),
+        Err(subsetter::Error::Unimplemented)
+    );
+}
+
+fn glyph<'a>(
+    face: &skrifa::FontRef<'a>,
+    gid: u16,
+) -> Option<skrifa::raw::tables::glyf::Glyph<'a>> {
+    // CFF fonts don't have any TrueType glyphs.
+    let (Ok(loca), Ok(glyf)) = (face.loca(None), face.glyf()) else {
+        return None;
+    };
+    loca.get_glyf(skrifa::GlyphId::new(gid as u32), &glyf).unwrap()
+}
+
+/// The number of points and contours of a glyph, and its component depth.
+fn glyph_stats(face: &skrifa::FontRef, gid: u16) -> (u16, u16, u16) {
+    use skrifa::raw::tables::glyf::Glyph;
+
+    match glyph(face, gid) {
+        Some(Glyph::Simple(simple)) => {
+            (simple.num_points() as u16, simple.number_of_contours() as u16, 0)
+        }
+        Some(Glyph::Composite(composite)) => {
+            composite.components().fold((0, 0, 1), |acc, component| {
+                let (points, contours, depth) =
+                    glyph_stats(face, component.glyph.to_u32() as u16);
+                (acc.0 + points, acc.1 + contours, acc.2.max(depth + 1))
+            })
+        }
+        None => (0, 0, 0),
+    }
+}
+
+#[cfg(feature = "variable-fonts")]
+fn name(face: &ttf_parser::Face, id: u16) -> Option<String> {
+    face.names()
+        .into_iter()
+        .find(|name| name.name_id == id)
+        .and_then(|name| name.to_string())
+}
+
 fn glyph_metrics(font_file: &str, gids: &str) {
     let ctx = get_test_context(font_file, gids).unwrap();
     let old_face = ttf_parser::Face::parse(&ctx.font, 0).unwrap();
@@ -355,6 +2092,35 @@ fn glyph_outlines_ttf_parser(font_file: &str, gids: &str) {
 #[derive(Debug, Default, PartialEq)]
 struct Sink(Vec<Inst>);
 
+impl Sink {
+    #[cfg(feature = "variable-fonts")]
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        let close = |a: &[f32], b: &[f32]| {
+            a.iter().zip(b).all(|(a, b)| (a - b).abs() <= tolerance)
+        };
+
+        self.0.len() == other.0.len()
+            && self.0.iter().zip(&other.0).all(|(a, b)| match (a, b) {
+                (Inst::MoveTo(x1, y1), Inst::MoveTo(x2, y2))
+                | (Inst::LineTo(x1, y1), Inst::LineTo(x2, y2)) => {
+                    close(&[*x1, *y1], &[*x2, *y2])
+                }
+                (Inst::QuadTo(a1, b1, c1, d1), Inst::QuadTo(a2, b2, c2, d2)) => {
+                    close(&[*a1, *b1, *c1, *d1], &[*a2, *b2, *c2, *d2])
+                }
+                (
+                    Inst::CurveTo(a1, b1, c1, d1, e1, f1),
+                    Inst::CurveTo(a2, b2, c2, d2, e2, f2),
+                ) => close(
+                    &[*a1, *b1, *c1, *d1, *e1, *f1],
+                    &[*a2, *b2, *c2, *d2, *e2, *f2],
+                ),
+                (Inst::Close, Inst::Close) => true,
+                _ => false,
+            })
+    }
+}
+
 #[derive(Debug, PartialEq)]
 enum Inst {
     MoveTo(f32, f32),
@@ -407,3 +2173,49 @@ impl ttf_parser::OutlineBuilder for Sink {
         self.0.push(Inst::Close);
     }
 }
+
+/// Records the operations of a color glyph, with outlines instead of glyph IDs.
+struct Canvas<'a, 'b> {
+    face: &'b ttf_parser::Face<'a>,
+    ops: Vec<String>,
+}
+
+impl<'a> ttf_parser::colr::Painter<'a> for Canvas<'a, '_> {
+    fn outline_glyph(&mut self, glyph_id: GlyphId) {
+        let mut sink = Sink::default();
+        self.face.outline_glyph(glyph_id, &mut sink);
+        self.ops.push(format!("outline {sink:?}"));
+    }
+
+    fn paint(&mut self, paint: ttf_parser::colr::Paint<'a>) {
+        self.ops.push(format!("paint {paint:?}"));
+    }
+
+    fn push_clip(&mut self) {
+        self.ops.push("push clip".into());
+    }
+
+    fn push_clip_box(&mut self, clipbox: ttf_parser::colr::ClipBox) {
+        self.ops.push(format!("push clip box {clipbox:?}"));
+    }
+
+    fn pop_clip(&mut self) {
+        self.ops.push("pop clip".into());
+    }
+
+    fn push_layer(&mut self, mode: ttf_parser::colr::CompositeMode) {
+        self.ops.push(format!("push layer {mode:?}"));
+    }
+
+    fn pop_layer(&mut self) {
+        self.ops.push("pop layer".into());
+    }
+
+    fn push_transform(&mut self, transform: ttf_parser::Transform) {
+        self.ops.push(format!("push transform {transform:?}"));
+    }
+
+    fn pop_transform(&mut self) {
+        self.ops.push("pop transform".into());
+    }
+}